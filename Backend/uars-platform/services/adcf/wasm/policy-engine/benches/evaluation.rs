@@ -0,0 +1,40 @@
+// Evaluation throughput. Run with `cargo bench`; criterion reports the
+// change against the previous run so regressions show up in review.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use uars_policy_engine::profile::{synthetic_contexts, synthetic_policy_set};
+use uars_policy_engine::PolicyEngine;
+
+fn engine(extra_policies: usize) -> PolicyEngine {
+    let mut engine = PolicyEngine::new();
+    engine.load_policies(&synthetic_policy_set(extra_policies)).unwrap();
+    engine
+}
+
+fn bench_evaluate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("evaluate");
+    for policies in [1, 50] {
+        let mut engine = engine(policies);
+        let context = synthetic_contexts(1).remove(0);
+        group.bench_with_input(BenchmarkId::from_parameter(policies), &context, |b, context| {
+            b.iter(|| engine.evaluate(black_box(context)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_evaluate_batch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("evaluate_batch");
+    for size in [10, 100] {
+        let mut engine = engine(50);
+        let batch = format!("[{}]", synthetic_contexts(size).join(","));
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &batch, |b, batch| {
+            b.iter(|| engine.evaluate_batch(black_box(batch)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_evaluate, bench_evaluate_batch);
+criterion_main!(benches);
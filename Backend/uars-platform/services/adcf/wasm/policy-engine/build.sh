@@ -27,6 +27,23 @@ mkdir -p pkg
 echo "Compiling Rust to WebAssembly..."
 wasm-pack build --target web --out-dir pkg --release
 
+# SIMD build for the evaluation hot path. wasm cannot detect SIMD at
+# runtime, so hosts feature-detect (e.g. with wasm-feature-detect) and
+# load pkg-simd/ when supported, pkg/ otherwise.
+if [ "${SIMD:-1}" = "1" ]; then
+    echo "Compiling SIMD variant..."
+    RUSTFLAGS="-C target-feature=+simd128" wasm-pack build --target web --out-dir pkg-simd --release
+fi
+
+# Size-optimized build without the optional subsystems (crypto, geo,
+# telemetry): opt-level "z", panic=abort and wee_alloc. Set FEATURES to
+# add some back, e.g. FEATURES=crypto.
+if [ "${MINIMAL:-0}" = "1" ]; then
+    echo "Compiling minimal variant..."
+    wasm-pack build --target web --out-dir pkg-minimal --profile release-size -- \
+        --no-default-features --features "wee_alloc ${FEATURES:-}"
+fi
+
 # Verify the build
 if [ -f "pkg/uars_policy_engine.wasm" ]; then
     echo "✅ WASM module built successfully!"
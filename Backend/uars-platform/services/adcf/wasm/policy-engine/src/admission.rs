@@ -0,0 +1,247 @@
+use wasm_bindgen::prelude::*;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::advice;
+use crate::bag::AttributeBag;
+use crate::decision::Decision;
+use crate::error::{to_json, PolicyEngineError};
+use crate::expr;
+use crate::functions::EvalScope;
+use crate::obligations::ObligationCall;
+use crate::{guard, PolicyContext, PolicyEngine, PolicyResult, PolicySelection};
+
+const ADMISSION_API_VERSION: &str = "admission.k8s.io/v1";
+
+// Obligation a permitting rule uses to mutate the admitted object:
+// `patch("add", "/metadata/labels/owner", subject.id)`. The arguments are
+// a JSON Patch op, a path, and the value (`from` for move and copy; none
+// for remove).
+pub const PATCH_OBLIGATION: &str = "patch";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct AdmissionReview {
+    api_version: Option<String>,
+    request: Option<AdmissionRequest>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct AdmissionRequest {
+    uid: String,
+    kind: GroupVersionKind,
+    resource: GroupVersionResource,
+    sub_resource: String,
+    name: String,
+    namespace: String,
+    operation: String,
+    user_info: UserInfo,
+    object: Value,
+    old_object: Value,
+    dry_run: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct GroupVersionKind {
+    group: String,
+    version: String,
+    kind: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct GroupVersionResource {
+    group: String,
+    version: String,
+    resource: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct UserInfo {
+    username: String,
+    uid: String,
+    groups: Vec<String>,
+    extra: HashMap<String, Vec<String>>,
+}
+
+impl AdmissionRequest {
+    // The object being admitted; a DELETE only carries the old one
+    fn subject_object(&self) -> &Value {
+        if self.object.is_null() { &self.old_object } else { &self.object }
+    }
+
+    // Policies address the request as
+    //   subject.{id, uid, groups, extra}
+    //   resource.{type, id, kind, group, version, resource, subresource,
+    //             namespace, name, labels, annotations, object, old_object}
+    //   action.{id, dry_run}
+    // with action.id the lowercased operation (create, update, delete,
+    // connect) and resource.id `namespace/name`
+    fn bag(&self) -> AttributeBag {
+        let metadata = &self.subject_object()["metadata"];
+        // Objects created with generateName have no name yet
+        let name = Some(self.name.as_str())
+            .filter(|name| !name.is_empty())
+            .or_else(|| metadata["name"].as_str())
+            .unwrap_or_default();
+        let id = if self.namespace.is_empty() { name.to_string() } else { format!("{}/{}", self.namespace, name) };
+
+        let mut bag = AttributeBag::default();
+        bag.subject.insert("id".to_string(), json!(self.user_info.username));
+        bag.subject.insert("uid".to_string(), json!(self.user_info.uid));
+        bag.subject.insert("groups".to_string(), json!(self.user_info.groups));
+        bag.subject.insert("extra".to_string(), json!(self.user_info.extra));
+
+        let resource = [
+            ("type", json!(self.kind.kind)),
+            ("id", json!(id)),
+            ("kind", json!(self.kind.kind)),
+            ("group", json!(self.kind.group)),
+            ("version", json!(self.kind.version)),
+            ("resource", json!(self.resource.resource)),
+            ("subresource", json!(self.sub_resource)),
+            ("namespace", json!(self.namespace)),
+            ("name", json!(name)),
+            ("labels", metadata.get("labels").cloned().unwrap_or_else(|| json!({}))),
+            ("annotations", metadata.get("annotations").cloned().unwrap_or_else(|| json!({}))),
+            ("object", self.object.clone()),
+            ("old_object", self.old_object.clone()),
+        ];
+        bag.resource.extend(resource.into_iter().map(|(key, value)| (key.to_string(), value)));
+
+        bag.action.insert("id".to_string(), json!(self.operation.to_lowercase()));
+        bag.action.insert("dry_run".to_string(), json!(self.dry_run));
+        if !self.uid.is_empty() {
+            bag.environment.insert("request_id".to_string(), json!(self.uid));
+        }
+        bag
+    }
+}
+
+// Expression values as JSON; whole numbers stay integers so patches to
+// int fields (replicas, ports) are accepted by the API server
+fn to_json_value(value: &expr::Value) -> Value {
+    match value {
+        expr::Value::Number(n) if n.fract() == 0.0 && n.abs() < 9.0e15 => json!(*n as i64),
+        expr::Value::List(items) => Value::Array(items.iter().map(to_json_value).collect()),
+        expr::Value::Map(entries) => Value::Object(entries.iter().map(|(key, value)| (key.clone(), to_json_value(value))).collect()),
+        other => serde_json::to_value(other).unwrap_or(Value::Null),
+    }
+}
+
+fn patch_operation(args: &[expr::Value]) -> Result<Value, String> {
+    let text = |index: usize, name: &str| match args.get(index) {
+        Some(expr::Value::String(s)) => Ok(s.clone()),
+        _ => Err(format!("argument {} ({}) must be a string", index + 1, name)),
+    };
+    let op = text(0, "op")?;
+    let path = text(1, "path")?;
+    match (op.as_str(), args.len()) {
+        ("remove", 2) => Ok(json!({ "op": op, "path": path })),
+        ("move" | "copy", 3) => Ok(json!({ "op": op, "path": path, "from": text(2, "from")? })),
+        ("add" | "replace" | "test", 3) => Ok(json!({ "op": op, "path": path, "value": to_json_value(&args[2]) })),
+        ("remove" | "move" | "copy" | "add" | "replace" | "test", count) => Err(format!("'{}' does not take {} arguments", op, count)),
+        _ => Err(format!("unknown JSON Patch op '{}'", op)),
+    }
+}
+
+// AdmissionResponse fields for a decision. Only PERMIT admits; advice
+// becomes warnings shown to kubectl users.
+fn admission_response(uid: &str, result: &PolicyResult, patch: &[Value]) -> Value {
+    let warnings: Vec<String> = advice::parse_list(&result.advice).iter().map(|advice| advice.text().to_string()).collect();
+    let mut response = json!({ "uid": uid, "allowed": result.decision == Decision::Permit });
+    if !warnings.is_empty() {
+        response["warnings"] = json!(warnings);
+    }
+    if result.decision == Decision::Permit {
+        if !patch.is_empty() {
+            response["patchType"] = json!("JSONPatch");
+            response["patch"] = json!(STANDARD.encode(Value::Array(patch.to_vec()).to_string()));
+        }
+        return response;
+    }
+
+    let (code, reason) = match result.decision {
+        Decision::Indeterminate => (500, "InternalError"),
+        _ => (403, "Forbidden"),
+    };
+    let message = result.message.clone().unwrap_or_else(|| result.reason.to_string());
+    response["status"] = json!({ "code": code, "reason": reason, "message": message });
+    response
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Validating/mutating webhook core: takes an AdmissionReview JSON,
+    // evaluates the request as an attribute bag against the global set and
+    // returns the AdmissionReview with its response filled in. A permit's
+    // patch obligations become the response's JSON Patch; one that cannot
+    // be resolved turns the permit into a denial, since the object would
+    // otherwise be admitted without the mutation the policy requires.
+    #[wasm_bindgen]
+    pub fn evaluate_admission(&mut self, admission_review_json: &str) -> Result<String, JsValue> {
+        let review: AdmissionReview = serde_json::from_str(admission_review_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse AdmissionReview: {}", e)).logged())?;
+        let request = review.request.ok_or_else(|| {
+            PolicyEngineError::validation("AdmissionReview has no request")
+                .with_details(json!({ "field": "request" }))
+                .logged()
+        })?;
+
+        let (result, patch) = match self.bag_context(request.bag()) {
+            Ok(context) => {
+                let mut result = guard::guarded(|| self.evaluate_request(context.clone(), PolicySelection::Global));
+                let patch = if result.decision == Decision::Permit { self.patch_obligations(&mut result, &context) } else { Vec::new() };
+                (result, patch)
+            }
+            Err(e) => (PolicyResult::failure(&e.logged()), Vec::new()),
+        };
+
+        if self.debug_mode {
+            console_log!("Admission {} {}: {}", request.operation, request.kind.kind, result.decision);
+        }
+        let response = json!({
+            "apiVersion": review.api_version.unwrap_or_else(|| ADMISSION_API_VERSION.to_string()),
+            "kind": "AdmissionReview",
+            "response": admission_response(&request.uid, &result, &patch),
+        });
+        Ok(to_json(&response)?)
+    }
+}
+
+impl PolicyEngine {
+    // JSON Patch operations from the result's patch obligations, in order.
+    // On the first one that does not resolve the result becomes a DENY.
+    fn patch_obligations(&self, result: &mut PolicyResult, context: &PolicyContext) -> Vec<Value> {
+        let specs: Vec<String> = serde_json::from_str(&result.obligations).unwrap_or_default();
+        let scope = EvalScope::new(self, context, None);
+        let mut patch = Vec::new();
+
+        for spec in &specs {
+            let call = ObligationCall::parse(spec);
+            if call.id != PATCH_OBLIGATION {
+                continue;
+            }
+            let operation = call.resolve_args(&scope).map_err(|e| e.to_string()).and_then(|args| patch_operation(&args));
+            match operation {
+                Ok(operation) => patch.push(operation),
+                Err(e) => {
+                    let error = PolicyEngineError::evaluation(format!("Patch obligation '{}' cannot be applied: {}", spec, e)).logged();
+                    let mut denied = PolicyResult::new(Decision::Deny, error.message().to_string(), result.confidence);
+                    denied.policy_id = result.policy_id.take();
+                    denied.rule_id = result.rule_id.take();
+                    denied.error_code = Some(error.code().to_string());
+                    *result = denied;
+                    return Vec::new();
+                }
+            }
+        }
+        patch
+    }
+}
@@ -0,0 +1,151 @@
+use wasm_bindgen::prelude::*;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::vocabulary::Effect;
+
+// How prominently a UI should show an advice, lowest first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    #[default]
+    Info,
+    Notice,
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    pub const ALL: [Severity; 4] = [Severity::Info, Severity::Notice, Severity::Warning, Severity::Critical];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Notice => "notice",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Severity> {
+        let text = text.trim();
+        Severity::ALL.into_iter().find(|severity| severity.as_str().eq_ignore_ascii_case(text))
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// Non-binding guidance attached to a decision, e.g.
+//
+//   { "id": "remind_classification", "severity": "notice",
+//     "message": "This document is confidential",
+//     "attributes": { "classification": "confidential" } }
+//
+// A bare string is read as an info advice with that id. Policy-level
+// advice can carry `"fulfill_on": "PERMIT"` or `"DENY"` (see
+// obligations.rs); results never do.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "AdviceSpec")]
+#[wasm_bindgen]
+pub struct Advice {
+    #[wasm_bindgen(getter_with_clone)]
+    pub id: String,
+    #[wasm_bindgen(skip)]
+    pub severity: Severity,
+    #[wasm_bindgen(getter_with_clone)]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub message: String,
+    #[wasm_bindgen(skip)]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attributes: BTreeMap<String, serde_json::Value>,
+    #[wasm_bindgen(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fulfill_on: Option<Effect>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum AdviceSpec {
+    Id(String),
+    Structured {
+        id: String,
+        #[serde(default)]
+        severity: Severity,
+        #[serde(default)]
+        message: String,
+        #[serde(default)]
+        attributes: BTreeMap<String, serde_json::Value>,
+        #[serde(default)]
+        fulfill_on: Option<Effect>,
+    },
+}
+
+// Documents both spellings
+impl JsonSchema for Advice {
+    fn schema_name() -> String {
+        "Advice".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        AdviceSpec::json_schema(gen)
+    }
+}
+
+impl From<AdviceSpec> for Advice {
+    fn from(spec: AdviceSpec) -> Advice {
+        match spec {
+            AdviceSpec::Id(id) => Advice::new(id, Severity::Info),
+            AdviceSpec::Structured { id, severity, message, attributes, fulfill_on } => {
+                Advice { id, severity, message, attributes, fulfill_on }
+            }
+        }
+    }
+}
+
+impl Advice {
+    pub fn new(id: impl Into<String>, severity: Severity) -> Advice {
+        Advice { id: id.into(), severity, message: String::new(), attributes: BTreeMap::new(), fulfill_on: None }
+    }
+
+    pub fn with_message(mut self, message: impl Into<String>) -> Advice {
+        self.message = message.into();
+        self
+    }
+
+    // The message, or the id for advice without one
+    pub fn text(&self) -> &str {
+        if self.message.is_empty() { &self.id } else { &self.message }
+    }
+}
+
+#[wasm_bindgen]
+impl Advice {
+    #[wasm_bindgen(getter)]
+    pub fn severity(&self) -> String {
+        self.severity.as_str().to_string()
+    }
+
+    // JSON object of the advice's attributes
+    #[wasm_bindgen(getter)]
+    pub fn attributes(&self) -> String {
+        serde_json::to_string(&self.attributes).unwrap_or_else(|_| "{}".to_string())
+    }
+}
+
+// Most severe first; equal severities keep their order
+pub fn rank(advice: &mut [Advice]) {
+    advice.sort_by_key(|advice| std::cmp::Reverse(advice.severity));
+}
+
+// Advice parsed from a PolicyResult's advice JSON
+pub fn parse_list(json: &str) -> Vec<Advice> {
+    serde_json::from_str(json).unwrap_or_default()
+}
@@ -0,0 +1,340 @@
+use wasm_bindgen::prelude::*;
+use chrono::{DateTime, TimeDelta, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::clock;
+use crate::decision::Decision;
+use crate::error::{to_json, PolicyEngineError};
+use crate::{state_key, PolicyContext, PolicyEngine, PolicyResult, PolicyRule};
+
+pub const DEFAULT_APPROVAL_SECONDS: i64 = 3600;
+pub const MAX_APPROVAL_SECONDS: i64 = 30 * 86_400;
+
+fn default_required_approvals() -> usize {
+    1
+}
+
+fn default_expires_in() -> i64 {
+    DEFAULT_APPROVAL_SECONDS
+}
+
+// Approval requirement attached to a PENDING_APPROVAL rule. The requester
+// never counts as an approver, so one approval already makes two people.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ApprovalSpec {
+    // Roles allowed to approve; empty means any approver
+    #[serde(default)]
+    pub approver_roles: Vec<String>,
+
+    // Distinct approvals needed before the request resolves to PERMIT
+    #[serde(default = "default_required_approvals")]
+    pub required_approvals: usize,
+
+    // Seconds the request stays open after it is first raised
+    #[serde(default = "default_expires_in")]
+    pub expires_in_seconds: i64,
+}
+
+impl ApprovalSpec {
+    pub fn check(&self) -> Result<(), String> {
+        if !(1..=MAX_APPROVAL_SECONDS).contains(&self.expires_in_seconds) {
+            return Err(format!(
+                "approval expires_in_seconds must be between 1 and {}, not {}",
+                MAX_APPROVAL_SECONDS, self.expires_in_seconds
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Default for ApprovalSpec {
+    fn default() -> Self {
+        ApprovalSpec {
+            approver_roles: Vec::new(),
+            required_approvals: default_required_approvals(),
+            expires_in_seconds: default_expires_in(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ApprovalStatus {
+    Pending,
+    Approved,
+    Denied,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApprovalVote {
+    pub approver: String,
+    pub approved: bool,
+    pub timestamp: DateTime<Utc>,
+}
+
+// Approval request descriptor, returned to the PEP (as JSON in
+// `PolicyResult.approval`) and kept by the engine under its request_id
+// once raised. A later evaluation with the same request_id resolves
+// against the recorded votes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApprovalRequest {
+    pub request_id: String,
+    pub requester: String,
+    pub operation: String,
+    pub resource_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule_id: Option<String>,
+    pub approver_roles: Vec<String>,
+    pub required_approvals: usize,
+    pub expires_at: DateTime<Utc>,
+    pub status: ApprovalStatus,
+    #[serde(default)]
+    pub votes: Vec<ApprovalVote>,
+}
+
+impl ApprovalRequest {
+    // Specs are checked on load; one that still does not fit expires at once
+    fn raise(spec: &ApprovalSpec, rule: &PolicyRule, context: &PolicyContext, now: DateTime<Utc>) -> ApprovalRequest {
+        ApprovalRequest {
+            request_id: context.request_id.clone(),
+            requester: context.user_id.clone(),
+            operation: context.operation.clone(),
+            resource_id: context.resource_id.clone(),
+            rule_id: Some(rule.id.clone()),
+            approver_roles: spec.approver_roles.clone(),
+            required_approvals: spec.required_approvals,
+            expires_at: TimeDelta::try_seconds(spec.expires_in_seconds)
+                .and_then(|duration| now.checked_add_signed(duration))
+                .unwrap_or(now),
+            status: ApprovalStatus::Pending,
+            votes: Vec::new(),
+        }
+    }
+
+    // An approval only covers the request it was raised for
+    fn covers(&self, context: &PolicyContext) -> bool {
+        self.requester == context.user_id
+            && self.operation == context.operation
+            && self.resource_id == context.resource_id
+    }
+
+    fn approvals(&self) -> usize {
+        self.votes.iter().filter(|vote| vote.approved).count()
+    }
+}
+
+fn parse_verdict(verdict: &str) -> Option<bool> {
+    match verdict.trim().to_ascii_uppercase().as_str() {
+        "APPROVE" | "APPROVED" | "PERMIT" => Some(true),
+        "REJECT" | "REJECTED" | "DENY" => Some(false),
+        _ => None,
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Records an approver's verdict ("approve" or "deny") on a pending
+    // request and returns the updated ApprovalRequest JSON. The host
+    // authenticates the approver; when `approver_roles_json` (a JSON
+    // array) is given it must include one of the request's approver roles.
+    // A single deny resolves the request to DENY. Requests raised while
+    // evaluating for a tenant are found under that `tenant_id`.
+    #[wasm_bindgen]
+    pub fn record_approval(
+        &mut self,
+        request_id: &str,
+        approver: &str,
+        verdict: &str,
+        approver_roles_json: Option<String>,
+        tenant_id: Option<String>,
+    ) -> Result<String, JsValue> {
+        let approved = parse_verdict(verdict).ok_or_else(|| {
+            PolicyEngineError::validation(format!("Unknown approval verdict '{}'", verdict))
+                .with_details(json!({ "verdict": verdict }))
+                .logged()
+        })?;
+        let approver = approver.trim();
+        if approver.is_empty() {
+            return Err(PolicyEngineError::validation("Approver must not be empty").logged().into());
+        }
+        let approver_roles: Option<Vec<String>> = approver_roles_json
+            .map(|json| serde_json::from_str(&json))
+            .transpose()
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse approver roles: {}", e)).logged())?;
+
+        let now = clock::now();
+        let request = self.approvals.get_mut(&state_key(tenant_id.as_deref(), request_id)).ok_or_else(|| {
+            PolicyEngineError::not_found(format!("No approval request '{}'", request_id))
+                .with_details(json!({ "request_id": request_id }))
+                .logged()
+        })?;
+        if request.status != ApprovalStatus::Pending {
+            return Err(PolicyEngineError::invalid_state(format!("Approval request '{}' is already resolved", request_id))
+                .with_details(json!({ "request_id": request_id, "status": request.status }))
+                .logged()
+                .into());
+        }
+        if now >= request.expires_at {
+            return Err(PolicyEngineError::invalid_state(format!("Approval request '{}' has expired", request_id))
+                .with_details(json!({ "request_id": request_id, "expires_at": request.expires_at }))
+                .logged()
+                .into());
+        }
+        if approver == request.requester {
+            return Err(PolicyEngineError::conflict("Requesters cannot approve their own request")
+                .with_details(json!({ "request_id": request_id, "approver": approver }))
+                .logged()
+                .into());
+        }
+        if request.votes.iter().any(|vote| vote.approver == approver) {
+            return Err(PolicyEngineError::conflict(format!("'{}' has already voted on '{}'", approver, request_id))
+                .with_details(json!({ "request_id": request_id, "approver": approver }))
+                .logged()
+                .into());
+        }
+        if let Some(roles) = &approver_roles {
+            if !request.approver_roles.is_empty() && !roles.iter().any(|role| request.approver_roles.contains(role)) {
+                return Err(PolicyEngineError::validation(format!("'{}' holds none of the approver roles", approver))
+                    .with_details(json!({ "request_id": request_id, "approver_roles": request.approver_roles }))
+                    .logged()
+                    .into());
+            }
+        }
+
+        request.votes.push(ApprovalVote { approver: approver.to_string(), approved, timestamp: now });
+        if !approved {
+            request.status = ApprovalStatus::Denied;
+        } else if request.approvals() >= request.required_approvals {
+            request.status = ApprovalStatus::Approved;
+        }
+        Ok(to_json(&*request)?)
+    }
+
+    // JSON array of open (pending, unexpired) approval requests of
+    // `tenant_id` (global ones without it), oldest expiry first
+    #[wasm_bindgen]
+    pub fn get_pending_approvals(&self, tenant_id: Option<String>) -> Result<String, JsValue> {
+        let now = clock::now();
+        let mut pending: Vec<&ApprovalRequest> = self
+            .approvals
+            .iter()
+            .filter(|(key, request)| **key == state_key(tenant_id.as_deref(), &request.request_id))
+            .map(|(_, request)| request)
+            .filter(|request| request.status == ApprovalStatus::Pending && request.expires_at > now)
+            .collect();
+        pending.sort_by(|a, b| a.expires_at.cmp(&b.expires_at).then_with(|| a.request_id.cmp(&b.request_id)));
+        Ok(to_json(&pending)?)
+    }
+}
+
+impl PolicyEngine {
+    // Decides a matched PENDING_APPROVAL rule from the votes recorded for
+    // the context's request_id (in `tenant`). Without a record the result
+    // stays pending and carries a fresh descriptor. Expiry is by the
+    // engine clock.
+    pub(crate) fn resolve_approval(&self, rule: &PolicyRule, context: &PolicyContext, tenant: Option<&str>, result: &mut PolicyResult) {
+        let spec = rule.approval.clone().unwrap_or_default();
+        let now = clock::now();
+        let request = match self.approvals.get(&state_key(tenant, &context.request_id)) {
+            None => ApprovalRequest::raise(&spec, rule, context, now),
+            Some(request) if !request.covers(context) => {
+                result.decision = Decision::Deny;
+                result.reason = format!("Approval '{}' was raised for a different request", context.request_id).into();
+                return;
+            }
+            Some(request) => request.clone(),
+        };
+
+        match request.status {
+            ApprovalStatus::Approved => {
+                result.decision = Decision::Permit;
+                result.reason = format!("Rule '{}' matched (approved)", rule.name).into();
+            }
+            ApprovalStatus::Denied => {
+                result.decision = Decision::Deny;
+                result.reason = format!("Rule '{}' matched (approval denied)", rule.name).into();
+            }
+            ApprovalStatus::Pending if now >= request.expires_at => {
+                result.decision = Decision::Deny;
+                result.reason = format!("Rule '{}' matched (approval expired)", rule.name).into();
+            }
+            ApprovalStatus::Pending => {
+                result.approval = serde_json::to_string(&request).unwrap_or_else(|_| "null".to_string());
+            }
+        }
+    }
+
+    // Keeps the descriptor of a pending decision so approvers can vote on
+    // it, and drops expired requests
+    pub(crate) fn register_approval(&mut self, result: &PolicyResult, tenant: Option<&str>) {
+        let now = clock::now();
+        self.approvals.retain(|_, request| request.expires_at > now);
+        if result.decision != Decision::PendingApproval {
+            return;
+        }
+        if let Ok(request) = serde_json::from_str::<ApprovalRequest>(&result.approval) {
+            self.approvals.entry(state_key(tenant, &request.request_id)).or_insert(request);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+    use crate::Policy;
+    use std::rc::Rc;
+
+    fn rule(expires_in_seconds: i64) -> PolicyRule {
+        serde_json::from_value(json!({
+            "id": "needs-approval", "name": "needs-approval", "description": "", "priority": 0,
+            "condition": "true", "effect": "PENDING_APPROVAL", "obligations": [], "advice": [],
+            "approval": { "expires_in_seconds": expires_in_seconds }
+        }))
+        .unwrap()
+    }
+
+    fn context(user_id: &str, timestamp: DateTime<Utc>) -> PolicyContext {
+        PolicyContext { request_id: "r1".to_string(), user_id: user_id.to_string(), timestamp, ..PolicyContext::default() }
+    }
+
+    fn resolve(engine: &PolicyEngine, context: &PolicyContext, tenant: Option<&str>) -> PolicyResult {
+        let mut result = PolicyResult::new(Decision::PendingApproval, "pending", 1.0);
+        engine.resolve_approval(&rule(3600), context, tenant, &mut result);
+        result
+    }
+
+    #[test]
+    fn rejects_an_unrepresentable_expiry_on_load() {
+        let policy: Policy = serde_json::from_value(json!({
+            "id": "p1", "name": "p1", "version": "1.0.0", "description": "", "target": "true",
+            "combining_algorithm": "deny-overrides", "obligations": [], "advice": [],
+            "rules": [rule(i64::MAX)]
+        }))
+        .unwrap();
+        assert!(PolicyEngine::new().compile_policy(policy).is_err());
+    }
+
+    #[test]
+    fn approvals_are_per_tenant_and_expire_by_the_engine_clock() {
+        let start = DateTime::parse_from_rfc3339("2026-01-01T00:00:00Z").unwrap().with_timezone(&Utc);
+        clock::set_clock(Rc::new(FixedClock(start)));
+        let mut engine = PolicyEngine::new();
+        let alice = context("alice", start);
+        let result = resolve(&engine, &alice, Some("t1"));
+        engine.register_approval(&result, Some("t1"));
+        assert!(engine.approvals.contains_key(&state_key(Some("t1"), "r1")));
+        assert!(!engine.approvals.contains_key("r1"));
+
+        // Another tenant reusing the request id gets its own request
+        let result = resolve(&engine, &context("bob", start), Some("t2"));
+        assert_eq!(result.decision, Decision::PendingApproval);
+
+        // A backdated request cannot revive an expired approval
+        clock::set_clock(Rc::new(FixedClock(start + TimeDelta::hours(2))));
+        assert_eq!(resolve(&engine, &alice, Some("t1")).decision, Decision::Deny);
+        clock::use_system_clock();
+    }
+}
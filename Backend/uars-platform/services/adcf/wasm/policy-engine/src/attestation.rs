@@ -0,0 +1,373 @@
+use wasm_bindgen::prelude::*;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+use chrono::{DateTime, Duration, Utc};
+use p256::ecdsa::signature::Verifier;
+use p256::pkcs8::DecodePublicKey;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use x509_cert::der::{Decode, Encode};
+use x509_cert::ext::pkix::{BasicConstraints, KeyUsage};
+use x509_cert::Certificate;
+
+use crate::clock;
+use crate::error::PolicyEngineError;
+use crate::jose::{self, Jwks};
+pub use crate::DeviceAttestation;
+use crate::vocabulary::DeviceTrust;
+use crate::{PolicyContext, PolicyEngine};
+
+const ECDSA_WITH_SHA256: &str = "1.2.840.10045.4.3.2";
+const SHA256_WITH_RSA: &str = "1.2.840.113549.1.1.11";
+// COSE algorithm identifier for ES256
+const COSE_ES256: i128 = -7;
+// How long an issued WebAuthn challenge may be answered, and how many may
+// be outstanding at once
+const CHALLENGE_SECONDS: i64 = 300;
+const MAX_CHALLENGES: usize = 10_000;
+
+// Trust anchors for device evidence. Posture JWTs are verified against
+// `posture_keys`; WebAuthn "packed" attestation chains must end at one
+// of `root_certificates` (base64 DER), be made for `rp_id` from one of
+// `origins`, and answer a challenge from issue_attestation_challenge.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AttestationConfig {
+    #[serde(default)]
+    pub posture_keys: Jwks,
+    #[serde(default)]
+    pub posture_issuer: Option<String>,
+    #[serde(default)]
+    pub root_certificates: Vec<String>,
+    #[serde(default)]
+    pub rp_id: Option<String>,
+    #[serde(default)]
+    pub origins: Vec<String>,
+}
+
+pub struct AttestationVerifier {
+    config: AttestationConfig,
+    roots: Vec<Certificate>,
+    // Outstanding WebAuthn challenges (base64url) and when each expires.
+    // Each is consumed by the attestation that answers it, so an
+    // attestation cannot be replayed.
+    challenges: RefCell<HashMap<String, DateTime<Utc>>>,
+}
+
+impl AttestationVerifier {
+    pub fn new(config: AttestationConfig) -> Result<AttestationVerifier, String> {
+        let roots = config
+            .root_certificates
+            .iter()
+            .map(|encoded| {
+                let der = STANDARD.decode(encoded.trim()).map_err(|e| format!("invalid root certificate: {}", e))?;
+                Certificate::from_der(&der).map_err(|e| format!("invalid root certificate: {}", e))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(AttestationVerifier { config, roots, challenges: RefCell::new(HashMap::new()) })
+    }
+
+    pub fn issue_challenge(&self, now: DateTime<Utc>) -> Result<String, String> {
+        let mut bytes = [0u8; 32];
+        getrandom::getrandom(&mut bytes).map_err(|e| format!("no entropy source available: {}", e))?;
+        let challenge = jose::encode_segment(&bytes);
+        let mut challenges = self.challenges.borrow_mut();
+        challenges.retain(|_, expires| *expires > now);
+        if challenges.len() >= MAX_CHALLENGES {
+            return Err("too many outstanding attestation challenges".to_string());
+        }
+        let expires = now.checked_add_signed(Duration::seconds(CHALLENGE_SECONDS)).ok_or("attestation challenge expiry is out of range")?;
+        challenges.insert(challenge.clone(), expires);
+        Ok(challenge)
+    }
+
+    // Claims of a valid posture token issued for this device. Expiry is
+    // checked against the engine clock: the request timestamp is the
+    // caller's to choose.
+    pub fn verify_posture(&self, token: &str, context: &PolicyContext) -> Result<Value, String> {
+        let (_, claims) = jose::verify(token, &self.config.posture_keys)?;
+        jose::check_times(&claims, clock::now())?;
+
+        if let Some(issuer) = &self.config.posture_issuer {
+            if claims.get("iss").and_then(Value::as_str) != Some(issuer.as_str()) {
+                return Err("unexpected issuer".to_string());
+            }
+        }
+        check_device(&claims, &context.device_id)?;
+        if claims.get("attested").and_then(Value::as_bool) == Some(false) {
+            return Err("posture service reports the device as not attested".to_string());
+        }
+        Ok(claims)
+    }
+
+    // Only the "packed" format with an x5c chain is accepted: self
+    // attestation proves nothing about the device, and "tpm" and other
+    // formats are not supported
+    pub fn verify_webauthn(&self, attestation: &DeviceAttestation, now: DateTime<Utc>) -> Result<(), String> {
+        let object = jose::decode_segment(&attestation.attestation_object)?;
+        let client_data = jose::decode_segment(&attestation.client_data_json)?;
+        let object: ciborium::Value =
+            ciborium::from_reader(object.as_slice()).map_err(|e| format!("invalid attestation object: {}", e))?;
+
+        let fmt = cbor_field(&object, "fmt").and_then(|v| v.as_text()).unwrap_or_default();
+        if fmt != "packed" {
+            return Err(format!("unsupported attestation format '{}'", fmt));
+        }
+        let auth_data = cbor_field(&object, "authData").and_then(|v| v.as_bytes()).ok_or("missing authData")?;
+        let challenge = self.check_client_data(&client_data, now)?;
+        self.check_rp_id(auth_data)?;
+        let statement = cbor_field(&object, "attStmt").ok_or("missing attStmt")?;
+
+        let alg = cbor_field(statement, "alg").and_then(|v| v.as_integer()).map(i128::from);
+        if alg != Some(COSE_ES256) {
+            return Err("only ES256 attestation signatures are supported".to_string());
+        }
+        let signature = cbor_field(statement, "sig").and_then(|v| v.as_bytes()).ok_or("missing sig")?;
+        let chain: Vec<Certificate> = cbor_field(statement, "x5c")
+            .and_then(|v| v.as_array())
+            .ok_or("self attestation is not trusted")?
+            .iter()
+            .map(|cert| {
+                let der = cert.as_bytes().ok_or("x5c entries must be byte strings")?;
+                Certificate::from_der(der).map_err(|e| format!("invalid attestation certificate: {}", e))
+            })
+            .collect::<Result<_, String>>()?;
+        let leaf = chain.first().ok_or("empty x5c")?;
+
+        let mut signed = auth_data.clone();
+        signed.extend_from_slice(&Sha256::digest(&client_data));
+        let key = ec_key(leaf)?;
+        let signature = p256::ecdsa::Signature::from_der(signature).map_err(|_| "malformed attestation signature")?;
+        key.verify(&signed, &signature).map_err(|_| "attestation signature mismatch")?;
+
+        self.verify_chain(&chain, now)?;
+        self.challenges.borrow_mut().remove(&challenge);
+        Ok(())
+    }
+
+    // The challenge clientData answers, once it is checked to be an
+    // outstanding one for a credential creation from a known origin
+    fn check_client_data(&self, client_data: &[u8], now: DateTime<Utc>) -> Result<String, String> {
+        let client_data: Value = serde_json::from_slice(client_data).map_err(|e| format!("invalid clientDataJSON: {}", e))?;
+        if client_data.get("type").and_then(Value::as_str) != Some("webauthn.create") {
+            return Err("clientDataJSON is not for a credential creation".to_string());
+        }
+        let origin = client_data.get("origin").and_then(Value::as_str).unwrap_or_default();
+        if !self.config.origins.iter().any(|allowed| allowed == origin) {
+            return Err(format!("unexpected origin '{}'", origin));
+        }
+        let challenge = client_data.get("challenge").and_then(Value::as_str).unwrap_or_default();
+        match self.challenges.borrow().get(challenge) {
+            Some(expires) if *expires > now => Ok(challenge.to_string()),
+            _ => Err("challenge was not issued or has expired".to_string()),
+        }
+    }
+
+    fn check_rp_id(&self, auth_data: &[u8]) -> Result<(), String> {
+        let rp_id = self.config.rp_id.as_deref().ok_or("no relying party id is configured")?;
+        if auth_data.len() < 32 || auth_data[..32] != Sha256::digest(rp_id.as_bytes())[..] {
+            return Err("authData is for a different relying party".to_string());
+        }
+        Ok(())
+    }
+
+    fn verify_chain(&self, chain: &[Certificate], now: DateTime<Utc>) -> Result<(), String> {
+        for cert in chain {
+            check_validity(cert, now)?;
+        }
+        for issuer in chain.iter().skip(1) {
+            check_issuer(issuer)?;
+        }
+        for pair in chain.windows(2) {
+            verify_signed_by(&pair[0], &pair[1])?;
+        }
+        let last = chain.last().ok_or("empty x5c")?;
+        let anchored = self.roots.iter().any(|root| root == last || verify_signed_by(last, root).is_ok());
+        if anchored {
+            Ok(())
+        } else {
+            Err("attestation chain does not end at a trusted root".to_string())
+        }
+    }
+}
+
+// A token naming a device is only good for a request from that device;
+// a request that names no device cannot present a device-bound token
+fn check_device(claims: &Value, device_id: &str) -> Result<(), String> {
+    let device = claims.get("device_id").or_else(|| claims.get("sub")).and_then(Value::as_str);
+    match device {
+        None if device_id.is_empty() => Ok(()),
+        Some(device) if device == device_id => Ok(()),
+        _ => Err("token was not issued for this device".to_string()),
+    }
+}
+
+fn cbor_field<'a>(map: &'a ciborium::Value, key: &str) -> Option<&'a ciborium::Value> {
+    map.as_map()?
+        .iter()
+        .find(|(k, _)| k.as_text() == Some(key))
+        .map(|(_, v)| v)
+}
+
+fn spki_der(cert: &Certificate) -> Result<Vec<u8>, String> {
+    cert.tbs_certificate
+        .subject_public_key_info
+        .to_der()
+        .map_err(|e| format!("invalid public key: {}", e))
+}
+
+fn ec_key(cert: &Certificate) -> Result<p256::ecdsa::VerifyingKey, String> {
+    p256::ecdsa::VerifyingKey::from_public_key_der(&spki_der(cert)?).map_err(|_| "expected a P-256 key".to_string())
+}
+
+fn check_validity(cert: &Certificate, now: DateTime<Utc>) -> Result<(), String> {
+    let validity = &cert.tbs_certificate.validity;
+    let now = now.timestamp();
+    let not_before = validity.not_before.to_unix_duration().as_secs() as i64;
+    let not_after = validity.not_after.to_unix_duration().as_secs() as i64;
+    if now < not_before || now > not_after {
+        return Err("certificate is outside its validity period".to_string());
+    }
+    Ok(())
+}
+
+// Intermediates must be CAs allowed to sign certificates, or any leaf
+// could vouch for further devices
+fn check_issuer(cert: &Certificate) -> Result<(), String> {
+    let tbs = &cert.tbs_certificate;
+    if !matches!(tbs.get::<BasicConstraints>(), Ok(Some((_, constraints))) if constraints.ca) {
+        return Err("attestation chain has an issuer that is not a CA".to_string());
+    }
+    if !matches!(tbs.get::<KeyUsage>(), Ok(Some((_, usage))) if usage.key_cert_sign()) {
+        return Err("attestation chain has an issuer that may not sign certificates".to_string());
+    }
+    Ok(())
+}
+
+fn verify_signed_by(cert: &Certificate, issuer: &Certificate) -> Result<(), String> {
+    let tbs = cert.tbs_certificate.to_der().map_err(|e| format!("invalid certificate: {}", e))?;
+    let signature = cert.signature.raw_bytes();
+
+    match cert.signature_algorithm.oid.to_string().as_str() {
+        ECDSA_WITH_SHA256 => {
+            let signature = p256::ecdsa::Signature::from_der(signature).map_err(|_| "malformed certificate signature")?;
+            ec_key(issuer)?.verify(&tbs, &signature).map_err(|_| "certificate signature mismatch".to_string())
+        }
+        SHA256_WITH_RSA => {
+            let key = rsa::RsaPublicKey::from_public_key_der(&spki_der(issuer)?).map_err(|_| "expected an RSA key")?;
+            let key = rsa::pkcs1v15::VerifyingKey::<Sha256>::new(key);
+            let signature = rsa::pkcs1v15::Signature::try_from(signature).map_err(|_| "malformed certificate signature")?;
+            key.verify(&tbs, &signature).map_err(|_| "certificate signature mismatch".to_string())
+        }
+        other => Err(format!("unsupported certificate signature algorithm {}", other)),
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Once anchors are configured, device_attested and device_trust are
+    // derived from the evidence in the context (device_posture_token,
+    // device_attestation) and the caller's values are ignored
+    #[wasm_bindgen]
+    pub fn set_attestation_trust_anchors(&mut self, config_json: &str) -> Result<(), JsValue> {
+        let config: AttestationConfig = serde_json::from_str(config_json).map_err(|e| {
+            PolicyEngineError::parse(format!("Failed to parse attestation trust anchors: {}", e)).logged()
+        })?;
+        let verifier = AttestationVerifier::new(config)
+            .map_err(|e| PolicyEngineError::validation(format!("Invalid attestation trust anchors: {}", e)).logged())?;
+        self.attestation = Some(verifier);
+        Ok(())
+    }
+
+    // A fresh challenge for navigator.credentials.create(); the resulting
+    // device_attestation is only accepted if it answers one, once, within
+    // five minutes
+    #[wasm_bindgen]
+    pub fn issue_attestation_challenge(&self) -> Result<String, JsValue> {
+        let verifier = self
+            .attestation
+            .as_ref()
+            .ok_or_else(|| PolicyEngineError::validation("No attestation trust anchors are configured").logged())?;
+        Ok(verifier
+            .issue_challenge(clock::now())
+            .map_err(|e| PolicyEngineError::internal(format!("Failed to issue attestation challenge: {}", e)).logged())?)
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_attestation_trust_anchors(&mut self) {
+        self.attestation = None;
+    }
+}
+
+impl PolicyEngine {
+    pub(crate) fn derive_device_trust(&self, context: &mut PolicyContext) {
+        let verifier = match &self.attestation {
+            Some(verifier) => verifier,
+            None => return,
+        };
+
+        let mut trust = None;
+        if let Some(token) = &context.device_posture_token {
+            match verifier.verify_posture(token, context) {
+                Ok(claims) => {
+                    let level = claims.get("device_trust").and_then(Value::as_str).unwrap_or("trusted");
+                    trust = Some(DeviceTrust::parse(level));
+                }
+                Err(e) if self.debug_mode => console_log!("Device posture token rejected: {}", e),
+                Err(_) => {}
+            }
+        }
+        if trust.is_none() {
+            if let Some(attestation) = &context.device_attestation {
+                match verifier.verify_webauthn(attestation, clock::now()) {
+                    Ok(()) => trust = Some(DeviceTrust::Trusted),
+                    Err(e) if self.debug_mode => console_log!("Device attestation rejected: {}", e),
+                    Err(_) => {}
+                }
+            }
+        }
+
+        context.device_attested = trust.is_some();
+        context.device_trust = trust.unwrap_or(DeviceTrust::Untrusted);
+        if let Some(fields) = context.supplied_fields.as_mut() {
+            fields.insert("device_attested".to_string());
+            fields.insert("device_trust".to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // Self-signed P-256 certificates, generated with openssl req -x509
+    const CA: &str = "MIIBgDCCASegAwIBAgIUTGPwFqMLg4OUBZTaYasF0/A+hrAwCgYIKoZIzj0EAwIwDTELMAkGA1UEAwwCY2EwIBcNMjYxMDE2MTQ0MzQwWhgPMjEyNjA5MjIxNDQzNDBaMA0xCzAJBgNVBAMMAmNhMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEvRfALIJZ5NtcKX4TjcEUT12sQYv5CMnrBVoUOZ9tdJsnsLHFZgugQ55zH15PUsOrK6lqX06aMj2T6z6Kl7rNSKNjMGEwHQYDVR0OBBYEFNDiQgyDCiXFso/sEsZyqaaQCKCLMB8GA1UdIwQYMBaAFNDiQgyDCiXFso/sEsZyqaaQCKCLMA8GA1UdEwEB/wQFMAMBAf8wDgYDVR0PAQH/BAQDAgEGMAoGCCqGSM49BAMCA0cAMEQCIHC8K7/G6kGmZ56O+IiJcilAXxSZmSWKwIadqUC7tGkPAiBqqvvgtsbtYENTqo1HLG5V/Y3mKRu1fwhpW6r7Y384pg==";
+    const LEAF: &str = "MIIBgjCCASigAwIBAgIUNPgyddH1LNQe/RCGXTN2UjFq4nEwCgYIKoZIzj0EAwIwDzENMAsGA1UEAwwEbGVhZjAgFw0yNjEwMTYxNDQzNDBaGA8yMTI2MDkyMjE0NDM0MFowDzENMAsGA1UEAwwEbGVhZjBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABL0XwCyCWeTbXCl+E43BFE9drEGL+QjJ6wVaFDmfbXSbJ7CxxWYLoEOecx9eT1LDqyupal9OmjI9k+s+ipe6zUijYDBeMB0GA1UdDgQWBBTQ4kIMgwolxbKP7BLGcqmmkAigizAfBgNVHSMEGDAWgBTQ4kIMgwolxbKP7BLGcqmmkAigizAMBgNVHRMBAf8EAjAAMA4GA1UdDwEB/wQEAwIHgDAKBggqhkjOPQQDAgNIADBFAiEAyTdCOLWbUgDnApcDYUjP1iJFA474V/dSttU/6ezm3XwCIA1tCyUaLYb5WrfIqao6/FipxZ3/7ks8irfdfsjNDz7l";
+    const NO_KEY_USAGE: &str = "MIIBezCCASGgAwIBAgIUdTvU22I/lzNX8G/klWa1gF82YMcwCgYIKoZIzj0EAwIwEjEQMA4GA1UEAwwHbm91c2FnZTAgFw0yNjEwMTYxNDQzNDBaGA8yMTI2MDkyMjE0NDM0MFowEjEQMA4GA1UEAwwHbm91c2FnZTBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABL0XwCyCWeTbXCl+E43BFE9drEGL+QjJ6wVaFDmfbXSbJ7CxxWYLoEOecx9eT1LDqyupal9OmjI9k+s+ipe6zUijUzBRMB0GA1UdDgQWBBTQ4kIMgwolxbKP7BLGcqmmkAigizAfBgNVHSMEGDAWgBTQ4kIMgwolxbKP7BLGcqmmkAigizAPBgNVHRMBAf8EBTADAQH/MAoGCCqGSM49BAMCA0gAMEUCICiQh+b9ZN1MBuJ0TW/xmDlOHDl/8DOT9iTZ67NXe0KGAiEAzPJeGud+4XY8iF9OPhipR/LgJbC8qvbFGYOXm7AS/+Q=";
+
+    fn certificate(base64: &str) -> Certificate {
+        Certificate::from_der(&STANDARD.decode(base64).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn issuers_must_be_certificate_signing_cas() {
+        assert!(check_issuer(&certificate(CA)).is_ok());
+        assert!(check_issuer(&certificate(LEAF)).is_err());
+        assert!(check_issuer(&certificate(NO_KEY_USAGE)).is_err());
+    }
+
+    #[test]
+    fn device_bound_tokens_need_the_requesting_device() {
+        let claims = json!({ "device_id": "laptop-1" });
+        assert!(check_device(&claims, "laptop-1").is_ok());
+        assert!(check_device(&claims, "laptop-2").is_err());
+        assert!(check_device(&claims, "").is_err());
+        assert!(check_device(&json!({ "sub": "laptop-1" }), "").is_err());
+
+        assert!(check_device(&json!({}), "").is_ok());
+        assert!(check_device(&json!({}), "laptop-1").is_err());
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+
+use crate::expr::Value;
+use crate::PolicyContext;
+
+// Maps an attribute path used in policy expressions onto a PolicyContext
+// field name plus any remaining segments (for free-form maps). Both flat
+// field names (`risk_score`, `user_roles`) and dotted category paths
+// (`user.roles`, `resource.classification`) are accepted.
+fn canonical_field<'a>(segments: &'a [&'a str]) -> Option<(&'static str, &'a [&'a str])> {
+    let field = match segments {
+        ["request_id"] | ["request", "id"] => "request_id",
+        ["timestamp"] | ["request", "timestamp"] => "timestamp",
+        ["operation"] | ["action"] | ["request", "operation"] => "operation",
+
+        ["user_id"] | ["user", "id"] => "user_id",
+        ["user_roles"] | ["user", "roles"] => "user_roles",
+        ["user_groups"] | ["user", "groups"] => "user_groups",
+        ["user_attributes", rest @ ..] | ["user", "attributes", rest @ ..] => {
+            return Some(("user_attributes", rest));
+        }
+        ["user", "clearance"] => return Some(("user_attributes", &["clearance"])),
+
+        ["device_id"] | ["device", "id"] => "device_id",
+        ["device_type"] | ["device", "type"] => "device_type",
+        ["device_trust"] | ["device", "trust"] => "device_trust",
+        ["device_attested"] | ["device", "attested"] => "device_attested",
+
+        ["ip_address"] | ["ip", "address"] | ["network", "ip"] => "ip_address",
+        ["ip_country"] | ["ip", "country"] | ["network", "country"] => "ip_country",
+        ["ip_city"] | ["ip", "city"] | ["network", "city"] => "ip_city",
+        ["network_zone"] | ["network", "zone"] => "network_zone",
+        ["vpn_detected"] | ["network", "vpn"] | ["vpn", "detected"] => "vpn_detected",
+
+        ["session_id"] | ["session", "id"] => "session_id",
+        ["session_age"] | ["session", "age"] => "session_age",
+        ["auth_method"] | ["session", "auth_method"] => "auth_method",
+        ["mfa_verified"] | ["mfa", "verified"] | ["session", "mfa_verified"] => "mfa_verified",
+
+        ["time_of_day"] | ["environment", "time_of_day"] => "time_of_day",
+        ["day_of_week"] | ["environment", "day_of_week"] => "day_of_week",
+        ["business_hours"] | ["environment", "business_hours"] => "business_hours",
+
+        ["risk_score"] | ["risk", "score"] => "risk_score",
+        ["threat_level"] | ["risk", "threat_level"] => "threat_level",
+
+        ["resource_type"] | ["resource", "type"] => "resource_type",
+        ["resource_id"] | ["resource", "id"] => "resource_id",
+        ["classification"] | ["resource_classification"] | ["resource", "classification"] => {
+            "resource_classification"
+        }
+        ["resource_owner"] | ["resource", "owner"] => "resource_owner",
+        ["resource_attributes", rest @ ..] | ["resource", "attributes", rest @ ..] => {
+            return Some(("resource_attributes", rest));
+        }
+        ["resource", "allowed_purposes"] => return Some(("resource_attributes", &["allowed_purposes"])),
+        // Capsule metadata from evaluate_lifecycle (see capsule.rs)
+        ["capsule", ..] => return Some(("resource_attributes", segments)),
+
+        ["intent_purpose"] | ["intent", "purpose"] => "intent_purpose",
+        ["intent_justification"] | ["intent", "justification"] => "intent_justification",
+        ["intent_duration"] | ["intent", "duration"] => "intent_duration",
+
+        ["constraints", rest @ ..] => return Some(("constraints", rest)),
+        ["metadata", rest @ ..] => return Some(("metadata", rest)),
+
+        _ => return None,
+    };
+    Some((field, &[]))
+}
+
+// Dotted canonical form of a path, so aliases such as `classification`
+// and `resource.classification` name the same attribute
+pub fn canonical_path(segments: &[&str]) -> String {
+    match canonical_field(segments) {
+        Some((field, [])) => field.to_string(),
+        Some((field, rest)) => format!("{}.{}", field, rest.join(".")),
+        None => segments.join("."),
+    }
+}
+
+// The top-level context field a path reads, if it names one
+pub fn context_field(segments: &[&str]) -> Option<&'static str> {
+    canonical_field(segments).map(|(field, _)| field)
+}
+
+impl PolicyContext {
+    pub fn attribute(&self, path: &[String]) -> Option<Value> {
+        let segments: Vec<&str> = path.iter().map(|s| s.as_str()).collect();
+        if let Some(value) = self.bag.as_ref().and_then(|bag| bag.attribute(&segments)) {
+            return Some(value);
+        }
+        let (field, rest) = canonical_field(&segments)?;
+
+        let value = match field {
+            "request_id" => text(&self.request_id),
+            "timestamp" => text(&self.timestamp.to_rfc3339()),
+            "operation" => text(&self.operation),
+            "user_id" => text(&self.user_id),
+            "user_roles" => list(&self.user_roles),
+            "user_groups" => list(&self.user_groups),
+            "user_attributes" => return nested(&self.user_attributes, rest),
+            "device_id" => text(&self.device_id),
+            "device_type" => text(&self.device_type),
+            "device_trust" => text(self.device_trust.as_str()),
+            "device_attested" => Value::Bool(self.device_attested),
+            "ip_address" => text(&self.ip_address),
+            "ip_country" => text(&self.ip_country),
+            "ip_city" => text(&self.ip_city),
+            "network_zone" => text(&self.network_zone),
+            "vpn_detected" => Value::Bool(self.vpn_detected),
+            "session_id" => text(&self.session_id),
+            "session_age" => Value::Number(self.session_age.num_seconds() as f64),
+            "auth_method" => text(&self.auth_method),
+            "mfa_verified" => Value::Bool(self.mfa_verified),
+            "time_of_day" => text(&self.time_of_day),
+            "day_of_week" => text(&self.day_of_week),
+            "business_hours" => Value::Bool(self.business_hours),
+            "risk_score" => Value::Number(self.risk_score),
+            "threat_level" => text(self.threat_level.as_str()),
+            "resource_type" => text(&self.resource_type),
+            "resource_id" => text(&self.resource_id),
+            "resource_classification" => text(&self.resource_classification),
+            "resource_owner" => text(&self.resource_owner),
+            "resource_attributes" => return nested(&self.resource_attributes, rest),
+            "intent_purpose" => optional_text(&self.intent_purpose),
+            "intent_justification" => optional_text(&self.intent_justification),
+            "intent_duration" => match &self.intent_duration {
+                Some(d) => Value::Number(d.num_seconds() as f64),
+                None => Value::Null,
+            },
+            "constraints" => return nested(&self.constraints, rest),
+            "metadata" => return nested(&self.metadata, rest),
+            _ => return None,
+        };
+
+        Some(value)
+    }
+
+    // Whether the attribute was actually supplied rather than defaulted.
+    // For map paths (`user.attributes.dept`) this checks the key exists.
+    pub fn has_attribute(&self, path: &[&str]) -> bool {
+        if let Some(bag) = self.bag.as_ref().filter(|bag| bag.is_category_path(path)) {
+            return !matches!(bag.attribute(path), Some(Value::Null) | None);
+        }
+
+        let (field, rest) = match canonical_field(path) {
+            Some(found) => found,
+            None => return false,
+        };
+
+        let supplied = match &self.supplied_fields {
+            Some(fields) => fields.contains(field),
+            None => true,
+        };
+        if !supplied {
+            return false;
+        }
+
+        let map = match field {
+            "user_attributes" => &self.user_attributes,
+            "resource_attributes" => &self.resource_attributes,
+            "constraints" => &self.constraints,
+            "metadata" => &self.metadata,
+            "intent_purpose" => return self.intent_purpose.is_some(),
+            "intent_justification" => return self.intent_justification.is_some(),
+            "intent_duration" => return self.intent_duration.is_some(),
+            _ => return true,
+        };
+        !matches!(nested(map, rest), Some(Value::Null) | None)
+    }
+}
+
+fn text(s: &str) -> Value {
+    Value::String(s.to_string())
+}
+
+fn optional_text(s: &Option<String>) -> Value {
+    match s {
+        Some(s) => Value::String(s.clone()),
+        None => Value::Null,
+    }
+}
+
+fn list(items: &[String]) -> Value {
+    Value::List(items.iter().map(|s| Value::String(s.clone())).collect())
+}
+
+// Free-form attribute maps: absent keys are known-but-missing (null)
+fn nested(map: &HashMap<String, serde_json::Value>, rest: &[&str]) -> Option<Value> {
+    let (first, tail) = match rest.split_first() {
+        Some(split) => split,
+        None => {
+            let object = map.iter().map(|(k, v)| (k.clone(), Value::from(v))).collect();
+            return Some(Value::Map(object));
+        }
+    };
+
+    let mut current = match map.get(*first) {
+        Some(v) => v,
+        None => return Some(Value::Null),
+    };
+    for segment in tail {
+        current = match current.get(*segment) {
+            Some(v) => v,
+            None => return Some(Value::Null),
+        };
+    }
+    Some(Value::from(current))
+}
@@ -0,0 +1,136 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Map;
+use std::collections::HashMap;
+
+use crate::expr::Value;
+use crate::error::PolicyEngineError;
+use crate::{guard, stats, PolicyContext, PolicyEngine, PolicyResult, PolicySelection};
+
+// Attributes that also populate the fixed PolicyContext fields, so risk
+// scoring, geo tracking, quotas and obligations keep working in bag mode
+const WELL_KNOWN: &[(&str, &str, &str)] = &[
+    ("subject", "id", "user_id"),
+    ("subject", "roles", "user_roles"),
+    ("subject", "groups", "user_groups"),
+    ("subject", "auth_method", "auth_method"),
+    ("subject", "mfa_verified", "mfa_verified"),
+    ("subject", "session_id", "session_id"),
+    ("resource", "id", "resource_id"),
+    ("resource", "type", "resource_type"),
+    ("resource", "classification", "resource_classification"),
+    ("resource", "owner", "resource_owner"),
+    ("action", "id", "operation"),
+    ("action", "purpose", "intent_purpose"),
+    ("action", "justification", "intent_justification"),
+    ("environment", "request_id", "request_id"),
+    ("environment", "timestamp", "timestamp"),
+    ("environment", "ip_address", "ip_address"),
+    ("environment", "ip_country", "ip_country"),
+    ("environment", "ip_city", "ip_city"),
+    ("environment", "network_zone", "network_zone"),
+    ("environment", "vpn_detected", "vpn_detected"),
+    ("environment", "device_id", "device_id"),
+    ("environment", "device_type", "device_type"),
+    ("environment", "device_trust", "device_trust"),
+    ("environment", "device_attested", "device_attested"),
+    ("environment", "risk_score", "risk_score"),
+    ("environment", "threat_level", "threat_level"),
+    ("environment", "time_of_day", "time_of_day"),
+    ("environment", "day_of_week", "day_of_week"),
+    ("environment", "business_hours", "business_hours"),
+];
+
+// Free-form request attributes grouped by category. Policies address them
+// as `subject.department`, `resource.labels.region`, `environment.zone`
+// and so on; any vocabulary works without changing PolicyContext.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AttributeBag {
+    pub subject: HashMap<String, serde_json::Value>,
+    pub resource: HashMap<String, serde_json::Value>,
+    pub action: HashMap<String, serde_json::Value>,
+    pub environment: HashMap<String, serde_json::Value>,
+}
+
+impl AttributeBag {
+    fn category(&self, name: &str) -> Option<&HashMap<String, serde_json::Value>> {
+        match name {
+            "subject" => Some(&self.subject),
+            "resource" => Some(&self.resource),
+            "action" => Some(&self.action),
+            "environment" => Some(&self.environment),
+            _ => None,
+        }
+    }
+
+    // Category paths resolve against the bag only; a missing key is null.
+    // Anything else (flat field names, `user.*`) is left to PolicyContext.
+    pub fn attribute(&self, path: &[&str]) -> Option<Value> {
+        let (category, key, rest) = match path {
+            [category, key, rest @ ..] => (self.category(category)?, *key, rest),
+            _ => return None,
+        };
+
+        let mut current = match category.get(key) {
+            Some(v) => v,
+            None => return Some(Value::Null),
+        };
+        for segment in rest {
+            current = match current.get(*segment) {
+                Some(v) => v,
+                None => return Some(Value::Null),
+            };
+        }
+        Some(Value::from(current))
+    }
+
+    pub fn is_category_path(&self, path: &[&str]) -> bool {
+        path.len() >= 2 && self.category(path[0]).is_some()
+    }
+
+    // PolicyContext JSON built from the well-known attributes
+    fn context_fields(&self) -> Map<String, serde_json::Value> {
+        let mut fields = Map::new();
+        for (category, key, field) in WELL_KNOWN {
+            if let Some(value) = self.category(category).and_then(|c| c.get(*key)) {
+                fields.insert(field.to_string(), value.clone());
+            }
+        }
+        fields
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Evaluates a `{ subject, resource, action, environment }` attribute
+    // bag instead of a fixed-shape PolicyContext
+    #[wasm_bindgen]
+    pub fn evaluate_attributes(&mut self, attributes_json: &str) -> Result<PolicyResult, JsValue> {
+        if self.debug_mode {
+            console_log!("Starting attribute-bag policy evaluation");
+        }
+
+        let bag: AttributeBag = serde_json::from_str(attributes_json).map_err(|e| {
+
+            PolicyEngineError::parse(format!("Failed to parse attributes: {}", e)).logged()
+
+        })?;
+
+        let context = self.bag_context(bag).map_err(PolicyEngineError::logged)?;
+        Ok(guard::guarded(|| self.evaluate_request(context, PolicySelection::Global)))
+    }
+}
+
+impl PolicyEngine {
+    // PolicyContext carrying the bag, with the well-known attributes
+    // copied into their fixed fields
+    pub(crate) fn bag_context(&self, bag: AttributeBag) -> Result<PolicyContext, PolicyEngineError> {
+        let started = stats::now_ms();
+        let context = self.check_context_value(serde_json::Value::Object(bag.context_fields()));
+        self.begin_profile(started);
+        let mut context = context?;
+        context.bag = Some(bag);
+        Ok(context)
+    }
+}
@@ -0,0 +1,201 @@
+use wasm_bindgen::prelude::*;
+use chrono::Timelike;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use crate::error::{to_json, PolicyEngineError};
+use crate::{PolicyContext, PolicyEngine};
+
+// Per-user behavioral baselines behind unusual_location(), unusual_time()
+// and unusual_resource(). Each user keeps a rolling window of their most
+// recent permitted requests; a request is unusual when its country, UTC
+// hour or resource makes up less than `min_share` of that window. Until a
+// user has `min_observations` requests nothing is unusual, so new users
+// are not challenged on every request while their baseline forms.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BaselineConfig {
+    // Requests remembered per user
+    pub window: usize,
+    pub min_observations: usize,
+    pub min_share: f64,
+    // Hours this close (either way, wrapping at midnight) count as the same
+    pub hour_tolerance: u32,
+}
+
+impl Default for BaselineConfig {
+    fn default() -> Self {
+        BaselineConfig { window: 200, min_observations: 20, min_share: 0.05, hour_tolerance: 1 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dimension {
+    Location,
+    Time,
+    Resource,
+}
+
+impl Dimension {
+    pub fn for_function(name: &str) -> Option<Dimension> {
+        match name {
+            "unusual_location" => Some(Dimension::Location),
+            "unusual_time" => Some(Dimension::Time),
+            "unusual_resource" => Some(Dimension::Resource),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Observation {
+    country: String,
+    hour: u32,
+    resource: String,
+}
+
+impl Observation {
+    fn of(context: &PolicyContext) -> Observation {
+        Observation {
+            country: context.ip_country.to_uppercase(),
+            hour: context.timestamp.hour(),
+            resource: resource_key(context),
+        }
+    }
+}
+
+// "type/id", or just the type for requests without a resource id
+fn resource_key(context: &PolicyContext) -> String {
+    match context.resource_id.as_str() {
+        "" => context.resource_type.clone(),
+        id => format!("{}/{}", context.resource_type, id),
+    }
+}
+
+fn hour_distance(a: u32, b: u32) -> u32 {
+    let diff = a.abs_diff(b);
+    diff.min(24 - diff)
+}
+
+// What get_user_baseline reports
+#[derive(Debug, Serialize)]
+pub struct BaselineSummary {
+    pub observations: usize,
+    pub countries: BTreeMap<String, usize>,
+    pub hours: BTreeMap<u32, usize>,
+    pub resources: BTreeMap<String, usize>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct BaselineTracker {
+    config: BaselineConfig,
+    users: HashMap<String, VecDeque<Observation>>,
+}
+
+impl BaselineTracker {
+    pub fn record(&mut self, user_key: &str, context: &PolicyContext) {
+        if context.user_id.is_empty() {
+            return;
+        }
+        let history = self.users.entry(user_key.to_string()).or_default();
+        history.push_back(Observation::of(context));
+        while history.len() > self.config.window {
+            history.pop_front();
+        }
+    }
+
+    pub fn unusual(&self, user_key: &str, dimension: Dimension, context: &PolicyContext) -> bool {
+        let Some(history) = self.users.get(user_key) else { return false };
+        if history.len() < self.config.min_observations.max(1) {
+            return false;
+        }
+        let current = Observation::of(context);
+        // A request without a location says nothing about it
+        if dimension == Dimension::Location && current.country.is_empty() {
+            return false;
+        }
+        let matching = history
+            .iter()
+            .filter(|seen| match dimension {
+                Dimension::Location => seen.country == current.country,
+                Dimension::Time => hour_distance(seen.hour, current.hour) <= self.config.hour_tolerance,
+                Dimension::Resource => seen.resource == current.resource,
+            })
+            .count();
+        (matching as f64) < self.config.min_share * history.len() as f64
+    }
+
+    fn summary(&self, user_key: &str) -> Option<BaselineSummary> {
+        let history = self.users.get(user_key)?;
+        let mut summary = BaselineSummary {
+            observations: history.len(),
+            countries: BTreeMap::new(),
+            hours: BTreeMap::new(),
+            resources: BTreeMap::new(),
+        };
+        for seen in history {
+            if !seen.country.is_empty() {
+                *summary.countries.entry(seen.country.clone()).or_default() += 1;
+            }
+            *summary.hours.entry(seen.hour).or_default() += 1;
+            *summary.resources.entry(seen.resource.clone()).or_default() += 1;
+        }
+        Some(summary)
+    }
+
+    // A smaller window applies to existing histories right away
+    fn configure(&mut self, config: BaselineConfig) {
+        for history in self.users.values_mut() {
+            while history.len() > config.window {
+                history.pop_front();
+            }
+        }
+        self.config = config;
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    #[wasm_bindgen]
+    pub fn set_baseline_config(&mut self, config_json: &str) -> Result<(), JsValue> {
+        let config: BaselineConfig = serde_json::from_str(config_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse baseline config: {}", e)).logged())?;
+        if config.window == 0 {
+            return Err(PolicyEngineError::validation("Baseline window must be at least 1")
+                .with_details(json!({ "field": "window", "value": config.window }))
+                .logged()
+                .into());
+        }
+        if !(0.0..=1.0).contains(&config.min_share) {
+            return Err(PolicyEngineError::validation("Baseline min_share must be in [0, 1]")
+                .with_details(json!({ "field": "min_share", "value": config.min_share }))
+                .logged()
+                .into());
+        }
+        self.baselines.configure(config);
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn get_baseline_config(&self) -> Result<String, JsValue> {
+        Ok(to_json(&self.baselines.config)?)
+    }
+
+    // Counts per country, UTC hour and resource in a user's window, or
+    // "null" for users without history
+    #[wasm_bindgen]
+    pub fn get_user_baseline(&self, user_id: &str) -> Result<String, JsValue> {
+        Ok(to_json(&self.baselines.summary(user_id))?)
+    }
+
+    #[wasm_bindgen]
+    pub fn forget_user_baseline(&mut self, user_id: &str) {
+        self.baselines.users.remove(user_id);
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_baselines(&mut self) {
+        self.baselines.users.clear();
+    }
+}
@@ -0,0 +1,46 @@
+use wasm_bindgen::prelude::*;
+
+use crate::error::{to_json, PolicyEngineError};
+use crate::expr::simd;
+use crate::{guard, stats, PolicyEngine, PolicyResult, PolicySelection};
+
+// Whether this module was built with wasm SIMD (see build.sh). Hosts that
+// load the SIMD build after feature detection can assert it here.
+#[wasm_bindgen]
+pub fn simd_enabled() -> bool {
+    simd::ENABLED
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Evaluates a JSON array of contexts in order, exactly as repeated
+    // `evaluate` calls would (stateful effects included), without a JS
+    // round trip per request. Returns a JSON array of PolicyResult, one per
+    // context; a context that fails to parse or validate yields an
+    // INDETERMINATE result with its error code instead of failing the batch.
+    #[wasm_bindgen]
+    pub fn evaluate_batch(&mut self, contexts_json: &str) -> Result<String, JsValue> {
+        let contexts: Vec<serde_json::Value> = serde_json::from_str(contexts_json).map_err(|e| {
+            PolicyEngineError::parse(format!("Failed to parse context batch: {}", e)).logged()
+        })?;
+        let results = self.evaluate_all(contexts);
+        Ok(to_json(&results)?)
+    }
+}
+
+impl PolicyEngine {
+    fn evaluate_all(&mut self, contexts: Vec<serde_json::Value>) -> Vec<PolicyResult> {
+        contexts
+            .into_iter()
+            .map(|raw| {
+                let started = stats::now_ms();
+                let context = self.check_context_value(raw);
+                self.begin_profile(started);
+                match context {
+                    Ok(context) => guard::guarded(|| self.evaluate_request(context, PolicySelection::Global)),
+                    Err(error) => PolicyResult::failure(&error.logged()),
+                }
+            })
+            .collect()
+    }
+}
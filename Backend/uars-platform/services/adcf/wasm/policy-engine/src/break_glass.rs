@@ -0,0 +1,253 @@
+use wasm_bindgen::prelude::*;
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::clock;
+use crate::decision::Decision;
+use crate::environment::format_time;
+use crate::error::{to_json, PolicyEngineError};
+use crate::functions::EvalScope;
+use crate::logging::LogLevel;
+use crate::templates::literal;
+use crate::{guard, PolicyContext, PolicyEngine, PolicyResult, PolicySelection};
+
+pub const DEFAULT_BREAK_GLASS_SECONDS: f64 = 3600.0;
+pub const MAX_BREAK_GLASS_SECONDS: f64 = 7.0 * 86_400.0;
+
+// Entries kept until drained; the oldest are dropped beyond this
+pub const MAX_BREAK_GLASS_ENTRIES: usize = 10_000;
+
+// Obligations attached to every break-glass PERMIT
+pub const BREAK_GLASS_ALERT_OBLIGATION: &str = "break_glass_alert";
+pub const RECORD_JUSTIFICATION_OBLIGATION: &str = "record_justification";
+pub const BREAK_GLASS_UNTIL_OBLIGATION: &str = "break_glass_until";
+
+// Audit entry for every break-glass attempt, granted or not
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BreakGlassEntry {
+    pub timestamp: DateTime<Utc>,
+    pub request_id: String,
+    pub user_id: String,
+    pub operation: String,
+    pub resource_id: String,
+    pub justification: String,
+    pub granted: bool,
+    // Decision and reason the override replaced (or kept)
+    pub original_decision: Decision,
+    pub original_reason: String,
+    pub policy_id: Option<String>,
+    pub rule_id: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+fn check_duration(seconds: f64) -> Result<f64, PolicyEngineError> {
+    if !seconds.is_finite() || seconds <= 0.0 || seconds > MAX_BREAK_GLASS_SECONDS {
+        return Err(PolicyEngineError::validation(format!(
+            "Break-glass duration must be positive and at most {} seconds",
+            MAX_BREAK_GLASS_SECONDS
+        ))
+        .with_details(serde_json::json!({ "seconds": seconds })));
+    }
+    Ok(seconds)
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Lifetime of a break-glass PERMIT
+    #[wasm_bindgen]
+    pub fn set_break_glass_duration(&mut self, seconds: f64) -> Result<(), JsValue> {
+        self.break_glass_seconds = check_duration(seconds).map_err(PolicyEngineError::logged)?;
+        Ok(())
+    }
+
+    // Evaluates like `evaluate`; a DENY produced by a rule marked
+    // `break_glass` becomes a PERMIT valid for the break-glass duration,
+    // carrying alert, justification and expiry obligations. Any other
+    // decision is returned unchanged. Every call is audited.
+    #[wasm_bindgen]
+    pub fn evaluate_break_glass(&mut self, context_json: &str, justification: &str) -> Result<PolicyResult, JsValue> {
+        let justification = justification.trim();
+        if justification.is_empty() {
+            return Err(PolicyEngineError::validation("Break-glass access requires a justification").logged().into());
+        }
+
+        let context = self.parse_context(context_json)?;
+        Ok(guard::guarded(|| self.break_glass_request(context, justification)))
+    }
+
+    // JSON array of BreakGlassEntry, oldest first; only the latest
+    // MAX_BREAK_GLASS_ENTRIES are kept between drains
+    #[wasm_bindgen]
+    pub fn get_break_glass_log(&self) -> Result<String, JsValue> {
+        Ok(to_json(&self.break_glass_log)?)
+    }
+
+    // Returns the log like `get_break_glass_log` and empties it, for
+    // shipping entries to durable storage
+    #[wasm_bindgen]
+    pub fn drain_break_glass_log(&mut self) -> Result<String, JsValue> {
+        let json = to_json(&self.break_glass_log)?;
+        self.break_glass_log.clear();
+        Ok(json)
+    }
+}
+
+impl PolicyEngine {
+    fn break_glass_request(&mut self, mut context: PolicyContext, justification: &str) -> Result<PolicyResult, JsValue> {
+        self.enrich_context(&mut context, None);
+        let _redacting = self.redact_logs(&context);
+        let original = self.evaluate_context(&context, PolicySelection::Global)?;
+
+        // The engine clock, not the caller's timestamp, bounds the override
+        let now = clock::now();
+        let expires = TimeDelta::try_milliseconds((self.break_glass_seconds * 1000.0) as i64)
+            .and_then(|duration| now.checked_add_signed(duration));
+        let overridable = expires.is_some()
+            && original.decision == Decision::Deny
+            && self.is_break_glass_rule(&original)
+            && self.denied_only_by_break_glass(&context);
+        let mut entry = BreakGlassEntry {
+            timestamp: now,
+            request_id: context.request_id.clone(),
+            user_id: context.user_id.clone(),
+            operation: context.operation.clone(),
+            resource_id: context.resource_id.clone(),
+            justification: justification.to_string(),
+            granted: overridable,
+            original_decision: original.decision,
+            original_reason: original.reason.to_string(),
+            policy_id: original.policy_id.clone(),
+            rule_id: original.rule_id.clone(),
+            expires_at: None,
+        };
+
+        let result = if let (true, Some(expires)) = (overridable, expires) {
+            entry.expires_at = Some(expires);
+            log_at!(
+                LogLevel::Warn,
+                "Break-glass access granted to '{}' for '{}': {}",
+                context.user_id,
+                context.resource_id,
+                justification
+            );
+
+            let mut permit = PolicyResult::new(
+                Decision::Permit,
+                format!("Break-glass override of: {}", original.reason),
+                original.confidence,
+            );
+            permit.policy_id = original.policy_id.clone();
+            permit.rule_id = original.rule_id.clone();
+            let obligations = vec![
+                BREAK_GLASS_ALERT_OBLIGATION.to_string(),
+                format!("{}({})", RECORD_JUSTIFICATION_OBLIGATION, literal(&serde_json::json!(justification))),
+                format!("{}({})", BREAK_GLASS_UNTIL_OBLIGATION, literal(&serde_json::json!(format_time(expires)))),
+            ];
+            permit.obligations = serde_json::to_string(&obligations).unwrap_or_else(|_| "[]".to_string()).into();
+            permit.valid_until = Some(format_time(expires));
+            permit
+        } else {
+            original
+        };
+
+        if let Some(redactor) = &self.redactor {
+            entry.user_id = redactor.field("user_id", &entry.user_id);
+            entry.resource_id = redactor.field("resource_id", &entry.resource_id);
+            entry.justification = redactor.scrub(&entry.justification, &context);
+            entry.original_reason = redactor.scrub(&entry.original_reason, &context);
+        }
+        if self.break_glass_log.len() >= MAX_BREAK_GLASS_ENTRIES {
+            self.break_glass_log.pop_front();
+        }
+        self.break_glass_log.push_back(entry);
+        self.complete_request(Ok(result), &context, None)
+    }
+
+    // Whether every DENY rule that matches (or fails to evaluate) in an
+    // applicable policy is a break-glass rule. The combined result names
+    // only one of them, and an ordinary DENY must never be overridden
+    // because a break-glass rule happened to win the combination.
+    fn denied_only_by_break_glass(&self, context: &PolicyContext) -> bool {
+        let scope = EvalScope::new(self, context, None);
+        self.policies
+            .iter()
+            .filter(|compiled| self.policy_in_effect(compiled))
+            .filter(|compiled| self.evaluate_expression(&compiled.target, &scope).unwrap_or(false))
+            .flat_map(|compiled| compiled.rules())
+            .filter(|(rule, _)| self.in_deployment(&rule.environments) && rule.effect.decision() == Decision::Deny)
+            .all(|(rule, condition)| rule.break_glass || matches!(self.evaluate_expression(condition, &scope), Ok(false)))
+    }
+
+    fn is_break_glass_rule(&self, result: &PolicyResult) -> bool {
+        let (policy_id, rule_id) = match (&result.policy_id, &result.rule_id) {
+            (Some(policy_id), Some(rule_id)) => (policy_id, rule_id),
+            _ => return false,
+        };
+        self.policies
+            .iter()
+            .filter(|compiled| &compiled.policy.id == policy_id)
+            .flat_map(|compiled| compiled.rules())
+            .any(|(rule, _)| &rule.id == rule_id && rule.break_glass)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn engine_with(rules: serde_json::Value) -> PolicyEngine {
+        let policy = serde_json::from_value(json!({
+            "id": "p1",
+            "name": "p1",
+            "version": "1.0.0",
+            "description": "",
+            "target": "true",
+            "combining_algorithm": "deny-overrides",
+            "rules": rules,
+            "obligations": [],
+            "advice": []
+        }))
+        .unwrap();
+        let mut engine = PolicyEngine::new();
+        let compiled = engine.compile_policy(policy).unwrap();
+        engine.policies.push(compiled);
+        engine
+    }
+
+    fn deny(id: &str, break_glass: bool) -> serde_json::Value {
+        json!({
+            "id": id, "name": id, "description": "", "priority": 0, "condition": "true",
+            "effect": "DENY", "obligations": [], "advice": [], "break_glass": break_glass
+        })
+    }
+
+    fn break_glass(engine: &mut PolicyEngine) -> PolicyResult {
+        engine.break_glass_request(PolicyContext::default(), "incident").unwrap()
+    }
+
+    #[test]
+    fn overrides_a_break_glass_deny() {
+        let mut engine = engine_with(json!([deny("bg", true)]));
+        assert_eq!(break_glass(&mut engine).decision, Decision::Permit);
+        assert!(engine.break_glass_log[0].granted);
+    }
+
+    #[test]
+    fn caps_the_break_glass_duration() {
+        let mut engine = engine_with(json!([deny("bg", true)]));
+        assert!(check_duration(1e300).is_err());
+        assert!(check_duration(f64::NAN).is_err());
+        engine.break_glass_seconds = check_duration(MAX_BREAK_GLASS_SECONDS).unwrap();
+        assert_eq!(break_glass(&mut engine).decision, Decision::Permit);
+    }
+
+    #[test]
+    fn keeps_a_deny_when_any_matching_deny_is_not_break_glass() {
+        for rules in [json!([deny("terminated", false), deny("bg", true)]), json!([deny("bg", true), deny("terminated", false)])] {
+            let mut engine = engine_with(rules);
+            assert_eq!(break_glass(&mut engine).decision, Decision::Deny);
+            assert!(!engine.break_glass_log[0].granted);
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::replay::ENGINE_VERSION;
+
+// Output of `build_info`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildInfo {
+    pub version: String,
+    pub features: Vec<String>,
+    pub simd: bool,
+    pub panic_hook: bool,
+    pub allocator: String,
+    // "full" with crypto, geo and telemetry, "minimal" with none of them,
+    // "standard" otherwise. The crypto dependencies dominate module size.
+    pub size_tier: String,
+}
+
+// Cargo features this module was compiled with
+fn enabled_features() -> Vec<String> {
+    [
+        ("async", cfg!(feature = "async")),
+        ("console_error_panic_hook", cfg!(feature = "console_error_panic_hook")),
+        ("crypto", cfg!(feature = "crypto")),
+        ("fuzz", cfg!(feature = "fuzz")),
+        ("geo", cfg!(feature = "geo")),
+        ("mmdb", cfg!(feature = "mmdb")),
+        ("onnx", cfg!(feature = "onnx")),
+        ("persistence", cfg!(feature = "persistence")),
+        ("proto", cfg!(feature = "proto")),
+        ("regex", cfg!(feature = "regex")),
+        ("sync", cfg!(feature = "sync")),
+        ("telemetry", cfg!(feature = "telemetry")),
+        ("wee_alloc", cfg!(feature = "wee_alloc")),
+        ("yaml", cfg!(feature = "yaml")),
+    ]
+    .into_iter()
+    .filter(|(_, enabled)| *enabled)
+    .map(|(name, _)| name.to_string())
+    .collect()
+}
+
+fn size_tier() -> &'static str {
+    let subsystems = [cfg!(feature = "crypto"), cfg!(feature = "geo"), cfg!(feature = "telemetry")];
+    if subsystems.iter().all(|enabled| *enabled) {
+        "full"
+    } else if subsystems.iter().any(|enabled| *enabled) {
+        "standard"
+    } else {
+        "minimal"
+    }
+}
+
+// JSON BuildInfo, so hosts can check a module offers what they call
+// before relying on it
+#[wasm_bindgen]
+pub fn build_info() -> String {
+    let info = BuildInfo {
+        version: ENGINE_VERSION.to_string(),
+        features: enabled_features(),
+        simd: crate::expr::simd::ENABLED,
+        panic_hook: cfg!(feature = "console_error_panic_hook"),
+        allocator: if cfg!(feature = "wee_alloc") { "wee_alloc" } else { "default" }.to_string(),
+        size_tier: size_tier().to_string(),
+    };
+    serde_json::to_string(&info).unwrap_or_default()
+}
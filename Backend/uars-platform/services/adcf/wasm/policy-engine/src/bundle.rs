@@ -0,0 +1,274 @@
+use wasm_bindgen::prelude::*;
+#[cfg(feature = "crypto")]
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+#[cfg(feature = "crypto")]
+use aes_gcm::Aes256Gcm;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "crypto")]
+use zeroize::Zeroizing;
+
+use crate::error::PolicyEngineError;
+use crate::format;
+use crate::{CompiledPolicy, Policy, PolicyEngine};
+
+const BUNDLE_MAGIC: &str = "uars-policy-bundle";
+const BUNDLE_FORMAT_VERSION: u32 = 1;
+#[cfg(feature = "crypto")]
+const ENCRYPTED_BUNDLE_MAGIC: &str = "uars-policy-bundle-encrypted";
+#[cfg(feature = "crypto")]
+const NONCE_LEN: usize = 12;
+
+// CBOR policy bundle. Policies are stored together with their parsed
+// target and condition expressions so loading skips JSON and expression
+// parsing entirely.
+#[derive(Serialize, Deserialize)]
+struct PolicyBundle {
+    magic: String,
+    format_version: u32,
+    policies: Vec<CompiledPolicy>,
+}
+
+// AES-256-GCM envelope around an encoded PolicyBundle. The magic is
+// authenticated as associated data.
+#[cfg(feature = "crypto")]
+#[derive(Serialize, Deserialize)]
+struct EncryptedBundle {
+    magic: String,
+    format_version: u32,
+    #[serde(with = "serde_bytes_vec")]
+    nonce: Vec<u8>,
+    #[serde(with = "serde_bytes_vec")]
+    ciphertext: Vec<u8>,
+}
+
+// CBOR byte strings rather than arrays of integers
+#[cfg(any(feature = "crypto", feature = "persistence"))]
+pub(crate) mod serde_bytes_vec {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(bytes)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        ciborium::Value::deserialize(deserializer)?
+            .into_bytes()
+            .map_err(|_| serde::de::Error::custom("expected a byte string"))
+    }
+}
+
+#[cfg(feature = "crypto")]
+pub fn bundle_cipher(key: &[u8]) -> Result<Aes256Gcm, PolicyEngineError> {
+    Aes256Gcm::new_from_slice(key)
+        .map_err(|_| PolicyEngineError::validation(format!("Bundle key must be 32 bytes, got {}", key.len())))
+}
+
+#[cfg(feature = "crypto")]
+fn encrypt(cipher: &Aes256Gcm, plaintext: &[u8]) -> Result<Vec<u8>, PolicyEngineError> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let payload = Payload { msg: plaintext, aad: ENCRYPTED_BUNDLE_MAGIC.as_bytes() };
+    let ciphertext = cipher
+        .encrypt(&nonce, payload)
+        .map_err(|_| PolicyEngineError::internal("Failed to encrypt bundle"))?;
+
+    let envelope = EncryptedBundle {
+        magic: ENCRYPTED_BUNDLE_MAGIC.to_string(),
+        format_version: BUNDLE_FORMAT_VERSION,
+        nonce: nonce.to_vec(),
+        ciphertext,
+    };
+    let mut bytes = Vec::new();
+    ciborium::into_writer(&envelope, &mut bytes)
+        .map_err(|e| PolicyEngineError::internal(format!("Failed to encode bundle: {}", e)))?;
+    Ok(bytes)
+}
+
+// The plaintext is wiped as soon as it has been decoded
+#[cfg(feature = "crypto")]
+fn decrypt_and_decode(cipher: &Aes256Gcm, bytes: &[u8]) -> Result<Vec<CompiledPolicy>, PolicyEngineError> {
+    let envelope: EncryptedBundle = ciborium::from_reader(bytes).map_err(|e| PolicyEngineError::parse(e.to_string()))?;
+    if envelope.magic != ENCRYPTED_BUNDLE_MAGIC {
+        return Err(PolicyEngineError::parse("not an encrypted policy bundle"));
+    }
+    if envelope.format_version != BUNDLE_FORMAT_VERSION || envelope.nonce.len() != NONCE_LEN {
+        return Err(PolicyEngineError::parse(format!(
+            "unsupported encrypted bundle format version {}",
+            envelope.format_version
+        )));
+    }
+
+    let payload = Payload { msg: &envelope.ciphertext, aad: ENCRYPTED_BUNDLE_MAGIC.as_bytes() };
+    let plaintext = cipher
+        .decrypt(envelope.nonce.as_slice().into(), payload)
+        .map(Zeroizing::new)
+        .map_err(|_| PolicyEngineError::validation("Bundle decryption failed (wrong key or tampered bundle)"))?;
+    decode(&plaintext)
+}
+
+pub(crate) fn encode(policies: Vec<CompiledPolicy>) -> Result<Vec<u8>, PolicyEngineError> {
+    let bundle = PolicyBundle {
+        magic: BUNDLE_MAGIC.to_string(),
+        format_version: BUNDLE_FORMAT_VERSION,
+        policies,
+    };
+    let mut bytes = Vec::new();
+    ciborium::into_writer(&bundle, &mut bytes)
+        .map_err(|e| PolicyEngineError::internal(format!("Failed to encode bundle: {}", e)))?;
+    Ok(bytes)
+}
+
+pub(crate) fn decode(bytes: &[u8]) -> Result<Vec<CompiledPolicy>, PolicyEngineError> {
+    let bundle: PolicyBundle = ciborium::from_reader(bytes).map_err(|e| PolicyEngineError::parse(e.to_string()))?;
+    if bundle.magic != BUNDLE_MAGIC {
+        return Err(PolicyEngineError::parse("not a policy bundle"));
+    }
+    if bundle.format_version != BUNDLE_FORMAT_VERSION {
+        return Err(PolicyEngineError::parse(format!(
+            "unsupported bundle format version {} (expected {})",
+            bundle.format_version, BUNDLE_FORMAT_VERSION
+        )));
+    }
+    Ok(bundle.policies)
+}
+
+// Compiles a JSON policy array into a bundle. Intended for build
+// pipelines (native) but also exported to JS.
+#[wasm_bindgen]
+pub fn compile_bundle(policies_json: &str) -> Result<Vec<u8>, JsValue> {
+    Ok(compile_bundle_native(policies_json)?)
+}
+
+// Compiles and encrypts with a 32-byte AES-256-GCM key
+#[cfg(feature = "crypto")]
+#[wasm_bindgen]
+pub fn compile_encrypted_bundle(policies_json: &str, key: &[u8]) -> Result<Vec<u8>, JsValue> {
+    let cipher = bundle_cipher(key)?;
+    let plaintext = Zeroizing::new(compile_bundle_native(policies_json)?);
+    Ok(encrypt(&cipher, &plaintext)?)
+}
+
+pub fn compile_bundle_native(policies_json: &str) -> Result<Vec<u8>, PolicyEngineError> {
+    let policies: Vec<Policy> = format::parse_policy_json(policies_json, "policies")?;
+
+    let compiled = CompiledPolicy::compile_all(policies).map_err(|e| e.context("Failed to compile policy"))?;
+    encode(compiled)
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Appends the bundle's policies to the loaded set, linking their rule
+    // references against the engine's rule libraries
+    #[wasm_bindgen]
+    pub fn load_bundle(&mut self, bytes: &[u8]) -> Result<(), JsValue> {
+        let decoded = decode(bytes);
+        self.install_bundle(decoded)
+    }
+
+    // Bundles the currently loaded policies
+    #[wasm_bindgen]
+    pub fn export_bundle(&self) -> Result<Vec<u8>, JsValue> {
+        Ok(encode(self.policies.clone())?)
+    }
+}
+
+// Encrypted bundles need the crypto feature
+#[cfg(feature = "crypto")]
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Key for encrypted bundles, 32 bytes. Callers should drop their own
+    // copy once it is set.
+    #[wasm_bindgen]
+    pub fn set_bundle_key(&mut self, key: &[u8]) -> Result<(), JsValue> {
+        self.bundle_cipher = Some(bundle_cipher(key).map_err(|e| e.logged())?);
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_bundle_key(&mut self) {
+        self.bundle_cipher = None;
+    }
+
+    #[wasm_bindgen]
+    pub fn load_encrypted_bundle(&mut self, bytes: &[u8]) -> Result<(), JsValue> {
+        let decoded = match &self.bundle_cipher {
+            Some(cipher) => decrypt_and_decode(cipher, bytes),
+            None => Err(PolicyEngineError::invalid_state("No bundle key configured")),
+        };
+        self.install_bundle(decoded)
+    }
+
+    // Bundles and encrypts the loaded policies with the configured key
+    #[wasm_bindgen]
+    pub fn export_encrypted_bundle(&self) -> Result<Vec<u8>, JsValue> {
+        let cipher = self
+            .bundle_cipher
+            .as_ref()
+            .ok_or_else(|| PolicyEngineError::invalid_state("No bundle key configured").logged())?;
+        let plaintext = Zeroizing::new(encode(self.policies.clone())?);
+        Ok(encrypt(cipher, &plaintext)?)
+    }
+}
+
+impl PolicyEngine {
+    fn install_bundle(&mut self, decoded: Result<Vec<CompiledPolicy>, PolicyEngineError>) -> Result<(), JsValue> {
+        let decoded = decoded.and_then(|mut policies| {
+            for compiled in &mut policies {
+                self.link_rule_refs(compiled)?;
+                self.check_policy_vocabulary(compiled)?;
+            }
+            Ok(policies)
+        });
+        match decoded {
+            Ok(policies) => {
+                if self.debug_mode {
+                    for compiled in &policies {
+                        console_log!("Loaded policy: {} ({})", compiled.policy.name, compiled.policy.id);
+                    }
+                }
+                let count = policies.len();
+                let mut added = 0;
+                for compiled in policies {
+                    if self.insert_unique(compiled, None) {
+                        added += 1;
+                    }
+                }
+                console_log!("Loaded {} of {} policies", added, count);
+                Ok(())
+            }
+            Err(e) => Err(e.context("Failed to load bundle").logged().into()),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "crypto"))]
+mod tests {
+    use super::*;
+
+    fn bundle() -> Vec<u8> {
+        let policies = r#"[{
+            "id": "p1", "name": "p1", "version": "1.0.0", "description": "", "target": "true",
+            "combining_algorithm": "deny-overrides", "rules": [], "obligations": [], "advice": []
+        }]"#;
+        compile_bundle_native(policies).unwrap()
+    }
+
+    #[test]
+    fn encrypted_bundles_need_the_key_and_an_untouched_ciphertext() {
+        let cipher = bundle_cipher(&[7; 32]).unwrap();
+        let encrypted = encrypt(&cipher, &bundle()).unwrap();
+        let policies = decrypt_and_decode(&cipher, &encrypted).unwrap();
+        assert_eq!(policies[0].policy.id, "p1");
+
+        let other = bundle_cipher(&[8; 32]).unwrap();
+        assert_eq!(decrypt_and_decode(&other, &encrypted).unwrap_err().code(), "VALIDATION_ERROR");
+
+        let mut envelope: EncryptedBundle = ciborium::from_reader(encrypted.as_slice()).unwrap();
+        let last = envelope.ciphertext.len() - 1;
+        envelope.ciphertext[last] ^= 1;
+        let mut tampered = Vec::new();
+        ciborium::into_writer(&envelope, &mut tampered).unwrap();
+        assert_eq!(decrypt_and_decode(&cipher, &tampered).unwrap_err().code(), "VALIDATION_ERROR");
+
+        assert!(bundle_cipher(&[7; 16]).is_err());
+    }
+}
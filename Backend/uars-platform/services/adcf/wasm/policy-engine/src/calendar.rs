@@ -0,0 +1,340 @@
+use wasm_bindgen::prelude::*;
+use chrono::{DateTime, Datelike, Days, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Offset, TimeZone, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+use crate::error::{to_json, PolicyEngineError};
+use crate::expr::ExprError;
+use crate::{PolicyContext, PolicyEngine};
+
+// Daylight saving schemes a region can follow; IANA zones are not
+// available in the engine, so a region is a standard UTC offset plus one
+// of these
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DstRule {
+    #[default]
+    None,
+    // Second Sunday in March to the first Sunday in November, 02:00 local
+    Us,
+    // Last Sunday in March to the last Sunday in October, 01:00 UTC
+    Eu,
+}
+
+// Working time on some weekdays, from `start` up to (not including) `end`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkingHours {
+    pub days: Vec<Weekday>,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalendarRegion {
+    // Standard offset, e.g. "+01:00"
+    pub utc_offset: String,
+    #[serde(default)]
+    pub dst: DstRule,
+    pub working_hours: Vec<WorkingHours>,
+    // Local dates: "2026-12-25" once, "12-25" every year
+    #[serde(default)]
+    pub holidays: Vec<String>,
+}
+
+// One span of a named window: a one-off interval (a change freeze) or a
+// weekly span in a region's local time (a maintenance window), UTC
+// without a region and no default
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum WindowSpan {
+    Interval {
+        start: DateTime<FixedOffset>,
+        end: DateTime<FixedOffset>,
+    },
+    Weekly {
+        days: Vec<Weekday>,
+        start: NaiveTime,
+        end: NaiveTime,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        region: Option<String>,
+    },
+}
+
+// Business hours per region, behind is_business_hours():
+//
+//   { "default_region": "emea",
+//     "regions": { "emea": { "utc_offset": "+01:00", "dst": "eu",
+//       "working_hours": [{ "days": ["mon", "tue", "wed", "thu", "fri"],
+//                           "start": "09:00", "end": "17:30" }],
+//       "holidays": ["12-25", "2026-04-03"] } } }
+//
+// With a default region the engine also sets each request's
+// `business_hours` from its timestamp, replacing the caller's value.
+//
+// Named blackout windows, behind in_window(), go alongside:
+//
+//   "windows": { "change-freeze": [{ "start": "2026-12-18T00:00:00Z",
+//                                    "end": "2027-01-04T00:00:00Z" }],
+//                "maintenance": [{ "days": ["sun"], "start": "02:00",
+//                                  "end": "04:00", "region": "emea" }] }
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Calendar {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_region: Option<String>,
+    #[serde(default)]
+    pub regions: BTreeMap<String, CalendarRegion>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub windows: BTreeMap<String, Vec<WindowSpan>>,
+}
+
+fn parse_offset(text: &str) -> Option<FixedOffset> {
+    let text = text.trim();
+    let (sign, rest) = match text.as_bytes().first()? {
+        b'+' => (1, &text[1..]),
+        b'-' => (-1, &text[1..]),
+        _ => return None,
+    };
+    let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+    let (hours, minutes): (i32, i32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    if hours > 14 || minutes >= 60 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+enum Holiday {
+    Once(NaiveDate),
+    Yearly(u32, u32),
+}
+
+fn parse_holiday(text: &str) -> Option<Holiday> {
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return Some(Holiday::Once(date));
+    }
+    let (month, day) = text.split_once('-')?;
+    let (month, day) = (month.parse().ok()?, day.parse().ok()?);
+    // Checked against a leap year so 02-29 is accepted
+    NaiveDate::from_ymd_opt(2024, month, day).map(|_| Holiday::Yearly(month, day))
+}
+
+// The `n`th (from 1) given weekday of a month, or with `n` 0 the last
+fn nth_weekday(year: i32, month: u32, weekday: Weekday, n: u8) -> Option<NaiveDate> {
+    if n == 0 {
+        let next = if month == 12 { NaiveDate::from_ymd_opt(year + 1, 1, 1) } else { NaiveDate::from_ymd_opt(year, month + 1, 1) };
+        let last = next?.pred_opt()?;
+        let back = (7 + last.weekday().num_days_from_monday() - weekday.num_days_from_monday()) % 7;
+        return last.checked_sub_days(Days::new(back as u64));
+    }
+    NaiveDate::from_weekday_of_month_opt(year, month, weekday, n)
+}
+
+fn utc_at(date: NaiveDate, hour: u32, offset_seconds: i32) -> Option<DateTime<Utc>> {
+    let local = NaiveDateTime::new(date, NaiveTime::from_hms_opt(hour, 0, 0)?);
+    Some(Utc.from_utc_datetime(&local.checked_sub_signed(Duration::seconds(offset_seconds as i64))?))
+}
+
+impl CalendarRegion {
+    fn validate(&self, name: &str) -> Result<(), PolicyEngineError> {
+        let invalid = |field: &str, value: String| {
+            Err(PolicyEngineError::validation(format!("Calendar region '{}' has an invalid {}: {}", name, field, value))
+                .with_details(json!({ "region": name, "field": field, "value": value })))
+        };
+        if parse_offset(&self.utc_offset).is_none() {
+            return invalid("utc_offset", self.utc_offset.clone());
+        }
+        for hours in &self.working_hours {
+            if hours.days.is_empty() || hours.start >= hours.end {
+                return invalid("working_hours span (split overnight shifts in two)", format!("{}-{}", hours.start, hours.end));
+            }
+        }
+        if let Some(holiday) = self.holidays.iter().find(|holiday| parse_holiday(holiday.trim()).is_none()) {
+            return invalid("holiday", holiday.clone());
+        }
+        Ok(())
+    }
+
+    fn local_time(&self, at: DateTime<Utc>) -> NaiveDateTime {
+        at.with_timezone(&self.offset_at(at)).naive_local()
+    }
+
+    // Offset in effect at `at`, daylight saving included
+    fn offset_at(&self, at: DateTime<Utc>) -> FixedOffset {
+        let standard = parse_offset(&self.utc_offset).unwrap_or(Utc.fix());
+        let seconds = standard.local_minus_utc();
+        let year = standard.from_utc_datetime(&at.naive_utc()).year();
+        let window = match self.dst {
+            DstRule::None => None,
+            DstRule::Us => nth_weekday(year, 3, Weekday::Sun, 2)
+                .and_then(|start| utc_at(start, 2, seconds))
+                .zip(nth_weekday(year, 11, Weekday::Sun, 1).and_then(|end| utc_at(end, 2, seconds + 3600))),
+            DstRule::Eu => nth_weekday(year, 3, Weekday::Sun, 0)
+                .and_then(|start| utc_at(start, 1, 0))
+                .zip(nth_weekday(year, 10, Weekday::Sun, 0).and_then(|end| utc_at(end, 1, 0))),
+        };
+        match window {
+            Some((start, end)) if at >= start && at < end => FixedOffset::east_opt(seconds + 3600).unwrap_or(standard),
+            _ => standard,
+        }
+    }
+
+    pub fn is_holiday(&self, at: DateTime<Utc>) -> bool {
+        let date = self.local_time(at).date();
+        self.holidays.iter().filter_map(|holiday| parse_holiday(holiday.trim())).any(|holiday| match holiday {
+            Holiday::Once(day) => day == date,
+            Holiday::Yearly(month, day) => date.month() == month && date.day() == day,
+        })
+    }
+
+    pub fn is_business_hours(&self, at: DateTime<Utc>) -> bool {
+        let local = self.local_time(at);
+        !self.is_holiday(at)
+            && self.working_hours.iter().any(|hours| {
+                hours.days.contains(&local.weekday()) && hours.start <= local.time() && local.time() < hours.end
+            })
+    }
+}
+
+impl Calendar {
+    fn region(&self, function: &str, region: Option<&str>) -> Result<&CalendarRegion, ExprError> {
+        let name = region
+            .or(self.default_region.as_deref())
+            .ok_or_else(|| ExprError::new(format!("{}() needs a region: the calendar has no default", function)))?;
+        self.regions
+            .get(name)
+            .ok_or_else(|| ExprError::new(format!("{}(): unknown calendar region '{}'", function, name)))
+    }
+
+    fn validate(&self) -> Result<(), PolicyEngineError> {
+        for (name, region) in &self.regions {
+            region.validate(name)?;
+        }
+        if let Some(region) = self.default_region.as_ref().filter(|region| !self.regions.contains_key(*region)) {
+            return Err(PolicyEngineError::validation(format!("Default calendar region '{}' is not defined", region))
+                .with_details(json!({ "region": region })));
+        }
+        for (name, spans) in &self.windows {
+            validate_window(self, name, spans)?;
+        }
+        Ok(())
+    }
+
+    fn in_span(&self, span: &WindowSpan, at: DateTime<Utc>) -> bool {
+        match span {
+            WindowSpan::Interval { start, end } => *start <= at && at < *end,
+            WindowSpan::Weekly { days, start, end, region } => {
+                let region = region.as_deref().or(self.default_region.as_deref()).and_then(|name| self.regions.get(name));
+                let local = region.map_or(at.naive_utc(), |region| region.local_time(at));
+                days.contains(&local.weekday()) && *start <= local.time() && local.time() < *end
+            }
+        }
+    }
+}
+
+fn validate_window(calendar: &Calendar, name: &str, spans: &[WindowSpan]) -> Result<(), PolicyEngineError> {
+    let invalid = |problem: String| {
+        Err(PolicyEngineError::validation(format!("Window '{}' {}", name, problem)).with_details(json!({ "window": name })))
+    };
+    if name.trim().is_empty() {
+        return invalid("needs a name".to_string());
+    }
+    for span in spans {
+        match span {
+            WindowSpan::Interval { start, end } if start >= end => return invalid(format!("ends before it starts ({})", start)),
+            WindowSpan::Weekly { days, start, end, .. } if days.is_empty() || start >= end => {
+                return invalid(format!("has an empty weekly span {}-{} (split overnight spans in two)", start, end))
+            }
+            WindowSpan::Weekly { region: Some(region), .. } if !calendar.regions.contains_key(region) => {
+                return invalid(format!("uses unknown region '{}'", region))
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Replaces the business-hours calendar (see Calendar)
+    #[wasm_bindgen]
+    pub fn load_calendar(&mut self, calendar_json: &str) -> Result<(), JsValue> {
+        let calendar: Calendar = serde_json::from_str(calendar_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse calendar: {}", e)).logged())?;
+        calendar.validate().map_err(|e| e.logged())?;
+        if self.debug_mode {
+            console_log!("Loaded calendar with {} regions and {} windows", calendar.regions.len(), calendar.windows.len());
+        }
+        self.calendar = Some(calendar);
+        Ok(())
+    }
+
+    // Defines (or replaces) one named window, a JSON array of WindowSpan,
+    // without reloading the calendar, e.g. to declare a change freeze
+    #[wasm_bindgen]
+    pub fn set_window(&mut self, name: &str, spans_json: &str) -> Result<(), JsValue> {
+        let spans: Vec<WindowSpan> = serde_json::from_str(spans_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse window '{}': {}", name, e)).logged())?;
+        let calendar = self.calendar.get_or_insert_with(Calendar::default);
+        validate_window(calendar, name, &spans).map_err(|e| e.logged())?;
+        calendar.windows.insert(name.to_string(), spans);
+        Ok(())
+    }
+
+    // Policies still calling in_window() with it then fail to evaluate
+    #[wasm_bindgen]
+    pub fn remove_window(&mut self, name: &str) -> bool {
+        self.calendar.as_mut().is_some_and(|calendar| calendar.windows.remove(name).is_some())
+    }
+
+    #[wasm_bindgen]
+    pub fn get_calendar(&self) -> Result<String, JsValue> {
+        Ok(to_json(&self.calendar)?)
+    }
+
+    // Business hours are the caller's `business_hours` again
+    #[wasm_bindgen]
+    pub fn clear_calendar(&mut self) {
+        self.calendar = None;
+    }
+}
+
+impl PolicyEngine {
+    fn calendar_for(&self, function: &str) -> Result<&Calendar, ExprError> {
+        self.calendar.as_ref().ok_or_else(|| ExprError::new(format!("{}() needs a calendar (load_calendar)", function)))
+    }
+
+    // is_business_hours(region) and is_holiday(region), the default
+    // region when omitted
+    pub(crate) fn calendar_function(&self, function: &str, region: Option<&str>, at: DateTime<Utc>) -> Result<bool, ExprError> {
+        let region = self.calendar_for(function)?.region(function, region)?;
+        Ok(match function {
+            "is_holiday" => region.is_holiday(at),
+            _ => region.is_business_hours(at),
+        })
+    }
+
+    // in_window(name): whether `at` falls in any span of the window. An
+    // undefined window is an error rather than "not in it", so a typo
+    // cannot quietly lift a freeze.
+    pub(crate) fn in_window(&self, name: &str, at: DateTime<Utc>) -> Result<bool, ExprError> {
+        let calendar = self.calendar_for("in_window")?;
+        let spans = calendar
+            .windows
+            .get(name)
+            .ok_or_else(|| ExprError::new(format!("in_window(): unknown window '{}'", name)))?;
+        Ok(spans.iter().any(|span| calendar.in_span(span, at)))
+    }
+
+    pub(crate) fn apply_calendar(&self, context: &mut PolicyContext) {
+        let Some(calendar) = &self.calendar else {
+            return;
+        };
+        let Some(region) = calendar.default_region.as_ref().and_then(|name| calendar.regions.get(name)) else {
+            return;
+        };
+        context.business_hours = region.is_business_hours(context.timestamp);
+        if let Some(supplied) = context.supplied_fields.as_mut() {
+            supplied.insert("business_hours".to_string());
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use wasm_bindgen::prelude::*;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::clock;
+use crate::decision::Decision;
+use crate::error::PolicyEngineError;
+use crate::expr::{self, Expr};
+use crate::messages;
+use crate::obligations::ObligationCall;
+use crate::{guard, stats, PolicyContext, PolicyEngine, PolicyResult, PolicySelection};
+
+pub const CAPSULE_RESOURCE_TYPE: &str = "data_capsule";
+
+// resource_attributes key holding the capsule metadata; policies read it
+// as `capsule.*` (see attributes.rs)
+pub const CAPSULE_ATTRIBUTE: &str = "capsule";
+
+pub const LIFECYCLE_OPERATIONS: &[&str] = &["create", "read", "update", "destroy"];
+
+// Obligations added to a lifecycle PERMIT:
+//
+//   ttl(3600)         the caller may hold the data (or, on create, keep the
+//                     capsule) for at most this many seconds
+//   self_destruct()   the capsule is destroyed once this read completes
+//
+// A policy can attach either itself, e.g. `ttl(86400)` on create. The
+// engine adds the capsule's remaining lifetime and keeps the smallest ttl.
+pub const TTL_OBLIGATION: &str = "ttl";
+pub const SELF_DESTRUCT_OBLIGATION: &str = "self_destruct";
+
+// Capsule metadata as the ADCF capsule service serializes it
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CapsuleMeta {
+    pub id: String,
+    pub owner_id: String,
+    pub data_hash: String,
+    pub policy_id: Option<String>,
+    pub metadata: Map<String, Value>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub revoked: bool,
+    pub self_destruct: bool,
+    pub access_count: u64,
+    pub locked_until: Option<DateTime<Utc>>,
+    pub size_bytes: u64,
+}
+
+impl CapsuleMeta {
+    fn seconds_until(at: Option<DateTime<Utc>>, now: DateTime<Utc>) -> Option<i64> {
+        at.map(|at| (at - now).num_seconds().max(0))
+    }
+
+    // The metadata plus state derived at `now`: expired, locked,
+    // ttl_remaining and age (seconds)
+    fn attribute(&self, now: DateTime<Utc>) -> Value {
+        let mut value = serde_json::to_value(self).unwrap_or_else(|_| json!({}));
+        value["expired"] = json!(self.expires_at.is_some_and(|at| at <= now));
+        value["locked"] = json!(self.locked_until.is_some_and(|until| until > now));
+        value["ttl_remaining"] = json!(Self::seconds_until(self.expires_at, now));
+        value["age"] = json!(self.created_at.map(|at| (now - at).num_seconds().max(0)));
+        value
+    }
+
+    // The built-in rule a lifecycle operation violates, if any: revoked
+    // and expired capsules can only be destroyed, locked ones not touched
+    fn violation(&self, operation: &str, now: DateTime<Utc>) -> Option<(&'static str, String, Option<f64>)> {
+        if operation == "create" {
+            return None;
+        }
+        if let Some(until) = self.locked_until.filter(|until| *until > now) {
+            let retry_after = (until - now).num_milliseconds() as f64 / 1000.0;
+            return Some((messages::CAPSULE_LOCKED, format!("Capsule is locked until {}", until.to_rfc3339()), Some(retry_after)));
+        }
+        if operation == "destroy" {
+            return None;
+        }
+        if self.revoked {
+            return Some((messages::CAPSULE_REVOKED, "Capsule has been revoked".to_string(), None));
+        }
+        if self.expires_at.is_some_and(|at| at <= now) {
+            return Some((messages::CAPSULE_EXPIRED, "Capsule has expired".to_string(), None));
+        }
+        None
+    }
+}
+
+fn lifecycle_operation(operation: &str) -> Option<&'static str> {
+    let operation = operation.trim().to_lowercase();
+    LIFECYCLE_OPERATIONS.iter().copied().find(|known| *known == operation)
+}
+
+// The capsule metadata a context carries, for data_capsule resources
+fn capsule_of(context: &PolicyContext) -> Option<CapsuleMeta> {
+    if context.resource_type != CAPSULE_RESOURCE_TYPE {
+        return None;
+    }
+    serde_json::from_value(context.resource_attributes.get(CAPSULE_ATTRIBUTE)?.clone()).ok()
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Evaluates a capsule lifecycle operation (create, read, update,
+    // destroy) from the capsule's metadata, as the capsule service stores
+    // it, and the requester's context. The context gets resource_type
+    // data_capsule, the capsule's id and owner, its metadata under
+    // `capsule` and, unless it has one, the classification from the
+    // capsule's `metadata.classification`. Beyond the policies, revoked
+    // and expired capsules can only be destroyed and locked ones not
+    // touched at all; a PERMIT carries ttl and self_destruct obligations.
+    // Stateful, like evaluate.
+    #[wasm_bindgen]
+    pub fn evaluate_lifecycle(&mut self, capsule_meta_json: &str, operation: &str, context_json: &str) -> Result<PolicyResult, JsValue> {
+        let Some(operation) = lifecycle_operation(operation) else {
+            return Err(PolicyEngineError::validation(format!("Unknown capsule lifecycle operation '{}'", operation))
+                .with_details(json!({ "operation": operation, "allowed": LIFECYCLE_OPERATIONS }))
+                .logged()
+                .into());
+        };
+        let capsule: CapsuleMeta = serde_json::from_str(capsule_meta_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse capsule metadata: {}", e)).logged())?;
+        let mut raw: Map<String, Value> = serde_json::from_str(context_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse context: {}", e)).logged())?;
+
+        raw.insert("operation".to_string(), json!(operation));
+        raw.insert("resource_type".to_string(), json!(CAPSULE_RESOURCE_TYPE));
+        raw.insert("resource_id".to_string(), json!(capsule.id));
+        raw.insert("resource_owner".to_string(), json!(capsule.owner_id));
+        if let Some(classification) = capsule.metadata.get("classification").filter(|value| value.is_string()) {
+            raw.entry("resource_classification").or_insert_with(|| classification.clone());
+        }
+        let attributes = raw.entry("resource_attributes").or_insert_with(|| json!({}));
+        if let Some(attributes) = attributes.as_object_mut() {
+            attributes.insert(CAPSULE_ATTRIBUTE.to_string(), json!(capsule));
+        }
+
+        let started = stats::now_ms();
+        let context = self.check_context_value(Value::Object(raw));
+        self.begin_profile(started);
+        Ok(match context {
+            Ok(context) => guard::guarded(|| self.evaluate_request(context, PolicySelection::Global)),
+            Err(error) => PolicyResult::failure(&error.logged()),
+        })
+    }
+}
+
+impl PolicyEngine {
+    // Replaces the capsule metadata with its state now (by the engine
+    // clock; the request timestamp is the caller's to choose) so policies
+    // can test capsule.expired, capsule.ttl_remaining and so on
+    pub(crate) fn inject_capsule_state(&self, context: &mut PolicyContext) {
+        if let Some(capsule) = capsule_of(context) {
+            context.resource_attributes.insert(CAPSULE_ATTRIBUTE.to_string(), capsule.attribute(clock::now()));
+        }
+    }
+
+    // Applies the built-in lifecycle rules to any decision but a DENY,
+    // then adds the ttl and self_destruct obligations to a PERMIT
+    pub(crate) fn apply_lifecycle(&self, result: &mut PolicyResult, context: &PolicyContext) {
+        let Some(capsule) = capsule_of(context) else {
+            return;
+        };
+        let Some(operation) = lifecycle_operation(&context.operation) else {
+            return;
+        };
+
+        let now = clock::now();
+        if result.decision != Decision::Deny {
+            if let Some((code, reason, retry_after)) = capsule.violation(operation, now) {
+                if self.debug_mode {
+                    console_log!("Capsule {} {}: {}", capsule.id, operation, reason);
+                }
+                *result = PolicyResult::new(Decision::Deny, reason, 1.0);
+                result.reason_code = Some(code.to_string());
+                result.retry_after = retry_after;
+                return;
+            }
+        }
+        if result.decision != Decision::Permit {
+            return;
+        }
+
+        let specs: Vec<String> = serde_json::from_str(&result.obligations).unwrap_or_default();
+        // A destroyed capsule has no lifetime left to bound
+        let mut ttl = CapsuleMeta::seconds_until(capsule.expires_at, now).filter(|_| operation != "destroy");
+        let mut self_destruct = capsule.self_destruct && operation == "read";
+        let mut obligations = Vec::with_capacity(specs.len() + 2);
+        for spec in specs {
+            let call = ObligationCall::parse(&spec);
+            match (call.id.as_str(), call.args.as_slice()) {
+                (TTL_OBLIGATION, [Expr::Literal(expr::Value::Number(seconds))]) => {
+                    let seconds = seconds.max(0.0) as i64;
+                    ttl = Some(ttl.map_or(seconds, |ttl| ttl.min(seconds)));
+                }
+                (SELF_DESTRUCT_OBLIGATION, []) => self_destruct = true,
+                _ => obligations.push(spec),
+            }
+        }
+        if let Some(ttl) = ttl {
+            obligations.push(format!("{}({})", TTL_OBLIGATION, ttl));
+        }
+        if self_destruct {
+            obligations.push(format!("{}()", SELF_DESTRUCT_OBLIGATION));
+        }
+        result.obligations = serde_json::to_string(&obligations).unwrap_or_else(|_| "[]".to_string()).into();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+    use chrono::TimeDelta;
+    use std::rc::Rc;
+
+    fn read_of(capsule: &CapsuleMeta, timestamp: DateTime<Utc>) -> PolicyContext {
+        let mut context = PolicyContext {
+            operation: "read".to_string(),
+            resource_type: CAPSULE_RESOURCE_TYPE.to_string(),
+            timestamp,
+            ..PolicyContext::default()
+        };
+        context.resource_attributes.insert(CAPSULE_ATTRIBUTE.to_string(), json!(capsule));
+        context
+    }
+
+    fn lifecycle(engine: &PolicyEngine, context: &PolicyContext) -> PolicyResult {
+        let mut result = PolicyResult::new(Decision::Permit, "permitted", 1.0);
+        engine.apply_lifecycle(&mut result, context);
+        result
+    }
+
+    #[test]
+    fn expired_capsules_stay_expired_for_backdated_requests() {
+        let now = clock::now();
+        clock::set_clock(Rc::new(FixedClock(now)));
+        let engine = PolicyEngine::new();
+        let capsule = CapsuleMeta { id: "c1".to_string(), expires_at: Some(now - TimeDelta::hours(1)), ..CapsuleMeta::default() };
+
+        let mut context = read_of(&capsule, now - TimeDelta::days(1));
+        engine.inject_capsule_state(&mut context);
+        assert_eq!(context.resource_attributes[CAPSULE_ATTRIBUTE]["expired"], json!(true));
+        let result = lifecycle(&engine, &context);
+        assert_eq!(result.decision, Decision::Deny);
+        assert_eq!(result.reason_code.as_deref(), Some(messages::CAPSULE_EXPIRED));
+
+        let revoked = CapsuleMeta { revoked: true, ..CapsuleMeta::default() };
+        assert_eq!(lifecycle(&engine, &read_of(&revoked, now)).reason_code.as_deref(), Some(messages::CAPSULE_REVOKED));
+        clock::use_system_clock();
+    }
+
+    #[test]
+    fn permits_are_bounded_by_the_remaining_lifetime() {
+        let now = clock::now();
+        clock::set_clock(Rc::new(FixedClock(now)));
+        let engine = PolicyEngine::new();
+        let capsule = CapsuleMeta { expires_at: Some(now + TimeDelta::seconds(600)), self_destruct: true, ..CapsuleMeta::default() };
+
+        let result = lifecycle(&engine, &read_of(&capsule, now - TimeDelta::days(1)));
+        assert_eq!(result.decision, Decision::Permit);
+        let obligations: Vec<String> = serde_json::from_str(&result.obligations).unwrap();
+        assert_eq!(obligations, ["ttl(600)", "self_destruct()"]);
+        clock::use_system_clock();
+    }
+}
@@ -0,0 +1,43 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::PolicyContext;
+
+// Step-up authentication requirement attached to a CHALLENGE rule and
+// returned to the PEP (as JSON in `PolicyResult.challenge`) so it can
+// drive the step-up flow.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct ChallengeSpec {
+    // Authentication method the user must complete, e.g. "mfa", "webauthn"
+    pub required_auth_method: String,
+
+    // Factor types the PEP may offer; empty means any
+    pub allowed_factors: Vec<String>,
+
+    // Maximum age of the authentication (session) in seconds; None means
+    // any session age is acceptable once the method is satisfied
+    pub max_age_seconds: Option<i64>,
+}
+
+impl ChallengeSpec {
+    // A CHALLENGE rule resolves to PERMIT once the context shows the
+    // step-up has already been completed.
+    pub fn is_satisfied(&self, context: &PolicyContext) -> bool {
+        let method_ok = match self.required_auth_method.to_lowercase().as_str() {
+            "" => true,
+            "mfa" => context.mfa_verified,
+            required => context.auth_method.eq_ignore_ascii_case(required),
+        };
+
+        let factor_ok = self.allowed_factors.is_empty()
+            || self.allowed_factors.iter().any(|f| f.eq_ignore_ascii_case(&context.auth_method));
+
+        let age_ok = match self.max_age_seconds {
+            Some(max) => context.session_age.num_seconds() <= max,
+            None => true,
+        };
+
+        method_ok && factor_ok && age_ok
+    }
+}
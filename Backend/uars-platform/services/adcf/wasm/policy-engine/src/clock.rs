@@ -0,0 +1,164 @@
+use wasm_bindgen::prelude::*;
+use chrono::{DateTime, Duration, Utc};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::environment::format_time;
+use crate::error::PolicyEngineError;
+
+// Furthest a test or offset clock may be moved from real time; keeps
+// every later now() far from chrono's range
+pub const MAX_CLOCK_OFFSET_DAYS: i64 = 100 * 365;
+
+// Source of "now" for everything time-of-day related: default context
+// timestamps, expiry checks (tokens, approvals, delegation grants,
+// consent, capsules, validity windows), quota and velocity windows,
+// now(), log and statistics timestamps. A request's own timestamp is the
+// caller's to choose and never decides whether something has expired.
+// Elapsed-time measurements (evaluation limits, rule timings) always use
+// the real clock.
+pub trait Clock {
+    fn now(&self) -> DateTime<Utc>;
+
+    // This clock moved forward (or back, for negative durations), or
+    // None when that leaves MAX_CLOCK_OFFSET_DAYS of real time
+    fn advanced(&self, by: Duration) -> Option<Rc<dyn Clock>>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn advanced(&self, by: Duration) -> Option<Rc<dyn Clock>> {
+        within_bounds(by).then(|| Rc::new(OffsetClock(by)) as Rc<dyn Clock>)
+    }
+}
+
+// The system clock shifted by a fixed amount
+pub struct OffsetClock(pub Duration);
+
+impl Clock for OffsetClock {
+    fn now(&self) -> DateTime<Utc> {
+        let now = Utc::now();
+        now.checked_add_signed(self.0).unwrap_or(now)
+    }
+
+    fn advanced(&self, by: Duration) -> Option<Rc<dyn Clock>> {
+        let offset = self.0.checked_add(&by).filter(|offset| within_bounds(*offset))?;
+        Some(Rc::new(OffsetClock(offset)))
+    }
+}
+
+// Frozen at one instant until advanced
+pub struct FixedClock(pub DateTime<Utc>);
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0
+    }
+
+    fn advanced(&self, by: Duration) -> Option<Rc<dyn Clock>> {
+        let time = self.0.checked_add_signed(by).filter(|time| within_bounds(*time - Utc::now()))?;
+        Some(Rc::new(FixedClock(time)))
+    }
+}
+
+thread_local! {
+    static CLOCK: RefCell<Rc<dyn Clock>> = RefCell::new(Rc::new(SystemClock));
+}
+
+pub fn now() -> DateTime<Utc> {
+    let clock = CLOCK.with(|clock| clock.borrow().clone());
+    clock.now()
+}
+
+// Installs a clock for every engine on this thread
+pub fn set_clock(clock: Rc<dyn Clock>) {
+    CLOCK.with(|current| *current.borrow_mut() = clock);
+}
+
+fn within_bounds(offset: Duration) -> bool {
+    offset.abs() <= Duration::days(MAX_CLOCK_OFFSET_DAYS)
+}
+
+fn duration_ms(ms: f64) -> Result<Duration, PolicyEngineError> {
+    let duration = Duration::microseconds((ms * 1000.0) as i64);
+    if !ms.is_finite() || !within_bounds(duration) {
+        return Err(out_of_bounds(ms));
+    }
+    Ok(duration)
+}
+
+fn out_of_bounds(ms: f64) -> PolicyEngineError {
+    PolicyEngineError::validation(format!("Clock can move at most {} days from real time", MAX_CLOCK_OFFSET_DAYS))
+        .with_details(serde_json::json!({ "ms": ms }))
+}
+
+#[wasm_bindgen]
+pub fn set_clock_offset(offset_ms: f64) -> Result<(), JsValue> {
+    set_clock(Rc::new(OffsetClock(duration_ms(offset_ms).map_err(PolicyEngineError::logged)?)));
+    Ok(())
+}
+
+// Freezes the clock at an RFC 3339 instant
+#[wasm_bindgen]
+pub fn set_test_clock(time: &str) -> Result<(), JsValue> {
+    let time = DateTime::parse_from_rfc3339(time).map_err(|e| {
+        PolicyEngineError::validation(format!("Invalid clock time '{}': {}", time, e)).logged()
+    })?;
+    let time = time.with_timezone(&Utc);
+    if !within_bounds(time - Utc::now()) {
+        return Err(PolicyEngineError::validation(format!(
+            "Clock time must be within {} days of real time",
+            MAX_CLOCK_OFFSET_DAYS
+        ))
+        .logged()
+        .into());
+    }
+    set_clock(Rc::new(FixedClock(time)));
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub fn advance_clock(ms: f64) -> Result<(), JsValue> {
+    set_clock(advance(ms).map_err(PolicyEngineError::logged)?);
+    Ok(())
+}
+
+fn advance(ms: f64) -> Result<Rc<dyn Clock>, PolicyEngineError> {
+    let by = duration_ms(ms)?;
+    let clock = CLOCK.with(|clock| clock.borrow().clone());
+    clock.advanced(by).ok_or_else(|| out_of_bounds(ms))
+}
+
+#[wasm_bindgen]
+pub fn use_system_clock() {
+    set_clock(Rc::new(SystemClock));
+}
+
+#[wasm_bindgen]
+pub fn get_clock_time() -> String {
+    format_time(now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offsets_stay_within_bounds() {
+        assert!(duration_ms(f64::MAX).is_err());
+        assert!(duration_ms(f64::NAN).is_err());
+        let limit_ms = (MAX_CLOCK_OFFSET_DAYS * 86_400_000) as f64;
+        assert!(OffsetClock(duration_ms(limit_ms).unwrap()).advanced(Duration::days(1)).is_none());
+
+        set_clock(Rc::new(FixedClock(Utc::now())));
+        set_clock(advance(limit_ms / 2.0).unwrap());
+        assert!(advance(limit_ms).is_err());
+        assert!(advance(-limit_ms).is_ok());
+        use_system_clock();
+    }
+}
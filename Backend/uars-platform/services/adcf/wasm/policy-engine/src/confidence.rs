@@ -0,0 +1,289 @@
+use wasm_bindgen::prelude::*;
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::attributes::canonical_path;
+use crate::decision::Decision;
+use crate::error::{to_json, PolicyEngineError};
+use crate::vocabulary::CombiningAlgorithm;
+use crate::{PolicyContext, PolicyEngine, PolicyResult, PolicyRule};
+
+// Provider-reported quality of a context attribute (see
+// PolicyContext::attribute_quality). An entry for a map attribute such as
+// `user_attributes` covers its nested keys.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct AttributeQuality {
+    // In [0, 1]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f64>,
+    // When the provider last observed the value
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub observed_at: Option<DateTime<Utc>>,
+}
+
+// How the confidences of results agreeing on a combined decision merge
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Aggregation {
+    // The strongest agreeing result
+    Max,
+    // Agreeing results as independent evidence: 1 - (1 - a)(1 - b)...
+    NoisyOr,
+    // The weakest agreeing result
+    Min,
+}
+
+impl Aggregation {
+    fn apply(self, confidences: &[f64]) -> f64 {
+        match self {
+            Aggregation::Max => confidences.iter().copied().fold(0.0, f64::max),
+            Aggregation::NoisyOr => 1.0 - confidences.iter().map(|c| 1.0 - c).product::<f64>(),
+            Aggregation::Min => confidences.iter().copied().fold(1.0, f64::min),
+        }
+    }
+}
+
+// Confidence model loaded from JSON. Without one, matched rules report
+// 1.0 and everything else 0.0.
+//
+// A rule's confidence is the certainty of its condition's outcome (matched
+// or not): the weakest provider-reported confidence among the attributes
+// it read, times a penalty per missing or stale attribute and per `&&`/`||`
+// operand that failed without deciding the outcome. Combining merges the
+// results that agree on the decision and discounts it by every rule that
+// could have overridden it but might have applied.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfidenceModel {
+    // Multiplier per attribute read that the context does not carry
+    pub missing_attribute: f64,
+    // Attributes observed longer ago than this are stale
+    pub stale_after_seconds: f64,
+    // Multiplier per stale attribute read
+    pub stale_attribute: f64,
+    // Multiplier per `&&`/`||` operand that failed to evaluate when the
+    // other operand decided the result. Only under a model do such
+    // failures not make the rule INDETERMINATE.
+    pub indeterminate_operand: f64,
+    // Chance that a rule whose condition failed would have applied
+    pub errored_rule: f64,
+    pub aggregation: Aggregation,
+    // Decisive results less confident than this become INDETERMINATE
+    pub min_confidence: f64,
+}
+
+impl Default for ConfidenceModel {
+    fn default() -> Self {
+        ConfidenceModel {
+            missing_attribute: 0.9,
+            stale_after_seconds: 3600.0,
+            stale_attribute: 0.7,
+            indeterminate_operand: 0.5,
+            errored_rule: 0.5,
+            aggregation: Aggregation::Max,
+            min_confidence: 0.0,
+        }
+    }
+}
+
+// Attribute reads and ignored operand failures of one condition
+#[derive(Debug, Default)]
+pub struct ConditionTrace {
+    reads: Vec<(Vec<String>, bool)>,
+    indeterminate: i32,
+}
+
+impl ConditionTrace {
+    pub fn read(&mut self, path: &[String], present: bool) {
+        if !self.reads.iter().any(|(read, _)| read == path) {
+            self.reads.push((path.to_vec(), present));
+        }
+    }
+
+    pub fn indeterminate(&mut self) {
+        self.indeterminate += 1;
+    }
+}
+
+// A rule's (or policy's) part in a combined decision
+pub(crate) struct Outcome {
+    decision: Decision,
+    confidence: f64,
+    // What it would have decided had it applied; None when unknown
+    potential: Option<Decision>,
+    // Chance that it applied, or would have, without it showing
+    doubt: f64,
+}
+
+impl ConfidenceModel {
+    fn validate(&self) -> Result<(), PolicyEngineError> {
+        let factors = [
+            ("missing_attribute", self.missing_attribute),
+            ("stale_attribute", self.stale_attribute),
+            ("indeterminate_operand", self.indeterminate_operand),
+            ("errored_rule", self.errored_rule),
+            ("min_confidence", self.min_confidence),
+        ];
+        if let Some((field, value)) = factors.iter().find(|(_, value)| !(0.0..=1.0).contains(value)) {
+            return Err(PolicyEngineError::validation(format!("Confidence model {} must be in [0, 1]", field))
+                .with_details(json!({ "field": field, "value": value })));
+        }
+        if self.stale_after_seconds.is_nan() || self.stale_after_seconds < 0.0 {
+            return Err(PolicyEngineError::validation("Confidence model stale_after_seconds must not be negative")
+                .with_details(json!({ "field": "stale_after_seconds", "value": self.stale_after_seconds })));
+        }
+        Ok(())
+    }
+
+    pub fn certainty(&self, trace: &ConditionTrace, context: &PolicyContext) -> f64 {
+        let quality: Vec<(String, &AttributeQuality)> = context
+            .attribute_quality
+            .iter()
+            .map(|(path, quality)| (canonical_path(&path.split('.').collect::<Vec<_>>()), quality))
+            .collect();
+
+        let mut weakest = 1.0_f64;
+        let mut penalty = self.indeterminate_operand.powi(trace.indeterminate);
+        for (path, present) in &trace.reads {
+            if !present {
+                penalty *= self.missing_attribute;
+                continue;
+            }
+            let name = canonical_path(&path.iter().map(String::as_str).collect::<Vec<_>>());
+            let Some(quality) = quality_of(&quality, &name) else { continue };
+            if let Some(confidence) = quality.confidence {
+                weakest = weakest.min(confidence.clamp(0.0, 1.0));
+            }
+            if let Some(observed) = quality.observed_at {
+                let age = (context.timestamp - observed).num_milliseconds() as f64 / 1000.0;
+                if age > self.stale_after_seconds {
+                    penalty *= self.stale_attribute;
+                }
+            }
+        }
+        weakest * penalty
+    }
+
+    pub(crate) fn rule_outcome(&self, rule: &PolicyRule, result: &PolicyResult) -> Outcome {
+        let doubt = match result.decision {
+            Decision::NotApplicable => 1.0 - result.confidence,
+            Decision::Indeterminate => self.errored_rule,
+            _ => 0.0,
+        };
+        Outcome { decision: result.decision, confidence: result.confidence, potential: Some(rule.effect.decision()), doubt }
+    }
+
+    // A policy whose rule failed could have decided anything; a policy
+    // without applicable rules (no rule_id) decided nothing
+    pub(crate) fn policy_outcome(&self, result: &PolicyResult) -> Outcome {
+        let doubt = match result.decision {
+            Decision::Indeterminate if result.rule_id.is_some() => self.errored_rule,
+            _ => 0.0,
+        };
+        Outcome { decision: result.decision, confidence: result.confidence, potential: None, doubt }
+    }
+
+    // Replaces the confidence of a combined decisive result
+    pub(crate) fn calibrate(&self, algorithm: &CombiningAlgorithm, outcomes: &[Outcome], result: &mut PolicyResult) {
+        let selected = result.decision;
+        if !selected.is_decisive() {
+            return;
+        }
+        // first-applicable: the result is the first rule that applied
+        let first = outcomes
+            .iter()
+            .position(|outcome| outcome.decision != Decision::NotApplicable)
+            .unwrap_or(outcomes.len());
+        let agreeing: Vec<f64> = match algorithm {
+            CombiningAlgorithm::FirstApplicable => outcomes.get(first).map(|outcome| outcome.confidence).into_iter().collect(),
+            _ => outcomes.iter().filter(|outcome| outcome.decision == selected).map(|outcome| outcome.confidence).collect(),
+        };
+        // Defaults (permit-unless-deny, deny-unless-permit) are certain
+        // unless something could have overridden them
+        let mut confidence = if agreeing.is_empty() { 1.0 } else { self.aggregation.apply(&agreeing) };
+        for (index, outcome) in outcomes.iter().enumerate() {
+            if outcome.doubt > 0.0 && overrides(algorithm, outcome.potential, selected, index < first) {
+                confidence *= 1.0 - outcome.doubt;
+            }
+        }
+        result.confidence = confidence;
+    }
+
+    // Applies min_confidence to the final result
+    pub(crate) fn enforce_minimum(&self, result: &mut PolicyResult) {
+        if !result.decision.is_decisive() || result.confidence >= self.min_confidence {
+            return;
+        }
+        let mut indeterminate = PolicyResult::new(
+            Decision::Indeterminate,
+            format!(
+                "{} with confidence {:.2} is below the minimum of {:.2}",
+                result.decision, result.confidence, self.min_confidence
+            ),
+            result.confidence,
+        );
+        indeterminate.policy_id = result.policy_id.take();
+        indeterminate.rule_id = result.rule_id.take();
+        *result = indeterminate;
+    }
+}
+
+fn quality_of<'q>(quality: &[(String, &'q AttributeQuality)], name: &str) -> Option<&'q AttributeQuality> {
+    quality
+        .iter()
+        .filter(|(path, _)| name == path || name.strip_prefix(path.as_str()).is_some_and(|rest| rest.starts_with('.')))
+        .max_by_key(|(path, _)| path.len())
+        .map(|(_, quality)| *quality)
+}
+
+// Decisions in the order each algorithm lets them win (mirrors the
+// combining functions in lib.rs); deny-overrides also stands in for
+// unknown algorithms
+fn tiers(algorithm: &CombiningAlgorithm) -> &'static [&'static [Decision]] {
+    use Decision::*;
+    match algorithm {
+        CombiningAlgorithm::PermitOverrides | CombiningAlgorithm::OrderedPermitOverrides => {
+            &[&[Permit], &[Challenge], &[PendingApproval], &[Deny]]
+        }
+        CombiningAlgorithm::PermitUnlessDeny => &[&[Deny], &[Challenge, PendingApproval], &[Permit]],
+        CombiningAlgorithm::DenyUnlessPermit => &[&[Permit], &[Challenge, PendingApproval], &[Deny]],
+        _ => &[&[Deny], &[Challenge], &[PendingApproval], &[Permit]],
+    }
+}
+
+// Whether a result that would have decided `potential` (None: anything)
+// beats `selected` under `algorithm`
+fn overrides(algorithm: &CombiningAlgorithm, potential: Option<Decision>, selected: Decision, earlier: bool) -> bool {
+    if *algorithm == CombiningAlgorithm::FirstApplicable {
+        return earlier && potential != Some(selected);
+    }
+    let tiers = tiers(algorithm);
+    let rank = |decision: Decision| tiers.iter().position(|tier| tier.contains(&decision)).unwrap_or(tiers.len());
+    potential.map_or(0, rank) < rank(selected)
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    #[wasm_bindgen]
+    pub fn set_confidence_model(&mut self, model_json: &str) -> Result<(), JsValue> {
+        let model: ConfidenceModel = serde_json::from_str(model_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse confidence model: {}", e)).logged())?;
+        model.validate().map_err(PolicyEngineError::logged)?;
+        self.confidence = Some(model);
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_confidence_model(&mut self) {
+        self.confidence = None;
+    }
+
+    // The configured model as JSON, or "null"
+    #[wasm_bindgen]
+    pub fn get_confidence_model(&self) -> Result<String, JsValue> {
+        Ok(to_json(&self.confidence)?)
+    }
+}
@@ -0,0 +1,145 @@
+use wasm_bindgen::prelude::*;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::clock;
+use crate::error::{to_json, PolicyEngineError};
+use crate::quota;
+use crate::{state_key, PolicyEngine};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsentRecord {
+    pub granted_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl ConsentRecord {
+    fn covers(&self, at: DateTime<Utc>) -> bool {
+        self.granted_at <= at && self.expires_at.is_none_or(|expires| at < expires)
+    }
+}
+
+// Consent each user has given, per purpose, behind has_consent(); keyed
+// by state_key so each tenant's users have their own
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConsentRegistry {
+    users: HashMap<String, BTreeMap<String, ConsentRecord>>,
+}
+
+impl ConsentRegistry {
+    pub fn has_consent(&self, user_key: &str, purpose: &str, at: DateTime<Utc>) -> bool {
+        self.users
+            .get(user_key)
+            .and_then(|purposes| purposes.get(purpose.trim()))
+            .is_some_and(|record| record.covers(at))
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Records that a user (of `tenant_id`, when given) consents to
+    // `purpose` from now on, until `expiry` (an RFC 3339 time, or a
+    // duration from now such as "365d") or until revoked. Recording it
+    // again replaces the earlier consent.
+    #[wasm_bindgen]
+    pub fn record_consent(
+        &mut self,
+        user_id: &str,
+        purpose: &str,
+        expiry: Option<String>,
+        tenant_id: Option<String>,
+    ) -> Result<(), JsValue> {
+        if user_id.trim().is_empty() || purpose.trim().is_empty() {
+            return Err(PolicyEngineError::validation("User id and purpose must not be empty").logged().into());
+        }
+        if let Some(tenant) = tenant_id.as_deref().filter(|tenant| !self.tenants.contains_key(*tenant)) {
+            return Err(PolicyEngineError::not_found(format!("Tenant '{}' does not exist", tenant))
+                .with_details(json!({ "tenant_id": tenant }))
+                .logged()
+                .into());
+        }
+        let now = clock::now();
+        let expires_at = match expiry.as_deref().map(str::trim) {
+            None | Some("") => None,
+            Some(text) => {
+                let expires_at = quota::parse_window(text).and_then(|window| now.checked_add_signed(window))
+                    .or_else(|| DateTime::parse_from_rfc3339(text).ok().map(|time| time.with_timezone(&Utc)));
+                match expires_at {
+                    Some(expires_at) if expires_at > now => Some(expires_at),
+                    _ => {
+                        return Err(PolicyEngineError::validation(format!("Consent expiry '{}' is not a future time or a duration", text))
+                            .with_details(json!({ "user_id": user_id, "purpose": purpose, "expiry": text }))
+                            .logged()
+                            .into());
+                    }
+                }
+            }
+        };
+
+        let purposes = self.consents.users.entry(state_key(tenant_id.as_deref(), user_id)).or_default();
+        purposes.retain(|_, record| record.expires_at.is_none_or(|expires| expires > now));
+        purposes.insert(purpose.trim().to_string(), ConsentRecord { granted_at: now, expires_at });
+        if self.debug_mode {
+            console_log!("Recorded consent of '{}' to '{}'", user_id, purpose);
+        }
+        Ok(())
+    }
+
+    // Whether the user had consented to `purpose`
+    #[wasm_bindgen]
+    pub fn revoke_consent(&mut self, user_id: &str, purpose: &str, tenant_id: Option<String>) -> bool {
+        let key = state_key(tenant_id.as_deref(), user_id);
+        let Some(purposes) = self.consents.users.get_mut(&key) else {
+            return false;
+        };
+        let revoked = purposes.remove(purpose.trim()).is_some();
+        if purposes.is_empty() {
+            self.consents.users.remove(&key);
+        }
+        revoked
+    }
+
+    // A user's consents (purpose to ConsentRecord), expired ones included
+    #[wasm_bindgen]
+    pub fn get_consents(&self, user_id: &str, tenant_id: Option<String>) -> Result<String, JsValue> {
+        let key = state_key(tenant_id.as_deref(), user_id);
+        Ok(to_json(&self.consents.users.get(&key).cloned().unwrap_or_default())?)
+    }
+
+    // Erases everything recorded for a user, e.g. on account deletion
+    #[wasm_bindgen]
+    pub fn forget_consents(&mut self, user_id: &str, tenant_id: Option<String>) {
+        self.consents.users.remove(&state_key(tenant_id.as_deref(), user_id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+    use crate::expr::{Environment, Value};
+    use crate::functions::EvalScope;
+    use crate::PolicyContext;
+    use chrono::TimeDelta;
+    use std::rc::Rc;
+
+    #[test]
+    fn expired_consent_stays_expired_for_backdated_requests() {
+        let start = clock::now();
+        clock::set_clock(Rc::new(FixedClock(start)));
+        let mut engine = PolicyEngine::new();
+        engine.record_consent("alice", "research", Some("1h".to_string()), None).unwrap();
+        let context = PolicyContext { user_id: "alice".to_string(), timestamp: start, ..PolicyContext::default() };
+        let has_consent = |engine: &PolicyEngine| {
+            EvalScope::new(engine, &context, None).call("has_consent", &[Value::String("research".to_string())]).unwrap()
+        };
+        assert_eq!(has_consent(&engine), Value::Bool(true));
+
+        clock::set_clock(Rc::new(FixedClock(start + TimeDelta::hours(2))));
+        assert_eq!(has_consent(&engine), Value::Bool(false));
+        clock::use_system_clock();
+    }
+}
@@ -0,0 +1,100 @@
+use serde_json::Value;
+
+use crate::decision::Decision;
+use crate::messages;
+use crate::{PolicyContext, PolicyEngine, PolicyResult};
+
+// Constraints a PEP can put in the context to narrow what the policies
+// grant for one request:
+//
+//   max_classification: "internal"        resource classification at most
+//                                         this level (classification
+//                                         lattice, else the security one)
+//   max_risk_score: 0.6                   risk_score at most this
+//   allowed_operations: ["read"]          operation one of these
+//   allowed_resource_types: ["report"]    resource_type one of these
+//   require_mfa: true                     mfa_verified
+//
+// Each acts as a DENY rule over the policy decision. Other keys are left
+// to policies, which read them as `constraints.*`.
+pub const MAX_CLASSIFICATION: &str = "max_classification";
+pub const MAX_RISK_SCORE: &str = "max_risk_score";
+pub const ALLOWED_OPERATIONS: &str = "allowed_operations";
+pub const ALLOWED_RESOURCE_TYPES: &str = "allowed_resource_types";
+pub const REQUIRE_MFA: &str = "require_mfa";
+
+// In the order they are checked
+const ENFORCED: &[&str] = &[MAX_CLASSIFICATION, MAX_RISK_SCORE, ALLOWED_OPERATIONS, ALLOWED_RESOURCE_TYPES, REQUIRE_MFA];
+
+fn one_of(name: &str, allowed: &Value, actual: &str, what: &str) -> Result<(), String> {
+    let Some(allowed) = allowed.as_array().filter(|items| items.iter().all(Value::is_string)) else {
+        return Err(format!("{} must be a list of strings", name));
+    };
+    if allowed.iter().any(|item| item.as_str() == Some(actual)) {
+        Ok(())
+    } else {
+        Err(format!("{} '{}' is not allowed", what, actual))
+    }
+}
+
+impl PolicyEngine {
+    // Why the context violates `name`. A malformed value counts as
+    // violated: the PEP asked for a limit the engine cannot check.
+    fn violated_constraint(&self, name: &str, value: &Value, context: &PolicyContext) -> Result<(), String> {
+        match name {
+            MAX_CLASSIFICATION => {
+                let Some(max) = value.as_str() else {
+                    return Err(format!("{} must be a string", name));
+                };
+                let levels = self
+                    .lattices
+                    .levels(&["classification".to_string()])
+                    .unwrap_or(&self.lattices.security.levels);
+                let rank = |level: &str| levels.iter().position(|known| known == level);
+                let Some(max_rank) = rank(max) else {
+                    return Err(format!("'{}' is not a classification level", max));
+                };
+                match rank(&context.resource_classification) {
+                    Some(actual) if actual <= max_rank => Ok(()),
+                    Some(_) => Err(format!("classification '{}' is above '{}'", context.resource_classification, max)),
+                    None => Err(format!("classification '{}' cannot be compared with '{}'", context.resource_classification, max)),
+                }
+            }
+            MAX_RISK_SCORE => match value.as_f64() {
+                Some(max) if context.risk_score <= max => Ok(()),
+                Some(max) => Err(format!("risk score {:.2} is above {:.2}", context.risk_score, max)),
+                None => Err(format!("{} must be a number", name)),
+            },
+            ALLOWED_OPERATIONS => one_of(name, value, &context.operation, "operation"),
+            ALLOWED_RESOURCE_TYPES => one_of(name, value, &context.resource_type, "resource type"),
+            REQUIRE_MFA => match value.as_bool() {
+                Some(true) if !context.mfa_verified => Err("MFA is not verified".to_string()),
+                Some(_) => Ok(()),
+                None => Err(format!("{} must be a boolean", name)),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    // Turns any decision but a DENY into one when a constraint is
+    // violated, naming the constraint in clipped_by
+    pub(crate) fn apply_constraints(&self, result: &mut PolicyResult, context: &PolicyContext) {
+        if result.decision == Decision::Deny || context.constraints.is_empty() {
+            return;
+        }
+        for name in ENFORCED {
+            let Some(value) = context.constraints.get(*name) else {
+                continue;
+            };
+            if let Err(reason) = self.violated_constraint(name, value, context) {
+                if self.debug_mode {
+                    console_log!("Constraint {} clipped {}: {}", name, result.decision, reason);
+                }
+                *result = PolicyResult::new(Decision::Deny, format!("Constraint {} violated: {}", name, reason), 1.0);
+                result.reason_code = Some(messages::CONSTRAINT_VIOLATED.to_string());
+                result.clipped_by = Some(name.to_string());
+                return;
+            }
+        }
+    }
+}
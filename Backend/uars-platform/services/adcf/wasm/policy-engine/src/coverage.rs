@@ -0,0 +1,282 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::decision::Decision;
+use crate::expr::{self, BinaryOp, Expr, UnaryOp, Value};
+use crate::functions::EvalScope;
+use crate::{CompiledPolicy, PolicyEngine, PolicyResult};
+
+// Leaf conditions of a rule's boolean structure: the operands of
+// `&&`, `||` and `!` down to the first non-logical expression
+pub fn branches(condition: &Expr) -> Vec<&Expr> {
+    let mut leaves = Vec::new();
+    collect_branches(condition, &mut leaves);
+    leaves
+}
+
+fn collect_branches<'a>(expression: &'a Expr, leaves: &mut Vec<&'a Expr>) {
+    match expression {
+        Expr::Binary(BinaryOp::And | BinaryOp::Or, left, right) => {
+            collect_branches(left, leaves);
+            collect_branches(right, leaves);
+        }
+        Expr::Unary(UnaryOp::Not, operand) => collect_branches(operand, leaves),
+        other => leaves.push(other),
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct RuleHits {
+    evaluated: u64,
+    matched: u64,
+    errors: u64,
+    // (times true, times false) per branch
+    branches: Vec<(u64, u64)>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct PolicyHits {
+    evaluated: u64,
+    applicable: u64,
+    rules: HashMap<String, RuleHits>,
+}
+
+// Execution counts gathered while coverage collection is enabled
+#[derive(Debug, Default)]
+pub struct CoverageTracker {
+    enabled: bool,
+    evaluations: u64,
+    policies: HashMap<String, PolicyHits>,
+}
+
+impl CoverageTracker {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn record_target(&mut self, policy_id: &str, applicable: bool) {
+        let hits = self.policies.entry(policy_id.to_string()).or_default();
+        hits.evaluated += 1;
+        if applicable {
+            hits.applicable += 1;
+        }
+    }
+
+    fn record_rule(&mut self, policy_id: &str, rule_id: &str, decision: Decision, outcomes: Vec<Option<bool>>) {
+        let policy = self.policies.entry(policy_id.to_string()).or_default();
+        let rule = policy.rules.entry(rule_id.to_string()).or_default();
+        rule.evaluated += 1;
+        match decision {
+            Decision::NotApplicable => {}
+            Decision::Indeterminate => rule.errors += 1,
+            _ => rule.matched += 1,
+        }
+
+        if rule.branches.len() < outcomes.len() {
+            rule.branches.resize(outcomes.len(), (0, 0));
+        }
+        for (counts, outcome) in rule.branches.iter_mut().zip(outcomes) {
+            match outcome {
+                Some(true) => counts.0 += 1,
+                Some(false) => counts.1 += 1,
+                None => {}
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BranchCoverage {
+    pub expression: String,
+    pub true_count: u64,
+    pub false_count: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleCoverage {
+    pub rule_id: String,
+    pub evaluated: u64,
+    pub matched: u64,
+    pub errors: u64,
+    pub branches: Vec<BranchCoverage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyCoverage {
+    pub policy_id: String,
+    pub evaluated: u64,
+    pub applicable: u64,
+    pub rules: Vec<RuleCoverage>,
+}
+
+// Everything that was never exercised, as "policy", "policy/rule" and
+// "policy/rule#branch: expression (never true|never false)" entries
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Unexercised {
+    pub policies: Vec<String>,
+    pub rules: Vec<String>,
+    pub branches: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoverageReport {
+    pub enabled: bool,
+    pub evaluations: u64,
+    pub policy_coverage: f64,
+    pub rule_coverage: f64,
+    pub branch_coverage: f64,
+    pub policies: Vec<PolicyCoverage>,
+    pub unexercised: Unexercised,
+}
+
+fn ratio(covered: usize, total: usize) -> f64 {
+    if total == 0 {
+        1.0
+    } else {
+        covered as f64 / total as f64
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Coverage collection is off by default because recording branch
+    // outcomes evaluates every leaf condition of each rule
+    #[wasm_bindgen]
+    pub fn set_coverage_enabled(&mut self, enabled: bool) {
+        self.coverage.get_mut().enabled = enabled;
+        if self.debug_mode {
+            console_log!("Coverage collection {}", if enabled { "enabled" } else { "disabled" });
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn reset_coverage(&mut self) {
+        let tracker = self.coverage.get_mut();
+        tracker.evaluations = 0;
+        tracker.policies.clear();
+    }
+
+    // JSON CoverageReport over every loaded policy (global and tenant),
+    // reporting policies that never applied, rules that never matched and
+    // branches never seen both true and false
+    #[wasm_bindgen]
+    pub fn get_coverage(&self) -> String {
+        let tracker = self.coverage.borrow();
+        let mut policies: Vec<&CompiledPolicy> = self.policies.iter().collect();
+        let mut tenant_ids: Vec<&String> = self.tenants.keys().collect();
+        tenant_ids.sort();
+        for id in tenant_ids {
+            if let Some(tenant) = self.tenants.get(id) {
+                policies.extend(tenant.own_policies());
+            }
+        }
+
+        let mut unexercised = Unexercised::default();
+        let mut report_policies = Vec::with_capacity(policies.len());
+        let (mut rule_total, mut rule_hit, mut branch_total, mut branch_hit) = (0, 0, 0, 0);
+
+        for compiled in &policies {
+            let policy = &compiled.policy;
+            let hits = tracker.policies.get(&policy.id).cloned().unwrap_or_default();
+            if hits.applicable == 0 {
+                unexercised.policies.push(policy.id.clone());
+            }
+
+            let mut rules = Vec::with_capacity(policy.rules.len() + compiled.linked.len());
+            for (rule, condition) in compiled.rules() {
+                let rule_hits = hits.rules.get(&rule.id).cloned().unwrap_or_default();
+                rule_total += 1;
+                if rule_hits.matched > 0 {
+                    rule_hit += 1;
+                } else {
+                    unexercised.rules.push(format!("{}/{}", policy.id, rule.id));
+                }
+
+                let mut branch_report = Vec::new();
+                for (index, branch) in branches(condition).into_iter().enumerate() {
+                    let (true_count, false_count) = rule_hits.branches.get(index).copied().unwrap_or((0, 0));
+                    branch_total += 2;
+                    branch_hit += (true_count > 0) as usize + (false_count > 0) as usize;
+                    for (count, outcome) in [(true_count, "never true"), (false_count, "never false")] {
+                        if count == 0 {
+                            unexercised
+                                .branches
+                                .push(format!("{}/{}#{}: {} ({})", policy.id, rule.id, index, branch, outcome));
+                        }
+                    }
+                    branch_report.push(BranchCoverage { expression: branch.to_string(), true_count, false_count });
+                }
+
+                rules.push(RuleCoverage {
+                    rule_id: rule.id.clone(),
+                    evaluated: rule_hits.evaluated,
+                    matched: rule_hits.matched,
+                    errors: rule_hits.errors,
+                    branches: branch_report,
+                });
+            }
+
+            report_policies.push(PolicyCoverage {
+                policy_id: policy.id.clone(),
+                evaluated: hits.evaluated,
+                applicable: hits.applicable,
+                rules,
+            });
+        }
+
+        let report = CoverageReport {
+            enabled: tracker.enabled,
+            evaluations: tracker.evaluations,
+            policy_coverage: ratio(policies.len() - unexercised.policies.len(), policies.len()),
+            rule_coverage: ratio(rule_hit, rule_total),
+            branch_coverage: ratio(branch_hit, branch_total),
+            policies: report_policies,
+            unexercised,
+        };
+        serde_json::to_string(&report).unwrap_or_default()
+    }
+}
+
+impl PolicyEngine {
+    pub(crate) fn record_evaluation_coverage(&self) {
+        let mut tracker = self.coverage.borrow_mut();
+        if tracker.enabled {
+            tracker.evaluations += 1;
+        }
+    }
+
+    pub(crate) fn record_target_coverage(&self, policy: &CompiledPolicy, applicable: bool) {
+        let mut tracker = self.coverage.borrow_mut();
+        if tracker.enabled {
+            tracker.record_target(&policy.policy.id, applicable);
+        }
+    }
+
+    // Leaf branches are evaluated independently (no short-circuiting) so
+    // every branch gets an outcome; a branch that errors records neither
+    pub(crate) fn record_rule_coverage(&self, policy: &CompiledPolicy, index: usize, result: &PolicyResult, scope: &EvalScope) {
+        if !self.coverage.borrow().enabled {
+            return;
+        }
+        let (rule, condition) = match policy.rules().nth(index) {
+            Some(found) => found,
+            None => return,
+        };
+        // Branches the rule short-circuited past were not read by it
+        scope.with_usage(|recorder| recorder.set_paused(true));
+        let outcomes: Vec<Option<bool>> = branches(condition)
+            .into_iter()
+            .map(|branch| match expr::evaluate(branch, scope) {
+                Ok(Value::Bool(b)) => Some(b),
+                Ok(Value::Null) => Some(false),
+                _ => None,
+            })
+            .collect();
+        scope.with_usage(|recorder| recorder.set_paused(false));
+
+        self.coverage
+            .borrow_mut()
+            .record_rule(&policy.policy.id, &rule.id, result.decision, outcomes);
+    }
+}
@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use std::fmt;
+use std::sync::Arc;
+
+// Decision carried by a PolicyResult. It crosses the JS boundary (and
+// JSON) as the upper-case name, so hosts see the same strings as before.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub enum Decision {
+    #[serde(rename = "PERMIT")]
+    Permit,
+    #[serde(rename = "DENY")]
+    Deny,
+    #[serde(rename = "NOTAPPLICABLE")]
+    NotApplicable,
+    #[serde(rename = "INDETERMINATE")]
+    Indeterminate,
+    // Permits once the step-up in `challenge` is completed
+    #[serde(rename = "CHALLENGE")]
+    Challenge,
+    // Waits for the approvers in `approval`
+    #[serde(rename = "PENDING_APPROVAL")]
+    PendingApproval,
+}
+
+impl Decision {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Decision::Permit => "PERMIT",
+            Decision::Deny => "DENY",
+            Decision::NotApplicable => "NOTAPPLICABLE",
+            Decision::Indeterminate => "INDETERMINATE",
+            Decision::Challenge => "CHALLENGE",
+            Decision::PendingApproval => "PENDING_APPROVAL",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Decision> {
+        match name {
+            "PERMIT" => Some(Decision::Permit),
+            "DENY" => Some(Decision::Deny),
+            "NOTAPPLICABLE" => Some(Decision::NotApplicable),
+            "INDETERMINATE" => Some(Decision::Indeterminate),
+            "CHALLENGE" => Some(Decision::Challenge),
+            "PENDING_APPROVAL" => Some(Decision::PendingApproval),
+            _ => None,
+        }
+    }
+
+    // A rule or policy result that decided something
+    pub fn is_decisive(self) -> bool {
+        !matches!(self, Decision::NotApplicable | Decision::Indeterminate)
+    }
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+thread_local! {
+    static EMPTY_LIST: Arc<str> = Arc::from("[]");
+}
+
+// Shared "[]" for results without obligations or advice
+pub fn empty_list() -> Arc<str> {
+    EMPTY_LIST.with(Arc::clone)
+}
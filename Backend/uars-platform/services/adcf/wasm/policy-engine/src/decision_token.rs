@@ -0,0 +1,207 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::clock;
+use crate::decision::Decision;
+use crate::digest::policy_set_hash;
+use crate::error::{to_json, PolicyEngineError};
+use crate::jose::{self, Jwk, Jwks};
+use crate::validity::parse_valid_until;
+use crate::{guard, PolicyEngine, PolicySelection};
+
+// Decision tokens are short-lived: a downstream service should only
+// honour a decision for about as long as the request that produced it
+pub const DEFAULT_TOKEN_TTL_SECONDS: i64 = 300;
+
+fn default_ttl() -> i64 {
+    DEFAULT_TOKEN_TTL_SECONDS
+}
+
+// Input of `set_decision_signing_key`: a private P-256 JWK (with `d`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct DecisionSigningConfig {
+    pub key: Jwk,
+    #[serde(default)]
+    pub issuer: Option<String>,
+    #[serde(default = "default_ttl")]
+    pub ttl_seconds: i64,
+}
+
+pub struct DecisionSigner {
+    key: p256::ecdsa::SigningKey,
+    kid: Option<String>,
+    issuer: Option<String>,
+    ttl_seconds: i64,
+}
+
+// Payload of a decision token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecisionClaims {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    pub iat: i64,
+    pub exp: i64,
+    pub request_id: String,
+    pub decision: Decision,
+    pub obligations: Vec<String>,
+    pub policy_set_hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule_id: Option<String>,
+    // End of the granted window (seconds since the epoch), see
+    // `is_decision_still_valid`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_until: Option<i64>,
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    #[wasm_bindgen]
+    pub fn set_decision_signing_key(&mut self, config_json: &str) -> Result<(), JsValue> {
+        let config: DecisionSigningConfig = serde_json::from_str(config_json).map_err(|e| {
+            PolicyEngineError::parse(format!("Failed to parse decision signing key: {}", e)).logged()
+        })?;
+        if config.ttl_seconds <= 0 {
+            return Err(PolicyEngineError::validation("ttl_seconds must be positive")
+                .with_details(json!({ "ttl_seconds": config.ttl_seconds }))
+                .logged()
+                .into());
+        }
+        let key = config
+            .key
+            .signing_key()
+            .map_err(|e| PolicyEngineError::validation(format!("Invalid decision signing key: {}", e)).logged())?;
+
+        self.decision_signer = Some(DecisionSigner {
+            key,
+            kid: config.key.kid,
+            issuer: config.issuer,
+            ttl_seconds: config.ttl_seconds,
+        });
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_decision_signing_key(&mut self) {
+        self.decision_signer = None;
+    }
+
+    // JWKS with the public half of the signing key, for publishing to
+    // the services that verify decision tokens
+    #[wasm_bindgen]
+    pub fn get_decision_verification_keys(&self) -> Result<String, JsValue> {
+        Ok(to_json(&self.decision_verification_keys())?)
+    }
+
+    // Evaluates like `evaluate` and returns the decision as a compact
+    // ES256 JWS whose payload is a DecisionClaims
+    #[wasm_bindgen]
+    pub fn evaluate_signed(&mut self, context_json: &str) -> Result<String, JsValue> {
+        if self.decision_signer.is_none() {
+            return Err(no_signer().into());
+        }
+
+        let context = self.parse_context(context_json)?;
+        let request_id = context.request_id.clone();
+        let result = guard::guarded(|| self.evaluate_request(context, PolicySelection::Global));
+
+        let Some(signer) = self.decision_signer.as_ref() else {
+            return Err(no_signer().into());
+        };
+        let now = clock::now().timestamp();
+        let claims = DecisionClaims {
+            iss: signer.issuer.clone(),
+            iat: now,
+            exp: now + signer.ttl_seconds,
+            request_id,
+            decision: result.decision,
+            obligations: serde_json::from_str(&result.obligations).unwrap_or_default(),
+            policy_set_hash: policy_set_hash(&self.policies),
+            policy_id: result.policy_id.clone(),
+            rule_id: result.rule_id.clone(),
+            valid_until: parse_valid_until(&result).map(|until| until.timestamp()),
+        };
+        let payload = serde_json::to_value(&claims)
+            .map_err(|e| PolicyEngineError::internal(format!("Failed to serialize decision claims: {}", e)).logged())?;
+        Ok(jose::sign_es256(&payload, &signer.key, signer.kid.as_deref()))
+    }
+}
+
+fn no_signer() -> PolicyEngineError {
+    PolicyEngineError::invalid_state("No decision signing key configured").logged()
+}
+
+impl PolicyEngine {
+    pub(crate) fn decision_verification_keys(&self) -> Jwks {
+        let keys = self
+            .decision_signer
+            .iter()
+            .map(|signer| Jwk::from_signing_key(&signer.key, signer.kid.clone()))
+            .collect();
+        Jwks { keys }
+    }
+}
+
+// Verifies a decision token against a JWKS (e.g. the output of
+// `get_decision_verification_keys`) and returns its DecisionClaims JSON
+#[wasm_bindgen]
+pub fn verify_decision_token(token: &str, jwks_json: &str) -> Result<String, JsValue> {
+    let jwks: Jwks = serde_json::from_str(jwks_json)
+        .map_err(|e| PolicyEngineError::parse(format!("Failed to parse JWKS: {}", e)).logged())?;
+    let claims = decision_claims(token, &jwks).map_err(|reason| {
+        PolicyEngineError::validation(format!("Invalid decision token: {}", reason))
+            .with_details(json!({ "reason": reason }))
+            .logged()
+    })?;
+    Ok(to_json(&claims)?)
+}
+
+fn decision_claims(token: &str, jwks: &Jwks) -> Result<DecisionClaims, String> {
+    let (_, claims) = jose::verify(token, jwks)?;
+    jose::check_times(&claims, clock::now())?;
+    serde_json::from_value(claims).map_err(|e| format!("not a decision token: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(byte: u8) -> p256::ecdsa::SigningKey {
+        p256::ecdsa::SigningKey::from_slice(&[byte; 32]).unwrap()
+    }
+
+    fn claims(exp: i64) -> serde_json::Value {
+        json!({
+            "iat": exp - DEFAULT_TOKEN_TTL_SECONDS,
+            "exp": exp,
+            "request_id": "r1",
+            "decision": "PERMIT",
+            "obligations": [],
+            "policy_set_hash": "h1"
+        })
+    }
+
+    #[test]
+    fn decision_tokens_verify_only_untouched_and_unexpired() {
+        let jwks = Jwks { keys: vec![Jwk::from_signing_key(&key(1), Some("k1".to_string()))] };
+        let exp = clock::now().timestamp() + DEFAULT_TOKEN_TTL_SECONDS;
+        let token = jose::sign_es256(&claims(exp), &key(1), Some("k1"));
+        let verified = decision_claims(&token, &jwks).unwrap();
+        assert_eq!((verified.decision, verified.request_id.as_str()), (Decision::Permit, "r1"));
+
+        let forged = jose::sign_es256(&claims(exp), &key(2), Some("k1"));
+        assert!(decision_claims(&forged, &jwks).is_err());
+
+        let (signed, signature) = token.rsplit_once('.').unwrap();
+        let (header, _) = signed.split_once('.').unwrap();
+        let mut denied = claims(exp);
+        denied["decision"] = json!("DENY");
+        let swapped = format!("{}.{}.{}", header, jose::encode_segment(denied.to_string().as_bytes()), signature);
+        assert!(decision_claims(&swapped, &jwks).is_err());
+
+        let expired = jose::sign_es256(&claims(clock::now().timestamp() - 3600), &key(1), Some("k1"));
+        assert!(decision_claims(&expired, &jwks).is_err());
+    }
+}
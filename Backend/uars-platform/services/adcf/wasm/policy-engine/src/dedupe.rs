@@ -0,0 +1,98 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::error::to_json;
+use crate::tenants::Tenant;
+use crate::{state_key, CompiledPolicy, PolicyEngine};
+
+// A policy loaded again with content identical to one already loaded
+// (same digest::policy_hash), e.g. on a reconnect or retried load
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollapsedPolicy {
+    pub content_hash: String,
+    pub policy_id: String,
+    pub version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    // Loads that were dropped, not counting the first
+    pub duplicates: u64,
+}
+
+// Collapsed loads by tenant and content hash
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DedupeTracker {
+    collapsed: BTreeMap<String, CollapsedPolicy>,
+}
+
+impl DedupeTracker {
+    fn record(&mut self, compiled: &CompiledPolicy, tenant: Option<&str>) {
+        let hash = compiled.content_hash();
+        self.collapsed
+            .entry(state_key(tenant, hash))
+            .or_insert_with(|| CollapsedPolicy {
+                content_hash: hash.to_string(),
+                policy_id: compiled.policy.id.clone(),
+                version: compiled.policy.version.clone(),
+                tenant: tenant.map(str::to_string),
+                duplicates: 0,
+            })
+            .duplicates += 1;
+    }
+}
+
+// Output of dedupe_report
+#[derive(Debug, Serialize)]
+pub struct DedupeReport {
+    // Policies loaded now, global and per tenant
+    pub loaded: usize,
+    pub duplicates_collapsed: u64,
+    pub collapsed: Vec<CollapsedPolicy>,
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Loads are idempotent: a policy identical to one already loaded (in
+    // the same tenant) is dropped rather than evaluated twice. Reports
+    // the loads collapsed so far, most duplicated first.
+    #[wasm_bindgen]
+    pub fn dedupe_report(&self) -> Result<String, JsValue> {
+        let mut collapsed: Vec<CollapsedPolicy> = self.dedupe.collapsed.values().cloned().collect();
+        collapsed.sort_by(|a, b| b.duplicates.cmp(&a.duplicates).then_with(|| a.policy_id.cmp(&b.policy_id)));
+        let report = DedupeReport {
+            loaded: self.policies.len() + self.tenants.values().map(|tenant| tenant.own_policies().len()).sum::<usize>(),
+            duplicates_collapsed: collapsed.iter().map(|entry| entry.duplicates).sum(),
+            collapsed,
+        };
+        Ok(to_json(&report)?)
+    }
+
+    #[wasm_bindgen]
+    pub fn reset_dedupe_report(&mut self) {
+        self.dedupe = DedupeTracker::default();
+    }
+}
+
+impl PolicyEngine {
+    // Adds a global (or, with `tenant`, a tenant) policy unless one with
+    // the same content is already loaded there. Returns whether it was
+    // added.
+    pub(crate) fn insert_unique(&mut self, compiled: CompiledPolicy, tenant: Option<&str>) -> bool {
+        let loaded = match tenant {
+            Some(tenant) => self.tenants.get(tenant).map_or(&[][..], Tenant::own_policies),
+            None => &self.policies,
+        };
+        if loaded.iter().any(|loaded| loaded.content_hash() == compiled.content_hash()) {
+            if self.debug_mode {
+                console_log!("Policy {} already loaded with the same content, skipped", compiled.policy.id);
+            }
+            self.dedupe.record(&compiled, tenant);
+            return false;
+        }
+        match tenant {
+            Some(tenant) => self.tenants.entry(tenant.to_string()).or_default().push_policy(compiled),
+            None => self.policies.push(compiled),
+        }
+        true
+    }
+}
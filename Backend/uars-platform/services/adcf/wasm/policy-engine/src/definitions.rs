@@ -0,0 +1,145 @@
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::error::PolicyEngineError;
+use crate::expr::{self, Expr};
+use crate::Policy;
+
+// Expansion inlines definitions, so a chain of definitions that each use
+// the previous one twice grows exponentially; cap the result
+pub const MAX_EXPANDED_NODES: usize = 10_000;
+
+// A policy's `definitions` parsed and fully expanded. A bare identifier
+// in a target or condition that names a definition is replaced by the
+// definition's expression; definitions may use each other but not
+// cyclically.
+pub struct Definitions {
+    expanded: HashMap<String, Expr>,
+}
+
+impl Definitions {
+    pub fn compile(policy: &Policy) -> Result<Definitions, PolicyEngineError> {
+        let mut parsed = BTreeMap::new();
+        for (name, source) in &policy.definitions {
+            let definition = expr::parse(source).map_err(|e| {
+                PolicyEngineError::compile(format!("Policy '{}' definition '{}': {}", policy.id, name, e))
+                    .with_details(json!({ "policy_id": policy.id, "definition": name, "offset": e.offset }))
+            })?;
+            parsed.insert(name.as_str(), definition);
+        }
+
+        let mut definitions = Definitions { expanded: HashMap::new() };
+        for name in parsed.keys() {
+            let mut path = Vec::new();
+            definitions.expand_definition(policy, &parsed, name, &mut path)?;
+        }
+        Ok(definitions)
+    }
+
+    // Depth-first; `path` holds the definitions being expanded so a
+    // repeat is a cycle
+    fn expand_definition<'a>(
+        &mut self,
+        policy: &Policy,
+        parsed: &BTreeMap<&'a str, Expr>,
+        name: &'a str,
+        path: &mut Vec<&'a str>,
+    ) -> Result<(), PolicyEngineError> {
+        if self.expanded.contains_key(name) {
+            return Ok(());
+        }
+        if let Some(start) = path.iter().position(|n| *n == name) {
+            let mut cycle: Vec<&str> = path[start..].to_vec();
+            cycle.push(name);
+            return Err(PolicyEngineError::compile(format!(
+                "Policy '{}' definitions are cyclic: {}",
+                policy.id,
+                cycle.join(" -> ")
+            ))
+            .with_details(json!({ "policy_id": policy.id, "cycle": cycle })));
+        }
+
+        path.push(name);
+        for dependency in references(&parsed[name]) {
+            if let Some((key, _)) = parsed.get_key_value(dependency.as_str()) {
+                self.expand_definition(policy, parsed, key, path)?;
+            }
+        }
+        path.pop();
+
+        let expanded = self.inline(&parsed[name]);
+        self.check_size(policy, name, &expanded)?;
+        self.expanded.insert(name.to_string(), expanded);
+        Ok(())
+    }
+
+    fn check_size(&self, policy: &Policy, name: &str, expanded: &Expr) -> Result<(), PolicyEngineError> {
+        if node_count(expanded) > MAX_EXPANDED_NODES {
+            return Err(PolicyEngineError::compile(format!(
+                "Policy '{}': '{}' expands to more than {} nodes",
+                policy.id, name, MAX_EXPANDED_NODES
+            ))
+            .with_details(json!({ "policy_id": policy.id, "definition": name })));
+        }
+        Ok(())
+    }
+
+    // Replaces definition names in a target or condition
+    pub fn apply(&self, policy: &Policy, label: &str, expression: Expr) -> Result<Expr, PolicyEngineError> {
+        if self.expanded.is_empty() {
+            return Ok(expression);
+        }
+        let expanded = self.inline(&expression);
+        self.check_size(policy, label, &expanded)?;
+        Ok(expanded)
+    }
+
+    fn inline(&self, expression: &Expr) -> Expr {
+        match expression {
+            Expr::Attribute(path) if path.len() == 1 => match self.expanded.get(&path[0]) {
+                Some(definition) => definition.clone(),
+                None => expression.clone(),
+            },
+            Expr::Literal(_) | Expr::Attribute(_) => expression.clone(),
+            Expr::List(items) => Expr::List(items.iter().map(|item| self.inline(item)).collect()),
+            Expr::Unary(op, operand) => Expr::Unary(*op, Box::new(self.inline(operand))),
+            Expr::Binary(op, left, right) => {
+                Expr::Binary(*op, Box::new(self.inline(left)), Box::new(self.inline(right)))
+            }
+            Expr::Call(name, args) => Expr::Call(name.clone(), args.iter().map(|arg| self.inline(arg)).collect()),
+        }
+    }
+}
+
+// Single-segment identifiers used in an expression
+pub(crate) fn references(expression: &Expr) -> Vec<String> {
+    let mut names = Vec::new();
+    collect_references(expression, &mut names);
+    names
+}
+
+fn collect_references(expression: &Expr, names: &mut Vec<String>) {
+    match expression {
+        Expr::Attribute(path) if path.len() == 1 => names.push(path[0].clone()),
+        Expr::Literal(_) | Expr::Attribute(_) => {}
+        Expr::List(items) | Expr::Call(_, items) => {
+            for item in items {
+                collect_references(item, names);
+            }
+        }
+        Expr::Unary(_, operand) => collect_references(operand, names),
+        Expr::Binary(_, left, right) => {
+            collect_references(left, names);
+            collect_references(right, names);
+        }
+    }
+}
+
+fn node_count(expression: &Expr) -> usize {
+    1 + match expression {
+        Expr::Literal(_) | Expr::Attribute(_) => 0,
+        Expr::List(items) | Expr::Call(_, items) => items.iter().map(node_count).sum(),
+        Expr::Unary(_, operand) => node_count(operand),
+        Expr::Binary(_, left, right) => node_count(left) + node_count(right),
+    }
+}
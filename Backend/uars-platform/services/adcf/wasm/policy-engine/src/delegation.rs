@@ -0,0 +1,221 @@
+use wasm_bindgen::prelude::*;
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::clock;
+use crate::decision::Decision;
+use crate::error::{to_json, PolicyEngineError};
+use crate::expr::matches_path;
+use crate::messages;
+use crate::{state_key, PolicyContext, PolicyEngine, PolicyResult};
+
+// Longest accepted delegation chain
+pub const MAX_DELEGATION_DEPTH: usize = 8;
+
+// One hop of an on-behalf-of chain: `actor` acts for `on_behalf_of`
+// under grant `grant_id`. The first link acts for the context's user_id
+// and each later link acts for the previous link's actor, so the last
+// actor is the identity actually making the request.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DelegationLink {
+    pub actor: String,
+    pub on_behalf_of: String,
+    pub grant_id: String,
+}
+
+// Permission for `delegate` to act for `delegator`, optionally limited
+// to some operations and resource paths (matches_path patterns). Grants
+// belong to a tenant, or are global, like other per-user state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DelegationGrant {
+    pub id: String,
+    pub delegator: String,
+    pub delegate: String,
+    #[serde(default)]
+    pub operations: Vec<String>,
+    #[serde(default)]
+    pub resources: Vec<String>,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    // Whether the delegate may delegate further
+    #[serde(default)]
+    pub redelegable: bool,
+}
+
+// Why a chain was rejected, and the actor whose hop failed
+pub struct ChainRejection {
+    pub actor: String,
+    pub reason: String,
+}
+
+fn reject(link: &DelegationLink, reason: String) -> ChainRejection {
+    ChainRejection { actor: link.actor.clone(), reason }
+}
+
+impl DelegationGrant {
+    // Expiry is by the engine clock; the request timestamp is the
+    // caller's to choose
+    fn covers(&self, context: &PolicyContext, now: DateTime<Utc>) -> Result<(), String> {
+        if self.expires_at.is_some_and(|expires| now >= expires) {
+            return Err(format!("grant '{}' has expired", self.id));
+        }
+        if !self.operations.is_empty() && !self.operations.contains(&context.operation) {
+            return Err(format!("grant '{}' does not cover operation '{}'", self.id, context.operation));
+        }
+        if !self.resources.is_empty() && !self.resources.iter().any(|p| matches_path(&context.resource_id, p)) {
+            return Err(format!("grant '{}' does not cover resource '{}'", self.id, context.resource_id));
+        }
+        Ok(())
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Adds (or replaces by ID) a JSON array of DelegationGrant, for
+    // requests evaluated for `tenant_id` when given. Returns the number
+    // loaded.
+    #[wasm_bindgen]
+    pub fn load_delegation_grants(&mut self, grants_json: &str, tenant_id: Option<String>) -> Result<usize, JsValue> {
+        let grants: Vec<DelegationGrant> = serde_json::from_str(grants_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse delegation grants: {}", e)).logged())?;
+        if let Some(grant) = grants.iter().find(|g| g.delegator == g.delegate) {
+            return Err(PolicyEngineError::validation(format!("Grant '{}' delegates to its own delegator", grant.id))
+                .with_details(json!({ "grant_id": grant.id }))
+                .logged()
+                .into());
+        }
+        if let Some(tenant) = tenant_id.as_deref().filter(|tenant| !self.tenants.contains_key(*tenant)) {
+            return Err(PolicyEngineError::not_found(format!("Tenant '{}' does not exist", tenant))
+                .with_details(json!({ "tenant_id": tenant }))
+                .logged()
+                .into());
+        }
+
+        let count = grants.len();
+        for grant in grants {
+            self.delegation_grants.insert(state_key(tenant_id.as_deref(), &grant.id), grant);
+        }
+        Ok(count)
+    }
+
+    #[wasm_bindgen]
+    pub fn remove_delegation_grant(&mut self, grant_id: &str, tenant_id: Option<String>) -> bool {
+        self.delegation_grants.remove(&state_key(tenant_id.as_deref(), grant_id)).is_some()
+    }
+
+    // JSON array of the grants loaded for `tenant_id` (global ones
+    // without it), by ID
+    #[wasm_bindgen]
+    pub fn list_delegation_grants(&self, tenant_id: Option<String>) -> Result<String, JsValue> {
+        let mut grants: Vec<&DelegationGrant> = self
+            .delegation_grants
+            .iter()
+            .filter(|(key, grant)| **key == state_key(tenant_id.as_deref(), &grant.id))
+            .map(|(_, grant)| grant)
+            .collect();
+        grants.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(to_json(&grants)?)
+    }
+}
+
+impl PolicyEngine {
+    pub(crate) fn validate_delegation(&self, context: &PolicyContext, tenant: Option<&str>) -> Result<(), ChainRejection> {
+        let chain = &context.delegation;
+        if chain.len() > MAX_DELEGATION_DEPTH {
+            return Err(reject(&chain[chain.len() - 1], format!("chain is longer than {} links", MAX_DELEGATION_DEPTH)));
+        }
+
+        let now = clock::now();
+        let mut principal = context.user_id.as_str();
+        let mut may_delegate = true;
+        for link in chain {
+            if !may_delegate {
+                return Err(reject(link, format!("'{}' may not delegate further", link.on_behalf_of)));
+            }
+            if link.on_behalf_of != principal {
+                return Err(reject(link, format!("link acts for '{}', expected '{}'", link.on_behalf_of, principal)));
+            }
+            let grant = self
+                .delegation_grants
+                .get(&state_key(tenant, &link.grant_id))
+                .ok_or_else(|| reject(link, format!("unknown grant '{}'", link.grant_id)))?;
+            if grant.delegator != link.on_behalf_of || grant.delegate != link.actor {
+                return Err(reject(link, format!("grant '{}' is not from '{}' to '{}'", grant.id, link.on_behalf_of, link.actor)));
+            }
+            grant.covers(context, now).map_err(|reason| reject(link, reason))?;
+
+            principal = &link.actor;
+            may_delegate = grant.redelegable;
+        }
+        Ok(())
+    }
+
+    // The identity issuing the request: the last actor, or the user
+    pub(crate) fn acting_identity<'a>(&self, context: &'a PolicyContext) -> &'a str {
+        context.delegation.last().map_or(context.user_id.as_str(), |link| link.actor.as_str())
+    }
+
+    // Policies are evaluated with the subject's (user_id's) attributes. A
+    // delegated PERMIT additionally needs a valid chain; otherwise it
+    // becomes a DENY attributed to the actor whose hop failed.
+    pub(crate) fn apply_delegation(&self, result: &mut PolicyResult, context: &PolicyContext, tenant: Option<&str>) {
+        if context.delegation.is_empty() {
+            return;
+        }
+        match self.validate_delegation(context, tenant) {
+            Ok(()) => result.decisive_identity = Some(context.user_id.clone()),
+            Err(rejection) if result.decision == Decision::Permit => {
+                *result = PolicyResult::new(
+                    Decision::Deny,
+                    format!("Delegation rejected: {}", rejection.reason),
+                    1.0,
+                );
+                result.reason_code = Some(messages::DELEGATION_REJECTED.to_string());
+                result.decisive_identity = Some(rejection.actor);
+            }
+            Err(_) => result.decisive_identity = Some(context.user_id.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    fn engine_with_grant(key: &str, expires_at: DateTime<Utc>) -> PolicyEngine {
+        let grant: DelegationGrant = serde_json::from_value(json!({
+            "id": "g1", "delegator": "alice", "delegate": "bot", "expires_at": expires_at
+        }))
+        .unwrap();
+        let mut engine = PolicyEngine::new();
+        engine.delegation_grants.insert(key.to_string(), grant);
+        engine
+    }
+
+    fn context(timestamp: DateTime<Utc>) -> PolicyContext {
+        let link = DelegationLink { actor: "bot".to_string(), on_behalf_of: "alice".to_string(), grant_id: "g1".to_string() };
+        PolicyContext { user_id: "alice".to_string(), delegation: vec![link], timestamp, ..PolicyContext::default() }
+    }
+
+    #[test]
+    fn expired_grants_stay_expired_for_backdated_requests() {
+        let expired = clock::now() - TimeDelta::hours(1);
+        let engine = engine_with_grant("g1", expired);
+        let rejection = engine.validate_delegation(&context(expired - TimeDelta::days(1)), None).unwrap_err();
+        assert!(rejection.reason.contains("expired"));
+
+        let engine = engine_with_grant("g1", clock::now() + TimeDelta::hours(1));
+        assert!(engine.validate_delegation(&context(clock::now()), None).is_ok());
+    }
+
+    #[test]
+    fn grants_belong_to_their_tenant() {
+        let engine = engine_with_grant(&state_key(Some("t1"), "g1"), clock::now() + TimeDelta::hours(1));
+        assert!(engine.validate_delegation(&context(clock::now()), Some("t1")).is_ok());
+        assert!(engine.validate_delegation(&context(clock::now()), Some("t2")).is_err());
+        assert!(engine.validate_delegation(&context(clock::now()), None).is_err());
+    }
+}
@@ -0,0 +1,177 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::digest::policy_set_hash;
+use crate::error::PolicyEngineError;
+use crate::{CompiledPolicy, Policy, PolicyEngine};
+
+// Changeset pushed by a backend. `base_hash` must match the engine's
+// current policy-set hash or the delta is rejected, so a client that
+// missed an update resynchronises with a full load instead of drifting.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyDelta {
+    pub base_hash: String,
+    #[serde(default)]
+    pub added: Vec<Policy>,
+    #[serde(default)]
+    pub updated: Vec<Policy>,
+    #[serde(default)]
+    pub removed: Vec<String>,
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    #[wasm_bindgen]
+    pub fn get_policy_set_hash(&self) -> String {
+        policy_set_hash(&self.policies)
+    }
+
+    // Applies the delta atomically and returns the new policy-set hash.
+    // On any error the loaded policies are left unchanged.
+    #[wasm_bindgen]
+    pub fn apply_policy_delta(&mut self, delta_json: &str) -> Result<String, JsValue> {
+        let delta: PolicyDelta = serde_json::from_str(delta_json).map_err(|e| {
+            PolicyEngineError::parse(format!("Failed to parse policy delta: {}", e)).logged()
+        })?;
+
+        let updated = self
+            .apply_delta(delta)
+            .map_err(|e| e.context("Failed to apply policy delta").logged())?;
+
+        self.policies = updated;
+        let hash = policy_set_hash(&self.policies);
+        if self.debug_mode {
+            console_log!("Applied policy delta, {} policies loaded ({})", self.policies.len(), hash);
+        }
+        Ok(hash)
+    }
+}
+
+impl PolicyEngine {
+    // Builds the post-delta policy list without touching the engine
+    fn apply_delta(&self, delta: PolicyDelta) -> Result<Vec<CompiledPolicy>, PolicyEngineError> {
+        let current = policy_set_hash(&self.policies);
+        if delta.base_hash != current {
+            return Err(PolicyEngineError::conflict(format!(
+                "base hash mismatch (expected {}, engine has {})",
+                delta.base_hash, current
+            ))
+            .with_details(serde_json::json!({ "base_hash": delta.base_hash, "current_hash": current })));
+        }
+
+        let existing: HashSet<&str> = self.policies.iter().map(|p| p.policy.id.as_str()).collect();
+        let mut added = HashSet::new();
+        for policy in &delta.added {
+            if existing.contains(policy.id.as_str()) {
+                return Err(PolicyEngineError::conflict(format!("added policy '{}' already exists", policy.id)));
+            }
+            if !added.insert(policy.id.as_str()) {
+                return Err(PolicyEngineError::conflict(format!("added policy '{}' appears more than once", policy.id))
+                    .with_details(serde_json::json!({ "policy_id": policy.id })));
+            }
+        }
+        let mut updated = HashSet::new();
+        for policy in &delta.updated {
+            if !existing.contains(policy.id.as_str()) {
+                return Err(PolicyEngineError::not_found(format!("updated policy '{}' does not exist", policy.id)));
+            }
+            if !updated.insert(policy.id.as_str()) {
+                return Err(PolicyEngineError::conflict(format!("updated policy '{}' appears more than once", policy.id))
+                    .with_details(serde_json::json!({ "policy_id": policy.id })));
+            }
+        }
+        for id in &delta.removed {
+            if !existing.contains(id.as_str()) {
+                return Err(PolicyEngineError::not_found(format!("removed policy '{}' does not exist", id)));
+            }
+            if updated.contains(id.as_str()) {
+                return Err(PolicyEngineError::conflict(format!("policy '{}' is both updated and removed", id))
+                    .with_details(serde_json::json!({ "policy_id": id })));
+            }
+        }
+
+        let mut updates = Vec::with_capacity(delta.updated.len());
+        for policy in delta.updated {
+            updates.push(self.compile_policy(policy)?);
+        }
+        let mut additions = Vec::with_capacity(delta.added.len());
+        for policy in delta.added {
+            additions.push(self.compile_policy(policy)?);
+        }
+
+        // Updates replace in place to preserve evaluation order
+        let mut result = Vec::with_capacity(self.policies.len() + additions.len());
+        for compiled in &self.policies {
+            if delta.removed.contains(&compiled.policy.id) {
+                continue;
+            }
+            match updates.iter().position(|u| u.policy.id == compiled.policy.id) {
+                Some(index) => result.push(updates.swap_remove(index)),
+                None => result.push(compiled.clone()),
+            }
+        }
+        result.extend(additions);
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn policy(id: &str) -> Policy {
+        serde_json::from_value(json!({
+            "id": id,
+            "name": id,
+            "version": "1.0.0",
+            "description": "",
+            "target": "true",
+            "combining_algorithm": "deny-overrides",
+            "rules": [],
+            "obligations": [],
+            "advice": []
+        }))
+        .unwrap()
+    }
+
+    fn engine_with(ids: &[&str]) -> PolicyEngine {
+        let mut engine = PolicyEngine::new();
+        for id in ids {
+            let compiled = engine.compile_policy(policy(id)).unwrap();
+            engine.policies.push(compiled);
+        }
+        engine
+    }
+
+    fn delta(engine: &PolicyEngine, added: &[&str], updated: &[&str], removed: &[&str]) -> PolicyDelta {
+        PolicyDelta {
+            base_hash: policy_set_hash(&engine.policies),
+            added: added.iter().map(|id| policy(id)).collect(),
+            updated: updated.iter().map(|id| policy(id)).collect(),
+            removed: removed.iter().map(|id| id.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn rejects_an_id_added_twice() {
+        let engine = engine_with(&["a"]);
+        let error = engine.apply_delta(delta(&engine, &["b", "b"], &[], &[])).unwrap_err();
+        assert_eq!(error.code(), "CONFLICT");
+        assert!(engine.apply_delta(delta(&engine, &["b", "c"], &[], &[])).is_ok());
+    }
+
+    #[test]
+    fn rejects_an_id_both_updated_and_removed() {
+        let engine = engine_with(&["a", "b"]);
+        let error = engine.apply_delta(delta(&engine, &[], &["a"], &["a"])).unwrap_err();
+        assert_eq!(error.code(), "CONFLICT");
+        let error = engine.apply_delta(delta(&engine, &[], &["a", "a"], &[])).unwrap_err();
+        assert_eq!(error.code(), "CONFLICT");
+
+        let result = engine.apply_delta(delta(&engine, &[], &["a"], &["b"])).unwrap();
+        assert_eq!(result.iter().map(|p| p.policy.id.as_str()).collect::<Vec<_>>(), ["a"]);
+    }
+}
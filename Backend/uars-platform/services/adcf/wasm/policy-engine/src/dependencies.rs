@@ -0,0 +1,87 @@
+use wasm_bindgen::prelude::*;
+use serde_json::json;
+use std::collections::{BTreeSet, VecDeque};
+
+use crate::attributes::{canonical_path, context_field};
+use crate::error::{to_json, PolicyEngineError};
+use crate::{PolicyContext, PolicyEngine, PolicyResult};
+
+// Most recent decisions whose dependencies are kept
+const CAPACITY: usize = 10_000;
+
+struct DecisionDependencies {
+    request_id: String,
+    user_id: String,
+    attributes: BTreeSet<String>,
+}
+
+// Attributes recent decisions read, by request id, so a PEP caching
+// decisions learns exactly which ones a changed fact invalidates. Not
+// part of snapshots: the caches it describes belong to this instance.
+#[derive(Default)]
+pub struct DependencyIndex {
+    decisions: VecDeque<DecisionDependencies>,
+}
+
+// Whether a change to `changed` affects a read of `read`: the same
+// attribute, or one containing the other (`resource_attributes` and
+// `resource_attributes.department`)
+fn overlaps(changed: &str, read: &str) -> bool {
+    let within = |inner: &str, outer: &str| inner.strip_prefix(outer).is_some_and(|rest| rest.starts_with('.'));
+    changed == read || within(read, changed) || within(changed, read)
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Reports that a context attribute changed (for one user, or for
+    // everyone when `user_id` is omitted) and returns the request ids of
+    // recent decisions that read it, as a JSON array, oldest first. Those
+    // decisions are forgotten, so each is reported once.
+    #[wasm_bindgen]
+    pub fn notify_attribute_changed(&self, attribute: &str, user_id: Option<String>) -> Result<String, JsValue> {
+        let segments: Vec<&str> = attribute.split('.').map(str::trim).collect();
+        if context_field(&segments).is_none() {
+            return Err(PolicyEngineError::validation(format!("'{}' is not a context attribute", attribute))
+                .with_details(json!({ "attribute": attribute }))
+                .logged()
+                .into());
+        }
+        let changed = canonical_path(&segments);
+        let mut invalidated: Vec<String> = Vec::new();
+        self.dependencies.borrow_mut().decisions.retain(|decision| {
+            let affected = user_id.as_deref().is_none_or(|user_id| decision.user_id == user_id)
+                && decision.attributes.iter().any(|read| overlaps(&changed, read));
+            if affected && !invalidated.contains(&decision.request_id) {
+                invalidated.push(decision.request_id.clone());
+            }
+            !affected
+        });
+        if self.debug_mode {
+            console_log!("Attribute '{}' changed: {} decisions invalidated", changed, invalidated.len());
+        }
+        Ok(to_json(&invalidated)?)
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_decision_dependencies(&self) {
+        self.dependencies.borrow_mut().decisions.clear();
+    }
+}
+
+impl PolicyEngine {
+    // Requests without an id cannot be named in an invalidation
+    pub(crate) fn record_dependencies(&self, context: &PolicyContext, result: &PolicyResult) {
+        if context.request_id.is_empty() {
+            return;
+        }
+        let mut index = self.dependencies.borrow_mut();
+        if index.decisions.len() >= CAPACITY {
+            index.decisions.pop_front();
+        }
+        index.decisions.push_back(DecisionDependencies {
+            request_id: context.request_id.clone(),
+            user_id: context.user_id.clone(),
+            attributes: result.dependencies.iter().cloned().collect(),
+        });
+    }
+}
@@ -0,0 +1,170 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+use crate::error::{to_json, PolicyEngineError};
+use crate::expr::{Expr, Value};
+use crate::{CompiledPolicy, Policy, PolicyEngine, PolicyRule};
+
+// Where the engine is deployed, and the constants policies are written
+// against there:
+//
+//   { "environment": "prod",
+//     "constants": { "max_risk": 70, "allowed_cidrs": ["10.0.0.0/8"] },
+//     "environments": { "prod": { "max_risk": 40 } } }
+//
+// Policies and rules declaring `environments` only apply in those. A
+// `${name}` in a target, condition or definition is replaced at load time
+// by the constant as a literal, the environment's own value over the
+// shared one, so `risk_score > ${max_risk}` loads as `risk_score > 40`
+// in prod.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeploymentConfig {
+    // Unset: only policies and rules without `environments` apply
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>,
+    pub constants: BTreeMap<String, serde_json::Value>,
+    // Environment name to constants overriding the shared ones there
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub environments: BTreeMap<String, BTreeMap<String, serde_json::Value>>,
+}
+
+// Constants must render as expression literals, which maps and null
+// cannot usefully be
+fn literal_value(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Bool(_) | serde_json::Value::Number(_) | serde_json::Value::String(_) => true,
+        serde_json::Value::Array(items) => items.iter().all(literal_value),
+        serde_json::Value::Null | serde_json::Value::Object(_) => false,
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_') && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl DeploymentConfig {
+    fn validate(&self) -> Result<(), PolicyEngineError> {
+        if self.environment.as_deref().is_some_and(|name| name.trim().is_empty()) {
+            return Err(PolicyEngineError::validation("Deployment environment name must not be empty"));
+        }
+        let shared = self.constants.iter().map(|(name, value)| (None, name, value));
+        let overrides = self
+            .environments
+            .iter()
+            .flat_map(|(environment, constants)| constants.iter().map(move |(name, value)| (Some(environment), name, value)));
+        for (environment, name, value) in shared.chain(overrides) {
+            if !is_identifier(name) || !literal_value(value) {
+                return Err(PolicyEngineError::validation(format!(
+                    "Constant '{}' needs an identifier name and a boolean, number, string or list value",
+                    name
+                ))
+                .with_details(json!({ "constant": name, "environment": environment, "value": value })));
+            }
+        }
+        Ok(())
+    }
+
+    fn constant(&self, name: &str) -> Option<&serde_json::Value> {
+        self.environment
+            .as_ref()
+            .and_then(|environment| self.environments.get(environment)?.get(name))
+            .or_else(|| self.constants.get(name))
+    }
+
+    // `text` with each `${name}` replaced, or the name of the first
+    // constant that is not defined
+    fn substitute(&self, text: &str) -> Result<String, String> {
+        if !text.contains("${") {
+            return Ok(text.to_string());
+        }
+        let mut substituted = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("${") {
+            substituted.push_str(&rest[..start]);
+            let Some(end) = rest[start..].find('}') else {
+                return Err(rest[start + 2..].trim().to_string());
+            };
+            let name = rest[start + 2..start + end].trim();
+            let value = self.constant(name).ok_or_else(|| name.to_string())?;
+            substituted.push_str(&Expr::Literal(Value::from(value)).to_string());
+            rest = &rest[start + end + 1..];
+        }
+        substituted.push_str(rest);
+        Ok(substituted)
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Replaces the deployment configuration (see DeploymentConfig). The
+    // environment takes effect at once; constants only reach policies
+    // and rule libraries loaded afterwards, since loaded ones already
+    // have theirs substituted.
+    #[wasm_bindgen]
+    pub fn configure_deployment(&mut self, config_json: &str) -> Result<(), JsValue> {
+        let config: DeploymentConfig = serde_json::from_str(config_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse deployment configuration: {}", e)).logged())?;
+        config.validate().map_err(|e| e.logged())?;
+        if self.debug_mode {
+            console_log!("Deployment environment: {}", config.environment.as_deref().unwrap_or("(none)"));
+        }
+        self.deployment = config;
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn get_deployment(&self) -> Result<String, JsValue> {
+        Ok(to_json(&self.deployment)?)
+    }
+}
+
+impl PolicyEngine {
+    // Whether something declaring these environments applies here
+    pub(crate) fn in_deployment(&self, environments: &[String]) -> bool {
+        environments.is_empty()
+            || self.deployment.environment.as_deref().is_some_and(|current| {
+                environments.iter().any(|environment| environment.trim().eq_ignore_ascii_case(current.trim()))
+            })
+    }
+
+    // Enabled, and meant for this environment
+    pub(crate) fn policy_in_effect(&self, compiled: &CompiledPolicy) -> bool {
+        compiled.policy.enabled && self.in_deployment(&compiled.policy.environments)
+    }
+
+    fn substitute_constants(&self, text: &mut String, location: &str, mut details: serde_json::Value) -> Result<(), PolicyEngineError> {
+        *text = self.deployment.substitute(text).map_err(|name| {
+            details["constant"] = json!(name);
+            PolicyEngineError::compile(format!("Undefined constant '${{{}}}' in {}", name, location)).with_details(details)
+        })?;
+        Ok(())
+    }
+
+    // Substitutes constants into a policy's target, definitions and rule
+    // conditions before it is compiled
+    pub(crate) fn substitute_policy_constants(&self, policy: &mut Policy) -> Result<(), PolicyEngineError> {
+        let id = &policy.id;
+        self.substitute_constants(&mut policy.target, &format!("policy '{}' target", id), json!({ "policy_id": id }))?;
+        for (name, definition) in policy.definitions.iter_mut() {
+            let location = format!("policy '{}' definition '{}'", id, name);
+            self.substitute_constants(definition, &location, json!({ "policy_id": id, "definition": name }))?;
+        }
+        for rule in &mut policy.rules {
+            let location = format!("policy '{}' rule '{}'", id, rule.id);
+            self.substitute_constants(&mut rule.condition, &location, json!({ "policy_id": id, "rule_id": rule.id }))?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn substitute_library_constants(&self, library: &str, rules: &mut [PolicyRule]) -> Result<(), PolicyEngineError> {
+        for rule in rules {
+            let location = format!("rule library '{}' rule '{}'", library, rule.id);
+            self.substitute_constants(&mut rule.condition, &location, json!({ "library": library, "rule_id": rule.id }))?;
+        }
+        Ok(())
+    }
+}
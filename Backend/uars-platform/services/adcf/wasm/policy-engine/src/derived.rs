@@ -0,0 +1,131 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+use crate::diagnostics;
+use crate::error::{to_json, PolicyEngineError};
+use crate::expr::{self, Expr};
+use crate::strict;
+use crate::vocabulary;
+use crate::PolicyEngine;
+
+// Root of computed attributes in targets and conditions
+pub const DERIVED_ROOT: &str = "derived";
+
+// A computed attribute, `derived.<name>` in any target or condition:
+//
+//   engine.define_derived_attribute("off_hours", "!business_hours && !is_holiday()")
+//
+// Unlike a policy's definitions, which are inlined wherever they are
+// used, it is evaluated the first time a request reads it, in that
+// request's scope (tenant state and timestamp), and the value is reused
+// for the rest of the request. It may read other derived attributes, but
+// not cyclically.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DerivedAttribute {
+    pub source: String,
+    pub expression: Expr,
+}
+
+// Names of the derived attributes an expression reads
+fn derived_references(expression: &Expr, names: &mut Vec<String>) {
+    match expression {
+        Expr::Attribute(path) if path.len() >= 2 && path[0] == DERIVED_ROOT => names.push(path[1].clone()),
+        Expr::Literal(_) | Expr::Attribute(_) => {}
+        Expr::List(items) | Expr::Call(_, items) => items.iter().for_each(|item| derived_references(item, names)),
+        Expr::Unary(_, operand) => derived_references(operand, names),
+        Expr::Binary(_, left, right) => {
+            derived_references(left, names);
+            derived_references(right, names);
+        }
+    }
+}
+
+// The first cycle through `name`, if the attribute reaches itself
+fn find_cycle(attributes: &BTreeMap<String, DerivedAttribute>, name: &str, path: &mut Vec<String>) -> Option<Vec<String>> {
+    let mut references = Vec::new();
+    derived_references(&attributes.get(name)?.expression, &mut references);
+    path.push(name.to_string());
+    for reference in references {
+        if reference == path[0] {
+            let mut cycle = path.clone();
+            cycle.push(reference);
+            return Some(cycle);
+        }
+        if !path.contains(&reference) {
+            if let Some(cycle) = find_cycle(attributes, &reference, path) {
+                return Some(cycle);
+            }
+        }
+    }
+    path.pop();
+    None
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_') && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Defines (or replaces) `derived.<name>` as the value of `expression`.
+    // The name may be given with or without the `derived.` prefix.
+    #[wasm_bindgen]
+    pub fn define_derived_attribute(&mut self, name: &str, expression: &str) -> Result<(), JsValue> {
+        let name = name.trim();
+        let name = name.strip_prefix("derived.").unwrap_or(name);
+        if !is_identifier(name) {
+            return Err(PolicyEngineError::validation(format!("Derived attribute name '{}' is not an identifier", name))
+                .with_details(json!({ "attribute": name }))
+                .logged()
+                .into());
+        }
+        let mut parsed = expr::parse(expression).map_err(|e| {
+            let mut details = diagnostics::expression_diagnostic(expression, &e).details();
+            details["attribute"] = json!(name);
+            PolicyEngineError::compile(format!("Derived attribute '{}': {}", name, e)).with_details(details).logged()
+        })?;
+        vocabulary::canonicalize(&mut parsed);
+        if self.strict_mode {
+            let mut unknown = Vec::new();
+            strict::unknown_attributes(&parsed, None, &mut unknown);
+            if !unknown.is_empty() {
+                return Err(PolicyEngineError::validation(format!("Derived attribute '{}' reads unknown attributes", name))
+                    .with_details(json!({ "attribute": name, "unknown": unknown }))
+                    .logged()
+                    .into());
+            }
+        }
+
+        let mut attributes = self.derived.clone();
+        attributes.insert(name.to_string(), DerivedAttribute { source: expression.to_string(), expression: parsed });
+        if let Some(cycle) = find_cycle(&attributes, name, &mut Vec::new()) {
+            return Err(PolicyEngineError::compile(format!("Derived attributes are cyclic: {}", cycle.join(" -> ")))
+                .with_details(json!({ "attribute": name, "cycle": cycle }))
+                .logged()
+                .into());
+        }
+        self.derived = attributes;
+        if self.debug_mode {
+            console_log!("Defined derived attribute '{}'", name);
+        }
+        Ok(())
+    }
+
+    // Policies still reading it then fail to evaluate
+    #[wasm_bindgen]
+    pub fn remove_derived_attribute(&mut self, name: &str) -> bool {
+        let name = name.trim();
+        self.derived.remove(name.strip_prefix("derived.").unwrap_or(name)).is_some()
+    }
+
+    // Name to expression source
+    #[wasm_bindgen]
+    pub fn get_derived_attributes(&self) -> Result<String, JsValue> {
+        let sources: BTreeMap<&str, &str> =
+            self.derived.iter().map(|(name, attribute)| (name.as_str(), attribute.source.as_str())).collect();
+        Ok(to_json(&sources)?)
+    }
+}
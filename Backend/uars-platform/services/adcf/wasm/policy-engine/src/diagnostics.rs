@@ -0,0 +1,264 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+
+use crate::expr::{self, ExprError};
+use crate::Policy;
+
+// 1-based line and column (in characters) plus the byte offset
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+    pub offset: usize,
+}
+
+impl Position {
+    pub fn of(source: &str, offset: usize) -> Position {
+        let offset = floor_char_boundary(source, offset.min(source.len()));
+        let before = &source[..offset];
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        Position {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            offset,
+        }
+    }
+}
+
+// Authoring problem in a policy document. `position` locates it in the
+// document; for expression errors `expression_position` locates it within
+// the expression text, and `field` names the expression ("target",
+// "rules[2].condition", "definitions.high_risk").
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<Position>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expression_position: Option<Position>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+}
+
+impl Diagnostic {
+    // Error details for PolicyEngineError: position, token, suggestion
+    pub fn details(&self) -> Value {
+        let position = self.position.or(self.expression_position);
+        json!({
+            "line": position.map(|p| p.line),
+            "column": position.map(|p| p.column),
+            "offset": position.map(|p| p.offset),
+            "token": self.token,
+            "suggestion": self.suggestion,
+        })
+    }
+}
+
+fn floor_char_boundary(source: &str, mut offset: usize) -> usize {
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+// The lexeme starting at `offset`: a word, a quoted string, an operator
+// run or a single character
+fn token_at(source: &str, offset: usize) -> Option<String> {
+    let rest = source.get(offset..)?;
+    let first = rest.chars().next()?;
+    let end = if first.is_alphanumeric() || first == '_' {
+        rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len())
+    } else if first == '"' || first == '\'' {
+        rest[1..].find(first).map_or(rest.len(), |close| close + 2)
+    } else if "=!<>&|".contains(first) {
+        rest.find(|c: char| !"=!<>&|".contains(c)).unwrap_or(rest.len())
+    } else {
+        first.len_utf8()
+    };
+    let token: String = rest[..end].chars().take(40).collect();
+    Some(token)
+}
+
+fn expression_suggestion(message: &str, token: Option<&str>) -> Option<String> {
+    let suggestion = match token {
+        Some("=") => "did you mean `==`?",
+        Some("&") => "did you mean `&&`?",
+        Some("|") => "did you mean `||`?",
+        Some("===") => "did you mean `==`?",
+        Some("!==") => "did you mean `!=`?",
+        Some("<>") => "did you mean `!=`?",
+        Some("AND") | Some("And") => "did you mean `&&` (or lowercase `and`)?",
+        Some("OR") | Some("Or") => "did you mean `||` (or lowercase `or`)?",
+        Some("NOT") | Some("Not") => "did you mean `!` (or lowercase `not`)?",
+        Some("IN") | Some("In") => "did you mean lowercase `in`?",
+        Some("TRUE") | Some("True") => "did you mean `true`?",
+        Some("FALSE") | Some("False") => "did you mean `false`?",
+        Some("NULL") | Some("None") | Some("nil") => "did you mean `null`?",
+        _ if message.starts_with("Expected RParen") => "add the missing `)`",
+        _ if message.starts_with("Expected RBracket") => "add the missing `]`",
+        _ if message.starts_with("Unterminated string") => "close the string with a matching quote",
+        _ if message.starts_with("Unexpected end") => "the expression is incomplete; check for a missing operand",
+        _ if message.starts_with("Unknown escape") => "escape a literal backslash as `\\\\`",
+        _ => return None,
+    };
+    Some(suggestion.to_string())
+}
+
+fn json_suggestion(message: &str, token: Option<&str>) -> Option<String> {
+    let suggestion = if message.starts_with("trailing comma") {
+        "remove the trailing comma".to_string()
+    } else if message.starts_with("expected `,` or `}`") || message.starts_with("expected `,` or `]`") {
+        "add the missing comma between entries".to_string()
+    } else if message.starts_with("key must be a string") {
+        "object keys must be double-quoted strings".to_string()
+    } else if message.starts_with("EOF while parsing") {
+        "the document ends early; check for an unclosed `{` or `[`".to_string()
+    } else if message.starts_with("missing field") {
+        format!("add the required field {}", message.trim_start_matches("missing field").trim())
+    } else if token.is_some_and(|t| t.starts_with('\'')) {
+        "JSON strings use double quotes".to_string()
+    } else if message.starts_with("unknown field") {
+        "check the field name for typos".to_string()
+    } else {
+        return None;
+    };
+    Some(suggestion)
+}
+
+// Strips serde_json's " at line X column Y" suffix
+fn json_message(error: &serde_json::Error) -> String {
+    let message = error.to_string();
+    match message.rfind(" at line ") {
+        Some(index) => message[..index].to_string(),
+        None => message,
+    }
+}
+
+pub fn json_diagnostic(source: &str, error: &serde_json::Error) -> Diagnostic {
+    // serde_json reports a 1-based line and byte column of the last
+    // character it read
+    let line_start: usize = source.split_inclusive('\n').take(error.line().saturating_sub(1)).map(str::len).sum();
+    let offset = (line_start + error.column().saturating_sub(1)).min(source.len());
+    let position = Position::of(source, offset);
+    let message = json_message(error);
+    let token = token_at(source, position.offset);
+    Diagnostic {
+        suggestion: json_suggestion(&message, token.as_deref()),
+        message,
+        position: Some(position),
+        expression_position: None,
+        token,
+        policy_id: None,
+        rule_id: None,
+        field: None,
+    }
+}
+
+// Diagnostic positioned within the expression text only
+pub fn expression_diagnostic(expression: &str, error: &ExprError) -> Diagnostic {
+    let position = error.offset.map(|offset| Position::of(expression, offset));
+    let token = position.and_then(|p| token_at(expression, p.offset));
+    Diagnostic {
+        message: error.message.clone(),
+        position: None,
+        expression_position: position,
+        suggestion: expression_suggestion(&error.message, token.as_deref()),
+        token,
+        policy_id: None,
+        rule_id: None,
+        field: None,
+    }
+}
+
+// Finds where a JSON string value for `key` holding `text` starts in the
+// document, skipping occurrences already attributed to another field
+fn locate_string(document: &str, key: &str, text: &str, used: &mut HashSet<usize>) -> Option<usize> {
+    let literal = serde_json::to_string(text).ok()?;
+    let key = serde_json::to_string(key).ok()?;
+    let found = document.match_indices(&literal).map(|(start, _)| start).find(|start| {
+        let before = document[..*start].trim_end();
+        !used.contains(start)
+            && before.ends_with(':')
+            && before[..before.len() - 1].trim_end().ends_with(&key)
+    })?;
+    used.insert(found);
+    Some(found)
+}
+
+// Maps an offset within `text` to the document, given where its JSON
+// literal starts
+fn document_offset(literal_start: usize, text: &str, offset: usize) -> usize {
+    let offset = floor_char_boundary(text, offset.min(text.len()));
+    let escaped = serde_json::to_string(&text[..offset]).map_or(0, |s| s.len() - 2);
+    literal_start + 1 + escaped
+}
+
+// Expression checks over a whole document, mapping each error back to
+// the document position of the offending JSON string
+struct DocumentChecker<'a> {
+    document: &'a str,
+    used: HashSet<usize>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl DocumentChecker<'_> {
+    fn check(&mut self, policy: &Policy, key: &str, field: String, rule_id: Option<&str>, text: &str) {
+        let literal_start = locate_string(self.document, key, text, &mut self.used);
+        if let Err(error) = expr::parse(text) {
+            let mut diagnostic = expression_diagnostic(text, &error);
+            if let (Some(start), Some(within)) = (literal_start, diagnostic.expression_position) {
+                let offset = document_offset(start, text, within.offset);
+                diagnostic.position = Some(Position::of(self.document, offset));
+            }
+            diagnostic.policy_id = Some(policy.id.clone());
+            diagnostic.rule_id = rule_id.map(String::from);
+            diagnostic.field = Some(field);
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    fn check_policy(&mut self, policy: &Policy) {
+        self.check(policy, "target", "target".to_string(), None, &policy.target);
+        for (name, text) in &policy.definitions {
+            self.check(policy, name, format!("definitions.{}", name), None, text);
+        }
+        for (index, rule) in policy.rules.iter().enumerate() {
+            self.check(policy, "condition", format!("rules[{}].condition", index), Some(&rule.id), &rule.condition);
+        }
+    }
+}
+
+// Checks a policy (or a JSON array of policies) as written in an editor
+// and returns a JSON array of Diagnostic: the JSON syntax or shape error,
+// or else every target, definition and condition that does not parse.
+// An empty array means the document parses.
+#[wasm_bindgen]
+pub fn diagnose_policy(policy_json: &str) -> String {
+    let policies = if policy_json.trim_start().starts_with('[') {
+        serde_json::from_str::<Vec<Policy>>(policy_json)
+    } else {
+        serde_json::from_str::<Policy>(policy_json).map(|policy| vec![policy])
+    };
+
+    let diagnostics = match policies {
+        Err(error) => vec![json_diagnostic(policy_json, &error)],
+        Ok(policies) => {
+            let mut checker = DocumentChecker { document: policy_json, used: HashSet::new(), diagnostics: Vec::new() };
+            for policy in &policies {
+                checker.check_policy(policy);
+            }
+            checker.diagnostics
+        }
+    };
+    serde_json::to_string(&diagnostics).unwrap_or_else(|_| "[]".to_string())
+}
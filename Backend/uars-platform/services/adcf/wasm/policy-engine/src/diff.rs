@@ -0,0 +1,108 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::decision::Decision;
+use crate::error::{to_json, PolicyEngineError};
+use crate::format;
+use crate::{CompiledPolicy, Policy, PolicyEngine, PolicyResult, PolicySelection};
+
+// A context whose decision differs between the two policy sets
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecisionChange {
+    pub index: usize,
+    pub request_id: String,
+    pub decision_a: Decision,
+    pub decision_b: Decision,
+    pub reason_a: String,
+    pub reason_b: String,
+}
+
+// Changes attributed to one rule: the rule that decided under set B, or
+// under set A when set B reached no rule at all
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleChanges {
+    pub policy_id: Option<String>,
+    pub rule_id: Option<String>,
+    pub changes: Vec<DecisionChange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecisionDiff {
+    pub total: usize,
+    pub changed: usize,
+    pub by_rule: Vec<RuleChanges>,
+}
+
+fn responsible_rule(a: &PolicyResult, b: &PolicyResult) -> (Option<String>, Option<String>) {
+    let decider = if b.rule_id.is_some() { b } else { a };
+    (decider.policy_id.clone(), decider.rule_id.clone())
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Offline comparison of two policy sets (JSON arrays of policies) over
+    // a corpus of contexts (JSON array). Uses this engine's risk profile
+    // and geo data but never touches its loaded policies or state.
+    #[wasm_bindgen]
+    pub fn diff_decisions(&self, policy_set_a: &str, policy_set_b: &str, contexts_json: &str) -> Result<String, JsValue> {
+        let set_a = self.compile_set("A", policy_set_a)?;
+        let set_b = self.compile_set("B", policy_set_b)?;
+
+        let contexts: Vec<serde_json::Value> = serde_json::from_str(contexts_json).map_err(|e| {
+
+            PolicyEngineError::parse(format!("Failed to parse contexts: {}", e)).logged()
+
+        })?;
+
+        let mut groups: BTreeMap<(Option<String>, Option<String>), Vec<DecisionChange>> = BTreeMap::new();
+        let mut changed = 0;
+
+        for (index, raw) in contexts.iter().enumerate() {
+            let mut context = self
+                .check_context(&raw.to_string())
+                .map_err(|e| e.context(&format!("Context {} is invalid", index)).logged())?;
+            self.enrich_context(&mut context, None);
+
+            let a = self.evaluate_context(&context, PolicySelection::Explicit(&set_a))?;
+            let b = self.evaluate_context(&context, PolicySelection::Explicit(&set_b))?;
+            if a.decision == b.decision {
+                continue;
+            }
+
+            changed += 1;
+            groups.entry(responsible_rule(&a, &b)).or_default().push(DecisionChange {
+                index,
+                request_id: context.request_id.clone(),
+                decision_a: a.decision,
+                decision_b: b.decision,
+                reason_a: a.reason.into_owned(),
+                reason_b: b.reason.into_owned(),
+            });
+        }
+
+        let diff = DecisionDiff {
+            total: contexts.len(),
+            changed,
+            by_rule: groups
+                .into_iter()
+                .map(|((policy_id, rule_id), changes)| RuleChanges { policy_id, rule_id, changes })
+                .collect(),
+        };
+
+        if self.debug_mode {
+            console_log!("Decision diff: {} of {} contexts changed", diff.changed, diff.total);
+        }
+        Ok(to_json(&diff)?)
+    }
+}
+
+impl PolicyEngine {
+    // Rule references resolve against this engine's rule libraries
+    fn compile_set(&self, label: &str, policies_json: &str) -> Result<Vec<CompiledPolicy>, PolicyEngineError> {
+        let policies: Vec<Policy> = format::parse_policy_json(policies_json, &format!("policy set {}", label))
+            .map_err(|e| e.logged())?;
+        self.compile_policies(policies)
+            .map_err(|e| e.context(&format!("Failed to compile policy set {}", label)).logged())
+    }
+}
@@ -0,0 +1,35 @@
+use sha2::{Digest, Sha256};
+
+use crate::{CompiledPolicy, Policy};
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// serde_json::Value keeps object keys sorted, so equal policies give
+// equal text whatever order their JSON was written in
+fn canonical_json(policy: &Policy) -> String {
+    serde_json::to_value(policy).map(|value| value.to_string()).unwrap_or_default()
+}
+
+// Content address of a single policy
+pub fn policy_hash(policy: &Policy) -> String {
+    to_hex(&Sha256::digest(canonical_json(policy).as_bytes()))
+}
+
+// Order-independent hash of a policy set. Each policy is hashed via its
+// canonical JSON and the entries are combined in policy-ID order.
+pub fn policy_set_hash<'a>(policies: impl IntoIterator<Item = &'a CompiledPolicy>) -> String {
+    let mut entries: Vec<(&str, String)> = policies
+        .into_iter()
+        .map(|compiled| (compiled.policy.id.as_str(), canonical_json(&compiled.policy)))
+        .collect();
+    entries.sort();
+
+    let mut hasher = Sha256::new();
+    for (_, canonical) in &entries {
+        hasher.update(canonical.as_bytes());
+        hasher.update(b"\n");
+    }
+    to_hex(&hasher.finalize())
+}
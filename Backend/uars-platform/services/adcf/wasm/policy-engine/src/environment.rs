@@ -0,0 +1,84 @@
+use wasm_bindgen::prelude::*;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+use crate::clock;
+use crate::error::PolicyEngineError;
+use crate::expr::ExprError;
+use crate::fuzz::FuzzRng;
+use crate::PolicyEngine;
+
+// Replaces the sources behind now(), random() and uuid() so tests get
+// stable results. `now` is RFC 3339; `random_seed` seeds a SplitMix64
+// generator shared by random() and uuid().
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnvironmentOverrides {
+    pub now: Option<DateTime<Utc>>,
+    pub random_seed: Option<u64>,
+}
+
+#[derive(Default)]
+pub struct EnvironmentSources {
+    now: Option<DateTime<Utc>>,
+    rng: RefCell<Option<FuzzRng>>,
+}
+
+impl EnvironmentSources {
+    pub fn now(&self) -> DateTime<Utc> {
+        self.now.unwrap_or_else(clock::now)
+    }
+
+    pub fn random_bytes(&self, bytes: &mut [u8]) -> Result<(), ExprError> {
+        if let Some(rng) = self.rng.borrow_mut().as_mut() {
+            for chunk in bytes.chunks_mut(8) {
+                let word = rng.next_u64().to_le_bytes();
+                chunk.copy_from_slice(&word[..chunk.len()]);
+            }
+            return Ok(());
+        }
+        getrandom::getrandom(bytes).map_err(|e| ExprError::new(format!("No entropy source available: {}", e)))
+    }
+
+    // Uniform in [0, 1) from 53 random bits
+    pub fn random(&self) -> Result<f64, ExprError> {
+        let mut bytes = [0u8; 8];
+        self.random_bytes(&mut bytes)?;
+        Ok((u64::from_le_bytes(bytes) >> 11) as f64 / (1u64 << 53) as f64)
+    }
+
+    // Random (version 4) UUID in hyphenated lowercase form
+    pub fn uuid(&self) -> Result<String, ExprError> {
+        let mut bytes = [0u8; 16];
+        self.random_bytes(&mut bytes)?;
+        bytes[6] = (bytes[6] & 0x0f) | 0x40;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+        let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        Ok(format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32]))
+    }
+}
+
+pub fn format_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    #[wasm_bindgen]
+    pub fn set_environment_overrides(&mut self, overrides_json: &str) -> Result<(), JsValue> {
+        let overrides: EnvironmentOverrides = serde_json::from_str(overrides_json).map_err(|e| {
+            PolicyEngineError::parse(format!("Failed to parse environment overrides: {}", e)).logged()
+        })?;
+        self.environment = EnvironmentSources {
+            now: overrides.now,
+            rng: RefCell::new(overrides.random_seed.map(FuzzRng::new)),
+        };
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_environment_overrides(&mut self) {
+        self.environment = EnvironmentSources::default();
+    }
+}
@@ -0,0 +1,297 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::decision::Decision;
+use crate::error::{to_json, PolicyEngineError};
+use crate::{guard, http, stats, PolicyEngine, PolicyResult, PolicySelection};
+
+// gRPC status codes a CheckResponse carries
+const GRPC_OK: u16 = 0;
+const GRPC_PERMISSION_DENIED: u16 = 7;
+const GRPC_UNAUTHENTICATED: u16 = 16;
+
+// How evaluate_envoy reads a CheckRequest and answers it. Identity comes
+// from headers set by an upstream authentication filter (falling back to
+// the source principal, e.g. a SPIFFE ID from mTLS); the resource is the
+// request path unless the route sets `resource_type`/`resource_id`
+// context extensions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnvoyConfig {
+    pub user_header: String,
+    // Comma-separated lists
+    pub roles_header: String,
+    pub groups_header: String,
+    // Use the first X-Forwarded-For hop as ip_address; only behind a proxy
+    // that overwrites the header
+    pub trust_forwarded_for: bool,
+    pub resource_type: String,
+    // HTTP method to operation; unlisted methods pass through lowercased
+    pub operations: BTreeMap<String, String>,
+    // HTTP status for denials other than challenges and rate limits
+    pub deny_status: u16,
+}
+
+impl Default for EnvoyConfig {
+    fn default() -> Self {
+        EnvoyConfig {
+            user_header: "x-user-id".to_string(),
+            roles_header: "x-user-roles".to_string(),
+            groups_header: "x-user-groups".to_string(),
+            trust_forwarded_for: false,
+            resource_type: "http".to_string(),
+            operations: http::default_operations(),
+            deny_status: 403,
+        }
+    }
+}
+
+// The parts of envoy.service.auth.v3.CheckRequest the mapping reads, in
+// its proto3 JSON form (lowerCamelCase, original field names accepted)
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct CheckRequest {
+    attributes: AttributeContext,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct AttributeContext {
+    source: Peer,
+    destination: Peer,
+    request: Request,
+    #[serde(rename = "contextExtensions", alias = "context_extensions")]
+    context_extensions: HashMap<String, String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Peer {
+    address: Address,
+    principal: String,
+    labels: HashMap<String, String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Address {
+    #[serde(rename = "socketAddress", alias = "socket_address")]
+    socket_address: SocketAddress,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct SocketAddress {
+    address: String,
+    #[serde(rename = "portValue", alias = "port_value")]
+    port_value: u32,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Request {
+    time: Option<String>,
+    http: HttpRequest,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct HttpRequest {
+    id: String,
+    method: String,
+    headers: HashMap<String, String>,
+    path: String,
+    host: String,
+    scheme: String,
+    protocol: String,
+}
+
+impl HttpRequest {
+    // Envoy lowercases header names; hosts building requests by hand may not
+    fn header(&self, name: &str) -> Option<&str> {
+        http::header(&self.headers, name)
+    }
+}
+
+fn peer(peer: &Peer) -> Value {
+    json!({
+        "principal": peer.principal,
+        "address": peer.address.socket_address.address,
+        "port": peer.address.socket_address.port_value,
+        "labels": peer.labels,
+    })
+}
+
+impl EnvoyConfig {
+    // The PolicyContext JSON for a CheckRequest; the raw request is kept
+    // under metadata (http, source, destination, context_extensions)
+    fn context(&self, request: &CheckRequest) -> Value {
+        let attributes = &request.attributes;
+        let http = &attributes.request.http;
+        let extension = |name: &str| attributes.context_extensions.get(name).filter(|value| !value.is_empty());
+        let mut context = Map::new();
+
+        if let Some(id) = Some(http.id.as_str()).filter(|id| !id.is_empty()).or_else(|| http.header("x-request-id")) {
+            context.insert("request_id".to_string(), json!(id));
+        }
+        if let Some(time) = &attributes.request.time {
+            context.insert("timestamp".to_string(), json!(time));
+        }
+        let method = http.method.to_uppercase();
+        context.insert("operation".to_string(), json!(http::operation(&self.operations, &method)));
+
+        let user = http.header(&self.user_header).or(Some(attributes.source.principal.as_str()).filter(|p| !p.is_empty()));
+        if let Some(user) = user {
+            context.insert("user_id".to_string(), json!(user));
+        }
+        let roles = http::list(http.header(&self.roles_header));
+        if !roles.is_empty() {
+            context.insert("user_roles".to_string(), json!(roles));
+        }
+        let groups = http::list(http.header(&self.groups_header));
+        if !groups.is_empty() {
+            context.insert("user_groups".to_string(), json!(groups));
+        }
+
+        let forwarded = if self.trust_forwarded_for { http::list(http.header("x-forwarded-for")).into_iter().next() } else { None };
+        let ip = forwarded.unwrap_or_else(|| attributes.source.address.socket_address.address.clone());
+        if !ip.is_empty() {
+            context.insert("ip_address".to_string(), json!(ip));
+        }
+
+        let path = http.path.split(['?', '#']).next().unwrap_or_default();
+        let resource_type = extension("resource_type").cloned().unwrap_or_else(|| self.resource_type.clone());
+        let resource_id = extension("resource_id").cloned().unwrap_or_else(|| path.to_string());
+        context.insert("resource_type".to_string(), json!(resource_type));
+        context.insert("resource_id".to_string(), json!(resource_id));
+        for field in ["resource_classification", "resource_owner"] {
+            if let Some(value) = extension(field) {
+                context.insert(field.to_string(), json!(value));
+            }
+        }
+
+        let headers: BTreeMap<String, &String> =
+            http.headers.iter().map(|(name, value)| (name.to_lowercase(), value)).collect();
+        context.insert(
+            "metadata".to_string(),
+            json!({
+                "http": {
+                    "method": method,
+                    "path": http.path,
+                    "host": http.host,
+                    "scheme": http.scheme,
+                    "protocol": http.protocol,
+                    "headers": headers,
+                },
+                "source": peer(&attributes.source),
+                "destination": peer(&attributes.destination),
+                "context_extensions": attributes.context_extensions,
+            }),
+        );
+        Value::Object(context)
+    }
+}
+
+fn header(key: &str, value: impl Into<String>) -> Value {
+    json!({ "header": { "key": key, "value": value.into() } })
+}
+
+// envoy.service.auth.v3.CheckResponse JSON for a decision. Only PERMIT is
+// allowed through; the decision, policy and rule travel as x-policy-*
+// headers, and obligations on a permit as x-policy-obligations for the
+// upstream to honour.
+fn check_response(result: &PolicyResult, config: &EnvoyConfig) -> Value {
+    let mut headers = vec![header("x-policy-decision", result.decision.as_str())];
+    if let Some(policy_id) = &result.policy_id {
+        headers.push(header("x-policy-id", policy_id.clone()));
+    }
+    if let Some(rule_id) = &result.rule_id {
+        headers.push(header("x-policy-rule", rule_id.clone()));
+    }
+
+    if result.decision == Decision::Permit {
+        if &*result.obligations != "[]" {
+            headers.push(header("x-policy-obligations", result.obligations.to_string()));
+        }
+        return json!({ "status": { "code": GRPC_OK }, "okResponse": { "headers": headers } });
+    }
+
+    // Step-ups are 401s carrying the challenge, rate limits 429s with
+    // Retry-After; everything else fails closed with the deny status
+    let (grpc, http) = match (result.decision, result.retry_after) {
+        (Decision::Challenge, _) => (GRPC_UNAUTHENTICATED, 401),
+        (_, Some(_)) => (GRPC_PERMISSION_DENIED, 429),
+        _ => (GRPC_PERMISSION_DENIED, config.deny_status),
+    };
+    if let Some(seconds) = result.retry_after {
+        headers.push(header("retry-after", (seconds.ceil() as u64).to_string()));
+    }
+    headers.push(header("content-type", "application/json"));
+    let challenge: Value = serde_json::from_str(&result.challenge).unwrap_or(Value::Null);
+    let body = json!({
+        "decision": result.decision,
+        "reason_code": result.reason_code,
+        "message": result.message,
+        "error_code": result.error_code,
+        "challenge": challenge,
+    });
+    json!({
+        "status": { "code": grpc },
+        "deniedResponse": { "status": { "code": http }, "headers": headers, "body": body.to_string() },
+    })
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // ext_authz adapter: takes a CheckRequest (proto3 JSON), evaluates it
+    // as a request (stateful effects included) and returns a CheckResponse
+    // JSON. A request that cannot be mapped into a valid context is denied
+    // with the error as body rather than failing the call, so the filter
+    // always gets an answer.
+    #[wasm_bindgen]
+    pub fn evaluate_envoy(&mut self, check_request_json: &str) -> Result<String, JsValue> {
+        let request: CheckRequest = serde_json::from_str(check_request_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse CheckRequest: {}", e)).logged())?;
+        let started = stats::now_ms();
+        let context = self.check_context_value(self.envoy.context(&request));
+        self.begin_profile(started);
+        let result = match context {
+            Ok(context) => guard::guarded(|| self.evaluate_request(context, PolicySelection::Global)),
+            Err(error) => PolicyResult::failure(&error.logged()),
+        };
+        Ok(to_json(&check_response(&result, &self.envoy))?)
+    }
+
+    // The PolicyContext JSON evaluate_envoy would evaluate, for checking
+    // a gateway's mapping
+    #[wasm_bindgen]
+    pub fn envoy_context(&self, check_request_json: &str) -> Result<String, JsValue> {
+        let request: CheckRequest = serde_json::from_str(check_request_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse CheckRequest: {}", e)).logged())?;
+        Ok(to_json(&self.envoy.context(&request))?)
+    }
+
+    #[wasm_bindgen]
+    pub fn set_envoy_config(&mut self, config_json: &str) -> Result<(), JsValue> {
+        let config: EnvoyConfig = serde_json::from_str(config_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse Envoy config: {}", e)).logged())?;
+        if !(400..600).contains(&config.deny_status) {
+            return Err(PolicyEngineError::validation("Envoy deny_status must be an HTTP error status")
+                .with_details(json!({ "field": "deny_status", "value": config.deny_status }))
+                .logged()
+                .into());
+        }
+        self.envoy = EnvoyConfig {
+            operations: config.operations.into_iter().map(|(method, operation)| (method.to_uppercase(), operation)).collect(),
+            ..config
+        };
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn get_envoy_config(&self) -> Result<String, JsValue> {
+        Ok(to_json(&self.envoy)?)
+    }
+}
@@ -0,0 +1,188 @@
+use wasm_bindgen::prelude::*;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+
+
+// Message plus optional structured context (field errors, ids, offsets)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorDetail {
+    pub message: String,
+    pub details: Value,
+}
+
+// Error returned by every fallible API. It crosses the JS boundary as a
+// JSON string `{ code, message, details }` so callers can branch on
+// `code` instead of matching message text.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PolicyEngineError {
+    // Malformed JSON, YAML or CBOR input
+    ParseError(ErrorDetail),
+    // A policy's target or condition is not a valid expression
+    CompileError(ErrorDetail),
+    // A context or argument is well-formed but fails validation
+    ValidationError(ErrorDetail),
+    // An attribute the policy requires is absent from the context
+    MissingAttribute(ErrorDetail),
+    // A policy names a combining algorithm the engine does not know
+    UnknownAlgorithm(ErrorDetail),
+    // A policy is written in a newer format than this engine reads
+    UnsupportedFormat(ErrorDetail),
+    // A referenced policy, tenant or other object does not exist
+    NotFound(ErrorDetail),
+    // The request conflicts with engine state (stale hash, duplicate id)
+    Conflict(ErrorDetail),
+    // The operation is not valid in the engine's current state
+    InvalidState(ErrorDetail),
+    // Evaluation could not complete
+    EvaluationError(ErrorDetail),
+    // Evaluation hit a configured rule, depth, step or time limit
+    LimitsExceeded(ErrorDetail),
+    // The host cancelled the operation through an AbortSignal
+    Cancelled(ErrorDetail),
+    // A bug: serialization failures, caught panics
+    InternalError(ErrorDetail),
+}
+
+macro_rules! constructors {
+    ($($name:ident => $variant:ident),* $(,)?) => {
+        impl PolicyEngineError {
+            $(
+                pub fn $name(message: impl Into<String>) -> PolicyEngineError {
+                    PolicyEngineError::$variant(ErrorDetail { message: message.into(), details: Value::Null })
+                }
+            )*
+        }
+    };
+}
+
+constructors! {
+    parse => ParseError,
+    compile => CompileError,
+    validation => ValidationError,
+    missing_attribute => MissingAttribute,
+    unknown_algorithm => UnknownAlgorithm,
+    unsupported_format => UnsupportedFormat,
+    not_found => NotFound,
+    conflict => Conflict,
+    invalid_state => InvalidState,
+    evaluation => EvaluationError,
+    limits_exceeded => LimitsExceeded,
+    cancelled => Cancelled,
+    internal => InternalError,
+}
+
+#[derive(Serialize)]
+struct WireError<'a> {
+    code: &'a str,
+    message: &'a str,
+    #[serde(skip_serializing_if = "Value::is_null")]
+    details: &'a Value,
+}
+
+impl PolicyEngineError {
+    fn detail(&self) -> &ErrorDetail {
+        match self {
+            PolicyEngineError::ParseError(d)
+            | PolicyEngineError::CompileError(d)
+            | PolicyEngineError::ValidationError(d)
+            | PolicyEngineError::MissingAttribute(d)
+            | PolicyEngineError::UnknownAlgorithm(d)
+            | PolicyEngineError::UnsupportedFormat(d)
+            | PolicyEngineError::NotFound(d)
+            | PolicyEngineError::Conflict(d)
+            | PolicyEngineError::InvalidState(d)
+            | PolicyEngineError::EvaluationError(d)
+            | PolicyEngineError::LimitsExceeded(d)
+            | PolicyEngineError::Cancelled(d)
+            | PolicyEngineError::InternalError(d) => d,
+        }
+    }
+
+    fn detail_mut(&mut self) -> &mut ErrorDetail {
+        match self {
+            PolicyEngineError::ParseError(d)
+            | PolicyEngineError::CompileError(d)
+            | PolicyEngineError::ValidationError(d)
+            | PolicyEngineError::MissingAttribute(d)
+            | PolicyEngineError::UnknownAlgorithm(d)
+            | PolicyEngineError::UnsupportedFormat(d)
+            | PolicyEngineError::NotFound(d)
+            | PolicyEngineError::Conflict(d)
+            | PolicyEngineError::InvalidState(d)
+            | PolicyEngineError::EvaluationError(d)
+            | PolicyEngineError::LimitsExceeded(d)
+            | PolicyEngineError::Cancelled(d)
+            | PolicyEngineError::InternalError(d) => d,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            PolicyEngineError::ParseError(_) => "PARSE_ERROR",
+            PolicyEngineError::CompileError(_) => "COMPILE_ERROR",
+            PolicyEngineError::ValidationError(_) => "VALIDATION_ERROR",
+            PolicyEngineError::MissingAttribute(_) => "MISSING_ATTRIBUTE",
+            PolicyEngineError::UnknownAlgorithm(_) => "UNKNOWN_ALGORITHM",
+            PolicyEngineError::UnsupportedFormat(_) => "UNSUPPORTED_FORMAT",
+            PolicyEngineError::NotFound(_) => "NOT_FOUND",
+            PolicyEngineError::Conflict(_) => "CONFLICT",
+            PolicyEngineError::InvalidState(_) => "INVALID_STATE",
+            PolicyEngineError::EvaluationError(_) => "EVALUATION_ERROR",
+            PolicyEngineError::LimitsExceeded(_) => "LIMITS_EXCEEDED",
+            PolicyEngineError::Cancelled(_) => "CANCELLED",
+            PolicyEngineError::InternalError(_) => "INTERNAL_ERROR",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.detail().message
+    }
+
+    pub fn details(&self) -> &Value {
+        &self.detail().details
+    }
+
+    pub fn with_details(mut self, details: Value) -> PolicyEngineError {
+        self.detail_mut().details = details;
+        self
+    }
+
+    // Prefixes the message with what the caller was doing
+    pub fn context(mut self, action: &str) -> PolicyEngineError {
+        let detail = self.detail_mut();
+        detail.message = format!("{}: {}", action, detail.message);
+        self
+    }
+
+    // Writes the message to the log at error level; returns self for `?`
+    // conversion
+    pub(crate) fn logged(self) -> PolicyEngineError {
+        log_at!(crate::logging::LogLevel::Error, "{}", self.message());
+        self
+    }
+
+    pub fn to_json(&self) -> String {
+        let wire = WireError { code: self.code(), message: self.message(), details: self.details() };
+        serde_json::to_string(&wire).unwrap_or_else(|_| self.message().to_string())
+    }
+}
+
+impl fmt::Display for PolicyEngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code(), self.message())
+    }
+}
+
+impl std::error::Error for PolicyEngineError {}
+
+impl From<PolicyEngineError> for JsValue {
+    fn from(error: PolicyEngineError) -> JsValue {
+        JsValue::from_str(&error.to_json())
+    }
+}
+
+// Serialization of our own result types only fails on a bug
+pub(crate) fn to_json<T: Serialize>(value: &T) -> Result<String, PolicyEngineError> {
+    serde_json::to_string(value).map_err(|e| PolicyEngineError::internal(format!("Failed to serialize result: {}", e)))
+}
@@ -0,0 +1,209 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+use crate::error::{to_json, PolicyEngineError};
+use crate::{guard, CompiledPolicy, Policy, PolicyEngine, PolicyResult, PolicySelection};
+
+// Decisions seen for one policy set over the compared traffic
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProfileTally {
+    pub requests: u64,
+    pub decisions: BTreeMap<String, u64>,
+    // Requests decided differently from the active set
+    pub changed: u64,
+    // "PERMIT -> DENY" (active -> profile) counts for the changed requests
+    pub transitions: BTreeMap<String, u64>,
+}
+
+// Named policy sets (A/B experiments) evaluated next to the active set,
+// and the tallies compare_profiles has collected for them
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Experiments {
+    profiles: BTreeMap<String, Vec<CompiledPolicy>>,
+    active: ProfileTally,
+    tallies: BTreeMap<String, ProfileTally>,
+}
+
+impl Experiments {
+    pub(crate) fn policies(&self) -> impl Iterator<Item = &CompiledPolicy> {
+        self.profiles.values().flatten()
+    }
+
+    pub(crate) fn policies_mut(&mut self) -> impl Iterator<Item = &mut CompiledPolicy> {
+        self.profiles.values_mut().flatten()
+    }
+}
+
+// Output of compare_profiles
+#[derive(Debug, Serialize)]
+pub struct ProfileComparison {
+    pub active: PolicyResult,
+    pub profiles: BTreeMap<String, PolicyResult>,
+    // Profiles whose decision differs from the active one
+    pub changed: Vec<String>,
+}
+
+// One profile in profile_report
+#[derive(Debug, Serialize)]
+pub struct ProfileSummary {
+    pub policy_count: usize,
+    #[serde(flatten)]
+    pub tally: ProfileTally,
+    // Fraction of its requests decided as the active set decided them
+    pub agreement: f64,
+}
+
+// Output of profile_report
+#[derive(Debug, Serialize)]
+pub struct ProfileReport {
+    pub active: ProfileTally,
+    pub profiles: BTreeMap<String, ProfileSummary>,
+}
+
+// Profiles run controlled experiments on policy changes: each holds its
+// own policy set, starting as a copy of the active one, and is evaluated
+// side-effect free like the staged set. compare_profiles runs the same
+// request through the active set and every profile and tallies the
+// outcomes for profile_report.
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Creates a profile holding a copy of the active policies
+    #[wasm_bindgen]
+    pub fn create_profile(&mut self, name: &str) -> Result<(), JsValue> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(PolicyEngineError::validation("Profile name must not be empty").logged().into());
+        }
+        if self.experiments.profiles.contains_key(name) {
+            return Err(PolicyEngineError::conflict(format!("Profile '{}' already exists", name))
+                .with_details(json!({ "profile": name }))
+                .logged()
+                .into());
+        }
+        if self.debug_mode {
+            console_log!("Created profile {} with {} policies", name, self.policies.len());
+        }
+        self.experiments.profiles.insert(name.to_string(), self.policies.clone());
+        Ok(())
+    }
+
+    // Replaces a profile's policy set. It passes the same gate as
+    // `stage_policies` or the profile is left unchanged.
+    #[wasm_bindgen]
+    pub fn load_profile_policies(&mut self, name: &str, policies_json: &str) -> Result<(), JsValue> {
+        self.require_profile(name)?;
+        let policies: Vec<Policy> = self.parse_policies(policies_json, &format!("policies for profile '{}'", name))
+            .map_err(|e| e.logged())?;
+        let compiled = self.prepare_policy_set(policies)
+            .map_err(|e| e.context(&format!("Rejected policy set for profile '{}'", name)).logged())?;
+
+        if self.debug_mode {
+            console_log!("Loaded {} policies into profile {}", compiled.len(), name);
+        }
+        self.experiments.profiles.insert(name.to_string(), compiled);
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn evaluate_profile(&self, name: &str, context_json: &str) -> Result<PolicyResult, JsValue> {
+        let policies = self.require_profile(name)?;
+        let mut context = self.parse_context(context_json)?;
+        self.enrich_context(&mut context, None);
+        Ok(guard::guarded(|| self.evaluate_context(&context, PolicySelection::Explicit(policies))))
+    }
+
+    // Evaluates the context against the active set and every profile,
+    // records the decisions for profile_report and returns a JSON
+    // ProfileComparison
+    #[wasm_bindgen]
+    pub fn compare_profiles(&mut self, context_json: &str) -> Result<String, JsValue> {
+        let mut context = self.parse_context(context_json)?;
+        self.enrich_context(&mut context, None);
+
+        let active = self.evaluate_context(&context, PolicySelection::Explicit(&self.policies))?;
+        let mut profiles = BTreeMap::new();
+        for (name, policies) in &self.experiments.profiles {
+            profiles.insert(name.clone(), self.evaluate_context(&context, PolicySelection::Explicit(policies))?);
+        }
+
+        let experiments = &mut self.experiments;
+        let active_decision = active.decision.as_str();
+        experiments.active.record(active_decision, active_decision);
+        let mut changed = Vec::new();
+        for (name, result) in &profiles {
+            let decision = result.decision.as_str();
+            experiments.tallies.entry(name.clone()).or_default().record(active_decision, decision);
+            if decision != active_decision {
+                changed.push(name.clone());
+            }
+        }
+
+        if self.debug_mode && !changed.is_empty() {
+            console_log!("Profiles deciding differently from {}: {}", active_decision, changed.join(", "));
+        }
+        Ok(to_json(&ProfileComparison { active, profiles, changed })?)
+    }
+
+    // Per-profile decision counts over the traffic given to
+    // compare_profiles, with how often and how each differed from the
+    // active set
+    #[wasm_bindgen]
+    pub fn profile_report(&self) -> Result<String, JsValue> {
+        let experiments = &self.experiments;
+        let profiles = experiments
+            .profiles
+            .iter()
+            .map(|(name, policies)| {
+                let tally = experiments.tallies.get(name).cloned().unwrap_or_default();
+                let agreement = match tally.requests {
+                    0 => 1.0,
+                    requests => (requests - tally.changed) as f64 / requests as f64,
+                };
+                (name.clone(), ProfileSummary { policy_count: policies.len(), tally, agreement })
+            })
+            .collect();
+        Ok(to_json(&ProfileReport { active: experiments.active.clone(), profiles })?)
+    }
+
+    // Clears the tallies, keeping the profiles
+    #[wasm_bindgen]
+    pub fn reset_profile_report(&mut self) {
+        self.experiments.active = ProfileTally::default();
+        self.experiments.tallies.clear();
+    }
+
+    #[wasm_bindgen]
+    pub fn delete_profile(&mut self, name: &str) -> bool {
+        self.experiments.tallies.remove(name);
+        self.experiments.profiles.remove(name).is_some()
+    }
+
+    #[wasm_bindgen]
+    pub fn list_profiles(&self) -> String {
+        let names: Vec<&String> = self.experiments.profiles.keys().collect();
+        serde_json::to_string(&names).unwrap_or_default()
+    }
+}
+
+impl ProfileTally {
+    fn record(&mut self, active: &str, decision: &str) {
+        self.requests += 1;
+        *self.decisions.entry(decision.to_string()).or_default() += 1;
+        if decision != active {
+            self.changed += 1;
+            *self.transitions.entry(format!("{} -> {}", active, decision)).or_default() += 1;
+        }
+    }
+}
+
+impl PolicyEngine {
+    fn require_profile(&self, name: &str) -> Result<&[CompiledPolicy], PolicyEngineError> {
+        self.experiments.profiles.get(name).map(Vec::as_slice).ok_or_else(|| {
+            PolicyEngineError::not_found(format!("No profile '{}'", name))
+                .with_details(json!({ "profile": name }))
+                .logged()
+        })
+    }
+}
@@ -0,0 +1,208 @@
+use wasm_bindgen::prelude::*;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+
+use crate::decision::Decision;
+use crate::error::to_json;
+use crate::expr::{self, BinaryOp, Environment, Expr, UnaryOp, Value};
+use crate::functions::EvalScope;
+use crate::vocabulary::Effect;
+use crate::{guard, PolicyEngine, PolicyRule, PolicySelection};
+
+// A comparison that has to change for the decision to flip
+#[derive(Debug, Clone, Serialize)]
+pub struct FailedCondition {
+    pub policy_id: String,
+    pub rule_id: String,
+    pub expression: String,
+    // e.g. "mfa_verified must be true"
+    pub requirement: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attribute: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actual: Option<JsonValue>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleRef {
+    pub policy_id: String,
+    pub rule_id: String,
+}
+
+// Output of explain_deny
+#[derive(Debug, Serialize)]
+pub struct DenyExplanation {
+    pub decision: Decision,
+    pub reason: String,
+    // Set when an engine check (quota, constraint, capsule state) denied
+    // rather than a rule
+    pub reason_code: Option<String>,
+    pub clipped_by: Option<String>,
+    // DENY rules that matched; each must stop matching
+    pub blocking_rules: Vec<RuleRef>,
+    // The PERMIT rule closest to matching, when none did
+    pub closest_permit: Option<RuleRef>,
+    pub failed_conditions: Vec<FailedCondition>,
+    // Remediation text of the rules above, as their authors wrote it
+    pub remediation: Vec<String>,
+}
+
+// An atom (comparison, call, attribute) whose truth is not `want`
+#[derive(Clone, Copy)]
+struct Unmet<'a> {
+    atom: &'a Expr,
+    want: bool,
+}
+
+fn truth(value: &Value) -> Option<bool> {
+    match value {
+        Value::Bool(b) => Some(*b),
+        Value::Null => Some(false),
+        _ => None,
+    }
+}
+
+// The fewest atoms of `expression` that have to change for it to
+// evaluate to `want`; empty when it already does. Atoms that fail to
+// evaluate count as unmet.
+fn unmet<'a>(expression: &'a Expr, want: bool, env: &dyn Environment) -> Vec<Unmet<'a>> {
+    let fewest = |a: Vec<Unmet<'a>>, b: Vec<Unmet<'a>>| if b.len() < a.len() { b } else { a };
+    match expression {
+        Expr::Unary(UnaryOp::Not, operand) => unmet(operand, !want, env),
+        // `a && b` is true when both are and false when either is;
+        // `a || b` the other way round
+        Expr::Binary(op @ (BinaryOp::And | BinaryOp::Or), left, right) => {
+            let (left, right) = (unmet(left, want, env), unmet(right, want, env));
+            if (*op == BinaryOp::And) == want {
+                left.into_iter().chain(right).collect()
+            } else {
+                fewest(left, right)
+            }
+        }
+        atom => match expr::evaluate(atom, env).ok().as_ref().and_then(truth) {
+            Some(actual) if actual == want => Vec::new(),
+            _ => vec![Unmet { atom, want }],
+        },
+    }
+}
+
+// "must be ..." phrasing of `attribute <op> value` holding (or not)
+fn phrase(op: BinaryOp, holds: bool) -> Option<&'static str> {
+    Some(match (op, holds) {
+        (BinaryOp::Eq, true) | (BinaryOp::Ne, false) => "must be",
+        (BinaryOp::Ne, true) | (BinaryOp::Eq, false) => "must not be",
+        (BinaryOp::Lt, true) | (BinaryOp::Ge, false) => "must be less than",
+        (BinaryOp::Le, true) | (BinaryOp::Gt, false) => "must be at most",
+        (BinaryOp::Gt, true) | (BinaryOp::Le, false) => "must be greater than",
+        (BinaryOp::Ge, true) | (BinaryOp::Lt, false) => "must be at least",
+        (BinaryOp::In, true) => "must be one of",
+        (BinaryOp::In, false) => "must not be one of",
+        (BinaryOp::Contains, true) => "must contain",
+        (BinaryOp::Contains, false) => "must not contain",
+        _ => return None,
+    })
+}
+
+fn failed_condition(policy_id: &str, rule: &PolicyRule, unmet: &Unmet, env: &dyn Environment) -> FailedCondition {
+    let (attribute, requirement) = match unmet.atom {
+        Expr::Attribute(path) => (Some(path), format!("{} must be {}", path.join("."), unmet.want)),
+        Expr::Binary(op, left, right) => match (left.as_ref(), phrase(*op, unmet.want)) {
+            (Expr::Attribute(path), Some(phrase)) => (Some(path), format!("{} {} {}", path.join("."), phrase, right)),
+            _ => (None, format!("{} must be {}", unmet.atom, unmet.want)),
+        },
+        atom => (None, format!("{} must be {}", atom, unmet.want)),
+    };
+    FailedCondition {
+        policy_id: policy_id.to_string(),
+        rule_id: rule.id.clone(),
+        expression: unmet.atom.to_string(),
+        requirement,
+        attribute: attribute.map(|path| path.join(".")),
+        actual: attribute
+            .and_then(|path| match env.derived(path) {
+                Some(value) => value.ok(),
+                None => env.resolve(path),
+            })
+            .and_then(|value| serde_json::to_value(value).ok()),
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Why a request is denied and what would permit it: the DENY rules
+    // that matched (each has to stop matching) and, if no PERMIT rule
+    // matched, the one needing the fewest changes, broken down into the
+    // comparisons that have to change ("mfa_verified must be true"),
+    // plus the rules' remediation text. A what-if against the active
+    // policies; exact under deny-overrides and a guide under the other
+    // algorithms. Returns a JSON DenyExplanation, with empty lists for
+    // decisions a PEP does not deny (PERMIT, CHALLENGE, ...).
+    #[wasm_bindgen]
+    pub fn explain_deny(&self, context_json: &str) -> Result<String, JsValue> {
+        let mut context = self.parse_context(context_json)?;
+        self.enrich_context(&mut context, None);
+        let mut result = guard::guarded(|| self.evaluate_context(&context, PolicySelection::Explicit(&self.policies)));
+        self.apply_constraints(&mut result, &context);
+        self.apply_lifecycle(&mut result, &context);
+
+        let mut explanation = DenyExplanation {
+            decision: result.decision,
+            reason: result.reason.to_string(),
+            reason_code: result.reason_code.clone(),
+            clipped_by: result.clipped_by.clone(),
+            blocking_rules: Vec::new(),
+            closest_permit: None,
+            failed_conditions: Vec::new(),
+            remediation: Vec::new(),
+        };
+        // PEPs deny when no rule applies or evaluation failed, too
+        if !matches!(result.decision, Decision::Deny | Decision::NotApplicable | Decision::Indeterminate) {
+            return Ok(to_json(&explanation)?);
+        }
+
+        let scope = EvalScope::new(self, &context, None);
+        let mut blocking = Vec::new();
+        let mut closest: Option<(&str, &PolicyRule, Vec<Unmet>)> = None;
+        for compiled in self.policies.iter().filter(|compiled| self.policy_in_effect(compiled)) {
+            let policy_id = compiled.policy.id.as_str();
+            let target = unmet(&compiled.target, true, &scope);
+            for (rule, condition) in compiled.rules().filter(|(rule, _)| self.in_deployment(&rule.environments)) {
+                match rule.effect {
+                    // Rules whose condition errors are INDETERMINATE, not blocking
+                    Effect::Deny
+                        if target.is_empty()
+                            && expr::evaluate(condition, &scope).ok().as_ref().and_then(truth) == Some(true) =>
+                    {
+                        blocking.push((policy_id, rule, unmet(condition, false, &scope)));
+                    }
+                    Effect::Permit => {
+                        let needed: Vec<Unmet> = target.iter().copied().chain(unmet(condition, true, &scope)).collect();
+                        if closest.as_ref().is_none_or(|(_, _, best)| needed.len() < best.len()) {
+                            closest = Some((policy_id, rule, needed));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let closest = closest.filter(|(_, _, needed)| !needed.is_empty());
+        if let Some((policy_id, rule, _)) = &closest {
+            explanation.closest_permit = Some(RuleRef { policy_id: policy_id.to_string(), rule_id: rule.id.clone() });
+        }
+        for (policy_id, rule, unmet) in blocking.iter().chain(&closest) {
+            if rule.effect == Effect::Deny {
+                explanation.blocking_rules.push(RuleRef { policy_id: policy_id.to_string(), rule_id: rule.id.clone() });
+            }
+            explanation.failed_conditions.extend(unmet.iter().map(|unmet| failed_condition(policy_id, rule, unmet, &scope)));
+            if let Some(text) = rule.remediation.as_ref().filter(|text| !explanation.remediation.contains(text)) {
+                explanation.remediation.push(text.clone());
+            }
+        }
+
+        if self.debug_mode {
+            console_log!("Deny explanation: {} failed conditions", explanation.failed_conditions.len());
+        }
+        Ok(to_json(&explanation)?)
+    }
+}
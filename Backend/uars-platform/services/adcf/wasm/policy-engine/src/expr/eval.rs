@@ -0,0 +1,240 @@
+use std::cmp::Ordering;
+use chrono::{DateTime, Duration, FixedOffset, SecondsFormat, Utc};
+
+use super::{pattern, simd, BinaryOp, Environment, Expr, ExprError, UnaryOp, Value};
+
+pub fn evaluate(expr: &Expr, env: &dyn Environment) -> Result<Value, ExprError> {
+    evaluate_at(expr, env, 1)
+}
+
+fn evaluate_at(expr: &Expr, env: &dyn Environment, depth: usize) -> Result<Value, ExprError> {
+    env.enter(depth)?;
+    match expr {
+        Expr::Literal(value) => Ok(value.clone()),
+        Expr::Attribute(path) => match env.derived(path) {
+            Some(value) => value,
+            None => Ok(env.resolve(path).unwrap_or(Value::Null)),
+        },
+        Expr::List(items) => {
+            let values = items
+                .iter()
+                .map(|item| evaluate_at(item, env, depth + 1))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Value::List(values))
+        }
+        Expr::Unary(op, operand) => {
+            let value = evaluate_at(operand, env, depth + 1)?;
+            match op {
+                UnaryOp::Not => Ok(Value::Bool(!truthy(&value)?)),
+                UnaryOp::Neg => match value {
+                    Value::Number(n) => Ok(Value::Number(-n)),
+                    other => Err(type_error("-", &other)),
+                },
+            }
+        }
+        Expr::Binary(op @ (BinaryOp::And | BinaryOp::Or), left, right) => {
+            // `&&` is decided by a false operand, `||` by a true one
+            let decisive = *op == BinaryOp::Or;
+            let failed = match evaluate_at(left, env, depth + 1).and_then(|value| truthy(&value)) {
+                Ok(value) if value == decisive => return Ok(Value::Bool(decisive)),
+                Ok(_) => None,
+                Err(error) if env.tolerates(&error) => Some(error),
+                Err(error) => return Err(error),
+            };
+            let value = truthy(&evaluate_at(right, env, depth + 1)?)?;
+            match failed {
+                Some(error) if value == decisive => {
+                    env.set_aside(&error);
+                    Ok(Value::Bool(decisive))
+                }
+                Some(error) => Err(error),
+                None => Ok(Value::Bool(value)),
+            }
+        }
+        Expr::Binary(op @ (BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge), left, right) => {
+            let lhs = evaluate_at(left, env, depth + 1)?;
+            let rhs = evaluate_at(right, env, depth + 1)?;
+            match lattice_ordering(left, right, &lhs, &rhs, env)? {
+                Some(ordering) => Ok(Value::Bool(ordered(*op, ordering))),
+                None => binary(*op, lhs, rhs),
+            }
+        }
+        Expr::Binary(op, left, right) => {
+            let lhs = evaluate_at(left, env, depth + 1)?;
+            let rhs = evaluate_at(right, env, depth + 1)?;
+            binary(*op, lhs, rhs)
+        }
+        Expr::Call(name, args) => {
+            let values = args
+                .iter()
+                .map(|arg| evaluate_at(arg, env, depth + 1))
+                .collect::<Result<Vec<_>, _>>()?;
+            env.call(name, &values)
+        }
+    }
+}
+
+// Missing attributes evaluate to null, which is treated as false in
+// boolean positions so absent facts never grant access.
+fn truthy(value: &Value) -> Result<bool, ExprError> {
+    match value {
+        Value::Bool(b) => Ok(*b),
+        Value::Null => Ok(false),
+        other => Err(ExprError::new(format!("Expected bool, found {}", other.type_name()))),
+    }
+}
+
+fn type_error(op: &str, value: &Value) -> ExprError {
+    ExprError::new(format!("Operator '{}' not supported for {}", op, value.type_name()))
+}
+
+fn binary(op: BinaryOp, lhs: Value, rhs: Value) -> Result<Value, ExprError> {
+    match op {
+        BinaryOp::Eq => Ok(Value::Bool(equal(&lhs, &rhs))),
+        BinaryOp::Ne => Ok(Value::Bool(!equal(&lhs, &rhs))),
+        BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+            // Comparisons involving a missing value are never satisfied
+            if lhs == Value::Null || rhs == Value::Null {
+                return Ok(Value::Bool(false));
+            }
+            Ok(Value::Bool(ordered(op, compare(&lhs, &rhs)?)))
+        }
+        BinaryOp::In => contains(&rhs, &lhs),
+        BinaryOp::Contains => contains(&lhs, &rhs),
+        BinaryOp::MatchesPath => match (&lhs, &rhs) {
+            (Value::String(path), Value::String(glob)) => Ok(Value::Bool(pattern::matches_path(path, glob))),
+            (Value::Null, _) => Ok(Value::Bool(false)),
+            (Value::String(_), other) | (other, _) => Err(type_error("matches_path", other)),
+        },
+        BinaryOp::Add => match (lhs, rhs) {
+            (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a + b)),
+            (Value::String(a), Value::Number(seconds)) => shift(&a, seconds, "+"),
+            (Value::String(a), Value::String(b)) => Ok(Value::String(a + &b)),
+            (a, _) => Err(type_error("+", &a)),
+        },
+        // Timestamp arithmetic: datetime - datetime is seconds, and
+        // datetime +/- seconds is a datetime
+        BinaryOp::Sub => match (&lhs, &rhs) {
+            (Value::String(a), Value::String(b)) => match (datetime(a), datetime(b)) {
+                (Some(a), Some(b)) => Ok(Value::Number((a - b).num_milliseconds() as f64 / 1000.0)),
+                _ => Err(type_error("-", &lhs)),
+            },
+            (Value::String(a), Value::Number(seconds)) => shift(a, -seconds, "-"),
+            (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a - b)),
+            (Value::Number(_), other) | (other, _) => Err(type_error("-", other)),
+        },
+        BinaryOp::Mul | BinaryOp::Div => {
+            let (a, b) = match (&lhs, &rhs) {
+                (Value::Number(a), Value::Number(b)) => (*a, *b),
+                (Value::Number(_), other) | (other, _) => {
+                    return Err(type_error(arithmetic_symbol(op), other));
+                }
+            };
+            match op {
+                BinaryOp::Mul => Ok(Value::Number(a * b)),
+                _ if b == 0.0 => Err(ExprError::new("Division by zero")),
+                _ => Ok(Value::Number(a / b)),
+            }
+        }
+        // Logical operators short-circuit in `evaluate` and never get here
+        BinaryOp::And | BinaryOp::Or => Err(ExprError::new("Logical operator evaluated eagerly")),
+    }
+}
+
+fn ordered(op: BinaryOp, ordering: Ordering) -> bool {
+    match op {
+        BinaryOp::Lt => ordering == Ordering::Less,
+        BinaryOp::Le => ordering != Ordering::Greater,
+        BinaryOp::Gt => ordering == Ordering::Greater,
+        _ => ordering != Ordering::Less,
+    }
+}
+
+// Two strings compared against an attribute with a configured lattice
+// (e.g. `classification >= "confidential"`) are ordered by level rather
+// than alphabetically
+fn lattice_ordering(
+    left: &Expr,
+    right: &Expr,
+    lhs: &Value,
+    rhs: &Value,
+    env: &dyn Environment,
+) -> Result<Option<Ordering>, ExprError> {
+    let (a, b) = match (lhs, rhs) {
+        (Value::String(a), Value::String(b)) => (a, b),
+        _ => return Ok(None),
+    };
+    let path = match (left, right) {
+        (Expr::Attribute(path), _) | (_, Expr::Attribute(path)) => path,
+        _ => return Ok(None),
+    };
+    match (env.lattice_rank(path, a), env.lattice_rank(path, b)) {
+        (Some(a), Some(b)) => Ok(Some(a?.cmp(&b?))),
+        _ => Ok(None),
+    }
+}
+
+fn arithmetic_symbol(op: BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Mul => "*",
+        _ => "/",
+    }
+}
+
+// RFC 3339 strings (the form `timestamp` and most JSON dates take)
+fn datetime(s: &str) -> Option<DateTime<FixedOffset>> {
+    if !s.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    DateTime::parse_from_rfc3339(s).ok()
+}
+
+fn shift(text: &str, seconds: f64, op: &str) -> Result<Value, ExprError> {
+    let start = datetime(text).ok_or_else(|| type_error(op, &Value::String(text.to_string())))?;
+    let shifted = Duration::try_milliseconds((seconds * 1000.0) as i64)
+        .and_then(|delta| start.with_timezone(&Utc).checked_add_signed(delta))
+        .ok_or_else(|| ExprError::new("Datetime arithmetic out of range"))?;
+    Ok(Value::String(shifted.to_rfc3339_opts(SecondsFormat::AutoSi, true)))
+}
+
+fn compare(lhs: &Value, rhs: &Value) -> Result<Ordering, ExprError> {
+    match (lhs, rhs) {
+        (Value::Number(a), Value::Number(b)) => {
+            a.partial_cmp(b).ok_or_else(|| ExprError::new("Cannot compare NaN"))
+        }
+        (Value::String(a), Value::String(b)) => match (datetime(a), datetime(b)) {
+            (Some(a), Some(b)) => Ok(a.cmp(&b)),
+            _ => Ok(a.cmp(b)),
+        },
+        (a, b) => Err(ExprError::new(format!(
+            "Cannot compare {} with {}",
+            a.type_name(),
+            b.type_name()
+        ))),
+    }
+}
+
+fn equal(lhs: &Value, rhs: &Value) -> bool {
+    match (lhs, rhs) {
+        (Value::String(a), Value::String(b)) => simd::str_eq(a, b),
+        _ => lhs == rhs,
+    }
+}
+
+fn contains(haystack: &Value, needle: &Value) -> Result<Value, ExprError> {
+    match (haystack, needle) {
+        // Role and group membership: the common case, compared as bytes
+        (Value::List(items), Value::String(needle)) => {
+            let strings = items.iter().filter_map(|item| match item {
+                Value::String(s) => Some(s.as_str()),
+                _ => None,
+            });
+            Ok(Value::Bool(simd::contains_str(strings, needle)))
+        }
+        (Value::List(items), needle) => Ok(Value::Bool(items.contains(needle))),
+        (Value::String(s), Value::String(sub)) => Ok(Value::Bool(s.contains(sub.as_str()))),
+        (Value::Map(map), Value::String(key)) => Ok(Value::Bool(map.contains_key(key))),
+        (Value::Null, _) => Ok(Value::Bool(false)),
+        (other, _) => Err(type_error("contains", other)),
+    }
+}
@@ -0,0 +1,427 @@
+use super::ExprError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Number(f64),
+    Str(String),
+    Ident(String),
+    True,
+    False,
+    Null,
+    And,
+    Or,
+    Not,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    In,
+    Contains,
+    MatchesPath,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+    Dot,
+}
+
+// Token plus its byte offset in the source expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned {
+    pub token: Token,
+    pub offset: usize,
+}
+
+pub fn tokenize(source: &str) -> Result<Vec<Spanned>, ExprError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+
+    while let Some(&(offset, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let token = match c {
+            '(' => single(&mut chars, Token::LParen),
+            ')' => single(&mut chars, Token::RParen),
+            '[' => single(&mut chars, Token::LBracket),
+            ']' => single(&mut chars, Token::RBracket),
+            ',' => single(&mut chars, Token::Comma),
+            '.' => single(&mut chars, Token::Dot),
+            '+' => single(&mut chars, Token::Plus),
+            '-' => single(&mut chars, Token::Minus),
+            '*' => single(&mut chars, Token::Star),
+            '/' => single(&mut chars, Token::Slash),
+            '=' | '!' | '<' | '>' | '&' | '|' => operator(&mut chars, offset)?,
+            '\'' | '"' => string(&mut chars, offset)?,
+            c if c.is_ascii_digit() => number(&mut chars, source, offset)?,
+            c if c.is_alphabetic() || c == '_' => word(&mut chars, source, offset),
+            other => {
+                return Err(ExprError::at(format!("Unexpected character '{}'", other), offset));
+            }
+        };
+
+        tokens.push(Spanned { token, offset });
+    }
+
+    Ok(tokens)
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::CharIndices<'a>>;
+
+fn single(chars: &mut Chars, token: Token) -> Token {
+    chars.next();
+    token
+}
+
+fn operator(chars: &mut Chars, offset: usize) -> Result<Token, ExprError> {
+    let (_, first) = chars.next().unwrap_or((offset, '\0'));
+    let second = chars.peek().map(|&(_, c)| c);
+
+    let (token, consumed_second) = match (first, second) {
+        ('=', Some('=')) => (Token::Eq, true),
+        ('!', Some('=')) => (Token::Ne, true),
+        ('<', Some('=')) => (Token::Le, true),
+        ('>', Some('=')) => (Token::Ge, true),
+        ('&', Some('&')) => (Token::And, true),
+        ('|', Some('|')) => (Token::Or, true),
+        ('!', _) => (Token::Not, false),
+        ('<', _) => (Token::Lt, false),
+        ('>', _) => (Token::Gt, false),
+        (c, _) => {
+            return Err(ExprError::at(format!("Unexpected operator '{}'", c), offset));
+        }
+    };
+
+    if consumed_second {
+        chars.next();
+    }
+    Ok(token)
+}
+
+// Single- or double-quoted; any UTF-8 may appear as is, and
+//   \n \t \r \0 \b \f \\ \' \" \/    the usual escapes
+//   \u00e9                       JSON style, surrogate pairs combined
+//   \u{1F600}                    1 to 6 hex digits
+fn string(chars: &mut Chars, offset: usize) -> Result<Token, ExprError> {
+    let (_, quote) = chars.next().unwrap_or((offset, '"'));
+    let mut value = String::new();
+
+    loop {
+        match chars.next() {
+            Some((_, c)) if c == quote => return Ok(Token::Str(value)),
+            Some((pos, '\\')) if chars.peek().is_some() => value.push(escape(chars, pos)?),
+            Some((_, '\\')) | None => break,
+            Some((_, c)) => value.push(c),
+        }
+    }
+
+    Err(ExprError::at("Unterminated string literal", offset))
+}
+
+// The character escaped by the backslash at `pos`
+fn escape(chars: &mut Chars, pos: usize) -> Result<char, ExprError> {
+    let Some((_, c)) = chars.next() else {
+        return Err(ExprError::at("Unterminated string literal", pos));
+    };
+    Ok(match c {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        '0' => '\0',
+        'b' => '\u{8}',
+        'f' => '\u{c}',
+        '\\' | '\'' | '"' | '/' => c,
+        'u' if chars.next_if(|&(_, c)| c == '{').is_some() => {
+            let code = hex_digits(chars, pos, 1, 6)?;
+            if chars.next_if(|&(_, c)| c == '}').is_none() {
+                return Err(ExprError::at("Unicode escape '\\u{...}' takes 1 to 6 hex digits and a closing '}'", pos));
+            }
+            scalar(code, pos)?
+        }
+        'u' => {
+            let code = hex_digits(chars, pos, 4, 4)?;
+            if !(0xD800..0xDC00).contains(&code) {
+                return scalar(code, pos);
+            }
+            // A high surrogate has to be followed by an escaped low one
+            let low = match (chars.next_if(|&(_, c)| c == '\\'), chars.next_if(|&(_, c)| c == 'u')) {
+                (Some(_), Some(_)) => hex_digits(chars, pos, 4, 4)?,
+                _ => 0,
+            };
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(ExprError::at(format!("Unpaired surrogate '\\u{:04X}' in unicode escape", code), pos));
+            }
+            scalar(0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00), pos)?
+        }
+        c => {
+            return Err(ExprError::at(format!("Unknown escape sequence '\\{}'", c), pos));
+        }
+    })
+}
+
+// Between `min` and `max` hex digits as a number
+fn hex_digits(chars: &mut Chars, pos: usize, min: usize, max: usize) -> Result<u32, ExprError> {
+    let mut code = 0;
+    let mut count = 0;
+    while count < max {
+        let Some(digit) = chars.peek().and_then(|&(_, c)| c.to_digit(16)) else {
+            break;
+        };
+        chars.next();
+        code = code * 16 + digit;
+        count += 1;
+    }
+    if count < min {
+        let expected = if min == max { format!("{}", min) } else { format!("{} to {}", min, max) };
+        return Err(ExprError::at(format!("Unicode escape takes {} hex digits", expected), pos));
+    }
+    Ok(code)
+}
+
+fn scalar(code: u32, pos: usize) -> Result<char, ExprError> {
+    char::from_u32(code)
+        .ok_or_else(|| ExprError::at(format!("Unicode escape U+{:04X} is not a valid character", code), pos))
+}
+
+// Renders `text` as a double-quoted literal `string` reads back
+// unchanged. Only quotes, backslashes and control characters are
+// escaped; other characters, non-ASCII included, are written as is.
+pub fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn number(chars: &mut Chars, source: &str, offset: usize) -> Result<Token, ExprError> {
+    let mut end = offset;
+    let mut seen_dot = false;
+
+    while let Some(&(pos, c)) = chars.peek() {
+        if c.is_ascii_digit() {
+            chars.next();
+            end = pos + 1;
+        } else if c == '.' && !seen_dot && next_is_digit(source, pos) {
+            seen_dot = true;
+            chars.next();
+            end = pos + 1;
+        } else {
+            break;
+        }
+    }
+
+    let value = source[offset..end]
+        .parse::<f64>()
+        .map_err(|_| ExprError::at(format!("Invalid number '{}'", &source[offset..end]), offset))?;
+
+    // Duration literals (`15m`, `8h`, `7d`) are numbers of seconds, the
+    // unit durations in the context (session_age, intent_duration) use
+    let suffix: String = source[end..].chars().take_while(|c| c.is_ascii_alphabetic()).collect();
+    match duration_unit(&suffix) {
+        Some(seconds) => {
+            for _ in 0..suffix.len() {
+                chars.next();
+            }
+            Ok(Token::Number(value * seconds))
+        }
+        None => Ok(Token::Number(value)),
+    }
+}
+
+fn duration_unit(suffix: &str) -> Option<f64> {
+    match suffix {
+        "ms" => Some(0.001),
+        "s" => Some(1.0),
+        "m" => Some(60.0),
+        "h" => Some(3_600.0),
+        "d" => Some(86_400.0),
+        "w" => Some(604_800.0),
+        _ => None,
+    }
+}
+
+fn next_is_digit(source: &str, dot_pos: usize) -> bool {
+    source[dot_pos + 1..].chars().next().is_some_and(|c| c.is_ascii_digit())
+}
+
+fn word(chars: &mut Chars, source: &str, offset: usize) -> Token {
+    let mut end = offset;
+    while let Some(&(pos, c)) = chars.peek() {
+        if c.is_alphanumeric() || c == '_' {
+            chars.next();
+            end = pos + c.len_utf8();
+        } else {
+            break;
+        }
+    }
+
+    match &source[offset..end] {
+        "true" => Token::True,
+        "false" => Token::False,
+        "null" => Token::Null,
+        "and" => Token::And,
+        "or" => Token::Or,
+        "not" => Token::Not,
+        "in" => Token::In,
+        "contains" => Token::Contains,
+        "matches_path" => Token::MatchesPath,
+        ident => Token::Ident(ident.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::{self, BinaryOp, Expr, Value};
+    use proptest::prelude::*;
+
+    fn string_literal(source: &str) -> Result<String, ExprError> {
+        match tokenize(source)?.as_slice() {
+            [Spanned { token: Token::Str(value), .. }] => Ok(value.clone()),
+            other => panic!("{:?} is not a single string token: {:?}", source, other),
+        }
+    }
+
+    #[test]
+    fn strings_hold_spaces_quotes_and_unicode() {
+        for (source, expected) in [
+            (r#""Q3 board report (final)""#, "Q3 board report (final)"),
+            (r#"'say "hi"'"#, r#"say "hi""#),
+            (r#""it's""#, "it's"),
+            (r#"'it\'s'"#, "it's"),
+            (r#""say \"hi\"""#, r#"say "hi""#),
+            ("\"résumé naïve Ærø\"", "résumé naïve Ærø"),
+            ("'東京 — 🔒 données'", "東京 — 🔒 données"),
+            ("\"e\u{301}\"", "e\u{301}"),
+            ("\"\u{202e}evil\"", "\u{202e}evil"),
+            ("\"tab\tand\nnewline\"", "tab\tand\nnewline"),
+            ("\"\"", ""),
+            ("''", ""),
+        ] {
+            assert_eq!(string_literal(source).as_deref(), Ok(expected), "{}", source);
+        }
+    }
+
+    #[test]
+    fn escapes_decode() {
+        for (source, expected) in [
+            (r#""a\nb\tc\rd""#, "a\nb\tc\rd"),
+            (r#""\0\b\f""#, "\0\u{8}\u{c}"),
+            (r#""\\ \/ \' \"""#, "\\ / ' \""),
+            (r#""caf\u00e9""#, "caf\u{e9}"),
+            (r#""caf\u00E9 \u00e9""#, "caf\u{e9} \u{e9}"),
+            (r#""\u{1F600}""#, "😀"),
+            (r#""\u{41}\u{10FFFF}""#, "A\u{10FFFF}"),
+            (r#""\uD83D\uDE00""#, "\u{1F600}"),
+            (r#""\u00411""#, "A1"),
+            (r#""\\u0041""#, "\\u0041"),
+        ] {
+            assert_eq!(string_literal(source).as_deref(), Ok(expected), "{}", source);
+        }
+    }
+
+    #[test]
+    fn malformed_strings_are_rejected() {
+        for source in [
+            r#""abc"#,
+            r#""abc\"#,
+            r#""abc\""#,
+            r#"'abc""#,
+            r#""\x41""#,
+            r#""\q""#,
+            r#""\u12""#,
+            r#""\uZZZZ""#,
+            r#""\u{}""#,
+            r#""\u{41""#,
+            r#""\u{1234567}""#,
+            r#""\u{110000}""#,
+            r#""\u{D800}""#,
+            r#""\uD800""#,
+            r#""\uD800x""#,
+            r#""\uD800\u0041""#,
+            r#""\uD800\uD800""#,
+            r#""\uD800\n""#,
+            r#""\uDC00""#,
+            "\"\\",
+            "\"\\u",
+            "\"\\u{",
+        ] {
+            assert!(tokenize(source).is_err(), "{} was accepted", source);
+        }
+    }
+
+    #[test]
+    fn errors_point_at_the_bad_escape() {
+        // Offsets are in bytes; "é" and "日" take 2 and 3
+        let source = r#"name == "é日" && note == "ok \q""#;
+        let error = tokenize(source).unwrap_err();
+        assert_eq!(error.offset, source.find("\\q"));
+        assert!(error.message.contains("\\q"), "{}", error.message);
+
+        let source = r#"title == "never closed"#;
+        assert_eq!(tokenize(source).unwrap_err().offset, source.find('"'));
+    }
+
+    #[test]
+    fn quoted_values_compare_in_conditions() {
+        let parsed = expr::parse(r#"resource_name == 'Année 2024 — "draft"' && reason in ["on call", "über"]"#).unwrap();
+        let Expr::Binary(BinaryOp::And, left, _) = &parsed else {
+            panic!("{:?}", parsed);
+        };
+        assert_eq!(
+            **left,
+            Expr::Binary(
+                BinaryOp::Eq,
+                Box::new(Expr::Attribute(vec!["resource_name".to_string()])),
+                Box::new(Expr::Literal(Value::String("Année 2024 — \"draft\"".to_string()))),
+            )
+        );
+    }
+
+    #[test]
+    fn quote_escapes_only_what_it_must() {
+        assert_eq!(quote("plain"), r#""plain""#);
+        assert_eq!(quote(r#"a "b" \c"#), r#""a \"b\" \\c""#);
+        assert_eq!(quote("l'été 😀"), "\"l'été 😀\"");
+        assert_eq!(quote("\0\u{1b}\u{7f}\u{85}"), r#""\u{0}\u{1b}\u{7f}\u{85}""#);
+    }
+
+    proptest! {
+        #[test]
+        fn quoted_strings_read_back_unchanged(text in any::<String>()) {
+            prop_assert_eq!(string_literal(&quote(&text)), Ok(text));
+        }
+
+        #[test]
+        fn rendered_string_literals_reparse(text in any::<String>()) {
+            let literal = Expr::Literal(Value::String(text));
+            prop_assert_eq!(expr::parse(&literal.to_string()), Ok(literal));
+        }
+
+        #[test]
+        fn lexer_never_panics_inside_strings(body in "(\\PC|\\\\|\\\\u|\\{|\\}|[0-9a-fA-F]){0,40}") {
+            let _ = tokenize(&format!("\"{}\"", body));
+            let _ = tokenize(&format!("'{}", body));
+        }
+    }
+}
@@ -0,0 +1,231 @@
+// Policy condition language.
+//
+// Conditions and targets are parsed once when a policy is loaded and
+// evaluated against an `Environment` that resolves attribute paths
+// (e.g. `user.roles`, `risk_score`) and dispatches function calls.
+
+mod eval;
+mod lexer;
+mod parser;
+mod partial;
+mod pattern;
+pub(crate) mod simd;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+pub use eval::evaluate;
+pub use lexer::quote;
+pub use parser::parse;
+pub use partial::partial;
+pub use pattern::matches_path;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    List(Vec<Value>),
+    Map(BTreeMap<String, Value>),
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Bool(_) => "bool",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+        }
+    }
+}
+
+impl From<&serde_json::Value> for Value {
+    fn from(value: &serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => Value::Null,
+            serde_json::Value::Bool(b) => Value::Bool(*b),
+            serde_json::Value::Number(n) => Value::Number(n.as_f64().unwrap_or(0.0)),
+            serde_json::Value::String(s) => Value::String(s.clone()),
+            serde_json::Value::Array(items) => Value::List(items.iter().map(Value::from).collect()),
+            serde_json::Value::Object(map) => Value::Map(
+                map.iter().map(|(k, v)| (k.clone(), Value::from(v))).collect(),
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::String(s) => f.write_str(&quote(s)),
+            Value::List(items) => {
+                let parts: Vec<String> = items.iter().map(|v| v.to_string()).collect();
+                write!(f, "[{}]", parts.join(", "))
+            }
+            Value::Map(_) => write!(f, "{{...}}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum UnaryOp {
+    Not,
+    Neg,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BinaryOp {
+    And,
+    Or,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    In,
+    Contains,
+    MatchesPath,
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Expr {
+    Literal(Value),
+    Attribute(Vec<String>),
+    List(Vec<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+impl BinaryOp {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOp::And => "&&",
+            BinaryOp::Or => "||",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+            BinaryOp::In => "in",
+            BinaryOp::Contains => "contains",
+            BinaryOp::MatchesPath => "matches_path",
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+        }
+    }
+}
+
+// Renders an expression back to (fully parenthesised) source text
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Literal(value) => write!(f, "{}", value),
+            Expr::Attribute(path) => write!(f, "{}", path.join(".")),
+            Expr::List(items) => {
+                let parts: Vec<String> = items.iter().map(|e| e.to_string()).collect();
+                write!(f, "[{}]", parts.join(", "))
+            }
+            Expr::Unary(UnaryOp::Not, operand) => write!(f, "!{}", Operand(operand)),
+            Expr::Unary(UnaryOp::Neg, operand) => write!(f, "-{}", Operand(operand)),
+            Expr::Binary(op, left, right) => {
+                write!(f, "{} {} {}", Operand(left), op.symbol(), Operand(right))
+            }
+            Expr::Call(name, args) => {
+                let parts: Vec<String> = args.iter().map(|e| e.to_string()).collect();
+                write!(f, "{}({})", name, parts.join(", "))
+            }
+        }
+    }
+}
+
+// Operand of an operator: compound expressions get parentheses
+struct Operand<'a>(&'a Expr);
+
+impl fmt::Display for Operand<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Expr::Binary(..) | Expr::Unary(..) => write!(f, "({})", self.0),
+            other => write!(f, "{}", other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExprError {
+    pub message: String,
+    pub offset: Option<usize>,
+}
+
+impl ExprError {
+    pub fn new(message: impl Into<String>) -> ExprError {
+        ExprError { message: message.into(), offset: None }
+    }
+
+    pub fn at(message: impl Into<String>, offset: usize) -> ExprError {
+        ExprError { message: message.into(), offset: Some(offset) }
+    }
+}
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.offset {
+            Some(offset) => write!(f, "{} at offset {}", self.message, offset),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+// Supplies attribute values and function implementations to the evaluator
+pub trait Environment {
+    // Returns None when the path does not name a known attribute
+    fn resolve(&self, path: &[String]) -> Option<Value>;
+
+    fn call(&self, name: &str, args: &[Value]) -> Result<Value, ExprError>;
+
+    // Called before each node is evaluated with its depth in the tree
+    // (the root is 1); an error aborts the evaluation
+    fn enter(&self, _depth: usize) -> Result<(), ExprError> {
+        Ok(())
+    }
+
+    // Position of `value` in the ordered lattice configured for the
+    // attribute at `path` (lowest first). None when the attribute has no
+    // lattice; an error when the value is not one of its levels.
+    fn lattice_rank(&self, _path: &[String], _value: &str) -> Option<Result<usize, ExprError>> {
+        None
+    }
+
+    // Value of the computed attribute at `path`, evaluated on demand. None
+    // when the path is not a computed attribute and resolves as usual; an
+    // error when computing it failed.
+    fn derived(&self, _path: &[String]) -> Option<Result<Value, ExprError>> {
+        None
+    }
+
+    // Whether an `&&`/`||` operand that failed may be set aside, so the
+    // other operand can decide the result alone (`false && <error>`)
+    fn tolerates(&self, _error: &ExprError) -> bool {
+        false
+    }
+
+    // Called when a tolerated failure was set aside
+    fn set_aside(&self, _error: &ExprError) {}
+}
@@ -0,0 +1,252 @@
+use super::lexer::{tokenize, Spanned, Token};
+use super::{BinaryOp, Expr, ExprError, UnaryOp, Value};
+
+// Recursive-descent parser. Precedence, lowest first:
+//   || / or
+//   && / and
+//   ! / not
+//   == != < <= > >= in contains
+//   + -
+//   * /
+//   unary -
+//
+// Nesting (parentheses, lists, operator chains, prefix operators) is
+// capped at MAX_DEPTH so hostile input cannot exhaust the stack while
+// parsing, evaluating or dropping the tree.
+pub const MAX_DEPTH: usize = 64;
+
+pub fn parse(source: &str) -> Result<Expr, ExprError> {
+    let tokens = tokenize(source)?;
+    if tokens.is_empty() {
+        return Ok(Expr::Literal(Value::Bool(true)));
+    }
+
+    let mut parser = Parser { tokens, pos: 0, end: source.len(), depth: 0 };
+    let expr = parser.parse_or()?;
+
+    if let Some(extra) = parser.tokens.get(parser.pos) {
+        return Err(ExprError::at(
+            format!("Unexpected token {:?} after end of expression", extra.token),
+            extra.offset,
+        ));
+    }
+
+    Ok(expr)
+}
+
+struct Parser {
+    tokens: Vec<Spanned>,
+    pos: usize,
+    end: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|t| &t.token)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens.get(self.pos).map(|t| t.offset).unwrap_or(self.end)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|t| t.token.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), ExprError> {
+        if self.eat(&token) {
+            Ok(())
+        } else {
+            Err(ExprError::at(format!("Expected {:?}", token), self.offset()))
+        }
+    }
+
+    // Counts one more level of nesting; callers restore `depth` when done
+    fn nest(&mut self) -> Result<(), ExprError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(ExprError::at(
+                format!("Expression nested more than {} levels deep", MAX_DEPTH),
+                self.offset(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, ExprError> {
+        let base = self.depth;
+        let mut left = self.parse_and()?;
+        while self.eat(&Token::Or) {
+            self.nest()?;
+            let right = self.parse_and()?;
+            left = Expr::Binary(BinaryOp::Or, Box::new(left), Box::new(right));
+        }
+        self.depth = base;
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, ExprError> {
+        let base = self.depth;
+        let mut left = self.parse_not()?;
+        while self.eat(&Token::And) {
+            self.nest()?;
+            let right = self.parse_not()?;
+            left = Expr::Binary(BinaryOp::And, Box::new(left), Box::new(right));
+        }
+        self.depth = base;
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, ExprError> {
+        if self.eat(&Token::Not) {
+            self.nest()?;
+            let operand = self.parse_not()?;
+            self.depth -= 1;
+            return Ok(Expr::Unary(UnaryOp::Not, Box::new(operand)));
+        }
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr, ExprError> {
+        let base = self.depth;
+        let mut left = self.parse_additive()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Eq) => BinaryOp::Eq,
+                Some(Token::Ne) => BinaryOp::Ne,
+                Some(Token::Lt) => BinaryOp::Lt,
+                Some(Token::Le) => BinaryOp::Le,
+                Some(Token::Gt) => BinaryOp::Gt,
+                Some(Token::Ge) => BinaryOp::Ge,
+                Some(Token::In) => BinaryOp::In,
+                Some(Token::Contains) => BinaryOp::Contains,
+                Some(Token::MatchesPath) => BinaryOp::MatchesPath,
+                _ => break,
+            };
+            self.advance();
+            self.nest()?;
+            let right = self.parse_additive()?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        self.depth = base;
+        Ok(left)
+    }
+
+    fn parse_additive(&mut self) -> Result<Expr, ExprError> {
+        let base = self.depth;
+        let mut left = self.parse_multiplicative()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Plus) => BinaryOp::Add,
+                Some(Token::Minus) => BinaryOp::Sub,
+                _ => break,
+            };
+            self.advance();
+            self.nest()?;
+            let right = self.parse_multiplicative()?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        self.depth = base;
+        Ok(left)
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Expr, ExprError> {
+        let base = self.depth;
+        let mut left = self.parse_unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Star) => BinaryOp::Mul,
+                Some(Token::Slash) => BinaryOp::Div,
+                _ => break,
+            };
+            self.advance();
+            self.nest()?;
+            let right = self.parse_unary()?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        self.depth = base;
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, ExprError> {
+        if self.eat(&Token::Minus) {
+            self.nest()?;
+            let operand = self.parse_unary()?;
+            self.depth -= 1;
+            return Ok(Expr::Unary(UnaryOp::Neg, Box::new(operand)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, ExprError> {
+        let offset = self.offset();
+        match self.advance() {
+            Some(Token::Number(n)) => Ok(Expr::Literal(Value::Number(n))),
+            Some(Token::Str(s)) => Ok(Expr::Literal(Value::String(s))),
+            Some(Token::True) => Ok(Expr::Literal(Value::Bool(true))),
+            Some(Token::False) => Ok(Expr::Literal(Value::Bool(false))),
+            Some(Token::Null) => Ok(Expr::Literal(Value::Null)),
+            Some(Token::LParen) => {
+                self.nest()?;
+                let inner = self.parse_or()?;
+                self.expect(Token::RParen)?;
+                self.depth -= 1;
+                Ok(inner)
+            }
+            Some(Token::LBracket) => {
+                let items = self.parse_list(Token::RBracket)?;
+                Ok(Expr::List(items))
+            }
+            Some(Token::Ident(name)) => self.parse_identifier(name),
+            Some(token) => Err(ExprError::at(format!("Unexpected token {:?}", token), offset)),
+            None => Err(ExprError::at("Unexpected end of expression", offset)),
+        }
+    }
+
+    // Dotted attribute path, or a function call when followed by '('
+    fn parse_identifier(&mut self, first: String) -> Result<Expr, ExprError> {
+        let mut path = vec![first];
+        while self.eat(&Token::Dot) {
+            let offset = self.offset();
+            match self.advance() {
+                Some(Token::Ident(segment)) => path.push(segment),
+                _ => return Err(ExprError::at("Expected attribute name after '.'", offset)),
+            }
+        }
+
+        if self.eat(&Token::LParen) {
+            let args = self.parse_list(Token::RParen)?;
+            return Ok(Expr::Call(path.join("."), args));
+        }
+
+        Ok(Expr::Attribute(path))
+    }
+
+    fn parse_list(&mut self, close: Token) -> Result<Vec<Expr>, ExprError> {
+        let mut items = Vec::new();
+        if self.eat(&close) {
+            return Ok(items);
+        }
+        self.nest()?;
+        loop {
+            items.push(self.parse_or()?);
+            if self.eat(&close) {
+                self.depth -= 1;
+                return Ok(items);
+            }
+            self.expect(Token::Comma)?;
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use super::{evaluate, BinaryOp, Environment, Expr, UnaryOp, Value};
+
+// Partial evaluation: folds every sub-expression whose attributes the
+// environment knows (resolve returns Some) into a literal and simplifies
+// `&&`, `||` and `!` around them. Calls are never folded, since functions
+// may read engine state or the clock. The residual evaluates exactly as the
+// original would for any context agreeing with the known attributes,
+// errors included: an operand that could fail is only dropped when the
+// original would never have evaluated it.
+pub fn partial(expr: &Expr, env: &dyn Environment) -> Expr {
+    if let Some(value) = fold(expr, env) {
+        return Expr::Literal(value);
+    }
+    match expr {
+        Expr::Binary(op @ (BinaryOp::And | BinaryOp::Or), left, right) => {
+            logical(*op, partial(left, env), partial(right, env))
+        }
+        // An attribute compared against a lattice is ordered by level, so
+        // it stays an attribute unless the whole comparison folds
+        Expr::Binary(op @ (BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge), left, right) => {
+            let keep = |operand: &Expr| match operand {
+                Expr::Attribute(_) => operand.clone(),
+                other => partial(other, env),
+            };
+            Expr::Binary(*op, Box::new(keep(left)), Box::new(keep(right)))
+        }
+        Expr::Binary(op, left, right) => {
+            settle(Expr::Binary(*op, Box::new(partial(left, env)), Box::new(partial(right, env))), env)
+        }
+        Expr::Unary(op, operand) => settle(Expr::Unary(*op, Box::new(partial(operand, env))), env),
+        Expr::List(items) => settle(Expr::List(items.iter().map(|item| partial(item, env)).collect()), env),
+        Expr::Call(name, args) => Expr::Call(name.clone(), args.iter().map(|arg| partial(arg, env)).collect()),
+        Expr::Literal(_) | Expr::Attribute(_) => expr.clone(),
+    }
+}
+
+// The expression's value when it only depends on known attributes and
+// evaluates cleanly to something a literal can render
+fn fold(expr: &Expr, env: &dyn Environment) -> Option<Value> {
+    if !closed(expr, env) {
+        return None;
+    }
+    evaluate(expr, env).ok().filter(renderable)
+}
+
+// Rebuilt node whose operands may all have folded
+fn settle(expr: Expr, env: &dyn Environment) -> Expr {
+    match fold(&expr, env) {
+        Some(value) => Expr::Literal(value),
+        None => expr,
+    }
+}
+
+fn closed(expr: &Expr, env: &dyn Environment) -> bool {
+    match expr {
+        Expr::Literal(_) => true,
+        Expr::Attribute(path) => env.resolve(path).is_some(),
+        Expr::List(items) => items.iter().all(|item| closed(item, env)),
+        Expr::Unary(_, operand) => closed(operand, env),
+        Expr::Binary(_, left, right) => closed(left, env) && closed(right, env),
+        Expr::Call(..) => false,
+    }
+}
+
+// Maps render as `{...}` and non-finite numbers not at all, so neither
+// can appear in residual source
+fn renderable(value: &Value) -> bool {
+    match value {
+        Value::Number(n) => n.is_finite(),
+        Value::List(items) => items.iter().all(renderable),
+        Value::Map(_) => false,
+        _ => true,
+    }
+}
+
+fn logical(op: BinaryOp, left: Expr, right: Expr) -> Expr {
+    // `&&` is decided by a false operand, `||` by a true one
+    let decisive = op == BinaryOp::Or;
+    match (truth(&left), truth(&right)) {
+        (Some(value), _) if value == decisive => Expr::Literal(Value::Bool(decisive)),
+        // The other operand alone decides, once it is known to be a bool
+        (Some(_), None) if boolean(&right) => right,
+        (Some(_), Some(value)) => Expr::Literal(Value::Bool(value)),
+        (None, Some(value)) if value != decisive && boolean(&left) => left,
+        // The decisive operand is evaluated second, so the first may only
+        // go when it cannot fail
+        (None, Some(value)) if value == decisive && infallible(&left) => Expr::Literal(Value::Bool(decisive)),
+        _ => Expr::Binary(op, Box::new(left), Box::new(right)),
+    }
+}
+
+// How a folded operand reads in a boolean position; None for residuals
+// and for literals that would fail there
+fn truth(expr: &Expr) -> Option<bool> {
+    match expr {
+        Expr::Literal(Value::Bool(b)) => Some(*b),
+        Expr::Literal(Value::Null) => Some(false),
+        _ => None,
+    }
+}
+
+// Whether the expression can only evaluate to a bool (or fail)
+fn boolean(expr: &Expr) -> bool {
+    match expr {
+        Expr::Literal(Value::Bool(_)) | Expr::Unary(UnaryOp::Not, _) => true,
+        Expr::Binary(op, ..) => !matches!(op, BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div),
+        _ => false,
+    }
+}
+
+// Whether the expression always evaluates to a bool without failing:
+// equality between attributes and literals, and logic over those
+fn infallible(expr: &Expr) -> bool {
+    fn value(expr: &Expr) -> bool {
+        match expr {
+            Expr::Literal(_) | Expr::Attribute(_) => true,
+            Expr::List(items) => items.iter().all(value),
+            other => infallible(other),
+        }
+    }
+    match expr {
+        Expr::Literal(Value::Bool(_) | Value::Null) => true,
+        Expr::Unary(UnaryOp::Not, operand) => infallible(operand),
+        Expr::Binary(BinaryOp::Eq | BinaryOp::Ne, left, right) => value(left) && value(right),
+        Expr::Binary(BinaryOp::And | BinaryOp::Or, left, right) => infallible(left) && infallible(right),
+        _ => false,
+    }
+}
@@ -0,0 +1,54 @@
+// Hierarchical resource patterns for `matches_path`. Paths and patterns
+// are split on '/'; `**` matches any number of segments (including none),
+// and within a segment `*` matches any run of characters and `?` one
+// character, so "projects/*/capsules/**" matches every capsule path of
+// every project.
+
+pub fn matches_path(path: &str, pattern: &str) -> bool {
+    let path: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let pattern: Vec<&str> = pattern.trim_matches('/').split('/').collect();
+    match_segments(&path, &pattern)
+}
+
+fn match_segments(path: &[&str], pattern: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => {
+            // Collapse runs of ** so the search stays linear per segment
+            let rest = match rest.iter().position(|segment| *segment != "**") {
+                Some(start) => &rest[start..],
+                None => return true,
+            };
+            (0..=path.len()).any(|skip| match_segments(&path[skip..], rest))
+        }
+        Some((segment, rest)) => match path.split_first() {
+            Some((first, path_rest)) => match_segment(first, segment) && match_segments(path_rest, rest),
+            None => false,
+        },
+    }
+}
+
+// Iterative wildcard match with single-star backtracking
+fn match_segment(text: &str, pattern: &str) -> bool {
+    let text: Vec<char> = text.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    let (mut t, mut p) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            t += 1;
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
@@ -0,0 +1,46 @@
+// String comparisons on the evaluation hot path (`==` on strings, `in`
+// and `contains` over role and group lists). Builds with the wasm
+// `simd128` target feature compare 16 bytes per step; other builds use
+// the scalar comparison. wasm has no runtime feature detection, so the
+// host picks the SIMD or scalar module when loading (see build.sh).
+
+pub const ENABLED: bool = cfg!(all(target_arch = "wasm32", target_feature = "simd128"));
+
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+pub fn bytes_eq(a: &[u8], b: &[u8]) -> bool {
+    use core::arch::wasm32::{i8x16_all_true, i8x16_eq, v128, v128_load};
+
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut chunks_a = a.chunks_exact(16);
+    let mut chunks_b = b.chunks_exact(16);
+    for (x, y) in (&mut chunks_a).zip(&mut chunks_b) {
+        // SAFETY: both chunks are exactly 16 bytes; v128_load allows
+        // unaligned reads
+        let equal = unsafe {
+            let x = v128_load(x.as_ptr() as *const v128);
+            let y = v128_load(y.as_ptr() as *const v128);
+            i8x16_all_true(i8x16_eq(x, y))
+        };
+        if !equal {
+            return false;
+        }
+    }
+    chunks_a.remainder() == chunks_b.remainder()
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_feature = "simd128")))]
+pub fn bytes_eq(a: &[u8], b: &[u8]) -> bool {
+    a == b
+}
+
+pub fn str_eq(a: &str, b: &str) -> bool {
+    bytes_eq(a.as_bytes(), b.as_bytes())
+}
+
+// Whether any string in `items` equals `needle`. Lengths are compared
+// first so most mismatches never touch the bytes.
+pub fn contains_str<'a>(items: impl IntoIterator<Item = &'a str>, needle: &str) -> bool {
+    items.into_iter().any(|item| item.len() == needle.len() && str_eq(item, needle))
+}
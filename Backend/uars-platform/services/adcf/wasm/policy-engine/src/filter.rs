@@ -0,0 +1,404 @@
+use wasm_bindgen::prelude::*;
+use serde::Serialize;
+use serde_json::{json, Value as JsonValue};
+
+use crate::attributes::context_field;
+use crate::error::{to_json, PolicyEngineError};
+use crate::expr::{BinaryOp, Expr, UnaryOp, Value};
+use crate::vocabulary::Effect;
+use crate::PolicyEngine;
+
+const DIALECTS: &[&str] = &["postgres", "mysql", "sqlite", "ast"];
+
+// Row filter over resource columns. Serialized as the generic AST
+// (`{"op": "and", "args": [...]}`, `{"op": "compare", "operator": "=",
+// "column": "owner", "value": "alice"}`, ...) for hosts that build queries
+// themselves.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Filter {
+    True,
+    False,
+    And { args: Vec<Filter> },
+    Or { args: Vec<Filter> },
+    Not { arg: Box<Filter> },
+    // column <operator> value
+    Compare { operator: &'static str, column: String, value: JsonValue },
+    // column <operator> column
+    Columns { operator: &'static str, left: String, right: String },
+    IsNull { column: String },
+    In { column: String, values: Vec<JsonValue> },
+    // Substring match
+    Contains { column: String, substring: String },
+}
+
+fn and(args: Vec<Filter>) -> Filter {
+    if args.contains(&Filter::False) {
+        return Filter::False;
+    }
+    let mut args: Vec<Filter> = args
+        .into_iter()
+        .filter(|arg| *arg != Filter::True)
+        .flat_map(|arg| match arg {
+            Filter::And { args } => args,
+            other => vec![other],
+        })
+        .collect();
+    match args.len() {
+        0 => Filter::True,
+        1 => args.remove(0),
+        _ => Filter::And { args },
+    }
+}
+
+fn or(args: Vec<Filter>) -> Filter {
+    if args.contains(&Filter::True) {
+        return Filter::True;
+    }
+    let mut args: Vec<Filter> = args
+        .into_iter()
+        .filter(|arg| *arg != Filter::False)
+        .flat_map(|arg| match arg {
+            Filter::Or { args } => args,
+            other => vec![other],
+        })
+        .collect();
+    match args.len() {
+        0 => Filter::False,
+        1 => args.remove(0),
+        _ => Filter::Or { args },
+    }
+}
+
+fn not(arg: Filter) -> Filter {
+    match arg {
+        Filter::True => Filter::False,
+        Filter::False => Filter::True,
+        Filter::Not { arg } => *arg,
+        other => Filter::Not { arg: Box::new(other) },
+    }
+}
+
+enum Operand {
+    // Column name and the attribute path it came from
+    Column(String, Vec<String>),
+    Value(Value),
+}
+
+// Resource fields map to columns without their `resource_` prefix
+// (owner, classification, ...); resource_attributes keys map to a column
+// of the same name
+fn column(path: &[String]) -> Result<String, String> {
+    let segments: Vec<&str> = path.iter().map(String::as_str).collect();
+    match context_field(&segments) {
+        Some("resource_attributes") => match segments.last() {
+            Some(key) if segments.len() >= 2 && !matches!(*key, "attributes" | "resource_attributes") => Ok(key.to_string()),
+            _ => Err(format!("'{}' does not name a single resource attribute", path.join("."))),
+        },
+        Some(field) if field.starts_with("resource_") => Ok(field.trim_start_matches("resource_").to_string()),
+        _ => Err(format!("'{}' is not a resource attribute and is not fixed by the subject context", path.join("."))),
+    }
+}
+
+fn scalar(value: &Value) -> Result<JsonValue, String> {
+    match value {
+        Value::String(s) => Ok(json!(s)),
+        Value::Number(n) if n.is_finite() => Ok(json!(n)),
+        Value::Bool(b) => Ok(json!(b)),
+        other => Err(format!("a {} has no SQL form", other.type_name())),
+    }
+}
+
+fn flip(operator: &'static str) -> &'static str {
+    match operator {
+        "<" => ">",
+        "<=" => ">=",
+        ">" => "<",
+        ">=" => "<=",
+        other => other,
+    }
+}
+
+struct Translator<'a> {
+    engine: &'a PolicyEngine,
+}
+
+impl Translator<'_> {
+    fn operand(&self, expr: &Expr) -> Result<Operand, String> {
+        match expr {
+            Expr::Attribute(path) => Ok(Operand::Column(column(path)?, path.clone())),
+            Expr::Literal(value) => Ok(Operand::Value(value.clone())),
+            Expr::List(items) => items
+                .iter()
+                .map(|item| match item {
+                    Expr::Literal(value) => Ok(value.clone()),
+                    other => Err(format!("`{}` has no SQL form", other)),
+                })
+                .collect::<Result<_, _>>()
+                .map(|items| Operand::Value(Value::List(items))),
+            other => Err(format!("`{}` has no SQL form", other)),
+        }
+    }
+
+    // The residual expression as a filter. Only comparisons between
+    // resource columns and literals translate; function calls and unfixed
+    // subject attributes do not, and each such comparison is replaced by
+    // `assume` (flipped under `!`) with the reason kept in `failures`.
+    fn filter(&self, expr: &Expr, assume: bool, failures: &mut Vec<String>) -> Filter {
+        let translated = match expr {
+            Expr::Literal(Value::Bool(b)) => Ok(if *b { Filter::True } else { Filter::False }),
+            Expr::Literal(Value::Null) => Ok(Filter::False),
+            Expr::Attribute(path) => column(path).map(|column| Filter::Compare { operator: "=", column, value: json!(true) }),
+            Expr::Unary(UnaryOp::Not, operand) => Ok(not(self.filter(operand, !assume, failures))),
+            Expr::Binary(BinaryOp::And, left, right) => {
+                Ok(and(vec![self.filter(left, assume, failures), self.filter(right, assume, failures)]))
+            }
+            Expr::Binary(BinaryOp::Or, left, right) => {
+                Ok(or(vec![self.filter(left, assume, failures), self.filter(right, assume, failures)]))
+            }
+            Expr::Binary(op, left, right) => {
+                self.operand(left).and_then(|left| self.operand(right).and_then(|right| self.comparison(*op, left, right)))
+            }
+            other => Err(format!("`{}` has no SQL form", other)),
+        };
+        translated.unwrap_or_else(|reason| {
+            failures.push(reason);
+            if assume { Filter::True } else { Filter::False }
+        })
+    }
+
+    fn comparison(&self, op: BinaryOp, left: Operand, right: Operand) -> Result<Filter, String> {
+        let operator = match op {
+            BinaryOp::Eq => "=",
+            BinaryOp::Ne => "<>",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+            BinaryOp::In => return self.membership(right, left),
+            BinaryOp::Contains => return self.membership(left, right),
+            other => return Err(format!("'{}' has no SQL form", other.symbol())),
+        };
+        match (left, right) {
+            (Operand::Column(left, left_path), Operand::Column(right, right_path)) => {
+                let lattice = self.engine.lattices.levels(&left_path).or(self.engine.lattices.levels(&right_path));
+                if lattice.is_some() && !matches!(operator, "=" | "<>") {
+                    return Err("ordering between two lattice attributes has no SQL form".to_string());
+                }
+                Ok(Filter::Columns { operator, left, right })
+            }
+            (Operand::Column(column, path), Operand::Value(value)) => self.compare(operator, column, &path, value),
+            (Operand::Value(value), Operand::Column(column, path)) => self.compare(flip(operator), column, &path, value),
+            (Operand::Value(_), Operand::Value(_)) => Err("comparison between values did not fold".to_string()),
+        }
+    }
+
+    fn compare(&self, operator: &'static str, column: String, path: &[String], value: Value) -> Result<Filter, String> {
+        match (operator, &value) {
+            ("=", Value::Null) => return Ok(Filter::IsNull { column }),
+            ("<>", Value::Null) => return Ok(not(Filter::IsNull { column })),
+            ("=" | "<>", _) => return Ok(Filter::Compare { operator, column, value: scalar(&value)? }),
+            _ => {}
+        }
+        // Ordered attributes become the set of levels on the right side
+        if let (Some(levels), Value::String(level)) = (self.engine.lattices.levels(path), &value) {
+            let rank = levels
+                .iter()
+                .position(|candidate| candidate == level)
+                .ok_or_else(|| format!("'{}' is not a level of {}", level, path.join(".")))?;
+            let values = levels
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| match operator {
+                    "<" => *index < rank,
+                    "<=" => *index <= rank,
+                    ">" => *index > rank,
+                    _ => *index >= rank,
+                })
+                .map(|(_, level)| json!(level))
+                .collect();
+            return Ok(Filter::In { column, values });
+        }
+        match value {
+            Value::Number(_) => Ok(Filter::Compare { operator, column, value: scalar(&value)? }),
+            other => Err(format!("ordering a {} has no SQL form", other.type_name())),
+        }
+    }
+
+    // `haystack contains needle`
+    fn membership(&self, haystack: Operand, needle: Operand) -> Result<Filter, String> {
+        match (haystack, needle) {
+            (Operand::Value(Value::List(items)), Operand::Column(column, _)) => {
+                let values = items.iter().filter(|item| **item != Value::Null).map(scalar).collect::<Result<Vec<_>, _>>()?;
+                Ok(if values.is_empty() { Filter::False } else { Filter::In { column, values } })
+            }
+            (Operand::Column(column, _), Operand::Value(Value::String(substring))) => Ok(Filter::Contains { column, substring }),
+            _ => Err("only list membership of a column and substring tests on a column have an SQL form".to_string()),
+        }
+    }
+}
+
+// Renders a filter for one SQL dialect, collecting bound parameters
+struct Sql<'a> {
+    dialect: &'a str,
+    params: Vec<JsonValue>,
+}
+
+impl Sql<'_> {
+    fn identifier(&self, name: &str) -> String {
+        match self.dialect {
+            "mysql" => format!("`{}`", name.replace('`', "``")),
+            _ => format!("\"{}\"", name.replace('"', "\"\"")),
+        }
+    }
+
+    fn param(&mut self, value: JsonValue) -> String {
+        self.params.push(value);
+        match self.dialect {
+            "postgres" => format!("${}", self.params.len()),
+            _ => "?".to_string(),
+        }
+    }
+
+    // MySQL compares strings under the column collation, usually case
+    // insensitive; policies compare exactly
+    fn text_column(&self, column: &str, value: &JsonValue) -> String {
+        match (self.dialect, value) {
+            ("mysql", JsonValue::String(_)) => format!("BINARY {}", self.identifier(column)),
+            _ => self.identifier(column),
+        }
+    }
+
+    fn render(&mut self, filter: &Filter) -> String {
+        match filter {
+            Filter::True => "TRUE".to_string(),
+            Filter::False => "FALSE".to_string(),
+            Filter::And { args } => self.join(args, " AND "),
+            Filter::Or { args } => self.join(args, " OR "),
+            Filter::Not { arg } => format!("NOT {}", self.render(arg)),
+            Filter::Compare { operator, column, value } => {
+                let column = self.text_column(column, value);
+                let value = match value {
+                    JsonValue::Bool(b) => b.to_string().to_uppercase(),
+                    other => self.param(other.clone()),
+                };
+                format!("{} {} {}", column, operator, value)
+            }
+            Filter::Columns { operator, left, right } => {
+                format!("{} {} {}", self.identifier(left), operator, self.identifier(right))
+            }
+            Filter::IsNull { column } => format!("{} IS NULL", self.identifier(column)),
+            Filter::In { column, values } => {
+                let column = match values.first() {
+                    Some(first) => self.text_column(column, first),
+                    None => self.identifier(column),
+                };
+                let values: Vec<String> = values.iter().map(|value| self.param(value.clone())).collect();
+                format!("{} IN ({})", column, values.join(", "))
+            }
+            Filter::Contains { column, substring } => {
+                let pattern = json!(format!("%{}%", substring.replace('!', "!!").replace('%', "!%").replace('_', "!_")));
+                let column = self.text_column(column, &pattern);
+                format!("{} LIKE {} ESCAPE '!'", column, self.param(pattern))
+            }
+        }
+    }
+
+    fn join(&mut self, args: &[Filter], separator: &str) -> String {
+        let parts: Vec<String> = args.iter().map(|arg| self.render(arg)).collect();
+        format!("({})", parts.join(separator))
+    }
+}
+
+// A target or rule whose residual had no SQL form
+#[derive(Debug, Serialize)]
+struct Untranslated {
+    policy_id: String,
+    rule_id: Option<String>,
+    reason: String,
+}
+
+fn report(untranslated: &mut Vec<Untranslated>, policy_id: &str, rule_id: Option<&String>, failures: Vec<String>) {
+    untranslated.extend(failures.into_iter().map(|reason| Untranslated {
+        policy_id: policy_id.to_string(),
+        rule_id: rule_id.cloned(),
+        reason,
+    }));
+}
+
+#[derive(Debug, Serialize)]
+struct RowFilter {
+    dialect: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sql: Option<String>,
+    params: Vec<JsonValue>,
+    filter: Filter,
+    untranslated: Vec<Untranslated>,
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Row-level security: a WHERE clause selecting the rows (resources) a
+    // subject is permitted, consistent with evaluate. The subject context
+    // fixes the subject's fields plus usually operation and resource_type
+    // (the policy target of the query), as for specialize; the remaining
+    // resource fields become columns. `dialect` is postgres (the default),
+    // mysql, sqlite, or ast for the filter tree alone. Values are bound as
+    // parameters, never inlined.
+    //
+    // A row passes when some PERMIT rule applies and no other rule does,
+    // which is exact under deny-overrides and stricter under the other
+    // algorithms. Comparisons with no SQL form (function calls, subject
+    // fields left unfixed) are reported in `untranslated` and assumed to
+    // keep rows out: false where they would let a PERMIT apply, true
+    // where they would let any other rule apply. The filter therefore
+    // never returns a row the engine would not permit.
+    #[wasm_bindgen]
+    pub fn to_filter(&self, subject_context_json: &str, dialect: Option<String>) -> Result<String, JsValue> {
+        let dialect = dialect.unwrap_or_else(|| "postgres".to_string());
+        if !DIALECTS.contains(&dialect.as_str()) {
+            return Err(PolicyEngineError::unsupported_format(format!("Unknown filter dialect '{}'", dialect))
+                .with_details(json!({ "dialect": dialect, "expected": DIALECTS }))
+                .logged()
+                .into());
+        }
+        let (fixed, context) = self.fixed_fields(subject_context_json)?;
+        let translator = Translator { engine: self };
+
+        let mut permits = Vec::new();
+        let mut denies = Vec::new();
+        let mut untranslated = Vec::new();
+        for compiled in self.policies.iter().filter(|compiled| self.policy_in_effect(compiled)) {
+            let policy_id = &compiled.policy.id;
+            let target = self.residual(&compiled.target, &context, &fixed);
+            // The target guards rules of both kinds, so each gets its own
+            // translation
+            let mut failures = Vec::new();
+            let permit_target = translator.filter(&target, false, &mut failures);
+            let deny_target = translator.filter(&target, true, &mut Vec::new());
+            if permit_target == Filter::False && deny_target == Filter::False {
+                continue;
+            }
+            report(&mut untranslated, policy_id, None, failures);
+            for (rule, condition) in compiled.rules().filter(|(rule, _)| self.in_deployment(&rule.environments)) {
+                let permit = rule.effect == Effect::Permit;
+                let mut failures = Vec::new();
+                let condition = translator.filter(&self.residual(condition, &context, &fixed), !permit, &mut failures);
+                report(&mut untranslated, policy_id, Some(&rule.id), failures);
+                if permit {
+                    permits.push(and(vec![permit_target.clone(), condition]));
+                } else {
+                    denies.push(and(vec![deny_target.clone(), condition]));
+                }
+            }
+        }
+
+        let filter = and(vec![or(permits), not(or(denies))]);
+        let mut sql = Sql { dialect: &dialect, params: Vec::new() };
+        let clause = (dialect != "ast").then(|| sql.render(&filter));
+        if self.debug_mode {
+            console_log!("Row filter ({}): {} untranslated residuals", dialect, untranslated.len());
+        }
+        Ok(to_json(&RowFilter { dialect: dialect.clone(), sql: clause, params: sql.params, filter, untranslated })?)
+    }
+}
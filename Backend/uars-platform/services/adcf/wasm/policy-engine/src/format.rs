@@ -0,0 +1,148 @@
+use serde::de::DeserializeOwned;
+use serde_json::{json, Map, Value};
+
+use crate::diagnostics::json_diagnostic;
+use crate::error::PolicyEngineError;
+use crate::schema;
+
+// Policy document format this engine writes and reads. A policy without
+// `format_version` is format 1; older formats are migrated on load, newer
+// ones are rejected with UNSUPPORTED_FORMAT so a stale (cached) engine
+// build fails clearly instead of misreading fields it does not know.
+//
+//   1  original layout, before metadata (labels, owner, tags, enabled),
+//      definitions, rule references and rule challenge/approval/break-glass
+//   2  `format_version`; every field above is part of the format
+//   3  structured advice objects {id, severity, message, attributes}
+//      instead of advice strings; policy `priority`, rule `remediation`,
+//      `fulfill_on` on policy obligations and advice
+pub const POLICY_FORMAT_VERSION: u32 = 3;
+pub const LEGACY_FORMAT_VERSION: u32 = 1;
+
+pub fn current_format_version() -> u32 {
+    POLICY_FORMAT_VERSION
+}
+
+fn fill(object: &mut Map<String, Value>, key: &str, default: Value) {
+    object.entry(key.to_string()).or_insert(default);
+}
+
+// 1 -> 2: fields added since format 1 take their defaults
+fn migrate_v1(policy: &mut Map<String, Value>) {
+    fill(policy, "definitions", json!({}));
+    fill(policy, "rule_refs", json!([]));
+    fill(policy, "labels", json!({}));
+    fill(policy, "tags", json!([]));
+    fill(policy, "enabled", json!(true));
+    if let Some(Value::Array(rules)) = policy.get_mut("rules") {
+        for rule in rules.iter_mut().filter_map(Value::as_object_mut) {
+            fill(rule, "break_glass", json!(false));
+        }
+    }
+}
+
+// 2 -> 3: advice strings become info advice with that id
+fn migrate_v2(policy: &mut Map<String, Value>) {
+    fn structure(advice: Option<&mut Value>) {
+        if let Some(Value::Array(items)) = advice {
+            for item in items.iter_mut() {
+                if let Value::String(id) = item {
+                    *item = json!({ "id": id, "severity": "info" });
+                }
+            }
+        }
+    }
+    structure(policy.get_mut("advice"));
+    if let Some(Value::Array(rules)) = policy.get_mut("rules") {
+        for rule in rules.iter_mut().filter_map(Value::as_object_mut) {
+            structure(rule.get_mut("advice"));
+        }
+    }
+}
+
+// Brings one policy object up to the current format. Returns whether it
+// changed.
+fn migrate_policy(policy: &mut Map<String, Value>) -> Result<bool, PolicyEngineError> {
+    let id = policy.get("id").and_then(Value::as_str).unwrap_or("<unnamed>").to_string();
+    let version = match policy.get("format_version") {
+        None | Some(Value::Null) => LEGACY_FORMAT_VERSION,
+        Some(value) => value
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .filter(|version| *version >= LEGACY_FORMAT_VERSION)
+            .ok_or_else(|| {
+                PolicyEngineError::parse(format!("Policy '{}' has an invalid format_version {}", id, value))
+                    .with_details(json!({ "policy_id": id, "format_version": value }))
+            })?,
+    };
+
+    if version > POLICY_FORMAT_VERSION {
+        return Err(PolicyEngineError::unsupported_format(format!(
+            "Engine too old: policy '{}' needs format >= {}, this engine reads up to format {}",
+            id, version, POLICY_FORMAT_VERSION
+        ))
+        .with_details(json!({
+            "policy_id": id,
+            "format_version": version,
+            "supported_format_version": POLICY_FORMAT_VERSION,
+        })));
+    }
+    if version == POLICY_FORMAT_VERSION {
+        return Ok(false);
+    }
+
+    if version < 2 {
+        migrate_v1(policy);
+    }
+    if version < 3 {
+        migrate_v2(policy);
+    }
+    policy.insert("format_version".to_string(), json!(POLICY_FORMAT_VERSION));
+    Ok(true)
+}
+
+// A policy object or an array of them
+fn migrate_document(document: &mut Value) -> Result<bool, PolicyEngineError> {
+    match document {
+        Value::Object(policy) => migrate_policy(policy),
+        Value::Array(policies) => {
+            let mut changed = false;
+            for policy in policies.iter_mut().filter_map(Value::as_object_mut) {
+                changed |= migrate_policy(policy)?;
+            }
+            Ok(changed)
+        }
+        _ => Ok(false),
+    }
+}
+
+// JSON pointers of fields in policy JSON the current format does not
+// have, once migrated (see schema::unknown_policy_fields)
+pub fn unknown_policy_fields(json: &str) -> Vec<String> {
+    let Ok(mut document) = serde_json::from_str::<Value>(json) else {
+        return Vec::new();
+    };
+    match migrate_document(&mut document) {
+        Ok(_) => schema::unknown_policy_fields(&document),
+        Err(_) => Vec::new(),
+    }
+}
+
+// Parses policy JSON (a Policy or Vec<Policy>) through the migration
+// layer. `what` names the input in error messages ("policies", "staged
+// policies"). Current-format documents are deserialized from the text so
+// shape errors keep their line and column.
+pub fn parse_policy_json<T: DeserializeOwned>(json: &str, what: &str) -> Result<T, PolicyEngineError> {
+    let parse_error = |e: serde_json::Error| {
+        PolicyEngineError::parse(format!("Failed to parse {}: {}", what, e))
+            .with_details(json_diagnostic(json, &e).details())
+    };
+
+    let mut document: Value = serde_json::from_str(json).map_err(parse_error)?;
+    if migrate_document(&mut document)? {
+        serde_json::from_value(document)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse {}: {}", what, e)))
+    } else {
+        serde_json::from_str(json).map_err(parse_error)
+    }
+}
@@ -0,0 +1,96 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::clock;
+use crate::decision::Decision;
+use crate::error::{to_json, PolicyEngineError};
+use crate::validity::parse_valid_until;
+use crate::{PolicyEngine, PolicyResult};
+
+// How long PEPs may reuse a decision. A decision that read a volatile
+// context field may be reused for that field's TTL at most (the shortest
+// when it read several), and must be re-evaluated once any of them
+// changes; one that read none gets `default_ttl_seconds`. Both are
+// capped by a PERMIT's valid_until.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FreshnessConfig {
+    // Unset: decisions without volatile inputs carry no TTL
+    pub default_ttl_seconds: Option<f64>,
+    // Top-level context field to seconds
+    pub volatile: BTreeMap<String, f64>,
+}
+
+impl Default for FreshnessConfig {
+    fn default() -> Self {
+        FreshnessConfig {
+            default_ttl_seconds: Some(300.0),
+            volatile: BTreeMap::from([
+                ("risk_score".to_string(), 60.0),
+                ("session_age".to_string(), 60.0),
+                ("timestamp".to_string(), 60.0),
+                ("threat_level".to_string(), 300.0),
+            ]),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    #[wasm_bindgen]
+    pub fn set_decision_freshness(&mut self, config_json: &str) -> Result<(), JsValue> {
+        let config: FreshnessConfig = serde_json::from_str(config_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse decision freshness: {}", e)).logged())?;
+        let invalid = config
+            .volatile
+            .iter()
+            .map(|(field, ttl)| (field.as_str(), *ttl))
+            .chain(config.default_ttl_seconds.map(|ttl| ("default_ttl_seconds", ttl)))
+            .find(|(_, ttl)| !ttl.is_finite() || *ttl < 0.0);
+        if let Some((field, ttl)) = invalid {
+            return Err(PolicyEngineError::validation(format!("TTL for '{}' must be a non-negative number of seconds", field))
+                .with_details(json!({ "field": field, "value": ttl }))
+                .logged()
+                .into());
+        }
+        self.freshness = config;
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn get_decision_freshness(&self) -> Result<String, JsValue> {
+        Ok(to_json(&self.freshness)?)
+    }
+}
+
+impl PolicyEngine {
+    // Sets ttl_seconds and revalidate_on from the attributes the
+    // evaluation read. INDETERMINATE is never reusable.
+    pub(crate) fn apply_freshness(&self, result: &mut PolicyResult, reads: &BTreeSet<String>) {
+        let config = &self.freshness;
+        let fields_read: BTreeSet<&str> = reads.iter().map(|path| path.split('.').next().unwrap_or(path)).collect();
+        let volatile: Vec<(&String, f64)> = config
+            .volatile
+            .iter()
+            .filter(|(field, _)| fields_read.contains(field.as_str()))
+            .map(|(field, ttl)| (field, *ttl))
+            .collect();
+        result.revalidate_on = volatile.iter().map(|(field, _)| field.to_string()).collect();
+        result.ttl_seconds = match result.decision {
+            Decision::Indeterminate => Some(0.0),
+            _ if volatile.is_empty() => config.default_ttl_seconds,
+            _ => volatile.iter().map(|(_, ttl)| *ttl).reduce(f64::min),
+        };
+    }
+
+    // A windowed PERMIT may not be reused past its valid_until
+    pub(crate) fn cap_ttl_to_validity(&self, result: &mut PolicyResult) {
+        let Some(valid_until) = parse_valid_until(result) else {
+            return;
+        };
+        let remaining = ((valid_until - clock::now()).num_milliseconds() as f64 / 1000.0).max(0.0);
+        result.ttl_seconds = Some(result.ttl_seconds.map_or(remaining, |ttl| ttl.min(remaining)));
+    }
+}
@@ -0,0 +1,333 @@
+use std::cell::{OnceCell, RefCell};
+use std::collections::{BTreeSet, HashMap};
+
+use crate::clock;
+use crate::attributes::{canonical_path, context_field};
+use crate::baseline::Dimension;
+use crate::confidence::ConditionTrace;
+use crate::environment::format_time;
+use crate::history::{self, Aggregate};
+use crate::scoring;
+use crate::usage::{self, UsageRecorder};
+use crate::velocity;
+use crate::derived::DERIVED_ROOT;
+use crate::expr::{self, Environment, ExprError, Value};
+use crate::{state_key, PolicyContext, PolicyEngine};
+
+// Evaluation environment for a single request: attributes come from the
+// context, built-in functions may consult engine state (namespaced by
+// tenant when evaluating for one).
+pub struct EvalScope<'a> {
+    pub engine: &'a PolicyEngine,
+    pub context: &'a PolicyContext,
+    pub tenant: Option<&'a str>,
+    // Resolved attributes. Targets and conditions across policies mostly
+    // read the same few attributes, so each is resolved once per request.
+    attributes: RefCell<HashMap<Vec<String>, Option<Value>>>,
+    // Reads of the rule condition being evaluated, under a confidence model
+    trace: RefCell<Option<ConditionTrace>>,
+    // Scorer output behind `model.*`, computed on first use
+    model: OnceCell<Option<Value>>,
+    // Derived attributes computed so far (see derived.rs)
+    derived: RefCell<HashMap<String, Value>>,
+    // Attributes read per policy, while attribute usage is tracked
+    usage: RefCell<Option<UsageRecorder>>,
+    // Attributes (canonical paths) read, directly or through functions
+    // and the scorer; behind ttl_seconds and dependencies
+    reads: RefCell<BTreeSet<String>>,
+}
+
+impl<'a> EvalScope<'a> {
+    pub fn new(engine: &'a PolicyEngine, context: &'a PolicyContext, tenant: Option<&'a str>) -> EvalScope<'a> {
+        EvalScope {
+            engine,
+            context,
+            tenant,
+            attributes: RefCell::new(HashMap::new()),
+            trace: RefCell::new(None),
+            model: OnceCell::new(),
+            derived: RefCell::new(HashMap::new()),
+            usage: RefCell::new(None),
+            reads: RefCell::new(BTreeSet::new()),
+        }
+    }
+
+    pub fn begin_trace(&self) {
+        *self.trace.borrow_mut() = Some(ConditionTrace::default());
+    }
+
+    pub fn take_trace(&self) -> Option<ConditionTrace> {
+        self.trace.borrow_mut().take()
+    }
+
+    pub fn track_usage(&self) {
+        *self.usage.borrow_mut() = Some(UsageRecorder::default());
+    }
+
+    // Applies `update` to the usage recorder, if usage is tracked
+    pub fn with_usage(&self, update: impl FnOnce(&mut UsageRecorder)) {
+        if let Some(recorder) = self.usage.borrow_mut().as_mut() {
+            update(recorder);
+        }
+    }
+
+    pub fn take_usage(&self) -> Option<UsageRecorder> {
+        self.usage.borrow_mut().take()
+    }
+
+    pub fn take_reads(&self) -> BTreeSet<String> {
+        self.reads.take()
+    }
+
+    fn read(&self, segments: &[&str]) {
+        if context_field(segments).is_some() {
+            self.reads.borrow_mut().insert(canonical_path(segments));
+        }
+    }
+
+    pub fn user_key(&self) -> String {
+        state_key(self.tenant, &self.context.user_id)
+    }
+
+    // `derived.<name>` (or a key within it), computed on first read
+    fn derived_value(&self, path: &[String]) -> Result<Value, ExprError> {
+        let Some((name, rest)) = path.split_first() else {
+            return Err(ExprError::new("'derived' needs an attribute name"));
+        };
+        let cached = self.derived.borrow().get(name).cloned();
+        let value = match cached {
+            Some(value) => value,
+            None => {
+                let attribute = self
+                    .engine
+                    .derived
+                    .get(name)
+                    .ok_or_else(|| ExprError::new(format!("Unknown derived attribute '{}'", name)))?;
+                let value = expr::evaluate(&attribute.expression, self)
+                    .map_err(|e| ExprError::new(format!("derived.{}: {}", name, e)))?;
+                self.derived.borrow_mut().insert(name.clone(), value.clone());
+                value
+            }
+        };
+        Ok(scoring::lookup(&value, rest).unwrap_or(Value::Null))
+    }
+}
+
+impl Environment for EvalScope<'_> {
+    fn resolve(&self, path: &[String]) -> Option<Value> {
+        let cached = self.attributes.borrow().get(path).cloned();
+        let value = cached.unwrap_or_else(|| {
+            let value = match path.split_first() {
+                Some((root, rest)) if root == "model" => {
+                    for feature in self.engine.model_features() {
+                        self.read(&feature.split('.').collect::<Vec<_>>());
+                    }
+                    let output = self.model.get_or_init(|| self.engine.model_output(self.context));
+                    output.as_ref().and_then(|output| scoring::lookup(output, rest))
+                }
+                _ => {
+                    self.read(&path.iter().map(String::as_str).collect::<Vec<_>>());
+                    self.context.attribute(path)
+                }
+            };
+            self.attributes.borrow_mut().insert(path.to_vec(), value.clone());
+            value
+        });
+        if let Some(trace) = self.trace.borrow_mut().as_mut() {
+            trace.read(path, !matches!(value, None | Some(Value::Null)));
+        }
+        self.with_usage(|recorder| match path.split_first() {
+            // The scorer reads its features
+            Some((root, _)) if root == "model" => {
+                for feature in self.engine.model_features() {
+                    recorder.read(&feature.split('.').collect::<Vec<_>>());
+                }
+            }
+            _ => recorder.read(&path.iter().map(String::as_str).collect::<Vec<_>>()),
+        });
+        value
+    }
+
+    fn call(&self, name: &str, args: &[Value]) -> Result<Value, ExprError> {
+        for attribute in usage::function_reads(name) {
+            self.read(&[attribute]);
+        }
+        if let ("has", Some(Value::String(path))) = (name, args.first()) {
+            self.read(&path.split('.').collect::<Vec<_>>());
+        }
+        self.with_usage(|recorder| {
+            for attribute in usage::function_reads(name) {
+                recorder.read(&[attribute]);
+            }
+            if let ("has", Some(Value::String(path))) = (name, args.first()) {
+                recorder.read(&path.split('.').collect::<Vec<_>>());
+            }
+        });
+        match name {
+            // Travel history is engine state a replayed record cannot carry
+            "impossible_travel" if self.engine.deterministic.get() => Err(ExprError::new(
+                "impossible_travel() depends on travel history and is unavailable in deterministic mode",
+            )),
+            #[cfg(not(feature = "geo"))]
+            "impossible_travel" => Err(ExprError::new("impossible_travel() requires the geo feature")),
+            #[cfg(feature = "geo")]
+            "impossible_travel" => {
+                let threshold = number_arg(name, args, 0)?;
+                let travel = self.engine.geo.impossible_travel(&self.user_key(), self.context, threshold);
+                Ok(Value::Bool(travel))
+            }
+            // Baselines are engine state too
+            "unusual_location" | "unusual_time" | "unusual_resource" if self.engine.deterministic.get() => Err(
+                ExprError::new(format!("{}() depends on user baselines and is unavailable in deterministic mode", name)),
+            ),
+            "unusual_location" | "unusual_time" | "unusual_resource" => {
+                if !args.is_empty() {
+                    return Err(ExprError::new(format!("{}() takes no arguments", name)));
+                }
+                let dimension = Dimension::for_function(name)
+                    .ok_or_else(|| ExprError::new(format!("Unknown function '{}'", name)))?;
+                Ok(Value::Bool(self.engine.baselines.unusual(&self.user_key(), dimension, self.context)))
+            }
+            // Attribute history is engine state too
+            "avg_over" | "min_over" | "max_over" | "sum_over" | "count_over" if self.engine.deterministic.get() => Err(
+                ExprError::new(format!("{}() depends on attribute history and is unavailable in deterministic mode", name)),
+            ),
+            "avg_over" | "min_over" | "max_over" | "sum_over" | "count_over" => {
+                let attribute = string_arg(name, args, 0)?;
+                let window = args.get(1).ok_or_else(|| ExprError::new(format!("{}() missing argument 2", name)))?;
+                let seconds = history::window_seconds(name, window)?;
+                let aggregate = Aggregate::for_function(name)
+                    .ok_or_else(|| ExprError::new(format!("Unknown function '{}'", name)))?;
+                Ok(self.engine.history.aggregate(&self.user_key(), attribute, seconds, self.context.timestamp, aggregate))
+            }
+            // Permit counters are engine state too
+            "count_operations" if self.engine.deterministic.get() => Err(ExprError::new(
+                "count_operations() depends on operation counters and is unavailable in deterministic mode",
+            )),
+            // count_operations(user, operation, window): the user's permits
+            // of the operation ("*" for any) in the window up to now
+            "count_operations" => {
+                let user = string_arg(name, args, 0)?;
+                let operation = string_arg(name, args, 1)?;
+                let window = velocity::window_arg(name, args, 2)?;
+                let user_key = state_key(self.tenant, user);
+                let count = self.engine.operation_counters.count(&user_key, operation, window, clock::now());
+                Ok(Value::Number(count as f64))
+            }
+            // is_business_hours(region?), is_holiday(region?) and
+            // in_window(name): the request timestamp against the calendar
+            // (see calendar.rs)
+            "is_business_hours" | "is_holiday" => {
+                let region = match args {
+                    [] => None,
+                    [_] => Some(string_arg(name, args, 0)?),
+                    _ => return Err(ExprError::new(format!("{}() takes at most one region", name))),
+                };
+                Ok(Value::Bool(self.engine.calendar_function(name, region, self.context.timestamp)?))
+            }
+            "in_window" => {
+                let window = string_arg(name, args, 0)?;
+                Ok(Value::Bool(self.engine.in_window(window, self.context.timestamp)?))
+            }
+            "has_consent" if self.engine.deterministic.get() => Err(ExprError::new(
+                "has_consent() depends on recorded consent and is unavailable in deterministic mode",
+            )),
+            // has_consent(purpose?): whether the requesting user consented
+            // to the purpose, the declared intent_purpose when omitted, as
+            // of now by the engine clock (see consent.rs)
+            "has_consent" => {
+                let purpose = match args {
+                    [] => self.context.intent_purpose.as_deref().unwrap_or_default(),
+                    [_] => string_arg(name, args, 0)?,
+                    _ => return Err(ExprError::new("has_consent() takes at most one purpose")),
+                };
+                Ok(Value::Bool(self.engine.consents.has_consent(&self.user_key(), purpose, clock::now())))
+            }
+            "dominates" => match args {
+                [a, b] => Ok(Value::Bool(self.engine.lattices.security.dominates(a, b)?)),
+                _ => Err(ExprError::new("dominates() takes two security labels")),
+            },
+            // Delegation: whether the on-behalf-of chain holds, who is
+            // actually calling, and how many hops away they are
+            "delegation_valid" => Ok(Value::Bool(
+                !self.context.delegation.is_empty() && self.engine.validate_delegation(self.context, self.tenant).is_ok(),
+            )),
+            "acting_identity" => Ok(Value::String(self.engine.acting_identity(self.context).to_string())),
+            "delegation_depth" => Ok(Value::Number(self.context.delegation.len() as f64)),
+            // has("path"): the attribute was supplied, not defaulted
+            "has" => {
+                let path = string_arg(name, args, 0)?;
+                let segments: Vec<&str> = path.split('.').collect();
+                Ok(Value::Bool(self.context.has_attribute(&segments)))
+            }
+            // Deterministic evaluations are pinned to the context timestamp
+            // and cannot draw random values
+            "now" if self.engine.deterministic.get() => Ok(Value::String(format_time(self.context.timestamp))),
+            "now" => Ok(Value::String(format_time(self.engine.environment.now()))),
+            "random" | "uuid" if self.engine.deterministic.get() => Err(ExprError::new(format!(
+                "{}() is unavailable in deterministic mode",
+                name
+            ))),
+            "random" => Ok(Value::Number(self.engine.environment.random()?)),
+            "uuid" => Ok(Value::String(self.engine.environment.uuid()?)),
+            _ => self
+                .engine
+                .call_threat_function(name, args)
+                .unwrap_or_else(|| Err(ExprError::new(format!("Unknown function '{}'", name)))),
+        }
+    }
+
+    fn enter(&self, depth: usize) -> Result<(), ExprError> {
+        self.engine.charge_step(depth)
+    }
+
+    fn lattice_rank(&self, path: &[String], value: &str) -> Option<Result<usize, ExprError>> {
+        self.engine.lattices.rank(path, value)
+    }
+
+    fn derived(&self, path: &[String]) -> Option<Result<Value, ExprError>> {
+        match path.split_first() {
+            Some((root, rest)) if root == DERIVED_ROOT => Some(self.derived_value(rest)),
+            _ => None,
+        }
+    }
+
+    // Only rule conditions under a confidence model, and never once a
+    // limit has been hit
+    fn tolerates(&self, _error: &ExprError) -> bool {
+        self.trace.borrow().is_some() && !self.engine.limits_hit()
+    }
+
+    fn set_aside(&self, _error: &ExprError) {
+        if let Some(trace) = self.trace.borrow_mut().as_mut() {
+            trace.indeterminate();
+        }
+    }
+}
+
+#[cfg(feature = "geo")]
+fn number_arg(function: &str, args: &[Value], index: usize) -> Result<f64, ExprError> {
+    match args.get(index) {
+        Some(Value::Number(n)) => Ok(*n),
+        Some(other) => Err(ExprError::new(format!(
+            "{}() argument {} must be a number, found {}",
+            function,
+            index + 1,
+            other.type_name()
+        ))),
+        None => Err(ExprError::new(format!("{}() missing argument {}", function, index + 1))),
+    }
+}
+
+fn string_arg<'v>(function: &str, args: &'v [Value], index: usize) -> Result<&'v str, ExprError> {
+    match args.get(index) {
+        Some(Value::String(s)) => Ok(s),
+        Some(other) => Err(ExprError::new(format!(
+            "{}() argument {} must be a string, found {}",
+            function,
+            index + 1,
+            other.type_name()
+        ))),
+        None => Err(ExprError::new(format!("{}() missing argument {}", function, index + 1))),
+    }
+}
@@ -0,0 +1,258 @@
+#[cfg(feature = "fuzz")]
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::expr;
+use crate::functions::EvalScope;
+use crate::vocabulary::{self, DeviceTrust};
+use crate::{PolicyContext, PolicyEngine};
+
+// Deterministic generator of random (and sometimes malformed) policy
+// expressions and contexts, shared by `fuzz_expression` and the property
+// tests below. The parser must reject bad input with an error, never a
+// panic, and evaluation must be a pure function of expression and context.
+
+const ATTRIBUTES: &[&str] = &[
+    "user.roles",
+    "user.id",
+    "user.attributes.department",
+    "risk_score",
+    "mfa.verified",
+    "classification",
+    "session_age",
+    "device.trust",
+    "ip_country",
+    "intent.purpose",
+    "constraints.max_rows",
+    "unknown.attribute",
+];
+
+const FUNCTIONS: &[&str] = &["has", "impossible_travel", "missing_function"];
+
+const BINARY_OPS: &[&str] = &[
+    "&&", "||", "and", "or", "==", "!=", "<", "<=", ">", ">=", "in", "contains", "matches_path", "+", "-", "*", "/",
+];
+
+const NOISE: &[&str] = &["(", ")", "[", "]", ",", ".", "\"", "'", "\\", "!", "&", "|", "=", "é", "🔒", "\0", "1e309"];
+
+// SplitMix64: tiny, seedable and identical on every target
+pub struct FuzzRng(u64);
+
+impl FuzzRng {
+    pub fn new(seed: u64) -> FuzzRng {
+        FuzzRng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n.max(1) as u64) as usize
+    }
+
+    pub fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+
+    pub fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+}
+
+pub fn random_expression(rng: &mut FuzzRng, depth: usize) -> String {
+    if depth == 0 || rng.chance(30) {
+        return random_leaf(rng);
+    }
+
+    match rng.below(6) {
+        0 => format!("!{}", random_expression(rng, depth - 1)),
+        1 => format!("({})", random_expression(rng, depth - 1)),
+        2 => {
+            let items: Vec<String> = (0..rng.below(4)).map(|_| random_expression(rng, depth - 1)).collect();
+            format!("[{}]", items.join(", "))
+        }
+        3 => {
+            let name = rng.pick(FUNCTIONS);
+            let args: Vec<String> = (0..rng.below(3)).map(|_| random_expression(rng, depth - 1)).collect();
+            format!("{}({})", name, args.join(", "))
+        }
+        _ => {
+            let op = rng.pick(BINARY_OPS);
+            let left = random_expression(rng, depth - 1);
+            let right = random_expression(rng, depth - 1);
+            format!("{} {} {}", left, op, right)
+        }
+    }
+}
+
+fn random_leaf(rng: &mut FuzzRng) -> String {
+    match rng.below(7) {
+        0 => rng.pick(ATTRIBUTES).to_string(),
+        1 => format!("{}", rng.below(20) as f64 / 2.0),
+        2 => format!("\"{}\"", rng.pick(&["admin", "classified", "", "a\\\"b", "US"])),
+        3 => format!("'{}'", rng.pick(&["user.roles", "x", "\\n"])),
+        4 => rng.pick(&["true", "false", "null"]).to_string(),
+        5 => "-1".to_string(),
+        _ => rng.pick(ATTRIBUTES).to_string(),
+    }
+}
+
+// Inserts, deletes or replaces a few characters to produce malformed input
+pub fn mutate(rng: &mut FuzzRng, source: &str) -> String {
+    let mut chars: Vec<String> = source.chars().map(|c| c.to_string()).collect();
+    for _ in 0..=rng.below(3) {
+        let at = rng.below(chars.len() + 1);
+        match rng.below(3) {
+            0 => chars.insert(at, rng.pick(NOISE).to_string()),
+            1 if at < chars.len() => {
+                chars.remove(at);
+            }
+            _ if at < chars.len() => chars[at] = rng.pick(NOISE).to_string(),
+            _ => chars.push(rng.pick(NOISE).to_string()),
+        }
+    }
+    chars.concat()
+}
+
+pub fn random_context(rng: &mut FuzzRng) -> PolicyContext {
+    let mut user_attributes = HashMap::new();
+    if rng.chance(50) {
+        user_attributes.insert("department".to_string(), serde_json::json!(rng.pick(&["eng", "ops"])));
+    }
+
+    PolicyContext {
+        user_id: format!("user-{}", rng.below(4)),
+        user_roles: (0..rng.below(3)).map(|_| rng.pick(&["admin", "analyst", "guest"]).to_string()).collect(),
+        user_attributes,
+        risk_score: rng.below(101) as f64 / 10.0,
+        mfa_verified: rng.chance(50),
+        resource_classification: rng.pick(&["public", "internal", "classified"]).to_string(),
+        device_trust: DeviceTrust::parse(rng.pick(&["trusted", "managed", "unknown"])),
+        ip_country: rng.pick(&["US", "DE", "JP", ""]).to_string(),
+        intent_purpose: if rng.chance(50) { Some("research".to_string()) } else { None },
+        session_age: chrono::Duration::seconds(rng.below(86_400) as i64),
+        ..PolicyContext::default()
+    }
+}
+
+// Outcome of one fuzz iteration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FuzzReport {
+    pub seed: u64,
+    pub expression: String,
+    pub parsed: bool,
+    pub result: Option<String>,
+    pub error: Option<String>,
+    pub deterministic: bool,
+}
+
+// Evaluates through the engine's own EvalScope, as policies are, once
+// per fresh scope so the second result is not served from the first
+// scope's attribute cache
+pub fn fuzz_once(engine: &PolicyEngine, seed: u64) -> FuzzReport {
+    let mut rng = FuzzRng::new(seed);
+    let mut expression = random_expression(&mut rng, 4);
+    if rng.chance(25) {
+        expression = mutate(&mut rng, &expression);
+    }
+    let context = random_context(&mut rng);
+
+    let mut parsed = match expr::parse(&expression) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            return FuzzReport {
+                seed,
+                expression,
+                parsed: false,
+                result: None,
+                error: Some(e.to_string()),
+                deterministic: true,
+            }
+        }
+    };
+
+    vocabulary::canonicalize(&mut parsed);
+    let first = expr::evaluate(&parsed, &EvalScope::new(engine, &context, None));
+    let second = expr::evaluate(&parsed, &EvalScope::new(engine, &context, None));
+    // Compare rendered outcomes so NaN results still count as equal
+    let deterministic = format!("{:?}", first) == format!("{:?}", second);
+
+    let (result, error) = match first {
+        Ok(value) => (Some(value.to_string()), None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    FuzzReport { seed, expression, parsed: true, result, error, deterministic }
+}
+
+// Generates, parses and evaluates one random expression for `seed` and
+// returns a JSON FuzzReport. Only in builds with the `fuzz` feature, for
+// soak-testing the evaluator in a WASM build otherwise like the shipped one.
+#[cfg(feature = "fuzz")]
+#[wasm_bindgen]
+pub fn fuzz_expression(seed: u64) -> String {
+    serde_json::to_string(&fuzz_once(&PolicyEngine::new(), seed)).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn token() -> impl Strategy<Value = String> {
+        prop_oneof![
+            prop::sample::select(BINARY_OPS).prop_map(str::to_string),
+            prop::sample::select(NOISE).prop_map(str::to_string),
+            prop::sample::select(ATTRIBUTES).prop_map(str::to_string),
+            any::<f64>().prop_map(|n| n.to_string()),
+            "[a-z_]{1,8}",
+            "\"[^\"]{0,8}\"",
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn parser_never_panics_on_arbitrary_text(source in "\\PC{0,200}") {
+            let _ = expr::parse(&source);
+        }
+
+        #[test]
+        fn parser_never_panics_on_token_soup(tokens in prop::collection::vec(token(), 0..40)) {
+            let _ = expr::parse(&tokens.join(" "));
+        }
+
+        #[test]
+        fn generated_expressions_evaluate_deterministically(seed in any::<u64>()) {
+            let report = fuzz_once(&PolicyEngine::new(), seed);
+            prop_assert!(report.deterministic, "non-deterministic result for {:?}", report.expression);
+        }
+
+        #[test]
+        fn rendered_expressions_reparse_to_the_same_tree(seed in any::<u64>()) {
+            let mut rng = FuzzRng::new(seed);
+            let source = random_expression(&mut rng, 4);
+            if let Ok(parsed) = expr::parse(&source) {
+                let rendered = parsed.to_string();
+                prop_assert_eq!(expr::parse(&rendered), Ok(parsed), "rendered as {}", rendered);
+            }
+        }
+    }
+
+    #[test]
+    fn deep_nesting_is_rejected_without_overflow() {
+        for source in [
+            "(".repeat(100_000),
+            "!".repeat(100_000) + "true",
+            "-".repeat(100_000) + "1",
+            vec!["1"; 100_000].join(" + "),
+            "[".repeat(100_000),
+        ] {
+            assert!(expr::parse(&source).is_err());
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+
+use crate::error::PolicyEngineError;
+use crate::{PolicyContext, PolicyEngine};
+
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+// Approximate country centroids (ISO 3166-1 alpha-2). Hosts can add or
+// override entries, including city-level coordinates, with
+// `load_geo_locations`.
+const COUNTRY_CENTROIDS: &[(&str, f64, f64)] = &[
+    ("US", 39.8, -98.6),
+    ("CA", 56.1, -106.3),
+    ("MX", 23.6, -102.6),
+    ("BR", -14.2, -51.9),
+    ("AR", -38.4, -63.6),
+    ("GB", 55.4, -3.4),
+    ("IE", 53.4, -8.2),
+    ("FR", 46.2, 2.2),
+    ("DE", 51.2, 10.5),
+    ("NL", 52.1, 5.3),
+    ("BE", 50.5, 4.5),
+    ("ES", 40.5, -3.7),
+    ("PT", 39.4, -8.2),
+    ("IT", 41.9, 12.6),
+    ("CH", 46.8, 8.2),
+    ("SE", 60.1, 18.6),
+    ("NO", 60.5, 8.5),
+    ("FI", 61.9, 25.7),
+    ("PL", 51.9, 19.1),
+    ("UA", 48.4, 31.2),
+    ("RU", 61.5, 105.3),
+    ("TR", 39.0, 35.2),
+    ("IL", 31.0, 34.9),
+    ("AE", 23.4, 53.8),
+    ("SA", 23.9, 45.1),
+    ("EG", 26.8, 30.8),
+    ("NG", 9.1, 8.7),
+    ("KE", 0.0, 37.9),
+    ("ZA", -30.6, 22.9),
+    ("IN", 20.6, 79.0),
+    ("PK", 30.4, 69.3),
+    ("CN", 35.9, 104.2),
+    ("JP", 36.2, 138.3),
+    ("KR", 35.9, 127.8),
+    ("SG", 1.35, 103.8),
+    ("ID", -0.8, 113.9),
+    ("AU", -25.3, 133.8),
+    ("NZ", -40.9, 174.9),
+];
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Coordinates {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl Coordinates {
+    // Great-circle distance (haversine)
+    pub fn distance_km(&self, other: &Coordinates) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let dlat = lat2 - lat1;
+        let dlon = (other.lon - self.lon).to_radians();
+        let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+    }
+}
+
+// Last location observed for a user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sighting {
+    pub country: String,
+    pub city: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GeoTracker {
+    locations: HashMap<String, Coordinates>,
+    last_seen: HashMap<String, Sighting>,
+}
+
+impl GeoTracker {
+    pub fn new() -> GeoTracker {
+        let locations = COUNTRY_CENTROIDS
+            .iter()
+            .map(|(code, lat, lon)| (code.to_string(), Coordinates { lat: *lat, lon: *lon }))
+            .collect();
+
+        GeoTracker {
+            locations,
+            last_seen: HashMap::new(),
+        }
+    }
+
+    // Keys are "CC" for countries or "CC/City" for cities
+    fn location_key(country: &str, city: Option<&str>) -> String {
+        match city {
+            Some(city) if !city.is_empty() => {
+                format!("{}/{}", country.to_uppercase(), city.to_lowercase())
+            }
+            _ => country.to_uppercase(),
+        }
+    }
+
+    pub fn add_location(&mut self, key: &str, coordinates: Coordinates) {
+        let normalized = match key.split_once('/') {
+            Some((country, city)) => Self::location_key(country, Some(city)),
+            None => Self::location_key(key, None),
+        };
+        self.locations.insert(normalized, coordinates);
+    }
+
+    // City coordinates when known, otherwise the country centroid
+    pub fn resolve(&self, country: &str, city: &str) -> Option<Coordinates> {
+        self.locations
+            .get(&Self::location_key(country, Some(city)))
+            .or_else(|| self.locations.get(&Self::location_key(country, None)))
+            .copied()
+    }
+
+    // Implied speed in km/h between the user's previous sighting and this
+    // context, or None if there is no history or a location is unknown.
+    pub fn travel_speed(&self, user_key: &str, context: &PolicyContext) -> Option<f64> {
+        let previous = self.last_seen.get(user_key)?;
+        let from = self.resolve(&previous.country, &previous.city)?;
+        let to = self.resolve(&context.ip_country, &context.ip_city)?;
+
+        let distance = from.distance_km(&to);
+        let hours = (context.timestamp - previous.timestamp).num_milliseconds() as f64 / 3_600_000.0;
+
+        if hours <= 0.0 {
+            return Some(if distance > 0.0 { f64::INFINITY } else { 0.0 });
+        }
+        Some(distance / hours)
+    }
+
+    pub fn impossible_travel(&self, user_key: &str, context: &PolicyContext, threshold_kmh: f64) -> bool {
+        self.travel_speed(user_key, context).is_some_and(|speed| speed > threshold_kmh)
+    }
+
+    // Record the context's location as the user's latest sighting
+    pub fn record(&mut self, user_key: &str, context: &PolicyContext) {
+        if context.user_id.is_empty() || context.ip_country.is_empty() {
+            return;
+        }
+        if let Some(previous) = self.last_seen.get(user_key) {
+            if previous.timestamp > context.timestamp {
+                return;
+            }
+        }
+        self.last_seen.insert(
+            user_key.to_string(),
+            Sighting {
+                country: context.ip_country.clone(),
+                city: context.ip_city.clone(),
+                timestamp: context.timestamp,
+            },
+        );
+    }
+
+    pub fn forget(&mut self, user_id: &str) {
+        self.last_seen.remove(user_id);
+    }
+
+    pub fn clear_history(&mut self) {
+        self.last_seen.clear();
+    }
+}
+
+impl Default for GeoTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Accepts a JSON object of "CC" or "CC/City" keys to { lat, lon }
+    #[wasm_bindgen]
+    pub fn load_geo_locations(&mut self, locations_json: &str) -> Result<(), JsValue> {
+        match serde_json::from_str::<HashMap<String, Coordinates>>(locations_json) {
+            Ok(locations) => {
+                if self.debug_mode {
+                    console_log!("Loaded {} geo locations", locations.len());
+                }
+                for (key, coordinates) in locations {
+                    self.geo.add_location(&key, coordinates);
+                }
+                Ok(())
+            }
+            Err(e) => Err(PolicyEngineError::parse(format!("Failed to parse geo locations: {}", e)).logged().into()),
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn forget_user_location(&mut self, user_id: &str) {
+        self.geo.forget(user_id);
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_travel_history(&mut self) {
+        self.geo.clear_history();
+    }
+}
@@ -0,0 +1,117 @@
+use wasm_bindgen::prelude::*;
+use maxminddb::{geoip2, Reader};
+use serde::Serialize;
+use std::net::IpAddr;
+
+use crate::error::{to_json, PolicyEngineError};
+use crate::{PolicyContext, PolicyEngine};
+
+// GeoIP enrichment from a MaxMind database (GeoLite2 or GeoIP2, Country
+// or City) loaded by the host as bytes. ip_country and ip_city are
+// resolved from ip_address in-engine, so every PEP sharing the policy set
+// derives them the same way. With `overwrite` the database replaces
+// caller-supplied values; without it, it only fills in missing ones.
+pub struct GeoIpDatabase {
+    reader: Reader<Vec<u8>>,
+    overwrite: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct GeoIpLocation {
+    pub country: Option<String>,
+    pub city: Option<String>,
+}
+
+impl GeoIpDatabase {
+    pub fn locate(&self, ip: IpAddr) -> GeoIpLocation {
+        let Ok(record) = self.reader.lookup::<geoip2::City>(ip) else {
+            return GeoIpLocation::default();
+        };
+        GeoIpLocation {
+            country: record.country.and_then(|country| country.iso_code).map(str::to_string),
+            city: record
+                .city
+                .and_then(|city| city.names)
+                .and_then(|names| names.get("en").copied())
+                .map(str::to_string),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    #[wasm_bindgen]
+    pub fn load_geoip_database(&mut self, database: &[u8], overwrite: bool) -> Result<(), JsValue> {
+        let reader = Reader::from_source(database.to_vec())
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to open GeoIP database: {}", e)).logged())?;
+        if self.debug_mode {
+            console_log!("Loaded GeoIP database {}", reader.metadata.database_type);
+        }
+        self.geoip = Some(GeoIpDatabase { reader, overwrite });
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_geoip_database(&mut self) {
+        self.geoip = None;
+    }
+
+    // {"database_type", "build_epoch", "ip_version", "overwrite"}, or "null"
+    #[wasm_bindgen]
+    pub fn get_geoip_info(&self) -> Result<String, JsValue> {
+        let info = self.geoip.as_ref().map(|database| {
+            let metadata = &database.reader.metadata;
+            serde_json::json!({
+                "database_type": metadata.database_type,
+                "build_epoch": metadata.build_epoch,
+                "ip_version": metadata.ip_version,
+                "overwrite": database.overwrite,
+            })
+        });
+        Ok(to_json(&info)?)
+    }
+
+    // {"country", "city"} for an address, null where the database has no
+    // answer
+    #[wasm_bindgen]
+    pub fn lookup_geoip(&self, ip_address: &str) -> Result<String, JsValue> {
+        let database = self
+            .geoip
+            .as_ref()
+            .ok_or_else(|| PolicyEngineError::validation("No GeoIP database loaded").logged())?;
+        let ip: IpAddr = ip_address.parse().map_err(|_| {
+            PolicyEngineError::validation(format!("'{}' is not an IP address", ip_address)).logged()
+        })?;
+        Ok(to_json(&database.locate(ip))?)
+    }
+}
+
+// Sets a context field to a resolved value, unless the caller supplied
+// one and the database does not overwrite
+fn fill(field: &mut String, value: Option<String>, overwrite: bool) -> bool {
+    match value {
+        Some(value) if overwrite || field.is_empty() => {
+            *field = value;
+            true
+        }
+        _ => false,
+    }
+}
+
+impl PolicyEngine {
+    pub(crate) fn resolve_geoip(&self, context: &mut PolicyContext) {
+        let Some(database) = &self.geoip else { return };
+        let Ok(ip) = context.ip_address.parse::<IpAddr>() else { return };
+        let location = database.locate(ip);
+        let mut resolved = Vec::new();
+        if fill(&mut context.ip_country, location.country, database.overwrite) {
+            resolved.push("ip_country");
+        }
+        if fill(&mut context.ip_city, location.city, database.overwrite) {
+            resolved.push("ip_city");
+        }
+        if let Some(supplied) = &mut context.supplied_fields {
+            supplied.extend(resolved.into_iter().map(str::to_string));
+        }
+    }
+}
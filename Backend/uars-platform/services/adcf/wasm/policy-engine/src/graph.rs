@@ -0,0 +1,207 @@
+use wasm_bindgen::prelude::*;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::definitions;
+use crate::error::PolicyEngineError;
+use crate::expr;
+use crate::{CompiledPolicy, PolicyEngine, PolicyRule};
+
+#[derive(Clone, Copy, PartialEq)]
+enum NodeKind {
+    PolicySet,
+    Policy,
+    Rule,
+    Definition,
+    LibraryRule,
+}
+
+struct Node {
+    id: String,
+    kind: NodeKind,
+    // Lines of the label
+    label: Vec<String>,
+}
+
+struct Edge {
+    from: String,
+    to: String,
+    label: Option<&'static str>,
+}
+
+// Policy sets -> policies -> rules, plus the conditions rules share:
+// definitions they use and library rules policies reference (one node
+// however many policies link it)
+#[derive(Default)]
+struct PolicyGraph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+    library_rules: BTreeMap<String, String>,
+}
+
+fn rule_label(id: &str, rule: &PolicyRule) -> Vec<String> {
+    vec![id.to_string(), format!("{}, priority {}", rule.effect, rule.priority)]
+}
+
+impl PolicyGraph {
+    fn add_node(&mut self, kind: NodeKind, label: Vec<String>) -> String {
+        let id = format!("n{}", self.nodes.len());
+        self.nodes.push(Node { id: id.clone(), kind, label });
+        id
+    }
+
+    fn add_edge(&mut self, from: &str, to: &str, label: Option<&'static str>) {
+        self.edges.push(Edge { from: from.to_string(), to: to.to_string(), label });
+    }
+
+    fn add_set(&mut self, label: Vec<String>, policies: &[CompiledPolicy]) -> String {
+        let set = self.add_node(NodeKind::PolicySet, label);
+        for compiled in policies {
+            let policy = self.add_policy(compiled);
+            self.add_edge(&set, &policy, None);
+        }
+        set
+    }
+
+    fn add_policy(&mut self, compiled: &CompiledPolicy) -> String {
+        let policy = &compiled.policy;
+        let mut label = vec![format!("{} ({})", policy.name, policy.id)];
+        label.push(format!("{}, priority {}", policy.combining_algorithm, policy.priority));
+        if !policy.enabled {
+            label.push("disabled".to_string());
+        }
+        let node = self.add_node(NodeKind::Policy, label);
+
+        let mut definition_nodes = BTreeMap::new();
+        for name in policy.definitions.keys() {
+            let id = self.add_node(NodeKind::Definition, vec![name.clone()]);
+            self.add_edge(&node, &id, None);
+            definition_nodes.insert(name.as_str(), id);
+        }
+        // Definitions using each other, by the names in their source
+        for (name, source) in &policy.definitions {
+            self.add_uses(&definition_nodes[name.as_str()], source, &definition_nodes);
+        }
+
+        for rule in &policy.rules {
+            let id = self.add_node(NodeKind::Rule, rule_label(&rule.id, rule));
+            self.add_edge(&node, &id, None);
+            self.add_uses(&id, &rule.condition, &definition_nodes);
+        }
+
+        for (rule, _) in &compiled.linked {
+            let id = match self.library_rules.get(&rule.id) {
+                Some(id) => id.clone(),
+                None => {
+                    let id = self.add_node(NodeKind::LibraryRule, rule_label(&rule.id, rule));
+                    self.library_rules.insert(rule.id.clone(), id.clone());
+                    id
+                }
+            };
+            self.add_edge(&node, &id, Some("references"));
+        }
+        node
+    }
+
+    fn add_uses(&mut self, from: &str, source: &str, definition_nodes: &BTreeMap<&str, String>) {
+        let Ok(expression) = expr::parse(source) else {
+            return;
+        };
+        let mut used = definitions::references(&expression);
+        used.sort();
+        used.dedup();
+        for name in used {
+            if let Some(to) = definition_nodes.get(name.as_str()) {
+                self.add_edge(from, to, Some("uses"));
+            }
+        }
+    }
+
+    fn to_dot(&self) -> String {
+        let escape = |text: &str| text.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut out = String::from("digraph policies {\n    rankdir=LR;\n    node [fontname=\"Helvetica\"];\n");
+        for node in &self.nodes {
+            let shape = match node.kind {
+                NodeKind::PolicySet => "shape=folder",
+                NodeKind::Policy => "shape=box",
+                NodeKind::Rule => "shape=box, style=rounded",
+                NodeKind::Definition => "shape=note",
+                NodeKind::LibraryRule => "shape=box, style=\"rounded,dashed\"",
+            };
+            let label: Vec<String> = node.label.iter().map(|line| escape(line)).collect();
+            let _ = writeln!(out, "    {} [{}, label=\"{}\"];", node.id, shape, label.join("\\n"));
+        }
+        for edge in &self.edges {
+            match edge.label {
+                Some(label) => {
+                    let _ = writeln!(out, "    {} -> {} [label=\"{}\", style=dashed];", edge.from, edge.to, label);
+                }
+                None => {
+                    let _ = writeln!(out, "    {} -> {};", edge.from, edge.to);
+                }
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn to_mermaid(&self) -> String {
+        let escape = |text: &str| text.replace('"', "#quot;").replace('<', "#lt;").replace('>', "#gt;");
+        let mut out = String::from("flowchart LR\n");
+        for node in &self.nodes {
+            let (open, close) = match node.kind {
+                NodeKind::PolicySet => ("[[", "]]"),
+                NodeKind::Policy => ("[", "]"),
+                NodeKind::Rule => ("(", ")"),
+                NodeKind::Definition => ("{{", "}}"),
+                NodeKind::LibraryRule => ("([", "])"),
+            };
+            let label: Vec<String> = node.label.iter().map(|line| escape(line)).collect();
+            let _ = writeln!(out, "    {}{}\"{}\"{}", node.id, open, label.join("<br/>"), close);
+        }
+        for edge in &self.edges {
+            match edge.label {
+                Some(label) => {
+                    let _ = writeln!(out, "    {} -. {} .-> {}", edge.from, label, edge.to);
+                }
+                None => {
+                    let _ = writeln!(out, "    {} --> {}", edge.from, edge.to);
+                }
+            }
+        }
+        out
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Renders the loaded policy sets (global, then each tenant) as a
+    // graph for documentation and review: sets -> policies -> rules with
+    // their effects and priorities, and the definitions and library
+    // rules they share. `format` is "dot" (Graphviz) or "mermaid".
+    #[wasm_bindgen]
+    pub fn export_graph(&self, format: &str) -> Result<String, JsValue> {
+        let mut graph = PolicyGraph::default();
+        let global = graph.add_set(vec!["global".to_string(), self.root_algorithm.to_string()], &self.policies);
+
+        let mut tenant_ids: Vec<&String> = self.tenants.keys().collect();
+        tenant_ids.sort();
+        for tenant_id in tenant_ids {
+            let tenant = &self.tenants[tenant_id];
+            let set = graph.add_set(vec![format!("tenant {}", tenant_id)], tenant.own_policies());
+            if tenant.inherits_global() {
+                graph.add_edge(&set, &global, Some("inherits"));
+            }
+        }
+
+        match format.trim().to_ascii_lowercase().as_str() {
+            "dot" | "graphviz" => Ok(graph.to_dot()),
+            "mermaid" => Ok(graph.to_mermaid()),
+            other => Err(PolicyEngineError::validation(format!("Unknown graph format '{}'", other))
+                .with_details(json!({ "format": other, "supported": ["dot", "mermaid"] }))
+                .logged()
+                .into()),
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::decision::Decision;
+use crate::error::{to_json, PolicyEngineError};
+use crate::{PolicyEngine, PolicySelection};
+
+// A field to authorize, as `{"type": "User", "field": "ssn"}` or the
+// schema coordinate "User.ssn"
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum FieldRef {
+    Pair {
+        #[serde(rename = "type")]
+        type_name: String,
+        field: String,
+    },
+    Coordinate(String),
+}
+
+impl FieldRef {
+    fn parts(&self) -> Option<(&str, &str)> {
+        match self {
+            FieldRef::Pair { type_name, field } => Some((type_name, field)),
+            FieldRef::Coordinate(coordinate) => coordinate.split_once('.'),
+        }
+    }
+}
+
+// Per-field result. A resolver returns the field when `permitted`, after
+// applying the obligations (masking and the like); otherwise it returns
+// null with an error for that path.
+#[derive(Debug, Serialize)]
+struct FieldDecision {
+    #[serde(rename = "type")]
+    type_name: String,
+    field: String,
+    decision: Decision,
+    permitted: bool,
+    obligations: Vec<String>,
+    policy_id: Option<String>,
+    rule_id: Option<String>,
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Field-level authorization for a GraphQL request: evaluates the
+    // request context once per field, with resource_type set to the type
+    // name and resource_id to the field name, so policies read
+    //
+    //   resource_type == 'User' && resource_id in ['ssn', 'salary']
+    //
+    // operation defaults to "read". Returns a JSON array of FieldDecision
+    // in input order. Like query_permissions these are what-ifs that leave
+    // quotas and other request state untouched.
+    #[wasm_bindgen]
+    pub fn evaluate_fields(&self, context_json: &str, fields_json: &str) -> Result<String, JsValue> {
+        let context: Map<String, Value> = serde_json::from_str(context_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse context: {}", e)).logged())?;
+        let fields: Vec<FieldRef> = serde_json::from_str(fields_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse fields: {}", e)).logged())?;
+
+        let mut decisions = Vec::with_capacity(fields.len());
+        for (index, field) in fields.iter().enumerate() {
+            let Some((type_name, field)) = field.parts().filter(|(type_name, field)| !type_name.is_empty() && !field.is_empty()) else {
+                return Err(PolicyEngineError::validation(format!("Field {} is not a Type.field coordinate", index))
+                    .with_details(json!({ "index": index }))
+                    .logged()
+                    .into());
+            };
+            let mut raw = context.clone();
+            raw.entry("operation").or_insert_with(|| json!("read"));
+            raw.insert("resource_type".to_string(), json!(type_name));
+            raw.insert("resource_id".to_string(), json!(field));
+            let label = format!("Field {}.{}", type_name, field);
+            let result = self.query(raw, &label, PolicySelection::Explicit(&self.policies))?;
+            decisions.push(FieldDecision {
+                type_name: type_name.to_string(),
+                field: field.to_string(),
+                decision: result.decision,
+                permitted: result.decision == Decision::Permit,
+                obligations: serde_json::from_str(&result.obligations).unwrap_or_default(),
+                policy_id: result.policy_id,
+                rule_id: result.rule_id,
+            });
+        }
+
+        if self.debug_mode {
+            let permitted = decisions.iter().filter(|decision| decision.permitted).count();
+            console_log!("Field authorization: {} of {} fields permitted", permitted, decisions.len());
+        }
+        Ok(to_json(&decisions)?)
+    }
+}
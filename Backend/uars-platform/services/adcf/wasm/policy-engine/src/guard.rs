@@ -0,0 +1,52 @@
+use wasm_bindgen::prelude::*;
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::decision::Decision;
+use crate::error::PolicyEngineError;
+use crate::PolicyResult;
+
+impl PolicyResult {
+    pub(crate) fn failure(error: &PolicyEngineError) -> PolicyResult {
+        let mut result = PolicyResult::new(Decision::Indeterminate, error.message().to_string(), 0.0);
+        result.error_code = Some(error.code().to_string());
+        // A failed evaluation is never reusable (see freshness.rs)
+        result.ttl_seconds = Some(0.0);
+        result
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+// Runs an evaluation so the caller gets a decision rather than an error:
+// errors become INDETERMINATE with the error's code (EVALUATION_ERROR for
+// untyped errors). Panics only become INDETERMINATE (INTERNAL_ERROR)
+// where they unwind, i.e. native builds. wasm32-unknown-unknown aborts on
+// panic, trapping the call with no decision, so evaluation code must not
+// panic at all; this is a backstop for native hosts and tests.
+pub(crate) fn guarded<F>(evaluation: F) -> PolicyResult
+where
+    F: FnOnce() -> Result<PolicyResult, JsValue>,
+{
+    match panic::catch_unwind(AssertUnwindSafe(evaluation)) {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => {
+            let message = e.as_string().unwrap_or_else(|| "evaluation failed".to_string());
+            let error = PolicyEngineError::evaluation(format!("Evaluation error: {}", message)).logged();
+            PolicyResult::failure(&error)
+        }
+        Err(payload) => {
+            let message = panic_message(payload.as_ref());
+            let error = PolicyEngineError::internal(format!("Internal error: {}", message)).logged();
+            PolicyResult::failure(&error)
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use wasm_bindgen::prelude::*;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+use crate::attributes::{canonical_path, context_field};
+use crate::error::{to_json, PolicyEngineError};
+use crate::expr::{ExprError, Value};
+use crate::quota;
+use crate::{PolicyContext, PolicyEngine};
+
+// Samples older than this are dropped, so windows reach back at most 30 days
+const RETENTION_SECONDS: i64 = 30 * 86_400;
+// Most recent samples kept per user and attribute
+const MAX_SAMPLES: usize = 1000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sample {
+    pub at: DateTime<Utc>,
+    pub value: f64,
+}
+
+// Aggregates over a user's recent values of an attribute
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aggregate {
+    Avg,
+    Min,
+    Max,
+    Sum,
+    Count,
+}
+
+impl Aggregate {
+    pub fn for_function(name: &str) -> Option<Aggregate> {
+        match name {
+            "avg_over" => Some(Aggregate::Avg),
+            "min_over" => Some(Aggregate::Min),
+            "max_over" => Some(Aggregate::Max),
+            "sum_over" => Some(Aggregate::Sum),
+            "count_over" => Some(Aggregate::Count),
+            _ => None,
+        }
+    }
+}
+
+// Per-user time series behind avg_over() and the other *_over()
+// functions. Values come from record_attribute, and from every request
+// for the attributes chosen with track_attribute_history.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct AttributeHistory {
+    tracked: BTreeSet<String>,
+    users: HashMap<String, HashMap<String, VecDeque<Sample>>>,
+}
+
+impl AttributeHistory {
+    fn push(&mut self, user_key: &str, attribute: String, sample: Sample) {
+        let series = self.users.entry(user_key.to_string()).or_default().entry(attribute).or_default();
+        // Keep the series in time order; samples mostly arrive in order
+        let position = series.iter().rposition(|seen| seen.at <= sample.at).map_or(0, |index| index + 1);
+        series.insert(position, sample);
+        let oldest = series.back().and_then(|latest| latest.at.checked_sub_signed(Duration::seconds(RETENTION_SECONDS)));
+        while series.len() > MAX_SAMPLES || series.front().zip(oldest).is_some_and(|(first, oldest)| first.at < oldest) {
+            series.pop_front();
+        }
+    }
+
+    // Numeric values of the tracked attributes in a request; defaults
+    // for attributes it left out are not samples
+    pub fn record(&mut self, user_key: &str, context: &PolicyContext) {
+        if context.user_id.is_empty() || self.tracked.is_empty() {
+            return;
+        }
+        let tracked: Vec<String> = self.tracked.iter().cloned().collect();
+        for attribute in tracked {
+            let segments: Vec<&str> = attribute.split('.').collect();
+            if !context.has_attribute(&segments) {
+                continue;
+            }
+            let path: Vec<String> = segments.iter().map(|segment| segment.to_string()).collect();
+            if let Some(Value::Number(value)) = context.attribute(&path) {
+                self.push(user_key, attribute, Sample { at: context.timestamp, value });
+            }
+        }
+    }
+
+    // Values of `attribute` in the `seconds` up to `until`, aggregated;
+    // null for an empty window, except count_over's 0
+    pub fn aggregate(&self, user_key: &str, attribute: &str, seconds: f64, until: DateTime<Utc>, aggregate: Aggregate) -> Value {
+        // A window reaching past the representable range covers everything
+        let since = Duration::try_milliseconds((seconds * 1000.0) as i64)
+            .and_then(|window| until.checked_sub_signed(window))
+            .unwrap_or(DateTime::<Utc>::MIN_UTC);
+        let values: Vec<f64> = self
+            .users
+            .get(user_key)
+            .and_then(|series| series.get(&series_name(attribute)))
+            .map(|series| series.iter().filter(|sample| sample.at > since && sample.at <= until).map(|sample| sample.value).collect())
+            .unwrap_or_default();
+        if values.is_empty() && aggregate != Aggregate::Count {
+            return Value::Null;
+        }
+        Value::Number(match aggregate {
+            Aggregate::Avg => values.iter().sum::<f64>() / values.len() as f64,
+            Aggregate::Min => values.iter().copied().fold(f64::INFINITY, f64::min),
+            Aggregate::Max => values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            Aggregate::Sum => values.iter().sum(),
+            Aggregate::Count => values.len() as f64,
+        })
+    }
+}
+
+fn series_name(attribute: &str) -> String {
+    let segments: Vec<&str> = attribute.split('.').map(str::trim).collect();
+    canonical_path(&segments)
+}
+
+// A window is a duration string ("24h") or a number of seconds (24h
+// written as a duration literal)
+pub fn window_seconds(function: &str, window: &Value) -> Result<f64, ExprError> {
+    let seconds = match window {
+        Value::Number(seconds) => Some(*seconds),
+        Value::String(text) => quota::parse_window(text).map(|window| window.num_milliseconds() as f64 / 1000.0),
+        _ => None,
+    };
+    match seconds {
+        Some(seconds) if seconds > 0.0 && seconds.is_finite() => Ok(seconds),
+        _ => Err(ExprError::new(format!("{}() window must be a positive duration such as \"24h\", found {}", function, window))),
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Records a value of `attribute` for a user at the engine's current
+    // time, for avg_over() and the other *_over() functions. Evaluations
+    // for a tenant read that tenant's series, which only tracked
+    // attributes feed.
+    #[wasm_bindgen]
+    pub fn record_attribute(&mut self, user_id: &str, attribute: &str, value: f64) -> Result<(), JsValue> {
+        if user_id.trim().is_empty() || attribute.trim().is_empty() {
+            return Err(PolicyEngineError::validation("User id and attribute must not be empty").logged().into());
+        }
+        if !value.is_finite() {
+            return Err(PolicyEngineError::validation(format!("Value of '{}' must be a finite number", attribute))
+                .with_details(json!({ "attribute": attribute }))
+                .logged()
+                .into());
+        }
+        let sample = Sample { at: self.environment.now(), value };
+        self.history.push(user_id, series_name(attribute), sample);
+        Ok(())
+    }
+
+    // Context attributes (a JSON array of paths, e.g. ["risk_score"])
+    // whose numeric values are recorded from every request; replaces the
+    // earlier selection, and [] stops tracking
+    #[wasm_bindgen]
+    pub fn track_attribute_history(&mut self, attributes_json: &str) -> Result<(), JsValue> {
+        let attributes: Vec<String> = serde_json::from_str(attributes_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse tracked attributes: {}", e)).logged())?;
+        let mut tracked = BTreeSet::new();
+        for attribute in &attributes {
+            let segments: Vec<&str> = attribute.split('.').map(str::trim).collect();
+            if context_field(&segments).is_none() {
+                return Err(PolicyEngineError::validation(format!("'{}' is not a context attribute", attribute))
+                    .with_details(json!({ "attribute": attribute }))
+                    .logged()
+                    .into());
+            }
+            tracked.insert(canonical_path(&segments));
+        }
+        self.history.tracked = tracked;
+        Ok(())
+    }
+
+    // A user's samples of `attribute`, oldest first
+    #[wasm_bindgen]
+    pub fn get_attribute_history(&self, user_id: &str, attribute: &str) -> Result<String, JsValue> {
+        let samples = self.history.users.get(user_id).and_then(|series| series.get(&series_name(attribute)));
+        Ok(to_json(&samples.cloned().unwrap_or_default())?)
+    }
+
+    #[wasm_bindgen]
+    pub fn forget_attribute_history(&mut self, user_id: &str) {
+        self.history.users.remove(user_id);
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_attribute_history(&mut self) {
+        self.history.users.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_past_the_representable_range_do_not_panic() {
+        let mut history = AttributeHistory::default();
+        history.push("alice", series_name("risk_score"), Sample { at: DateTime::<Utc>::MIN_UTC, value: 1.0 });
+        history.push("alice", series_name("risk_score"), Sample { at: Utc::now(), value: 2.0 });
+
+        let until = Utc::now();
+        let all = history.aggregate("alice", "risk_score", f64::MAX, until, Aggregate::Count);
+        assert_eq!(all, Value::Number(1.0));
+        let recent = history.aggregate("alice", "risk_score", 60.0, DateTime::<Utc>::MIN_UTC, Aggregate::Sum);
+        assert_eq!(recent, Value::Null);
+    }
+}
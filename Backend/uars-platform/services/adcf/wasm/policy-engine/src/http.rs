@@ -0,0 +1,211 @@
+use wasm_bindgen::prelude::*;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::error::PolicyEngineError;
+#[cfg(feature = "crypto")]
+use crate::jose::{self, Jwks};
+#[cfg(feature = "crypto")]
+use crate::jwt::{self, JwtOptions};
+
+// HTTP method to operation, shared by every HTTP-facing adapter
+pub(crate) fn default_operations() -> BTreeMap<String, String> {
+    [("GET", "read"), ("HEAD", "read"), ("POST", "create"), ("PUT", "update"), ("PATCH", "update"), ("DELETE", "delete")]
+        .iter()
+        .map(|(method, operation)| (method.to_string(), operation.to_string()))
+        .collect()
+}
+
+// Operation for a method; unlisted methods pass through lowercased
+pub(crate) fn operation(operations: &BTreeMap<String, String>, method: &str) -> String {
+    let method = method.to_uppercase();
+    operations.get(&method).cloned().unwrap_or_else(|| method.to_lowercase())
+}
+
+// Comma-separated header values, trimmed, empty items dropped
+pub(crate) fn list(header: Option<&str>) -> Vec<String> {
+    header
+        .map(|value| value.split(',').map(str::trim).filter(|item| !item.is_empty()).map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+// Header lookup ignoring case; empty values count as absent
+pub(crate) fn header<'a>(headers: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+        .filter(|value| !value.is_empty())
+}
+
+fn cookie<'a>(headers: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+    header(headers, "cookie")?
+        .split(';')
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.trim_matches('"'))
+        .filter(|value| !value.is_empty())
+}
+
+// Coarse device class from a User-Agent: bot, tablet, mobile or desktop
+fn device_type(user_agent: &str) -> &'static str {
+    let ua = user_agent.to_lowercase();
+    if ["bot", "crawler", "spider", "curl/", "wget/"].iter().any(|marker| ua.contains(marker)) {
+        "bot"
+    } else if ua.contains("ipad") || ua.contains("tablet") || (ua.contains("android") && !ua.contains("mobile")) {
+        "tablet"
+    } else if ua.contains("mobile") || ua.contains("iphone") || ua.contains("android") {
+        "mobile"
+    } else {
+        "desktop"
+    }
+}
+
+// First language tag of an Accept-Language header, without its weight
+fn locale(accept_language: &str) -> Option<String> {
+    accept_language
+        .split(',')
+        .map(|item| item.split(';').next().unwrap_or_default().trim())
+        .find(|tag| !tag.is_empty() && *tag != "*")
+        .map(str::to_string)
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct HttpContextOptions {
+    pub resource_type: String,
+    pub operations: BTreeMap<String, String>,
+    // Use the first X-Forwarded-For hop over client_ip; only behind a
+    // proxy that overwrites the header
+    pub trust_forwarded_for: bool,
+    pub session_cookie: String,
+    // Bearer tokens are only read when verified against this JWKS; the
+    // JWT options (audience, issuer, require_exp, roles_claim, groups_claim) apply
+    #[cfg(feature = "crypto")]
+    pub jwks: Option<Jwks>,
+    #[cfg(feature = "crypto")]
+    #[serde(flatten)]
+    pub jwt: JwtOptions,
+}
+
+impl Default for HttpContextOptions {
+    fn default() -> Self {
+        HttpContextOptions {
+            resource_type: "http".to_string(),
+            operations: default_operations(),
+            trust_forwarded_for: false,
+            session_cookie: "session".to_string(),
+            #[cfg(feature = "crypto")]
+            jwks: None,
+            #[cfg(feature = "crypto")]
+            jwt: JwtOptions::default(),
+        }
+    }
+}
+
+// Identity fields from a verified bearer token; a token that fails
+// verification is an error rather than an anonymous request
+#[cfg(feature = "crypto")]
+fn bearer_claims(headers: &HashMap<String, String>, options: &HttpContextOptions) -> Result<Map<String, Value>, PolicyEngineError> {
+    let (Some(jwks), Some(authorization)) = (&options.jwks, header(headers, "authorization")) else {
+        return Ok(Map::new());
+    };
+    let Some(token) = authorization.strip_prefix("Bearer ").or_else(|| authorization.strip_prefix("bearer ")) else {
+        return Ok(Map::new());
+    };
+    let (_, claims) = jose::verify(token.trim(), jwks)
+        .and_then(|(header, claims)| jwt::check_claims(&claims, &options.jwt).map(|_| (header, claims)))
+        .map_err(|reason| {
+            PolicyEngineError::validation(format!("Invalid JWT: {}", reason))
+                .with_details(json!({ "reason": reason }))
+                .logged()
+        })?;
+    Ok(jwt::map_claims(&claims, &options.jwt))
+}
+
+// Partial PolicyContext (JSON object) for an HTTP request: operation from
+// the method, resource_id from the path, ip_address, session and device
+// hints from headers, identity from a verified bearer token
+pub fn http_context(
+    method: &str,
+    path: &str,
+    headers: &HashMap<String, String>,
+    client_ip: &str,
+    options: &HttpContextOptions,
+) -> Result<Map<String, Value>, PolicyEngineError> {
+    let mut context = Map::new();
+    if let Some(id) = header(headers, "x-request-id") {
+        context.insert("request_id".to_string(), json!(id));
+    }
+    context.insert("operation".to_string(), json!(operation(&options.operations, method)));
+
+    let target = path.split('#').next().unwrap_or_default();
+    let (resource, query) = target.split_once('?').unwrap_or((target, ""));
+    context.insert("resource_type".to_string(), json!(options.resource_type));
+    context.insert("resource_id".to_string(), json!(resource));
+
+    let forwarded = if options.trust_forwarded_for { list(header(headers, "x-forwarded-for")).into_iter().next() } else { None };
+    let ip = forwarded.unwrap_or_else(|| client_ip.to_string());
+    if !ip.is_empty() {
+        context.insert("ip_address".to_string(), json!(ip));
+    }
+
+    if let Some(session) = header(headers, "x-session-id").or_else(|| cookie(headers, &options.session_cookie)) {
+        context.insert("session_id".to_string(), json!(session));
+    }
+    if let Some(device) = header(headers, "x-device-id") {
+        context.insert("device_id".to_string(), json!(device));
+    }
+    let user_agent = header(headers, "user-agent");
+    if let Some(user_agent) = user_agent {
+        context.insert("device_type".to_string(), json!(device_type(user_agent)));
+    }
+    if let Some(locale) = header(headers, "accept-language").and_then(locale) {
+        context.insert("locale".to_string(), json!(locale));
+    }
+
+    // Token claims win over headers for the fields both set (session_id)
+    #[cfg(feature = "crypto")]
+    context.extend(bearer_claims(headers, options)?);
+
+    let query: BTreeMap<&str, &str> = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| pair.split_once('=').unwrap_or((pair, "")))
+        .collect();
+    context.insert(
+        "metadata".to_string(),
+        json!({
+            "http": {
+                "method": method.to_uppercase(),
+                "path": path,
+                "query": query,
+                "user_agent": user_agent,
+            }
+        }),
+    );
+    Ok(context)
+}
+
+// Builds the partial context JSON a web PEP would otherwise assemble by
+// hand. `headers_json` is an object of header name to value; `options_json`
+// may set resource_type, operations, trust_forwarded_for, session_cookie
+// and, with the crypto feature, jwks plus the context_from_jwt options.
+#[wasm_bindgen]
+pub fn context_from_http(
+    method: &str,
+    path: &str,
+    headers_json: &str,
+    client_ip: &str,
+    options_json: Option<String>,
+) -> Result<String, JsValue> {
+    let headers: HashMap<String, String> = serde_json::from_str(headers_json)
+        .map_err(|e| PolicyEngineError::parse(format!("Failed to parse headers: {}", e)).logged())?;
+    let options: HttpContextOptions = match options_json {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse HTTP context options: {}", e)).logged())?,
+        None => HttpContextOptions::default(),
+    };
+    Ok(Value::Object(http_context(method, path, &headers, client_ip, &options)?).to_string())
+}
@@ -0,0 +1,189 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine as _;
+use chrono::{DateTime, Utc};
+use p256::ecdsa::signature::{Signer, Verifier};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// Minimal JOSE support for compact JWS/JWT: RS256 and ES256 only, which
+// is what device-posture services and identity providers issue. The
+// engine signs its own tokens with ES256.
+
+// Clock skew tolerated when checking exp and nbf
+pub const LEEWAY_SECONDS: i64 = 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Jwk {
+    pub kty: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alg: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crv: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub x: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub y: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub n: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub e: Option<String>,
+    // EC private scalar; accepted on input, never serialized
+    #[serde(default, skip_serializing)]
+    pub d: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Jwks {
+    pub keys: Vec<Jwk>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct JwsHeader {
+    pub alg: String,
+    #[serde(default)]
+    pub kid: Option<String>,
+}
+
+pub fn decode_segment(segment: &str) -> Result<Vec<u8>, String> {
+    URL_SAFE_NO_PAD
+        .decode(segment.trim_end_matches('='))
+        .map_err(|e| format!("invalid base64url: {}", e))
+}
+
+pub fn encode_segment(bytes: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn key_field<'a>(field: &'a Option<String>, name: &str) -> Result<&'a str, String> {
+    field.as_deref().ok_or_else(|| format!("JWK is missing '{}'", name))
+}
+
+impl Jwk {
+    // Whether this key may verify a token signed with `alg`
+    fn accepts(&self, alg: &str) -> bool {
+        let kty_matches = match alg {
+            "ES256" => self.kty == "EC" && self.crv.as_deref() == Some("P-256"),
+            "RS256" => self.kty == "RSA",
+            _ => false,
+        };
+        kty_matches && self.alg.as_deref().is_none_or(|a| a == alg)
+    }
+
+    // ES256 signing key from a private P-256 JWK
+    pub fn signing_key(&self) -> Result<p256::ecdsa::SigningKey, String> {
+        if self.kty != "EC" || self.crv.as_deref() != Some("P-256") {
+            return Err("only P-256 (ES256) signing keys are supported".to_string());
+        }
+        let scalar = decode_segment(key_field(&self.d, "d")?)?;
+        p256::ecdsa::SigningKey::from_slice(&scalar).map_err(|_| "invalid P-256 private key".to_string())
+    }
+
+    // Public JWK for an ES256 signing key
+    pub fn from_signing_key(key: &p256::ecdsa::SigningKey, kid: Option<String>) -> Jwk {
+        let point = key.verifying_key().to_encoded_point(false);
+        Jwk {
+            kty: "EC".to_string(),
+            kid,
+            alg: Some("ES256".to_string()),
+            crv: Some("P-256".to_string()),
+            x: point.x().map(|x| encode_segment(x)),
+            y: point.y().map(|y| encode_segment(y)),
+            n: None,
+            e: None,
+            d: None,
+        }
+    }
+
+    fn verify(&self, alg: &str, message: &[u8], signature: &[u8]) -> Result<(), String> {
+        match alg {
+            "ES256" => {
+                let mut point = vec![0x04];
+                point.extend(decode_segment(key_field(&self.x, "x")?)?);
+                point.extend(decode_segment(key_field(&self.y, "y")?)?);
+                let key = p256::ecdsa::VerifyingKey::from_sec1_bytes(&point)
+                    .map_err(|_| "invalid P-256 public key".to_string())?;
+                let signature = p256::ecdsa::Signature::from_slice(signature)
+                    .map_err(|_| "malformed ES256 signature".to_string())?;
+                key.verify(message, &signature).map_err(|_| "signature mismatch".to_string())
+            }
+            "RS256" => {
+                let n = rsa::BigUint::from_bytes_be(&decode_segment(key_field(&self.n, "n")?)?);
+                let e = rsa::BigUint::from_bytes_be(&decode_segment(key_field(&self.e, "e")?)?);
+                let key = rsa::RsaPublicKey::new(n, e).map_err(|e| format!("invalid RSA key: {}", e))?;
+                let key = rsa::pkcs1v15::VerifyingKey::<sha2::Sha256>::new(key);
+                let signature = rsa::pkcs1v15::Signature::try_from(signature)
+                    .map_err(|_| "malformed RS256 signature".to_string())?;
+                key.verify(message, &signature).map_err(|_| "signature mismatch".to_string())
+            }
+            other => Err(format!("unsupported algorithm '{}'", other)),
+        }
+    }
+}
+
+// Verifies a compact JWS against any matching key in `jwks` (by `kid`
+// when the token names one) and returns its header and JSON payload
+pub fn verify(token: &str, jwks: &Jwks) -> Result<(JwsHeader, Value), String> {
+    let mut parts = token.trim().split('.');
+    let (header_b64, payload_b64, signature_b64) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(h), Some(p), Some(s), None) => (h, p, s),
+        _ => return Err("token is not a compact JWS".to_string()),
+    };
+
+    let header: JwsHeader = serde_json::from_slice(&decode_segment(header_b64)?)
+        .map_err(|e| format!("invalid header: {}", e))?;
+    let signature = decode_segment(signature_b64)?;
+    let signing_input = format!("{}.{}", header_b64, payload_b64);
+
+    let candidates: Vec<&Jwk> = jwks
+        .keys
+        .iter()
+        .filter(|key| key.accepts(&header.alg))
+        .filter(|key| header.kid.is_none() || key.kid == header.kid)
+        .collect();
+    if candidates.is_empty() {
+        return Err(format!("no trusted key for alg '{}' kid {:?}", header.alg, header.kid));
+    }
+    if !candidates
+        .iter()
+        .any(|key| key.verify(&header.alg, signing_input.as_bytes(), &signature).is_ok())
+    {
+        return Err("signature verification failed".to_string());
+    }
+
+    let payload = serde_json::from_slice(&decode_segment(payload_b64)?)
+        .map_err(|e| format!("invalid payload: {}", e))?;
+    Ok((header, payload))
+}
+
+// Compact ES256 JWS over `claims`
+pub fn sign_es256(claims: &Value, key: &p256::ecdsa::SigningKey, kid: Option<&str>) -> String {
+    let mut header = serde_json::json!({ "alg": "ES256", "typ": "JWT" });
+    if let Some(kid) = kid {
+        header["kid"] = Value::from(kid);
+    }
+    let signing_input = format!(
+        "{}.{}",
+        encode_segment(header.to_string().as_bytes()),
+        encode_segment(claims.to_string().as_bytes())
+    );
+    let signature: p256::ecdsa::Signature = key.sign(signing_input.as_bytes());
+    format!("{}.{}", signing_input, encode_segment(&signature.to_bytes()))
+}
+
+// exp and nbf (seconds since the epoch) against `now`
+pub fn check_times(claims: &Value, now: DateTime<Utc>) -> Result<(), String> {
+    let now = now.timestamp();
+    if let Some(exp) = claims.get("exp").and_then(Value::as_i64) {
+        if now > exp + LEEWAY_SECONDS {
+            return Err("token has expired".to_string());
+        }
+    }
+    if let Some(nbf) = claims.get("nbf").and_then(Value::as_i64) {
+        if now + LEEWAY_SECONDS < nbf {
+            return Err("token is not yet valid".to_string());
+        }
+    }
+    Ok(())
+}
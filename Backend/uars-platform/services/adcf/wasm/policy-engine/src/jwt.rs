@@ -0,0 +1,206 @@
+use wasm_bindgen::prelude::*;
+use chrono::{TimeZone, Utc};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashSet;
+
+use crate::clock;
+use crate::error::PolicyEngineError;
+use crate::jose::{self, Jwks};
+
+// Claims mapped onto dedicated context fields; everything else lands in
+// user_attributes
+const REGISTERED_CLAIMS: &[&str] = &[
+    "iss", "sub", "aud", "exp", "nbf", "iat", "jti", "azp", "amr", "acr", "auth_time", "sid", "nonce",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Factor {
+    Knowledge,
+    Possession,
+    Inherence,
+}
+
+// Factor category of an amr value (RFC 8176) that is an authentication
+// factor in its own right
+fn factor(method: &str) -> Option<Factor> {
+    match method {
+        "pwd" | "pin" => Some(Factor::Knowledge),
+        "otp" | "sms" | "tel" | "hwk" | "swk" | "sc" => Some(Factor::Possession),
+        "fpt" | "face" | "iris" | "retina" | "vbm" => Some(Factor::Inherence),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct JwtOptions {
+    // Required `aud` value. Unset, tokens carrying an aud are rejected:
+    // they are meant for some specific audience, not necessarily this one.
+    pub audience: Option<String>,
+    pub issuer: Option<String>,
+    // Tokens without `exp` never expire and are rejected unless this is
+    // turned off
+    pub require_exp: bool,
+    // Dotted claim paths for roles and groups
+    pub roles_claim: String,
+    pub groups_claim: String,
+}
+
+impl Default for JwtOptions {
+    fn default() -> Self {
+        JwtOptions {
+            audience: None,
+            issuer: None,
+            require_exp: true,
+            roles_claim: "roles".to_string(),
+            groups_claim: "groups".to_string(),
+        }
+    }
+}
+
+fn claim<'a>(claims: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(claims, |value, segment| value.get(segment))
+}
+
+// Arrays of strings, or a space-separated string as in `scope`
+fn strings(value: Option<&Value>) -> Vec<String> {
+    match value {
+        Some(Value::Array(items)) => items.iter().filter_map(Value::as_str).map(str::to_string).collect(),
+        Some(Value::String(s)) => s.split_whitespace().map(str::to_string).collect(),
+        _ => Vec::new(),
+    }
+}
+
+pub(crate) fn check_claims(claims: &Value, options: &JwtOptions) -> Result<(), String> {
+    jose::check_times(claims, clock::now())?;
+    if options.require_exp && claims.get("exp").and_then(Value::as_i64).is_none() {
+        return Err("token has no expiry".to_string());
+    }
+    if let Some(issuer) = &options.issuer {
+        if claims.get("iss").and_then(Value::as_str) != Some(issuer.as_str()) {
+            return Err("unexpected issuer".to_string());
+        }
+    }
+    match &options.audience {
+        Some(audience) if !strings(claims.get("aud")).contains(audience) => {
+            Err("token is not intended for this audience".to_string())
+        }
+        None if claims.get("aud").is_some() => Err("token has an audience but none is configured".to_string()),
+        _ => Ok(()),
+    }
+}
+
+// Partial PolicyContext (JSON object) holding only the fields the token
+// supplies, so callers can merge it with request attributes
+pub fn map_claims(claims: &Value, options: &JwtOptions) -> Map<String, Value> {
+    let mut context = Map::new();
+    if let Some(sub) = claims.get("sub").and_then(Value::as_str) {
+        context.insert("user_id".to_string(), json!(sub));
+    }
+
+    let roles = claim(claims, &options.roles_claim).or_else(|| claim(claims, "realm_access.roles"));
+    if roles.is_some() {
+        context.insert("user_roles".to_string(), json!(strings(roles)));
+    }
+    if let Some(groups) = claim(claims, &options.groups_claim) {
+        context.insert("user_groups".to_string(), json!(strings(Some(groups))));
+    }
+
+    // mfa_verified: an explicit "mfa" method or factors of two distinct
+    // categories (a password and a PIN are both something you know)
+    let amr = strings(claims.get("amr"));
+    if !amr.is_empty() {
+        let factors = amr.iter().filter_map(|m| factor(m)).collect::<HashSet<Factor>>().len();
+        context.insert("auth_method".to_string(), json!(amr[0]));
+        context.insert("mfa_verified".to_string(), json!(amr.iter().any(|m| m == "mfa") || factors >= 2));
+    } else if let Some(acr) = claims.get("acr").and_then(Value::as_str) {
+        context.insert("auth_method".to_string(), json!(acr));
+    }
+
+    if let Some(sid) = claims.get("sid").and_then(Value::as_str) {
+        context.insert("session_id".to_string(), json!(sid));
+    }
+    if let Some(auth_time) = claims.get("auth_time").and_then(Value::as_i64) {
+        if let Some(authenticated) = Utc.timestamp_opt(auth_time, 0).single() {
+            let age = (clock::now() - authenticated).num_seconds().max(0);
+            // chrono durations serialize as [seconds, nanoseconds]
+            context.insert("session_age".to_string(), json!([age, 0]));
+        }
+    }
+
+    let mapped: Vec<&str> = [options.roles_claim.as_str(), options.groups_claim.as_str()]
+        .into_iter()
+        .chain(REGISTERED_CLAIMS.iter().copied())
+        .collect();
+    let attributes: Map<String, Value> = claims
+        .as_object()
+        .map(|all| {
+            all.iter()
+                .filter(|(name, _)| !mapped.contains(&name.as_str()))
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect()
+        })
+        .unwrap_or_default();
+    if !attributes.is_empty() {
+        context.insert("user_attributes".to_string(), Value::Object(attributes));
+    }
+    context
+}
+
+// Validates an RS256/ES256 JWT against a JWKS and returns the partial
+// context JSON. `options_json` may set audience, issuer, require_exp,
+// roles_claim and groups_claim.
+#[wasm_bindgen]
+pub fn context_from_jwt(token: &str, jwks_json: &str, options_json: Option<String>) -> Result<String, JsValue> {
+    let jwks: Jwks = serde_json::from_str(jwks_json)
+        .map_err(|e| PolicyEngineError::parse(format!("Failed to parse JWKS: {}", e)).logged())?;
+    let options: JwtOptions = match options_json {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse JWT options: {}", e)).logged())?,
+        None => JwtOptions::default(),
+    };
+
+    let (_, claims) = jose::verify(token, &jwks)
+        .and_then(|(header, claims)| check_claims(&claims, &options).map(|_| (header, claims)))
+        .map_err(|reason| {
+            PolicyEngineError::validation(format!("Invalid JWT: {}", reason))
+                .with_details(json!({ "reason": reason }))
+                .logged()
+        })?;
+
+    Ok(Value::Object(map_claims(&claims, &options)).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mfa(amr: Value) -> Option<Value> {
+        map_claims(&json!({ "sub": "u1", "amr": amr }), &JwtOptions::default()).get("mfa_verified").cloned()
+    }
+
+    #[test]
+    fn mfa_needs_two_factor_categories() {
+        assert_eq!(mfa(json!(["pwd", "pwd"])), Some(json!(false)));
+        assert_eq!(mfa(json!(["pwd", "pin"])), Some(json!(false)));
+        assert_eq!(mfa(json!(["pwd", "otp"])), Some(json!(true)));
+        assert_eq!(mfa(json!(["hwk", "fpt"])), Some(json!(true)));
+        assert_eq!(mfa(json!(["mfa"])), Some(json!(true)));
+    }
+
+    #[test]
+    fn claims_need_expiry_and_a_configured_audience() {
+        let exp = clock::now().timestamp() + 600;
+        let options = JwtOptions::default();
+        assert!(check_claims(&json!({ "sub": "u1", "exp": exp }), &options).is_ok());
+        assert!(check_claims(&json!({ "sub": "u1" }), &options).is_err());
+        assert!(check_claims(&json!({ "sub": "u1" }), &JwtOptions { require_exp: false, ..JwtOptions::default() }).is_ok());
+        assert!(check_claims(&json!({ "sub": "u1", "exp": exp, "aud": "api" }), &options).is_err());
+
+        let options = JwtOptions { audience: Some("api".to_string()), ..JwtOptions::default() };
+        assert!(check_claims(&json!({ "sub": "u1", "exp": exp, "aud": ["web", "api"] }), &options).is_ok());
+        assert!(check_claims(&json!({ "sub": "u1", "exp": exp, "aud": "web" }), &options).is_err());
+        assert!(check_claims(&json!({ "sub": "u1", "exp": exp }), &options).is_err());
+    }
+}
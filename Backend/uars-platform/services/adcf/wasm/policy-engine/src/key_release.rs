@@ -0,0 +1,171 @@
+use wasm_bindgen::prelude::*;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::Aes256Gcm;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+use serde::Serialize;
+use serde_json::json;
+use zeroize::Zeroizing;
+
+use crate::decision::Decision;
+use crate::error::{to_json, PolicyEngineError};
+use crate::expr::{Expr, Value};
+use crate::obligations::ObligationCall;
+use crate::{guard, PolicyEngine, PolicyResult, PolicySelection};
+
+// Obligation a PERMIT rule carries to release data keys: `release_key()`
+// for any configured KEK, `release_key("capsules-2024")` for one KEK
+pub const RELEASE_KEY_OBLIGATION: &str = "release_key";
+
+// Wrapped key layout: version, 12-byte nonce, AES-256-GCM ciphertext and
+// tag. The resource id is the associated data, so a wrapped key only
+// unwraps for the resource it was wrapped for.
+const WRAP_VERSION: u8 = 1;
+const NONCE_LEN: usize = 12;
+
+fn kek(key: &[u8]) -> Result<Aes256Gcm, PolicyEngineError> {
+    Aes256Gcm::new_from_slice(key)
+        .map_err(|_| PolicyEngineError::validation(format!("Key-encryption key must be 32 bytes, got {}", key.len())))
+}
+
+// Wraps a data key for `resource_id` under a 32-byte KEK. For capsule
+// producers; the engine only ever unwraps.
+#[wasm_bindgen]
+pub fn wrap_data_key(kek_bytes: &[u8], data_key: &[u8], resource_id: &str) -> Result<Vec<u8>, JsValue> {
+    let cipher = kek(kek_bytes).map_err(|e| e.logged())?;
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, Payload { msg: data_key, aad: resource_id.as_bytes() })
+        .map_err(|_| PolicyEngineError::internal("Failed to wrap data key").logged())?;
+    let mut wrapped = Vec::with_capacity(1 + NONCE_LEN + ciphertext.len());
+    wrapped.push(WRAP_VERSION);
+    wrapped.extend_from_slice(&nonce);
+    wrapped.extend_from_slice(&ciphertext);
+    Ok(wrapped)
+}
+
+fn unwrap_data_key(cipher: &Aes256Gcm, wrapped: &[u8], resource_id: &str) -> Result<Zeroizing<Vec<u8>>, PolicyEngineError> {
+    if wrapped.len() <= 1 + NONCE_LEN || wrapped[0] != WRAP_VERSION {
+        return Err(PolicyEngineError::parse("Not a wrapped data key"));
+    }
+    let (nonce, ciphertext) = wrapped[1..].split_at(NONCE_LEN);
+    cipher
+        .decrypt(nonce.into(), Payload { msg: ciphertext, aad: resource_id.as_bytes() })
+        .map(Zeroizing::new)
+        .map_err(|_| PolicyEngineError::validation("Data key unwrap failed (wrong KEK, wrong resource or tampered key)"))
+}
+
+// What evaluate_key_release returns: the decision, and the data key
+// (base64) when the decision released it
+#[derive(Serialize)]
+struct KeyRelease {
+    result: PolicyResult,
+    released: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    withheld: Option<String>,
+}
+
+// Whether the result's obligations release keys wrapped under `kek_id`
+fn releases(result: &PolicyResult, kek_id: &str) -> bool {
+    let specs: Vec<String> = serde_json::from_str(&result.obligations).unwrap_or_default();
+    specs.iter().map(|spec| ObligationCall::parse(spec)).any(|call| {
+        call.id == RELEASE_KEY_OBLIGATION
+            && match call.args.as_slice() {
+                [] => true,
+                [Expr::Literal(Value::String(id))] => id == kek_id,
+                _ => false,
+            }
+    })
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Registers a 32-byte key-encryption key. KEKs are host bindings: not
+    // part of snapshots, and callers should drop their copy once set.
+    #[wasm_bindgen]
+    pub fn set_key_encryption_key(&mut self, kek_id: &str, key: &[u8]) -> Result<(), JsValue> {
+        let cipher = kek(key).map_err(|e| e.logged())?;
+        self.key_encryption_keys.insert(kek_id.to_string(), cipher);
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn remove_key_encryption_key(&mut self, kek_id: &str) -> bool {
+        self.key_encryption_keys.remove(kek_id).is_some()
+    }
+
+    // Evaluates an access request (stateful, like evaluate) and unwraps
+    // the data key for its resource_id only when the decision is PERMIT
+    // and carries a release_key obligation covering `kek_id`, so capsule
+    // decryption is gated by the same policies as access. Returns a JSON
+    // KeyRelease; `withheld` says why a PERMIT released nothing.
+    #[wasm_bindgen]
+    pub fn evaluate_key_release(&mut self, context_json: &str, kek_id: &str, wrapped_key: &[u8]) -> Result<String, JsValue> {
+        if !self.key_encryption_keys.contains_key(kek_id) {
+            return Err(PolicyEngineError::not_found(format!("No key-encryption key '{}'", kek_id))
+                .with_details(json!({ "kek_id": kek_id }))
+                .logged()
+                .into());
+        }
+        let context = self.parse_context(context_json)?;
+        if context.resource_id.is_empty() {
+            return Err(PolicyEngineError::validation("Key release needs the resource_id the key was wrapped for")
+                .with_details(json!({ "field": "resource_id" }))
+                .logged()
+                .into());
+        }
+        let resource_id = context.resource_id.clone();
+        let result = guard::guarded(|| self.evaluate_request(context, PolicySelection::Global));
+
+        let mut release = KeyRelease { result, released: false, key: None, withheld: None };
+        if release.result.decision != Decision::Permit {
+            return Ok(to_json(&release)?);
+        }
+        if !releases(&release.result, kek_id) {
+            release.withheld = Some(format!("The permitting rule carries no {} obligation for '{}'", RELEASE_KEY_OBLIGATION, kek_id));
+            return Ok(to_json(&release)?);
+        }
+        let cipher = &self.key_encryption_keys[kek_id];
+        let key = unwrap_data_key(cipher, wrapped_key, &resource_id).map_err(|e| e.logged())?;
+        release.key = Some(STANDARD.encode(key.as_slice()));
+        release.released = true;
+        if self.debug_mode {
+            console_log!("Released data key for {} under KEK {}", resource_id, kek_id);
+        }
+        Ok(to_json(&release)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn permit(obligations: &[&str]) -> PolicyResult {
+        let mut result = PolicyResult::new(Decision::Permit, "permitted", 1.0);
+        result.obligations = serde_json::to_string(obligations).unwrap().into();
+        result
+    }
+
+    #[test]
+    fn data_keys_unwrap_only_for_their_resource_and_kek() {
+        let wrapped = wrap_data_key(&[1; 32], b"data key", "capsule-1").unwrap();
+        let key = unwrap_data_key(&kek(&[1; 32]).unwrap(), &wrapped, "capsule-1").unwrap();
+        assert_eq!(key.as_slice(), b"data key");
+
+        assert!(unwrap_data_key(&kek(&[1; 32]).unwrap(), &wrapped, "capsule-2").is_err());
+        assert!(unwrap_data_key(&kek(&[2; 32]).unwrap(), &wrapped, "capsule-1").is_err());
+        let mut tampered = wrapped.clone();
+        tampered[1 + NONCE_LEN] ^= 1;
+        assert!(unwrap_data_key(&kek(&[1; 32]).unwrap(), &tampered, "capsule-1").is_err());
+    }
+
+    #[test]
+    fn only_a_matching_release_key_obligation_releases() {
+        assert!(releases(&permit(&["release_key()"]), "k1"));
+        assert!(releases(&permit(&["log_access", "release_key(\"k1\")"]), "k1"));
+        assert!(!releases(&permit(&["release_key(\"k2\")"]), "k1"));
+        assert!(!releases(&permit(&["log_access"]), "k1"));
+    }
+}
@@ -0,0 +1,205 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use crate::attributes::canonical_path;
+use crate::error::PolicyEngineError;
+use crate::expr::{ExprError, Value};
+use crate::PolicyEngine;
+
+// Ordered levels per attribute path, lowest first, e.g.
+// "classification": ["public", "internal", "confidential", "secret"].
+// Ordering comparisons against that attribute use the level order.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Lattices {
+    levels: HashMap<String, Vec<String>>,
+    pub security: SecurityLattice,
+}
+
+// Security labels for dominates(): a hierarchical level plus a set of
+// compartments. A label is written "level" or "level:comp1,comp2", or as
+// a map {"level": ..., "compartments": [...]}.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecurityLattice {
+    // Lowest first
+    pub levels: Vec<String>,
+    // Known compartments; empty accepts any
+    #[serde(default)]
+    pub compartments: Vec<String>,
+}
+
+impl Default for SecurityLattice {
+    fn default() -> Self {
+        SecurityLattice {
+            levels: ["public", "internal", "confidential", "classified"].map(String::from).to_vec(),
+            compartments: Vec::new(),
+        }
+    }
+}
+
+struct Label {
+    level: usize,
+    compartments: BTreeSet<String>,
+}
+
+impl SecurityLattice {
+    fn label(&self, value: &Value) -> Result<Label, ExprError> {
+        let (level, compartments): (&str, Vec<String>) = match value {
+            Value::String(text) => match text.split_once(':') {
+                Some((level, list)) => (
+                    level,
+                    list.split(',').map(str::trim).filter(|c| !c.is_empty()).map(String::from).collect(),
+                ),
+                None => (text, Vec::new()),
+            },
+            Value::Map(map) => {
+                let level = match map.get("level") {
+                    Some(Value::String(level)) => level.as_str(),
+                    _ => return Err(ExprError::new("Security label map needs a string 'level'")),
+                };
+                let compartments = match map.get("compartments") {
+                    Some(Value::List(items)) => items
+                        .iter()
+                        .map(|item| match item {
+                            Value::String(c) => Ok(c.clone()),
+                            other => Err(ExprError::new(format!("Compartment must be a string, found {}", other.type_name()))),
+                        })
+                        .collect::<Result<_, _>>()?,
+                    None | Some(Value::Null) => Vec::new(),
+                    Some(other) => {
+                        return Err(ExprError::new(format!("Compartments must be a list, found {}", other.type_name())));
+                    }
+                };
+                (level, compartments)
+            }
+            other => return Err(ExprError::new(format!("Expected a security label, found {}", other.type_name()))),
+        };
+
+        let level = self
+            .levels
+            .iter()
+            .position(|l| l == level.trim())
+            .ok_or_else(|| ExprError::new(format!("Unknown security level '{}'", level.trim())))?;
+        if let Some(unknown) = compartments
+            .iter()
+            .find(|c| !self.compartments.is_empty() && !self.compartments.contains(c))
+        {
+            return Err(ExprError::new(format!("Unknown compartment '{}'", unknown)));
+        }
+        Ok(Label { level, compartments: compartments.into_iter().collect() })
+    }
+
+    // `a` dominates `b` when its level is at least b's and it holds every
+    // compartment of b. Read-down is dominates(subject, object); the
+    // *-property (no write-down) is dominates(object, subject). A missing
+    // label dominates nothing and is dominated by nothing.
+    pub fn dominates(&self, a: &Value, b: &Value) -> Result<bool, ExprError> {
+        if *a == Value::Null || *b == Value::Null {
+            return Ok(false);
+        }
+        let (a, b) = (self.label(a)?, self.label(b)?);
+        Ok(a.level >= b.level && a.compartments.is_superset(&b.compartments))
+    }
+}
+
+impl Lattices {
+    // Levels of the lattice on an attribute, lowest first
+    pub fn levels(&self, path: &[String]) -> Option<&[String]> {
+        let segments: Vec<&str> = path.iter().map(String::as_str).collect();
+        self.levels.get(&canonical_path(&segments)).map(Vec::as_slice)
+    }
+
+    pub fn rank(&self, path: &[String], value: &str) -> Option<Result<usize, ExprError>> {
+        let segments: Vec<&str> = path.iter().map(String::as_str).collect();
+        let levels = self.levels.get(&canonical_path(&segments))?;
+        Some(levels.iter().position(|level| level == value).ok_or_else(|| {
+            ExprError::new(format!("'{}' is not a level of {}", value, path.join(".")))
+        }))
+    }
+}
+
+fn canonical(attribute: &str) -> String {
+    canonical_path(&attribute.split('.').collect::<Vec<_>>())
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // `attribute` is any path naming the attribute (e.g. "classification"
+    // or "resource.classification"); `levels_json` is a JSON array, lowest
+    // first
+    #[wasm_bindgen]
+    pub fn set_lattice(&mut self, attribute: &str, levels_json: &str) -> Result<(), JsValue> {
+        let levels: Vec<String> = serde_json::from_str(levels_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse lattice levels: {}", e)).logged())?;
+
+        let mut seen = HashSet::new();
+        if let Some(duplicate) = levels.iter().find(|level| !seen.insert(level.as_str())) {
+            return Err(PolicyEngineError::validation(format!(
+                "Lattice for '{}' lists '{}' more than once",
+                attribute, duplicate
+            ))
+            .with_details(json!({ "attribute": attribute, "level": duplicate }))
+            .logged()
+            .into());
+        }
+        self.lattices.levels.insert(canonical(attribute), levels);
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn remove_lattice(&mut self, attribute: &str) -> bool {
+        self.lattices.levels.remove(&canonical(attribute)).is_some()
+    }
+
+    // Replaces the security lattice used by dominates(); see
+    // SecurityLattice for the JSON shape
+    #[wasm_bindgen]
+    pub fn set_security_lattice(&mut self, lattice_json: &str) -> Result<(), JsValue> {
+        let lattice: SecurityLattice = serde_json::from_str(lattice_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse security lattice: {}", e)).logged())?;
+        if lattice.levels.is_empty() {
+            return Err(PolicyEngineError::validation("Security lattice needs at least one level").logged().into());
+        }
+        self.lattices.security = lattice;
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn get_security_lattice(&self) -> String {
+        serde_json::to_string(&self.lattices.security).unwrap_or_default()
+    }
+
+    // JSON object of canonical attribute name -> levels
+    #[wasm_bindgen]
+    pub fn get_lattices(&self) -> String {
+        let sorted: BTreeMap<&String, &Vec<String>> = self.lattices.levels.iter().collect();
+        serde_json::to_string(&sorted).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn label(text: &str) -> Value {
+        Value::String(text.to_string())
+    }
+
+    #[test]
+    fn reads_need_the_level_and_every_compartment() {
+        let lattice = SecurityLattice::default();
+        let dominates = |a: &str, b: &str| lattice.dominates(&label(a), &label(b)).unwrap();
+        assert!(dominates("confidential", "internal"));
+        assert!(!dominates("internal", "confidential"));
+        assert!(dominates("classified:nuclear,crypto", "confidential:crypto"));
+        assert!(!dominates("classified:crypto", "confidential:crypto,nuclear"));
+
+        assert!(!lattice.dominates(&Value::Null, &label("public")).unwrap());
+        assert!(!lattice.dominates(&label("classified"), &Value::Null).unwrap());
+        assert!(lattice.dominates(&label("top-secret"), &label("public")).is_err());
+
+        let closed = SecurityLattice { compartments: vec!["crypto".to_string()], ..SecurityLattice::default() };
+        assert!(closed.dominates(&label("classified:forged"), &label("public")).is_err());
+    }
+}
@@ -1,66 +1,403 @@
+// The context schema in validation.rs is one large json! literal
+#![recursion_limit = "256"]
+
 use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use schemars::JsonSchema;
+use std::borrow::Cow;
+use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::sync::Arc;
 use chrono::{DateTime, Utc, Duration};
 
-#[wasm_bindgen]
-extern "C" {
-    #[wasm_bindgen(js_namespace = console)]
-    fn log(s: &str);
-    
-    #[wasm_bindgen(js_namespace = console, js_name = log)]
-    fn log_u32(a: u32);
+// Logging goes through the level-aware sink in `logging`
+macro_rules! log_at {
+    ($level:expr, $($t:tt)*) => {
+        if $crate::logging::enabled($level) {
+            $crate::logging::write($level, &format_args!($($t)*).to_string())
+        }
+    }
 }
 
 macro_rules! console_log {
-    ($($t:tt)*) => (log(&format_args!($($t)*).to_string()))
+    ($($t:tt)*) => (log_at!($crate::logging::LogLevel::Info, $($t)*))
 }
 
+// Smaller, slower allocator for size-constrained builds
+#[cfg(feature = "wee_alloc")]
+#[global_allocator]
+static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
+
+pub mod admission;
+pub mod advice;
+pub mod approval;
+#[cfg(feature = "crypto")]
+pub mod attestation;
+mod attributes;
+pub mod bag;
+pub mod baseline;
+pub mod batch;
+pub mod break_glass;
+pub mod build_info;
+pub mod bundle;
+pub mod calendar;
+pub mod capsule;
+pub mod challenge;
+pub mod clock;
+pub mod confidence;
+pub mod consent;
+pub mod constraints;
+pub mod coverage;
+pub mod decision;
+#[cfg(feature = "crypto")]
+pub mod decision_token;
+pub mod dedupe;
+pub mod definitions;
+pub mod delegation;
+pub mod delta;
+pub mod dependencies;
+pub mod deployment;
+pub mod derived;
+pub mod diagnostics;
+pub mod diff;
+pub mod envoy;
+pub mod environment;
+mod digest;
+pub mod error;
+pub mod experiments;
+pub mod explain;
+pub mod expr;
+mod functions;
+pub mod filter;
+pub mod format;
+pub mod freshness;
+pub mod fuzz;
+#[cfg(feature = "geo")]
+pub mod geo;
+#[cfg(feature = "mmdb")]
+pub mod geoip;
+pub mod graph;
+pub mod graphql;
+pub mod guard;
+pub mod history;
+pub mod http;
+#[cfg(feature = "crypto")]
+pub mod jose;
+#[cfg(feature = "crypto")]
+pub mod jwt;
+#[cfg(feature = "crypto")]
+pub mod key_release;
+pub mod lattice;
+pub mod limits;
+pub mod logging;
+pub mod masking;
+pub mod messages;
+pub mod metadata;
+pub mod obligations;
+pub mod permissions;
+#[cfg(feature = "persistence")]
+pub mod persistence;
+pub mod profile;
+#[cfg(feature = "proto")]
+pub mod proto;
+#[cfg(feature = "async")]
+pub mod providers;
+pub mod purpose;
+pub mod quota;
+pub mod redaction;
+pub mod replay;
+pub mod tenants;
+pub mod templates;
+pub mod testing;
+pub mod threat_feed;
+pub mod usage;
+pub mod validation;
+pub mod validity;
+pub mod velocity;
+pub mod vocabulary;
+#[cfg(feature = "crypto")]
+pub mod watermark;
+#[cfg(feature = "yaml")]
+pub mod yaml;
+pub mod risk;
+pub mod rule_library;
+pub mod schema;
+pub mod scoring;
+pub mod session;
+pub mod snapshot;
+pub mod specialize;
+pub mod staging;
+pub mod stats;
+pub mod strict;
+#[cfg(feature = "sync")]
+pub mod sync;
+
+use advice::{Advice, Severity};
+use approval::{ApprovalRequest, ApprovalSpec};
+#[cfg(feature = "crypto")]
+use attestation::AttestationVerifier;
+use bag::AttributeBag;
+use baseline::BaselineTracker;
+use break_glass::BreakGlassEntry;
+use challenge::ChallengeSpec;
+use confidence::{AttributeQuality, ConfidenceModel};
+use coverage::CoverageTracker;
+use decision::Decision;
+#[cfg(feature = "crypto")]
+use decision_token::DecisionSigner;
+use dedupe::DedupeTracker;
+use dependencies::DependencyIndex;
+use experiments::Experiments;
+use delegation::{DelegationGrant, DelegationLink};
+use definitions::Definitions;
+use environment::EnvironmentSources;
+use envoy::EnvoyConfig;
+use error::PolicyEngineError;
+use expr::{Expr, Value};
+use calendar::Calendar;
+use deployment::DeploymentConfig;
+use derived::DerivedAttribute;
+use consent::ConsentRegistry;
+use freshness::FreshnessConfig;
+use functions::EvalScope;
+#[cfg(feature = "geo")]
+use geo::GeoTracker;
+#[cfg(feature = "mmdb")]
+use geoip::GeoIpDatabase;
+use history::AttributeHistory;
+use lattice::Lattices;
+use limits::{Budget, EvaluationLimits};
+use messages::MessageCatalogs;
+use metadata::PolicySelector;
+use obligations::PolicyObligation;
+use profile::Phase;
+#[cfg(feature = "telemetry")]
+use profile::EvaluationProfile;
+use purpose::PurposeRegistry;
+use quota::QuotaTracker;
+use redaction::Redactor;
+use templates::PolicyTemplate;
+use tenants::Tenant;
+use threat_feed::ThreatFeed;
+use usage::UsageTracker;
+use risk::RiskScorer;
+use rule_library::RuleLibrary;
+use scoring::Scorer;
+#[cfg(feature = "telemetry")]
+use stats::StatsTracker;
+use validity::GrantedDecisions;
+use velocity::OperationCounters;
+use vocabulary::{CombiningAlgorithm, DeviceTrust, Effect, ThreatLevel};
+
 // Policy evaluation result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[wasm_bindgen]
 pub struct PolicyResult {
+    #[wasm_bindgen(skip)]
+    pub decision: Decision,
+    
+    // Fixed reasons are not allocated per evaluation
+    #[wasm_bindgen(skip)]
+    pub reason: Cow<'static, str>,
+    
+    pub confidence: f64,
+    
+    // JSON string arrays, shared with the rule that produced them
+    #[wasm_bindgen(skip)]
+    pub obligations: Arc<str>,
+    
+    #[wasm_bindgen(skip)]
+    pub advice: Arc<str>,
+    
     #[wasm_bindgen(getter_with_clone)]
-    pub decision: String,
+    pub challenge: String, // JSON ChallengeSpec when decision is CHALLENGE, else "null"
     
     #[wasm_bindgen(getter_with_clone)]
-    pub reason: String,
+    pub approval: String, // JSON ApprovalRequest when decision is PENDING_APPROVAL, else "null"
     
-    pub confidence: f64,
+    #[wasm_bindgen(getter_with_clone)]
+    pub acknowledged_obligations: String, // JSON string of obligation IDs handled by the host
+    
+    pub retry_after: Option<f64>, // Seconds until a rate-limited request may succeed
+    
+    #[wasm_bindgen(getter_with_clone)]
+    pub policy_id: Option<String>, // Policy that produced the decision, if any
+    
+    #[wasm_bindgen(getter_with_clone)]
+    pub rule_id: Option<String>, // Rule that produced the decision, if any
+    
+    #[wasm_bindgen(getter_with_clone)]
+    pub error_code: Option<String>, // Set when evaluation failed internally (see guard.rs)
+    
+    #[wasm_bindgen(getter_with_clone)]
+    #[serde(default)]
+    pub reason_code: Option<String>, // Stable code for why, from the deciding rule or the engine
+    
+    #[wasm_bindgen(getter_with_clone)]
+    #[serde(default)]
+    pub message: Option<String>, // reason_code in the context's locale (see messages.rs)
+    
+    // Session lifetime cap in seconds, from max_session_age obligations
+    #[serde(default)]
+    pub max_session_age: Option<f64>,
+    
+    // Set by a reauth_required obligation or a session older than the cap
+    #[serde(default)]
+    pub reauth_required: bool,
+    
+    #[wasm_bindgen(getter_with_clone)]
+    #[serde(default)]
+    pub decisive_identity: Option<String>, // For delegated requests, whose permissions decided
     
     #[wasm_bindgen(getter_with_clone)]
-    pub obligations: String, // JSON string
+    #[serde(default)]
+    pub valid_until: Option<String>, // RFC 3339 end of a PERMIT's window, from intent_duration
     
     #[wasm_bindgen(getter_with_clone)]
-    pub advice: String, // JSON string
+    #[serde(default)]
+    pub clipped_by: Option<String>, // Context constraint that turned the decision into a DENY (see constraints.rs)
+    
+    // How long a PEP may reuse the decision, in seconds (see freshness.rs)
+    #[serde(default)]
+    pub ttl_seconds: Option<f64>,
+    
+    // Volatile context fields the decision read; a change to any of them
+    // calls for re-evaluation
+    #[wasm_bindgen(skip)]
+    #[serde(default)]
+    pub revalidate_on: Vec<String>,
+    
+    // Attributes the decision read, as canonical paths; see
+    // notify_attribute_changed
+    #[wasm_bindgen(skip)]
+    #[serde(default)]
+    pub dependencies: Vec<String>,
 }
 
 #[wasm_bindgen]
 impl PolicyResult {
+    // Unknown decision names are rejected
     #[wasm_bindgen(constructor)]
-    pub fn new(decision: String, reason: String, confidence: f64) -> PolicyResult {
-        PolicyResult {
-            decision,
-            reason,
-            confidence,
-            obligations: "[]".to_string(),
-            advice: "[]".to_string(),
+    pub fn create(decision: &str, reason: String, confidence: f64) -> Result<PolicyResult, JsValue> {
+        match Decision::parse(decision) {
+            Some(decision) => Ok(PolicyResult::new(decision, reason, confidence)),
+            None => Err(PolicyEngineError::validation(format!("Unknown decision '{}'", decision)).into()),
         }
     }
     
+    #[wasm_bindgen(getter)]
+    pub fn decision(&self) -> String {
+        self.decision.as_str().to_string()
+    }
+    
+    #[wasm_bindgen(getter)]
+    pub fn reason(&self) -> String {
+        self.reason.to_string()
+    }
+    
+    #[wasm_bindgen(getter)]
+    pub fn obligations(&self) -> String {
+        self.obligations.to_string()
+    }
+    
+    // JSON array of Advice, most severe first
+    #[wasm_bindgen(getter)]
+    pub fn advice(&self) -> String {
+        self.advice.to_string()
+    }
+    
+    #[wasm_bindgen(getter)]
+    pub fn advice_items(&self) -> Vec<Advice> {
+        advice::parse_list(&self.advice)
+    }
+    
+    // The advice of one severity ("info", "notice", "warning", "critical")
+    #[wasm_bindgen]
+    pub fn advice_with_severity(&self, severity: &str) -> Result<Vec<Advice>, JsValue> {
+        let severity = Severity::parse(severity).ok_or_else(|| {
+            PolicyEngineError::validation(format!("Unknown advice severity '{}'", severity))
+                .with_details(serde_json::json!({ "severity": severity }))
+        })?;
+        Ok(advice::parse_list(&self.advice).into_iter().filter(|advice| advice.severity == severity).collect())
+    }
+    
+    // Severity of the most severe advice, if the result has any
+    #[wasm_bindgen(getter)]
+    pub fn highest_advice_severity(&self) -> Option<String> {
+        advice::parse_list(&self.advice).iter().map(|advice| advice.severity).max().map(|severity| severity.as_str().to_string())
+    }
+    
     #[wasm_bindgen(setter)]
     pub fn set_obligations(&mut self, obligations: String) {
-        self.obligations = obligations;
+        self.obligations = obligations.into();
     }
     
     #[wasm_bindgen(setter)]
     pub fn set_advice(&mut self, advice: String) {
-        self.advice = advice;
+        self.advice = advice.into();
+    }
+    
+    #[wasm_bindgen(setter)]
+    pub fn set_challenge(&mut self, challenge: String) {
+        self.challenge = challenge;
+    }
+    
+    #[wasm_bindgen(getter)]
+    pub fn revalidate_on(&self) -> Vec<String> {
+        self.revalidate_on.clone()
+    }
+    
+    #[wasm_bindgen(getter)]
+    pub fn dependencies(&self) -> Vec<String> {
+        self.dependencies.clone()
     }
 }
 
-// Policy context for evaluation
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl PolicyResult {
+    pub fn new(decision: Decision, reason: impl Into<Cow<'static, str>>, confidence: f64) -> PolicyResult {
+        PolicyResult {
+            decision,
+            reason: reason.into(),
+            confidence,
+            obligations: decision::empty_list(),
+            advice: decision::empty_list(),
+            challenge: "null".to_string(),
+            approval: "null".to_string(),
+            acknowledged_obligations: "[]".to_string(),
+            retry_after: None,
+            policy_id: None,
+            rule_id: None,
+            error_code: None,
+            reason_code: None,
+            message: None,
+            max_session_age: None,
+            reauth_required: false,
+            decisive_identity: None,
+            valid_until: None,
+            clipped_by: None,
+            ttl_seconds: None,
+            revalidate_on: Vec::new(),
+            dependencies: Vec::new(),
+        }
+    }
+}
+
+// WebAuthn attestation as returned by navigator.credentials.create(),
+// both fields base64url. Verified by `attestation` (crypto feature).
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DeviceAttestation {
+    pub attestation_object: String,
+    pub client_data_json: String,
+}
+
+// Policy context for evaluation. Every field is optional on the wire;
+// missing fields take the defaults below (empty strings/lists/maps,
+// `false` for booleans including mfa_verified and device_attested,
+// 0.0 risk_score, zero session_age, and the time of parsing for
+// timestamp). Policies can tell a default from a supplied value with
+// `has("field")`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
 pub struct PolicyContext {
     pub request_id: String,
     pub timestamp: DateTime<Utc>,
@@ -75,9 +412,15 @@ pub struct PolicyContext {
     // Device information
     pub device_id: String,
     pub device_type: String,
-    pub device_trust: String,
+    pub device_trust: DeviceTrust,
     pub device_attested: bool,
     
+    // Device evidence, verified when attestation trust anchors are set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_posture_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_attestation: Option<DeviceAttestation>,
+    
     // Network information
     pub ip_address: String,
     pub ip_country: String,
@@ -87,6 +430,7 @@ pub struct PolicyContext {
     
     // Session information
     pub session_id: String,
+    #[schemars(with = "(i64, i32)")]
     pub session_age: Duration,
     pub auth_method: String,
     pub mfa_verified: bool,
@@ -98,7 +442,7 @@ pub struct PolicyContext {
     
     // Risk assessment
     pub risk_score: f64,
-    pub threat_level: String,
+    pub threat_level: ThreatLevel,
     
     // Resource information
     pub resource_type: String,
@@ -110,45 +454,420 @@ pub struct PolicyContext {
     // Intent information
     pub intent_purpose: Option<String>,
     pub intent_justification: Option<String>,
+    #[schemars(with = "Option<(i64, i32)>")]
     pub intent_duration: Option<Duration>,
     
+    // On-behalf-of chain; user_id is the subject (see delegation.rs)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub delegation: Vec<DelegationLink>,
+    
     // Additional context
     pub constraints: HashMap<String, serde_json::Value>,
     pub metadata: HashMap<String, serde_json::Value>,
+    
+    // Provider-reported confidence and observation time of individual
+    // attributes, keyed by path; read by the confidence model
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub attribute_quality: HashMap<String, AttributeQuality>,
+    
+    // BCP 47 tag for the result's localized message, e.g. "de-AT"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+    
+    // Top-level fields present in the incoming JSON; None means every
+    // field counts as supplied (e.g. contexts constructed in Rust)
+    #[serde(skip)]
+    pub supplied_fields: Option<HashSet<String>>,
+    
+    // Free-form attributes when evaluating in attribute-bag mode
+    #[serde(skip)]
+    pub bag: Option<AttributeBag>,
+}
+
+impl Default for PolicyContext {
+    fn default() -> Self {
+        PolicyContext {
+            request_id: String::new(),
+            timestamp: clock::now(),
+            operation: String::new(),
+            user_id: String::new(),
+            user_roles: Vec::new(),
+            user_groups: Vec::new(),
+            user_attributes: HashMap::new(),
+            device_id: String::new(),
+            device_type: String::new(),
+            device_trust: DeviceTrust::Unspecified,
+            device_attested: false,
+            device_posture_token: None,
+            device_attestation: None,
+            ip_address: String::new(),
+            ip_country: String::new(),
+            ip_city: String::new(),
+            network_zone: String::new(),
+            vpn_detected: false,
+            session_id: String::new(),
+            session_age: Duration::zero(),
+            auth_method: String::new(),
+            mfa_verified: false,
+            time_of_day: String::new(),
+            day_of_week: String::new(),
+            business_hours: false,
+            risk_score: 0.0,
+            threat_level: ThreatLevel::Unspecified,
+            resource_type: String::new(),
+            resource_id: String::new(),
+            resource_classification: String::new(),
+            resource_owner: String::new(),
+            resource_attributes: HashMap::new(),
+            intent_purpose: None,
+            intent_justification: None,
+            intent_duration: None,
+            delegation: Vec::new(),
+            constraints: HashMap::new(),
+            metadata: HashMap::new(),
+            attribute_quality: HashMap::new(),
+            locale: None,
+            supplied_fields: None,
+            bag: None,
+        }
+    }
 }
 
 // Policy rule definition
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct PolicyRule {
     pub id: String,
     pub name: String,
     pub description: String,
     pub priority: i32,
     pub condition: String, // Boolean expression
-    pub effect: String,    // PERMIT, DENY, INDETERMINATE
+    pub effect: Effect,
     pub obligations: Vec<String>,
-    pub advice: Vec<String>,
+    pub advice: Vec<Advice>,
+    
+    // Step-up requirement for CHALLENGE rules
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub challenge: Option<ChallengeSpec>,
+    
+    // Approvers and expiry for PENDING_APPROVAL rules
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approval: Option<ApprovalSpec>,
+    
+    // A DENY from this rule may be overridden by evaluate_break_glass
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub break_glass: bool,
+    
+    // Reported as the result's reason_code when this rule decides, and
+    // looked up in the message catalogs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason_code: Option<String>,
+    
+    // What a denied user can do about this rule, shown by explain_deny
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remediation: Option<String>,
+    
+    // Deployment environments the rule applies in; empty means all (see
+    // deployment.rs)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub environments: Vec<String>,
+    
+    #[serde(skip)]
+    #[schemars(skip)]
+    rendered: OnceCell<RenderedOutputs>,
+}
+
+// A rule's obligations and advice as PolicyResult JSON, rendered on its
+// first match and shared by every result it produces after that
+#[derive(Debug, Clone)]
+struct RenderedOutputs {
+    obligations: Arc<str>,
+    advice: Arc<str>,
+}
+
+fn render_list(items: &[String]) -> Arc<str> {
+    if items.is_empty() {
+        return decision::empty_list();
+    }
+    serde_json::to_string(items).unwrap_or_else(|_| "[]".to_string()).into()
+}
+
+// Advice is ranked most severe first
+fn render_advice(advice: &[Advice]) -> Arc<str> {
+    if advice.is_empty() {
+        return decision::empty_list();
+    }
+    let mut ranked = advice.to_vec();
+    advice::rank(&mut ranked);
+    serde_json::to_string(&ranked).unwrap_or_else(|_| "[]".to_string()).into()
+}
+
+impl PolicyRule {
+    fn rendered(&self) -> &RenderedOutputs {
+        self.rendered.get_or_init(|| RenderedOutputs {
+            obligations: render_list(&self.obligations),
+            advice: render_advice(&self.advice),
+        })
+    }
 }
 
 // Policy definition
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Policy {
     pub id: String,
     pub name: String,
     pub version: String,
+    
+    // Document format (see format.rs); older formats are migrated on load
+    #[serde(default = "format::current_format_version")]
+    pub format_version: u32,
     pub description: String,
     pub target: String, // Target expression
     pub rules: Vec<PolicyRule>,
-    pub combining_algorithm: String,
-    pub obligations: Vec<String>,
-    pub advice: Vec<String>,
+    
+    // Named sub-expressions usable as bare identifiers in the target and
+    // rule conditions, e.g. "high_risk": "risk_score > 7.0"
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub definitions: BTreeMap<String, String>,
+    pub combining_algorithm: CombiningAlgorithm,
+    
+    // Appended to the policy's PERMIT and DENY results after the rule's
+    // own, filtered by each item's fulfill_on (see obligations.rs)
+    pub obligations: Vec<PolicyObligation>,
+    pub advice: Vec<Advice>,
+    
+    // Policies are evaluated highest priority first, which decides under
+    // ordered root algorithms (see set_root_algorithm)
+    #[serde(default)]
+    pub priority: i32,
+    
+    // Originating file or URI, for tracing a loaded policy back to source
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    
+    // Shared rules ("library:rule-id") evaluated after the policy's own
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rule_refs: Vec<String>,
+    
+    // Ownership metadata for find_policies; not used in evaluation
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub labels: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    
+    // Disabled policies stay loaded but never apply
+    #[serde(default = "default_enabled", skip_serializing_if = "is_enabled")]
+    pub enabled: bool,
+    
+    // Deployment environments the policy applies in; empty means all
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub environments: Vec<String>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn is_enabled(enabled: &bool) -> bool {
+    *enabled
 }
 
+// Policy with its target and rule conditions parsed at load time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CompiledPolicy {
+    policy: Policy,
+    target: Expr,
+    conditions: Vec<Expr>,
+    
+    // Rules resolved from `rule_refs` against the engine's rule libraries
+    // when the policy is loaded (bundles store them unresolved)
+    #[serde(skip)]
+    linked: Vec<(PolicyRule, Expr)>,
+    
+    // digest::policy_hash of `policy`, computed on first use
+    #[serde(skip)]
+    content_hash: OnceCell<String>,
+}
+
+// Compile error details: where in the expression it failed, the
+// offending token and a suggestion
+fn expression_details(expression: &str, error: &expr::ExprError, policy_id: &str, rule_id: Option<&str>) -> serde_json::Value {
+    let mut details = diagnostics::expression_diagnostic(expression, error).details();
+    details["policy_id"] = serde_json::json!(policy_id);
+    if let Some(rule_id) = rule_id {
+        details["rule_id"] = serde_json::json!(rule_id);
+    }
+    details
+}
+
+impl CompiledPolicy {
+    fn compile(policy: Policy) -> Result<CompiledPolicy, PolicyEngineError> {
+        if let CombiningAlgorithm::Other(algorithm) = &policy.combining_algorithm {
+            return Err(PolicyEngineError::unknown_algorithm(format!(
+                "Policy '{}' uses unknown combining algorithm '{}'",
+                policy.id, algorithm
+            ))
+            .with_details(serde_json::json!({
+                "policy_id": policy.id,
+                "algorithm": algorithm,
+                "expected": CombiningAlgorithm::KNOWN,
+            })));
+        }
+        
+        let definitions = Definitions::compile(&policy)?;
+        
+        let target = expr::parse(&policy.target).map_err(|e| {
+            PolicyEngineError::compile(format!("Policy '{}' target: {}", policy.id, e))
+                .with_details(expression_details(&policy.target, &e, &policy.id, None))
+        })?;
+        let mut target = definitions.apply(&policy, "target", target)?;
+        vocabulary::canonicalize(&mut target);
+        
+        let mut conditions = Vec::with_capacity(policy.rules.len());
+        for rule in &policy.rules {
+            let condition = expr::parse(&rule.condition).map_err(|e| {
+                PolicyEngineError::compile(format!("Policy '{}' rule '{}': {}", policy.id, rule.id, e))
+                    .with_details(expression_details(&rule.condition, &e, &policy.id, Some(&rule.id)))
+            })?;
+            if let Some(Err(e)) = rule.approval.as_ref().map(ApprovalSpec::check) {
+                return Err(PolicyEngineError::validation(format!("Policy '{}' rule '{}': {}", policy.id, rule.id, e))
+                    .with_details(serde_json::json!({ "policy_id": policy.id, "rule_id": rule.id })));
+            }
+            let mut condition = definitions.apply(&policy, &rule.id, condition)?;
+            vocabulary::canonicalize(&mut condition);
+            conditions.push(condition);
+        }
+        
+        Ok(CompiledPolicy { policy, target, conditions, linked: Vec::new(), content_hash: OnceCell::new() })
+    }
+    
+    // Compiles a whole set, failing on the first policy that does not compile
+    fn compile_all(policies: Vec<Policy>) -> Result<Vec<CompiledPolicy>, PolicyEngineError> {
+        policies.into_iter().map(CompiledPolicy::compile).collect()
+    }
+    
+    fn content_hash(&self) -> &str {
+        self.content_hash.get_or_init(|| digest::policy_hash(&self.policy))
+    }
+    
+    // Own rules followed by linked library rules, with their conditions
+    fn rules(&self) -> impl Iterator<Item = (&PolicyRule, &Expr)> {
+        self.policy
+            .rules
+            .iter()
+            .zip(&self.conditions)
+            .chain(self.linked.iter().map(|(rule, condition)| (rule, condition)))
+    }
+}
+
+// Which loaded policies an evaluation considers
+#[derive(Debug, Clone, Copy)]
+enum PolicySelection<'a> {
+    Global,
+    Tenant(&'a str),
+    Staged,
+    Explicit(&'a [CompiledPolicy]),
+    // Active policies an index has not ruled out (see permissions.rs)
+    Indexed(&'a [&'a CompiledPolicy]),
+    // Global policies matching a label selector (see metadata.rs)
+    Scoped(&'a PolicySelector),
+}
+
+impl<'a> PolicySelection<'a> {
+    fn tenant(&self) -> Option<&'a str> {
+        match self {
+            PolicySelection::Tenant(id) => Some(id),
+            _ => None,
+        }
+    }
+}
+
+// Key for per-user engine state (travel history, quota counters) so
+// tenants never observe each other's counters
+fn state_key(tenant: Option<&str>, key: &str) -> String {
+    match tenant {
+        Some(tenant) => format!("{}/{}", tenant, key),
+        None => key.to_string(),
+    }
+}
+
+// Winning order of decisions under permit- and deny-overrides
+const PERMIT_PRECEDENCE: &[Decision] = &[Decision::Permit, Decision::Challenge, Decision::PendingApproval, Decision::Deny, Decision::Indeterminate];
+const DENY_PRECEDENCE: &[Decision] = &[Decision::Deny, Decision::Challenge, Decision::PendingApproval, Decision::Permit, Decision::Indeterminate];
+
 // Policy engine
 #[wasm_bindgen]
 pub struct PolicyEngine {
-    policies: Vec<Policy>,
+    policies: Vec<CompiledPolicy>,
     debug_mode: bool,
+    strict_mode: bool,
+    root_algorithm: CombiningAlgorithm,
+    root_obligations: Vec<PolicyObligation>,
+    root_advice: Vec<Advice>,
+    risk: Option<RiskScorer>,
+    // Host risk signals by name, kept across profile changes
+    risk_signals: BTreeMap<String, js_sys::Function>,
+    confidence: Option<ConfidenceModel>,
+    scorer: Option<Scorer>,
+    threat_feed: Option<ThreatFeed>,
+    #[cfg(feature = "geo")]
+    geo: GeoTracker,
+    #[cfg(feature = "mmdb")]
+    geoip: Option<GeoIpDatabase>,
+    baselines: BaselineTracker,
+    history: AttributeHistory,
+    operation_counters: OperationCounters,
+    messages: MessageCatalogs,
+    redactor: Option<Redactor>,
+    obligation_handlers: HashMap<String, js_sys::Function>,
+    declared_obligations: BTreeSet<String>,
+    quotas: QuotaTracker,
+    dedupe: DedupeTracker,
+    tenants: HashMap<String, Tenant>,
+    context_schema: Option<serde_json::Value>,
+    staged: Option<Vec<CompiledPolicy>>,
+    experiments: Experiments,
+    coverage: RefCell<CoverageTracker>,
+    usage: RefCell<UsageTracker>,
+    dependencies: RefCell<DependencyIndex>,
+    #[cfg(feature = "telemetry")]
+    stats: RefCell<StatsTracker>,
+    limits: EvaluationLimits,
+    freshness: FreshnessConfig,
+    calendar: Option<Calendar>,
+    deployment: DeploymentConfig,
+    derived: BTreeMap<String, DerivedAttribute>,
+    budget: Budget,
+    deterministic: Cell<bool>,
+    templates: HashMap<String, PolicyTemplate>,
+    rule_libraries: HashMap<String, RuleLibrary>,
+    #[cfg(feature = "crypto")]
+    attestation: Option<AttestationVerifier>,
+    #[cfg(feature = "crypto")]
+    decision_signer: Option<DecisionSigner>,
+    #[cfg(feature = "crypto")]
+    bundle_cipher: Option<aes_gcm::Aes256Gcm>,
+    #[cfg(feature = "crypto")]
+    key_encryption_keys: HashMap<String, aes_gcm::Aes256Gcm>,
+    #[cfg(feature = "crypto")]
+    watermark_key: Option<watermark::WatermarkKey>,
+    environment: EnvironmentSources,
+    lattices: Lattices,
+    delegation_grants: HashMap<String, DelegationGrant>,
+    break_glass_seconds: f64,
+    break_glass_log: VecDeque<BreakGlassEntry>,
+    approvals: HashMap<String, ApprovalRequest>,
+    purposes: PurposeRegistry,
+    consents: ConsentRegistry,
+    granted: GrantedDecisions,
+    envoy: EnvoyConfig,
+    #[cfg(feature = "telemetry")]
+    last_profile: RefCell<EvaluationProfile>,
+    #[cfg(feature = "sync")]
+    sync: Option<sync::PolicySync>,
+    #[cfg(feature = "async")]
+    attribute_providers: providers::AttributeProviders,
 }
 
 #[wasm_bindgen]
@@ -159,6 +878,72 @@ impl PolicyEngine {
         PolicyEngine {
             policies: Vec::new(),
             debug_mode: false,
+            strict_mode: false,
+            root_algorithm: CombiningAlgorithm::DenyOverrides,
+            root_obligations: Vec::new(),
+            root_advice: Vec::new(),
+            risk: None,
+            risk_signals: BTreeMap::new(),
+            confidence: None,
+            scorer: None,
+            threat_feed: None,
+            #[cfg(feature = "geo")]
+            geo: GeoTracker::new(),
+            #[cfg(feature = "mmdb")]
+            geoip: None,
+            baselines: BaselineTracker::default(),
+            history: AttributeHistory::default(),
+            operation_counters: OperationCounters::default(),
+            messages: MessageCatalogs::default(),
+            redactor: None,
+            obligation_handlers: HashMap::new(),
+            declared_obligations: BTreeSet::new(),
+            quotas: QuotaTracker::default(),
+            dedupe: DedupeTracker::default(),
+            tenants: HashMap::new(),
+            context_schema: None,
+            staged: None,
+            experiments: Experiments::default(),
+            coverage: RefCell::new(CoverageTracker::default()),
+            usage: RefCell::new(UsageTracker::default()),
+            dependencies: RefCell::new(DependencyIndex::default()),
+            #[cfg(feature = "telemetry")]
+            stats: RefCell::new(StatsTracker::default()),
+            limits: EvaluationLimits::default(),
+            freshness: FreshnessConfig::default(),
+            calendar: None,
+            deployment: DeploymentConfig::default(),
+            derived: BTreeMap::new(),
+            budget: Budget::default(),
+            deterministic: Cell::new(false),
+            templates: HashMap::new(),
+            rule_libraries: HashMap::new(),
+            #[cfg(feature = "crypto")]
+            attestation: None,
+            #[cfg(feature = "crypto")]
+            decision_signer: None,
+            #[cfg(feature = "crypto")]
+            bundle_cipher: None,
+            #[cfg(feature = "crypto")]
+            key_encryption_keys: HashMap::new(),
+            #[cfg(feature = "crypto")]
+            watermark_key: None,
+            environment: EnvironmentSources::default(),
+            lattices: Lattices::default(),
+            delegation_grants: HashMap::new(),
+            break_glass_seconds: break_glass::DEFAULT_BREAK_GLASS_SECONDS,
+            break_glass_log: VecDeque::new(),
+            approvals: HashMap::new(),
+            purposes: PurposeRegistry::default(),
+            consents: ConsentRegistry::default(),
+            granted: GrantedDecisions::default(),
+            envoy: EnvoyConfig::default(),
+            #[cfg(feature = "telemetry")]
+            last_profile: RefCell::new(EvaluationProfile::default()),
+            #[cfg(feature = "sync")]
+            sync: None,
+            #[cfg(feature = "async")]
+            attribute_providers: providers::AttributeProviders::default(),
         }
     }
     
@@ -172,63 +957,222 @@ impl PolicyEngine {
     
     #[wasm_bindgen]
     pub fn load_policy(&mut self, policy_json: &str) -> Result<(), JsValue> {
-        match serde_json::from_str::<Policy>(policy_json) {
-            Ok(policy) => {
-                if self.debug_mode {
-                    console_log!("Loaded policy: {} ({})", policy.name, policy.id);
-                }
-                self.policies.push(policy);
-                Ok(())
-            }
-            Err(e) => {
-                let error_msg = format!("Failed to parse policy: {}", e);
-                console_log!("{}", error_msg);
-                Err(JsValue::from_str(&error_msg))
-            }
-        }
+        let policy: Policy = self.parse_policies(policy_json, "policy").map_err(|e| e.logged())?;
+        self.add_policy(policy)
     }
     
     #[wasm_bindgen]
     pub fn load_policies(&mut self, policies_json: &str) -> Result<(), JsValue> {
-        match serde_json::from_str::<Vec<Policy>>(policies_json) {
-            Ok(policies) => {
-                for policy in policies {
-                    if self.debug_mode {
-                        console_log!("Loading policy: {} ({})", policy.name, policy.id);
-                    }
-                    self.policies.push(policy);
-                }
-                console_log!("Loaded {} policies", self.policies.len());
-                Ok(())
-            }
-            Err(e) => {
-                let error_msg = format!("Failed to parse policies: {}", e);
-                console_log!("{}", error_msg);
-                Err(JsValue::from_str(&error_msg))
-            }
+        let policies: Vec<Policy> = self.parse_policies(policies_json, "policies").map_err(|e| e.logged())?;
+        for policy in policies {
+            self.add_policy(policy)?;
         }
+        console_log!("Loaded {} policies", self.policies.len());
+        Ok(())
+    }
+    
+    // Replaces the active set with `policies_json`. The whole set is
+    // parsed, compiled and validated first; on any failure the previous
+    // set stays active. Returns the number of policies now loaded.
+    #[wasm_bindgen]
+    pub fn load_policies_atomic(&mut self, policies_json: &str) -> Result<usize, JsValue> {
+        let policies: Vec<Policy> = self.parse_policies(policies_json, "policies").map_err(|e| e.logged())?;
+        let compiled = self
+            .prepare_policy_set(policies)
+            .map_err(|e| e.context("Rejected policy set").logged())?;
+        self.policies = compiled;
+        console_log!("Loaded {} policies", self.policies.len());
+        Ok(self.policies.len())
     }
     
     #[wasm_bindgen]
-    pub fn evaluate(&self, context_json: &str) -> Result<PolicyResult, JsValue> {
+    pub fn evaluate(&mut self, context_json: &str) -> Result<PolicyResult, JsValue> {
         if self.debug_mode {
             console_log!("Starting policy evaluation");
         }
         
-        let context: PolicyContext = match serde_json::from_str(context_json) {
-            Ok(ctx) => ctx,
-            Err(e) => {
-                let error_msg = format!("Failed to parse context: {}", e);
-                console_log!("{}", error_msg);
-                return Err(JsValue::from_str(&error_msg));
+        let context = self.parse_context(context_json)?;
+        Ok(guard::guarded(|| self.evaluate_request(context, PolicySelection::Global)))
+    }
+    
+    #[wasm_bindgen]
+    pub fn clear_policies(&mut self) {
+        self.policies.clear();
+        console_log!("Cleared all policies");
+    }
+    
+    #[wasm_bindgen]
+    pub fn get_policy_count(&self) -> usize {
+        self.policies.len()
+    }
+}
+
+impl Default for PolicyEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PolicyEngine {
+    fn add_policy(&mut self, policy: Policy) -> Result<(), JsValue> {
+        if self.debug_mode {
+            console_log!("Loaded policy: {} ({})", policy.name, policy.id);
+        }
+        let compiled = self.compile_policy(policy)
+            .map_err(|e| e.context("Failed to compile policy").logged())?;
+        self.insert_unique(compiled, None);
+        Ok(())
+    }
+    
+    // Compiles a complete replacement set, rejecting it as a whole if any
+    // policy fails to compile or ids collide
+    pub(crate) fn prepare_policy_set(&self, policies: Vec<Policy>) -> Result<Vec<CompiledPolicy>, PolicyEngineError> {
+        let mut seen = HashSet::new();
+        for (index, policy) in policies.iter().enumerate() {
+            if policy.id.trim().is_empty() {
+                return Err(PolicyEngineError::validation(format!("Policy at index {} has no id", index))
+                    .with_details(serde_json::json!({ "index": index })));
             }
-        };
+            if !seen.insert(policy.id.as_str()) {
+                return Err(PolicyEngineError::conflict(format!("Policy id '{}' appears more than once", policy.id))
+                    .with_details(serde_json::json!({ "index": index, "policy_id": policy.id })));
+            }
+            let mut rule_ids = HashSet::new();
+            if let Some(rule) = policy.rules.iter().find(|rule| !rule_ids.insert(rule.id.as_str())) {
+                return Err(PolicyEngineError::conflict(format!("Policy '{}' defines rule '{}' twice", policy.id, rule.id))
+                    .with_details(serde_json::json!({ "index": index, "policy_id": policy.id, "rule_id": rule.id })));
+            }
+        }
+        self.compile_policies(policies)
+    }
+    
+    // Full request pipeline: context enrichment, evaluation, then stateful
+    // post-processing (travel history, baselines, quotas, obligation dispatch)
+    fn evaluate_request(&mut self, mut context: PolicyContext, selection: PolicySelection) -> Result<PolicyResult, JsValue> {
+        #[cfg(feature = "sync")]
+        let _ = self.swap_pending_sync();
+        let started = stats::now_ms();
+        let tenant = selection.tenant();
+        self.enrich_context(&mut context, tenant);
+        self.record_phase(Phase::Enrich, stats::now_ms() - started);
+        let _redacting = self.redact_logs(&context);
+        
+        let result = self.evaluate_context(&context, selection);
+        let started = stats::now_ms();
+        let result = self.complete_request(result, &context, tenant);
+        self.finish_profile(&context, stats::now_ms() - started);
+        result
+    }
+    
+    // Request bookkeeping and post-processing once a decision is made
+    fn complete_request(&mut self, result: Result<PolicyResult, JsValue>, context: &PolicyContext, tenant: Option<&str>) -> Result<PolicyResult, JsValue> {
+        // Track location after evaluation so impossible_travel() compares
+        // against the previous request, not this one
+        #[cfg(feature = "geo")]
+        self.geo.record(&state_key(tenant, &context.user_id), context);
+        self.history.record(&state_key(tenant, &context.user_id), context);
+        
+        let mut result = result?;
+        self.register_approval(&result, tenant);
+        self.apply_delegation(&mut result, context, tenant);
+        self.apply_constraints(&mut result, context);
+        self.apply_lifecycle(&mut result, context);
+        self.enforce_quotas(&mut result, context, tenant);
+        self.apply_session_obligations(&mut result, context, tenant);
+        self.bind_purpose(&mut result, context);
+        self.apply_validity(&mut result, context, tenant);
+        self.cap_ttl_to_validity(&mut result);
+        #[cfg(feature = "crypto")]
+        self.apply_watermark(&mut result, context);
+        self.dispatch_obligations(&mut result, context, tenant);
+        self.localize_result(&mut result, context);
+        
+        // Only permitted requests shape the baseline and operation
+        // counters, so repeated denied attempts cannot make themselves
+        // usual or use up a user's velocity allowance
+        if result.decision == Decision::Permit {
+            let user_key = state_key(tenant, &context.user_id);
+            self.baselines.record(&user_key, context);
+            self.operation_counters.record(&user_key, context);
+        }
+        
+        Ok(result)
+    }
+    
+    // Derive location, device trust and risk_score in-engine when a
+    // GeoIP database, attestation anchors or a scoring profile are
+    // configured, and fill in declared resource purposes and capsule state.
+    // `tenant` selects whose travel history the risk score sees.
+    fn enrich_context(&self, context: &mut PolicyContext, tenant: Option<&str>) {
+        #[cfg(feature = "mmdb")]
+        self.resolve_geoip(context);
+        #[cfg(feature = "crypto")]
+        self.derive_device_trust(context);
+        self.inject_allowed_purposes(context);
+        self.inject_capsule_state(context);
+        self.apply_calendar(context);
+        if let Some(scorer) = &self.risk {
+            let observed = self.observations(context, tenant);
+            scorer.inject(context, &observed);
+            if self.debug_mode {
+                console_log!("Computed risk score: {:.2}", context.risk_score);
+            }
+        }
+    }
+    
+    fn selected_policies<'a>(&'a self, selection: PolicySelection<'a>) -> Vec<&'a CompiledPolicy> {
+        match selection {
+            PolicySelection::Global => self.policies.iter().collect(),
+            PolicySelection::Staged => self.staged_policies().iter().collect(),
+            PolicySelection::Explicit(policies) => policies.iter().collect(),
+            PolicySelection::Indexed(policies) => policies.to_vec(),
+            PolicySelection::Scoped(selector) => self.policies.iter().filter(|compiled| selector.matches(&compiled.policy)).collect(),
+            PolicySelection::Tenant(id) => match self.tenants.get(id) {
+                Some(tenant) => tenant.layered_policies(&self.policies),
+                None => Vec::new(),
+            },
+        }
+    }
+    
+    fn evaluate_context(&self, context: &PolicyContext, selection: PolicySelection) -> Result<PolicyResult, JsValue> {
+        let _redacting = self.redact_logs(context);
+        let scope = EvalScope::new(self, context, selection.tenant());
+        // Comparisons against explicit or staged sets are not requests
+        let tracked = matches!(selection, PolicySelection::Global | PolicySelection::Tenant(_) | PolicySelection::Scoped(_));
+        if tracked && self.usage.borrow().is_enabled() {
+            scope.track_usage();
+        }
+        let mut result = self.evaluate_scope(&scope, selection);
+        self.record_attribute_usage(&scope, &result);
+        if let Ok(result) = result.as_mut() {
+            let reads = scope.take_reads();
+            self.apply_freshness(result, &reads);
+            result.dependencies = reads.into_iter().collect();
+            if tracked {
+                self.record_dependencies(context, result);
+            }
+        }
+        result
+    }
+    
+    fn evaluate_scope(&self, scope: &EvalScope, selection: PolicySelection) -> Result<PolicyResult, JsValue> {
+        self.record_evaluation_coverage();
+        self.start_budget();
         
         // Find applicable policies
-        let applicable_policies: Vec<&Policy> = self.policies
-            .iter()
-            .filter(|policy| self.is_policy_applicable(policy, &context))
+        let started = stats::now_ms();
+        let selected = self.selected_policies(selection);
+        let considered = selected.len();
+        let mut applicable_policies: Vec<&CompiledPolicy> = selected
+            .into_iter()
+            .filter(|policy| self.is_policy_applicable(policy, scope))
             .collect();
+        applicable_policies.sort_by_key(|policy| std::cmp::Reverse(policy.policy.priority));
+        self.record_targets(considered, applicable_policies.len(), stats::now_ms() - started);
+        
+        if let Some(result) = self.limits_exceeded_result() {
+            return Ok(result);
+        }
         
         if self.debug_mode {
             console_log!("Found {} applicable policies", applicable_policies.len());
@@ -236,21 +1180,39 @@ impl PolicyEngine {
         
         if applicable_policies.is_empty() {
             return Ok(PolicyResult::new(
-                "INDETERMINATE".to_string(),
-                "No applicable policies found".to_string(),
+                Decision::Indeterminate,
+                "No applicable policies found",
                 0.0
             ));
         }
         
         // Evaluate each applicable policy
+        let started = stats::now_ms();
         let mut policy_results = Vec::new();
         for policy in applicable_policies {
-            let result = self.evaluate_policy(policy, &context)?;
+            let result = self.evaluate_policy(policy, scope)?;
             policy_results.push(result);
         }
+        self.record_phase(Phase::Rules, stats::now_ms() - started);
+        
+        // A partial evaluation must not produce a decision
+        if let Some(result) = self.limits_exceeded_result() {
+            return Ok(result);
+        }
         
         // Combine results using the appropriate algorithm
-        let final_result = self.combine_policy_results(policy_results)?;
+        let started = stats::now_ms();
+        let outcomes: Vec<_> = match &self.confidence {
+            Some(model) => policy_results.iter().map(|result| model.policy_outcome(result)).collect(),
+            None => Vec::new(),
+        };
+        let mut final_result = self.combine_policy_results(policy_results)?;
+        if let Some(model) = &self.confidence {
+            model.calibrate(&self.root_algorithm, &outcomes, &mut final_result);
+            model.enforce_minimum(&mut final_result);
+        }
+        self.fulfill_policy_outputs(&mut final_result, &self.root_obligations, &self.root_advice);
+        self.record_phase(Phase::Combine, stats::now_ms() - started);
         
         if self.debug_mode {
             console_log!("Final decision: {}", final_result.decision);
@@ -259,255 +1221,270 @@ impl PolicyEngine {
         Ok(final_result)
     }
     
-    #[wasm_bindgen]
-    pub fn clear_policies(&mut self) {
-        self.policies.clear();
-        console_log!("Cleared all policies");
-    }
-    
-    #[wasm_bindgen]
-    pub fn get_policy_count(&self) -> usize {
-        self.policies.len()
-    }
-}
-
-impl PolicyEngine {
-    fn is_policy_applicable(&self, policy: &Policy, context: &PolicyContext) -> bool {
-        if policy.target.is_empty() {
-            return true;
+    fn is_policy_applicable(&self, policy: &CompiledPolicy, scope: &EvalScope) -> bool {
+        if !self.policy_in_effect(policy) {
+            return false;
         }
-        
-        // Simple target evaluation - in production, use a proper expression evaluator
-        self.evaluate_expression(&policy.target, context).unwrap_or(false)
+        // Targets that fail to evaluate are treated as not matching
+        let started = stats::now_ms();
+        scope.with_usage(|recorder| recorder.enter_policy(&policy.policy.id));
+        let applicable = self.evaluate_expression(&policy.target, scope).unwrap_or(false);
+        scope.with_usage(|recorder| recorder.set_applicable(applicable));
+        self.record_target_stats(&policy.policy.id, applicable, stats::now_ms() - started);
+        self.record_target_coverage(policy, applicable);
+        applicable
     }
     
-    fn evaluate_policy(&self, policy: &Policy, context: &PolicyContext) -> Result<PolicyResult, JsValue> {
+    fn evaluate_policy(&self, compiled: &CompiledPolicy, scope: &EvalScope) -> Result<PolicyResult, JsValue> {
+        let policy = &compiled.policy;
         if self.debug_mode {
             console_log!("Evaluating policy: {}", policy.name);
         }
         
         let mut rule_results = Vec::new();
+        let mut outcomes = Vec::new();
+        scope.with_usage(|recorder| recorder.enter_policy(&policy.id));
         
         // Evaluate each rule
-        for rule in &policy.rules {
-            let rule_result = self.evaluate_rule(rule, context)?;
+        for (index, (rule, condition)) in compiled.rules().enumerate() {
+            if !self.in_deployment(&rule.environments) {
+                continue;
+            }
+            if !self.charge_rule() {
+                break;
+            }
+            let started = stats::now_ms();
+            let rule_result = self.evaluate_rule(rule, condition, scope)?;
+            self.record_rule_stats(&policy.id, &rule.id, &rule_result, stats::now_ms() - started);
+            self.record_rule_coverage(compiled, index, &rule_result, scope);
+            if let Some(model) = &self.confidence {
+                outcomes.push(model.rule_outcome(rule, &rule_result));
+            }
             rule_results.push(rule_result);
         }
         
         // Combine rule results using the policy's combining algorithm
-        self.combine_rule_results(&policy.combining_algorithm, rule_results)
+        let mut result = self.combine_rule_results(&policy.combining_algorithm, rule_results)?;
+        if let Some(model) = &self.confidence {
+            model.calibrate(&policy.combining_algorithm, &outcomes, &mut result);
+        }
+        if result.rule_id.is_some() {
+            result.policy_id = Some(policy.id.clone());
+        }
+        self.fulfill_policy_outputs(&mut result, &policy.obligations, &policy.advice);
+        self.record_policy_stats(&policy.id, &result);
+        Ok(result)
+    }
+    
+    fn evaluate_rule(&self, rule: &PolicyRule, condition: &Expr, scope: &EvalScope) -> Result<PolicyResult, JsValue> {
+        let mut result = self.evaluate_rule_condition(rule, condition, scope)?;
+        result.rule_id = Some(rule.id.clone());
+        Ok(result)
     }
     
-    fn evaluate_rule(&self, rule: &PolicyRule, context: &PolicyContext) -> Result<PolicyResult, JsValue> {
+    fn evaluate_rule_condition(&self, rule: &PolicyRule, condition: &Expr, scope: &EvalScope) -> Result<PolicyResult, JsValue> {
         if self.debug_mode {
             console_log!("Evaluating rule: {}", rule.name);
         }
         
-        // Evaluate the rule condition
-        let condition_result = self.evaluate_expression(&rule.condition, context)?;
+        // Evaluate the rule condition; errors make the rule indeterminate
+        if self.confidence.is_some() {
+            scope.begin_trace();
+        }
+        let evaluated = self.evaluate_expression(condition, scope);
+        let certainty = self.condition_certainty(scope);
+        let condition_result = match evaluated {
+            Ok(matched) => matched,
+            Err(e) => {
+                if self.debug_mode {
+                    log_at!(logging::LogLevel::Warn, "Rule '{}' condition error: {}", rule.name, e);
+                }
+                return Ok(PolicyResult::new(
+                    Decision::Indeterminate,
+                    format!("Rule '{}' condition error: {}", rule.name, e),
+                    0.0
+                ));
+            }
+        };
         
         if condition_result {
+            let effect = rule.effect.decision();
             let mut result = PolicyResult::new(
-                rule.effect.clone(),
+                effect,
                 format!("Rule '{}' matched", rule.name),
-                1.0
+                certainty.unwrap_or(1.0)
             );
             
-            if !rule.obligations.is_empty() {
-                result.set_obligations(serde_json::to_string(&rule.obligations).unwrap_or_default());
+            // CHALLENGE rules permit once the step-up has been completed
+            if effect == Decision::Challenge {
+                let spec = rule.challenge.clone().unwrap_or_default();
+                if spec.is_satisfied(scope.context) {
+                    result.decision = Decision::Permit;
+                    result.reason = format!("Rule '{}' matched (step-up satisfied)", rule.name).into();
+                } else {
+                    result.set_challenge(serde_json::to_string(&spec).unwrap_or_default());
+                }
             }
             
-            if !rule.advice.is_empty() {
-                result.set_advice(serde_json::to_string(&rule.advice).unwrap_or_default());
+            // PENDING_APPROVAL rules resolve once approvers have voted
+            if effect == Decision::PendingApproval {
+                self.resolve_approval(rule, scope.context, scope.tenant, &mut result);
             }
             
+            result.reason_code = rule.reason_code.clone();
+            let rendered = rule.rendered();
+            result.obligations = rendered.obligations.clone();
+            result.advice = rendered.advice.clone();
+            
             Ok(result)
         } else {
+            // Under a confidence model, how certain it is that the rule
+            // does not apply
             Ok(PolicyResult::new(
-                "NOTAPPLICABLE".to_string(),
+                Decision::NotApplicable,
                 format!("Rule '{}' condition not met", rule.name),
-                0.0
+                certainty.unwrap_or(0.0)
             ))
         }
     }
     
-    fn evaluate_expression(&self, expression: &str, context: &PolicyContext) -> Result<bool, JsValue> {
-        // Simple expression evaluator - in production, use a proper parser/evaluator
-        // This is a basic implementation for demonstration
-        
-        if expression.is_empty() {
-            return Ok(true);
-        }
-        
-        // Handle simple expressions
-        if expression == "true" {
-            return Ok(true);
-        }
-        
-        if expression == "false" {
-            return Ok(false);
-        }
-        
-        // Check for common patterns
-        if expression.contains("user.roles") && expression.contains("admin") {
-            return Ok(context.user_roles.contains(&"admin".to_string()));
-        }
-        
-        if expression.contains("device.attested") && expression.contains("true") {
-            return Ok(context.device_attested);
-        }
-        
-        if expression.contains("mfa.verified") && expression.contains("true") {
-            return Ok(context.mfa_verified);
-        }
-        
-        if expression.contains("business_hours") && expression.contains("true") {
-            return Ok(context.business_hours);
-        }
-        
-        if expression.contains("risk_score") {
-            if expression.contains("< 5.0") {
-                return Ok(context.risk_score < 5.0);
-            }
-            if expression.contains("> 7.0") {
-                return Ok(context.risk_score > 7.0);
-            }
-        }
-        
-        if expression.contains("classification") {
-            if expression.contains("classified") && expression.contains("!=") {
-                return Ok(context.resource_classification != "classified");
-            }
-            if expression.contains("public") && expression.contains("==") {
-                return Ok(context.resource_classification == "public");
-            }
-        }
-        
-        // Default to false for unrecognized expressions
-        if self.debug_mode {
-            console_log!("Unknown expression: {}", expression);
+    fn condition_certainty(&self, scope: &EvalScope) -> Option<f64> {
+        let trace = scope.take_trace()?;
+        Some(self.confidence.as_ref()?.certainty(&trace, scope.context))
+    }
+    
+    fn evaluate_expression(&self, expression: &Expr, scope: &EvalScope) -> Result<bool, expr::ExprError> {
+        match expr::evaluate(expression, scope)? {
+            Value::Bool(b) => Ok(b),
+            Value::Null => Ok(false),
+            other => Err(expr::ExprError::new(format!(
+                "Expression must evaluate to bool, found {}",
+                other.type_name()
+            ))),
         }
-        
-        Ok(false)
     }
     
-    fn combine_rule_results(&self, algorithm: &str, results: Vec<PolicyResult>) -> Result<PolicyResult, JsValue> {
+    fn combine_rule_results(&self, algorithm: &CombiningAlgorithm, results: Vec<PolicyResult>) -> Result<PolicyResult, JsValue> {
         match algorithm {
-            "permit-overrides" => self.permit_overrides(results),
-            "deny-overrides" => self.deny_overrides(results),
-            "first-applicable" => self.first_applicable(results),
-            "permit-unless-deny" => self.permit_unless_deny(results),
-            "deny-unless-permit" => self.deny_unless_permit(results),
-            _ => {
-                console_log!("Unknown combining algorithm: {}, using deny-overrides", algorithm);
+            CombiningAlgorithm::PermitOverrides => self.permit_overrides(results),
+            CombiningAlgorithm::DenyOverrides => self.deny_overrides(results),
+            CombiningAlgorithm::FirstApplicable => self.first_applicable(results),
+            CombiningAlgorithm::PermitUnlessDeny => self.permit_unless_deny(results),
+            CombiningAlgorithm::DenyUnlessPermit => self.deny_unless_permit(results),
+            CombiningAlgorithm::OrderedPermitOverrides => Ok(self.select_first_by_precedence(results, PERMIT_PRECEDENCE)),
+            CombiningAlgorithm::OrderedDenyOverrides => Ok(self.select_first_by_precedence(results, DENY_PRECEDENCE)),
+            CombiningAlgorithm::Other(_) => {
+                log_at!(logging::LogLevel::Warn, "Unknown combining algorithm: {}, using deny-overrides", algorithm);
                 self.deny_overrides(results)
             }
         }
     }
     
     fn permit_overrides(&self, results: Vec<PolicyResult>) -> Result<PolicyResult, JsValue> {
-        let mut permits = Vec::new();
-        let mut denies = Vec::new();
-        let mut indeterminates = Vec::new();
-        
-        for result in results {
-            match result.decision.as_str() {
-                "PERMIT" => permits.push(result),
-                "DENY" => denies.push(result),
-                "INDETERMINATE" => indeterminates.push(result),
-                _ => {} // NOTAPPLICABLE
+        Ok(self.select_by_precedence(results, PERMIT_PRECEDENCE))
+    }
+    
+    fn deny_overrides(&self, results: Vec<PolicyResult>) -> Result<PolicyResult, JsValue> {
+        Ok(self.select_by_precedence(results, DENY_PRECEDENCE))
+    }
+    
+    // Returns the highest-confidence result of the first decision in
+    // `precedence` that any result carries (NOTAPPLICABLE is never selected)
+    fn select_by_precedence(&self, results: Vec<PolicyResult>, precedence: &[Decision]) -> PolicyResult {
+        for decision in precedence {
+            let best = results
+                .iter()
+                .filter(|r| r.decision == *decision)
+                .max_by(|a, b| a.confidence.partial_cmp(&b.confidence).unwrap_or(std::cmp::Ordering::Equal));
+            if let Some(best) = best {
+                return best.clone();
             }
         }
         
-        if !permits.is_empty() {
-            Ok(permits.into_iter().max_by(|a, b| a.confidence.partial_cmp(&b.confidence).unwrap_or(std::cmp::Ordering::Equal)).unwrap())
-        } else if !denies.is_empty() {
-            Ok(denies.into_iter().max_by(|a, b| a.confidence.partial_cmp(&b.confidence).unwrap_or(std::cmp::Ordering::Equal)).unwrap())
-        } else if !indeterminates.is_empty() {
-            Ok(indeterminates.into_iter().max_by(|a, b| a.confidence.partial_cmp(&b.confidence).unwrap_or(std::cmp::Ordering::Equal)).unwrap())
-        } else {
-            Ok(PolicyResult::new(
-                "INDETERMINATE".to_string(),
-                "No applicable rules".to_string(),
-                0.0
-            ))
-        }
+        PolicyResult::new(
+            Decision::Indeterminate,
+            "No applicable rules",
+            0.0
+        )
     }
     
-    fn deny_overrides(&self, results: Vec<PolicyResult>) -> Result<PolicyResult, JsValue> {
-        let mut permits = Vec::new();
-        let mut denies = Vec::new();
-        let mut indeterminates = Vec::new();
-        
-        for result in results {
-            match result.decision.as_str() {
-                "PERMIT" => permits.push(result),
-                "DENY" => denies.push(result),
-                "INDETERMINATE" => indeterminates.push(result),
-                _ => {} // NOTAPPLICABLE
+    // Like select_by_precedence, but the first result in evaluation order
+    // of the winning decision is returned
+    fn select_first_by_precedence(&self, results: Vec<PolicyResult>, precedence: &[Decision]) -> PolicyResult {
+        for decision in precedence {
+            if let Some(first) = results.iter().find(|r| r.decision == *decision) {
+                return first.clone();
             }
         }
         
-        if !denies.is_empty() {
-            Ok(denies.into_iter().max_by(|a, b| a.confidence.partial_cmp(&b.confidence).unwrap_or(std::cmp::Ordering::Equal)).unwrap())
-        } else if !permits.is_empty() {
-            Ok(permits.into_iter().max_by(|a, b| a.confidence.partial_cmp(&b.confidence).unwrap_or(std::cmp::Ordering::Equal)).unwrap())
-        } else if !indeterminates.is_empty() {
-            Ok(indeterminates.into_iter().max_by(|a, b| a.confidence.partial_cmp(&b.confidence).unwrap_or(std::cmp::Ordering::Equal)).unwrap())
-        } else {
-            Ok(PolicyResult::new(
-                "INDETERMINATE".to_string(),
-                "No applicable rules".to_string(),
-                0.0
-            ))
-        }
+        PolicyResult::new(
+            Decision::Indeterminate,
+            "No applicable rules",
+            0.0
+        )
     }
     
     fn first_applicable(&self, results: Vec<PolicyResult>) -> Result<PolicyResult, JsValue> {
         for result in results {
-            if result.decision != "NOTAPPLICABLE" {
+            if result.decision != Decision::NotApplicable {
                 return Ok(result);
             }
         }
         
         Ok(PolicyResult::new(
-            "INDETERMINATE".to_string(),
-            "No applicable rules".to_string(),
+            Decision::Indeterminate,
+            "No applicable rules",
             0.0
         ))
     }
     
     fn permit_unless_deny(&self, results: Vec<PolicyResult>) -> Result<PolicyResult, JsValue> {
         for result in &results {
-            if result.decision == "DENY" {
+            if result.decision == Decision::Deny {
+                return Ok(result.clone());
+            }
+        }
+        
+        // A pending step-up or approval still blocks the default permit
+        for result in &results {
+            if matches!(result.decision, Decision::Challenge | Decision::PendingApproval) {
                 return Ok(result.clone());
             }
         }
         
         Ok(PolicyResult::new(
-            "PERMIT".to_string(),
-            "Permit unless deny".to_string(),
+            Decision::Permit,
+            "Permit unless deny",
             1.0
         ))
     }
     
     fn deny_unless_permit(&self, results: Vec<PolicyResult>) -> Result<PolicyResult, JsValue> {
         for result in &results {
-            if result.decision == "PERMIT" {
+            if result.decision == Decision::Permit {
                 return Ok(result.clone());
             }
         }
         
-        Ok(PolicyResult::new(
-            "DENY".to_string(),
-            "Deny unless permit".to_string(),
+        // Offer the step-up or approval rather than a flat deny
+        for result in &results {
+            if matches!(result.decision, Decision::Challenge | Decision::PendingApproval) {
+                return Ok(result.clone());
+            }
+        }
+        
+        let mut result = PolicyResult::new(
+            Decision::Deny,
+            "Deny unless permit",
             1.0
-        ))
+        );
+        result.reason_code = Some(messages::DEFAULT_DENY.to_string());
+        Ok(result)
     }
     
+    // Results arrive in evaluation order (descending policy priority)
     fn combine_policy_results(&self, results: Vec<PolicyResult>) -> Result<PolicyResult, JsValue> {
-        // Use deny-overrides for combining policy results
-        self.deny_overrides(results)
+        self.combine_rule_results(&self.root_algorithm, results)
     }
 }
 
@@ -518,9 +1495,10 @@ pub fn create_sample_policy() -> String {
         id: "sample-policy-001".to_string(),
         name: "Sample Access Policy".to_string(),
         version: "1.0.0".to_string(),
+        format_version: format::POLICY_FORMAT_VERSION,
         description: "A sample policy for demonstration".to_string(),
         target: "true".to_string(),
-        combining_algorithm: "deny-overrides".to_string(),
+        combining_algorithm: CombiningAlgorithm::DenyOverrides,
         rules: vec![
             PolicyRule {
                 id: "rule-001".to_string(),
@@ -528,9 +1506,16 @@ pub fn create_sample_policy() -> String {
                 description: "Multi-factor authentication is required for accessing classified data".to_string(),
                 priority: 100,
                 condition: "classification == 'classified' && mfa.verified == true".to_string(),
-                effect: "PERMIT".to_string(),
+                effect: Effect::Permit,
                 obligations: vec!["log_access".to_string()],
-                advice: vec!["remind_classification".to_string()],
+                advice: vec![Advice::new("remind_classification", Severity::Notice).with_message("Classified data: handle per classification policy")],
+                challenge: None,
+                approval: None,
+                break_glass: false,
+                reason_code: None,
+                remediation: Some("Sign in again with multi-factor authentication".to_string()),
+                environments: vec![],
+                rendered: OnceCell::new(),
             },
             PolicyRule {
                 id: "rule-002".to_string(),
@@ -538,13 +1523,29 @@ pub fn create_sample_policy() -> String {
                 description: "Deny access when risk score is too high".to_string(),
                 priority: 200,
                 condition: "risk_score > 7.0".to_string(),
-                effect: "DENY".to_string(),
+                effect: Effect::Deny,
                 obligations: vec!["alert_security".to_string()],
                 advice: vec![],
+                challenge: None,
+                approval: None,
+                break_glass: false,
+                reason_code: None,
+                remediation: None,
+                environments: vec![],
+                rendered: OnceCell::new(),
             },
         ],
         obligations: vec![],
         advice: vec![],
+        priority: 0,
+        source: None,
+        rule_refs: vec![],
+        labels: HashMap::new(),
+        owner: None,
+        tags: vec![],
+        enabled: true,
+        environments: vec![],
+        definitions: BTreeMap::new(),
     };
     
     serde_json::to_string(&sample_policy).unwrap_or_default()
@@ -554,7 +1555,7 @@ pub fn create_sample_policy() -> String {
 pub fn create_sample_context() -> String {
     let sample_context = PolicyContext {
         request_id: "req-12345".to_string(),
-        timestamp: Utc::now(),
+        timestamp: clock::now(),
         operation: "read".to_string(),
         user_id: "user-123".to_string(),
         user_roles: vec!["analyst".to_string()],
@@ -562,8 +1563,10 @@ pub fn create_sample_context() -> String {
         user_attributes: HashMap::new(),
         device_id: "device-456".to_string(),
         device_type: "laptop".to_string(),
-        device_trust: "trusted".to_string(),
+        device_trust: DeviceTrust::Trusted,
         device_attested: true,
+        device_posture_token: None,
+        device_attestation: None,
         ip_address: "192.168.1.100".to_string(),
         ip_country: "US".to_string(),
         ip_city: "Seattle".to_string(),
@@ -577,7 +1580,7 @@ pub fn create_sample_context() -> String {
         day_of_week: "Tuesday".to_string(),
         business_hours: true,
         risk_score: 3.5,
-        threat_level: "low".to_string(),
+        threat_level: ThreatLevel::Low,
         resource_type: "data_capsule".to_string(),
         resource_id: "capsule-001".to_string(),
         resource_classification: "internal".to_string(),
@@ -586,8 +1589,13 @@ pub fn create_sample_context() -> String {
         intent_purpose: Some("research analysis".to_string()),
         intent_justification: Some("Required for project XYZ".to_string()),
         intent_duration: Some(Duration::hours(4)),
+        delegation: Vec::new(),
         constraints: HashMap::new(),
         metadata: HashMap::new(),
+        attribute_quality: HashMap::new(),
+        locale: None,
+        supplied_fields: None,
+        bag: None,
     };
     
     serde_json::to_string(&sample_context).unwrap_or_default()
@@ -596,5 +1604,7 @@ pub fn create_sample_context() -> String {
 // Initialize the WASM module
 #[wasm_bindgen(start)]
 pub fn main() {
+    #[cfg(feature = "console_error_panic_hook")]
+    console_error_panic_hook::set_once();
     console_log!("UARS Policy Engine WASM module initialized");
 }
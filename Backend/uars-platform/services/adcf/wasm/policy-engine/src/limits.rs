@@ -0,0 +1,152 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use chrono::{DateTime, Utc};
+
+use crate::error::{self, PolicyEngineError};
+use crate::expr::ExprError;
+use crate::{PolicyEngine, PolicyResult};
+
+// The wall clock is only read every this many expression steps. The
+// duration limit measures real elapsed time, so it ignores clock.rs.
+const CLOCK_CHECK_INTERVAL: u64 = 1024;
+
+// Per-request resource caps so a hostile or buggy policy set cannot hang
+// the host. Absent fields keep their defaults; `null` lifts the limit.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EvaluationLimits {
+    // Rule conditions evaluated across all applicable policies
+    pub max_rules: Option<u64>,
+    // Expression tree depth reached while evaluating (the root is 1)
+    pub max_depth: Option<usize>,
+    // Expression nodes evaluated, targets included
+    pub max_steps: Option<u64>,
+    // Wall-clock budget for the evaluation
+    pub max_duration_ms: Option<i64>,
+}
+
+impl Default for EvaluationLimits {
+    fn default() -> Self {
+        EvaluationLimits {
+            max_rules: Some(10_000),
+            max_depth: Some(128),
+            max_steps: Some(1_000_000),
+            max_duration_ms: Some(1_000),
+        }
+    }
+}
+
+// Usage against the limits for the evaluation in progress. The first
+// limit hit is remembered so every later check fails fast.
+#[derive(Default)]
+pub struct Budget {
+    rules: Cell<u64>,
+    steps: Cell<u64>,
+    started: Cell<Option<DateTime<Utc>>>,
+    exceeded: RefCell<Option<String>>,
+}
+
+impl Budget {
+    fn start(&self) {
+        self.rules.set(0);
+        self.steps.set(0);
+        self.started.set(Some(Utc::now()));
+        self.exceeded.replace(None);
+    }
+
+    fn exceed(&self, reason: String) -> String {
+        self.exceeded.borrow_mut().get_or_insert(reason).clone()
+    }
+
+    fn exceeded(&self) -> Option<String> {
+        self.exceeded.borrow().clone()
+    }
+
+    fn check_clock(&self, limits: &EvaluationLimits) -> Result<(), String> {
+        if let (Some(max), Some(started)) = (limits.max_duration_ms, self.started.get()) {
+            if (Utc::now() - started).num_milliseconds() > max {
+                return Err(self.exceed(format!("evaluation took longer than {}ms", max)));
+            }
+        }
+        Ok(())
+    }
+
+    fn charge_rule(&self, limits: &EvaluationLimits) -> Result<(), String> {
+        if let Some(reason) = self.exceeded() {
+            return Err(reason);
+        }
+        let rules = self.rules.get() + 1;
+        self.rules.set(rules);
+        if let Some(max) = limits.max_rules.filter(|max| rules > *max) {
+            return Err(self.exceed(format!("more than {} rules evaluated", max)));
+        }
+        self.check_clock(limits)
+    }
+
+    fn charge_step(&self, limits: &EvaluationLimits, depth: usize) -> Result<(), String> {
+        if let Some(reason) = self.exceeded() {
+            return Err(reason);
+        }
+        if let Some(max) = limits.max_depth.filter(|max| depth > *max) {
+            return Err(self.exceed(format!("expression nested deeper than {} levels", max)));
+        }
+        let steps = self.steps.get() + 1;
+        self.steps.set(steps);
+        if let Some(max) = limits.max_steps.filter(|max| steps > *max) {
+            return Err(self.exceed(format!("more than {} expression steps", max)));
+        }
+        if steps.is_multiple_of(CLOCK_CHECK_INTERVAL) {
+            self.check_clock(limits)?;
+        }
+        Ok(())
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // JSON object with any of max_rules, max_depth, max_steps and
+    // max_duration_ms
+    #[wasm_bindgen]
+    pub fn set_evaluation_limits(&mut self, limits_json: &str) -> Result<(), JsValue> {
+        let limits: EvaluationLimits = serde_json::from_str(limits_json).map_err(|e| {
+            PolicyEngineError::parse(format!("Failed to parse evaluation limits: {}", e)).logged()
+        })?;
+        self.limits = limits;
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn get_evaluation_limits(&self) -> Result<String, JsValue> {
+        Ok(error::to_json(&self.limits)?)
+    }
+}
+
+impl PolicyEngine {
+    pub(crate) fn start_budget(&self) {
+        self.budget.start();
+    }
+
+    // Counts a rule about to be evaluated; false once any limit is hit
+    pub(crate) fn charge_rule(&self) -> bool {
+        self.budget.charge_rule(&self.limits).is_ok()
+    }
+
+    pub(crate) fn charge_step(&self, depth: usize) -> Result<(), ExprError> {
+        self.budget
+            .charge_step(&self.limits, depth)
+            .map_err(|reason| ExprError::new(format!("Limits exceeded: {}", reason)))
+    }
+
+    pub(crate) fn limits_hit(&self) -> bool {
+        self.budget.exceeded().is_some()
+    }
+
+    // The INDETERMINATE result that replaces the decision once a limit
+    // has been hit
+    pub(crate) fn limits_exceeded_result(&self) -> Option<PolicyResult> {
+        let reason = self.budget.exceeded()?;
+        let error = PolicyEngineError::limits_exceeded(format!("Limits exceeded: {}", reason)).logged();
+        Some(PolicyResult::failure(&error))
+    }
+}
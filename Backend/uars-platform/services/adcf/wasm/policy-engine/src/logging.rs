@@ -0,0 +1,186 @@
+use wasm_bindgen::prelude::*;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+
+use crate::clock;
+use crate::error::PolicyEngineError;
+use crate::redaction;
+
+const DEFAULT_BUFFER_CAPACITY: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+}
+
+impl LogLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LogEntry {
+    pub level: LogLevel,
+    pub message: String,
+    pub timestamp: String,
+}
+
+// Where log lines go. Only one sink is active at a time.
+enum LogSink {
+    Console,
+    Callback(js_sys::Function),
+    Buffer { entries: VecDeque<LogEntry>, capacity: usize },
+}
+
+// Module-wide rather than per-engine: errors and panics are logged from
+// places that have no engine at hand. `max_level` of None means off.
+struct Logger {
+    max_level: Option<LogLevel>,
+    sink: Option<LogSink>,
+}
+
+impl Default for Logger {
+    // Release builds stay quiet until the host picks a sink
+    fn default() -> Self {
+        Logger {
+            max_level: Some(LogLevel::Info),
+            sink: if cfg!(debug_assertions) { Some(LogSink::Console) } else { None },
+        }
+    }
+}
+
+thread_local! {
+    static LOGGER: RefCell<Logger> = RefCell::new(Logger::default());
+}
+
+pub fn enabled(level: LogLevel) -> bool {
+    LOGGER.with(|logger| {
+        let logger = logger.borrow();
+        logger.sink.is_some() && logger.max_level.is_some_and(|max| level <= max)
+    })
+}
+
+pub fn write(level: LogLevel, message: &str) {
+    let message = &redaction::scrub_log(message);
+    // The callback is cloned out so it can log (or re-enter the engine)
+    // without hitting the RefCell borrow
+    let callback = LOGGER.with(|logger| {
+        let mut logger = logger.borrow_mut();
+        match logger.sink.as_mut() {
+            Some(LogSink::Console) => {
+                console(level, message);
+                None
+            }
+            Some(LogSink::Callback(f)) => Some(f.clone()),
+            Some(LogSink::Buffer { entries, capacity }) => {
+                if entries.len() >= *capacity {
+                    entries.pop_front();
+                }
+                entries.push_back(LogEntry {
+                    level,
+                    message: message.to_string(),
+                    timestamp: clock::now().to_rfc3339(),
+                });
+                None
+            }
+            None => None,
+        }
+    });
+
+    if let Some(f) = callback {
+        let _ = f.call2(&JsValue::NULL, &JsValue::from_str(level.as_str()), &JsValue::from_str(message));
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn console(level: LogLevel, message: &str) {
+    let message = JsValue::from_str(message);
+    match level {
+        LogLevel::Error => web_sys::console::error_1(&message),
+        LogLevel::Warn => web_sys::console::warn_1(&message),
+        LogLevel::Info | LogLevel::Debug => web_sys::console::log_1(&message),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn console(level: LogLevel, message: &str) {
+    eprintln!("[{}] {}", level.as_str(), message);
+}
+
+// "off", "error", "warn", "info" or "debug"
+#[wasm_bindgen]
+pub fn set_log_level(level: &str) -> Result<(), JsValue> {
+    let max_level = match level.to_lowercase().as_str() {
+        "off" => None,
+        "error" => Some(LogLevel::Error),
+        "warn" => Some(LogLevel::Warn),
+        "info" => Some(LogLevel::Info),
+        "debug" => Some(LogLevel::Debug),
+        other => return Err(PolicyEngineError::validation(format!("Unknown log level: {}", other)).into()),
+    };
+    LOGGER.with(|logger| logger.borrow_mut().max_level = max_level);
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub fn get_log_level() -> String {
+    LOGGER.with(|logger| match logger.borrow().max_level {
+        Some(level) => level.as_str().to_string(),
+        None => "off".to_string(),
+    })
+}
+
+#[wasm_bindgen]
+pub fn use_console_log() {
+    LOGGER.with(|logger| logger.borrow_mut().sink = Some(LogSink::Console));
+}
+
+// Calls `callback(level, message)` for every log line
+#[wasm_bindgen]
+pub fn set_log_callback(callback: js_sys::Function) {
+    LOGGER.with(|logger| logger.borrow_mut().sink = Some(LogSink::Callback(callback)));
+}
+
+// Keeps the most recent `capacity` lines in memory for `get_logs`
+#[wasm_bindgen]
+pub fn use_log_buffer(capacity: Option<usize>) {
+    let capacity = capacity.unwrap_or(DEFAULT_BUFFER_CAPACITY).max(1);
+    LOGGER.with(|logger| {
+        logger.borrow_mut().sink = Some(LogSink::Buffer { entries: VecDeque::new(), capacity });
+    });
+}
+
+#[wasm_bindgen]
+pub fn disable_log_sink() {
+    LOGGER.with(|logger| logger.borrow_mut().sink = None);
+}
+
+// Buffered lines as a JSON array of { level, message, timestamp }; empty
+// unless the buffer sink is active
+#[wasm_bindgen]
+pub fn get_logs() -> String {
+    LOGGER.with(|logger| match &logger.borrow().sink {
+        Some(LogSink::Buffer { entries, .. }) => serde_json::to_string(entries).unwrap_or_default(),
+        _ => "[]".to_string(),
+    })
+}
+
+#[wasm_bindgen]
+pub fn clear_logs() {
+    LOGGER.with(|logger| {
+        if let Some(LogSink::Buffer { entries, .. }) = logger.borrow_mut().sink.as_mut() {
+            entries.clear();
+        }
+    });
+}
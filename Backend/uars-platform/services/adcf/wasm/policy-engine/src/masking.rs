@@ -0,0 +1,210 @@
+use wasm_bindgen::prelude::*;
+use serde_json::{json, Value as JsonValue};
+
+use crate::decision::Decision;
+use crate::error::{to_json, PolicyEngineError};
+use crate::expr::{self, Environment, ExprError, Value};
+use crate::obligations::ObligationCall;
+use crate::redaction::{mask, MASK};
+use crate::{PolicyEngine, PolicyResult};
+
+// Response transformations a policy can attach to a PERMIT:
+//
+//   mask_fields(["ssn", "employee.salary"])   values become "[REDACTED]"
+//   redact_pattern("\\d{3}-\\d{2}-\\d{4}")     matches in strings become
+//                                              "[REDACTED]" (or a second
+//                                              replacement argument)
+//   downsample(2)                             numbers rounded to 2 places
+//   downsample(1, ["lat", "lon"])             ... only in those fields
+//
+// A name without dots matches the key at any depth; a dotted path is
+// followed from the root, through arrays. Arguments must be literals,
+// since the request context is gone by the time a payload is transformed.
+pub const MASK_FIELDS_OBLIGATION: &str = "mask_fields";
+pub const REDACT_PATTERN_OBLIGATION: &str = "redact_pattern";
+pub const DOWNSAMPLE_OBLIGATION: &str = "downsample";
+
+// Arguments are evaluated without any attributes
+struct Literals;
+
+impl Environment for Literals {
+    fn resolve(&self, _path: &[String]) -> Option<Value> {
+        None
+    }
+
+    fn call(&self, name: &str, _args: &[Value]) -> Result<Value, ExprError> {
+        Err(ExprError::new(format!("{}() cannot be used in a transformation argument", name)))
+    }
+}
+
+fn fields(value: Option<&Value>) -> Result<Vec<String>, String> {
+    match value {
+        Some(Value::List(items)) => items
+            .iter()
+            .map(|item| match item {
+                Value::String(field) if !field.is_empty() => Ok(field.clone()),
+                other => Err(format!("field names must be non-empty strings, found {}", other.type_name())),
+            })
+            .collect(),
+        Some(Value::String(field)) if !field.is_empty() => Ok(vec![field.clone()]),
+        Some(other) => Err(format!("expected a list of field names, found {}", other.type_name())),
+        None => Err("missing the list of field names".to_string()),
+    }
+}
+
+// Calls `apply` on every value the field selects
+fn select(value: &mut JsonValue, field: &str, apply: &mut dyn FnMut(&mut JsonValue)) {
+    fn path(value: &mut JsonValue, segments: &[&str], apply: &mut dyn FnMut(&mut JsonValue)) {
+        let Some((first, rest)) = segments.split_first() else {
+            return apply(value);
+        };
+        match value {
+            JsonValue::Object(map) => {
+                if let Some(child) = map.get_mut(*first) {
+                    path(child, rest, apply);
+                }
+            }
+            JsonValue::Array(items) => items.iter_mut().for_each(|item| path(item, segments, apply)),
+            _ => {}
+        }
+    }
+    fn anywhere(value: &mut JsonValue, key: &str, apply: &mut dyn FnMut(&mut JsonValue)) {
+        match value {
+            JsonValue::Object(map) => {
+                for (name, child) in map.iter_mut() {
+                    if name == key { apply(child) } else { anywhere(child, key, apply) }
+                }
+            }
+            JsonValue::Array(items) => items.iter_mut().for_each(|item| anywhere(item, key, apply)),
+            _ => {}
+        }
+    }
+
+    if field.contains('.') {
+        path(value, &field.split('.').collect::<Vec<_>>(), apply);
+    } else {
+        anywhere(value, field, apply);
+    }
+}
+
+fn round_numbers(value: &mut JsonValue, precision: i32) {
+    match value {
+        JsonValue::Object(map) => map.values_mut().for_each(|value| round_numbers(value, precision)),
+        JsonValue::Array(items) => items.iter_mut().for_each(|value| round_numbers(value, precision)),
+        JsonValue::Number(n) => {
+            if let Some(number) = n.as_f64() {
+                let scale = 10f64.powi(precision);
+                let rounded = (number * scale).round() / scale;
+                *value = if precision <= 0 && rounded.abs() < 9.0e15 { json!(rounded as i64) } else { json!(rounded) };
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(feature = "regex")]
+fn replace_strings(value: &mut JsonValue, pattern: &regex::Regex, replacement: &str) {
+    match value {
+        JsonValue::Object(map) => map.values_mut().for_each(|value| replace_strings(value, pattern, replacement)),
+        JsonValue::Array(items) => items.iter_mut().for_each(|value| replace_strings(value, pattern, replacement)),
+        JsonValue::String(text) => {
+            if let std::borrow::Cow::Owned(replaced) = pattern.replace_all(text, replacement) {
+                *text = replaced;
+            }
+        }
+        _ => {}
+    }
+}
+
+// Applies one transformation; Ok(false) for obligations it does not know
+fn transform(payload: &mut JsonValue, call: &ObligationCall, args: &[Value]) -> Result<bool, String> {
+    match call.id.as_str() {
+        MASK_FIELDS_OBLIGATION => {
+            for field in fields(args.first())? {
+                select(payload, &field, &mut |value| mask(value, &|_| MASK.to_string()));
+            }
+        }
+        DOWNSAMPLE_OBLIGATION => {
+            let precision = match args.first() {
+                Some(Value::Number(n)) if n.fract() == 0.0 && n.abs() <= 15.0 => *n as i32,
+                _ => return Err("precision must be a whole number of decimal places between -15 and 15".to_string()),
+            };
+            match args.get(1) {
+                None => round_numbers(payload, precision),
+                fields_arg => {
+                    for field in fields(fields_arg)? {
+                        select(payload, &field, &mut |value| round_numbers(value, precision));
+                    }
+                }
+            }
+        }
+        REDACT_PATTERN_OBLIGATION => {
+            #[cfg(feature = "regex")]
+            {
+                let Some(Value::String(pattern)) = args.first() else {
+                    return Err("pattern must be a string".to_string());
+                };
+                let replacement = match args.get(1) {
+                    Some(Value::String(replacement)) => replacement.as_str(),
+                    None => MASK,
+                    Some(other) => return Err(format!("replacement must be a string, found {}", other.type_name())),
+                };
+                let pattern = regex::Regex::new(pattern).map_err(|e| format!("invalid pattern: {}", e))?;
+                replace_strings(payload, &pattern, replacement);
+            }
+            #[cfg(not(feature = "regex"))]
+            return Err("requires the regex feature".to_string());
+        }
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Performs a PERMIT's transformation obligations (mask_fields,
+    // redact_pattern, downsample) on a JSON payload, in the order the
+    // policy lists them, and returns the transformed payload. Other
+    // obligations are left to their handlers. Fails for results that are
+    // not PERMIT, whose payload must not be returned at all, and for
+    // malformed transformations, which must not be skipped silently.
+    #[wasm_bindgen]
+    pub fn apply_obligations(&self, result: &PolicyResult, payload_json: &str) -> Result<String, JsValue> {
+        if result.decision != Decision::Permit {
+            return Err(PolicyEngineError::invalid_state(format!("Obligations transform permitted payloads; the decision was {}", result.decision))
+                .with_details(json!({ "decision": result.decision }))
+                .logged()
+                .into());
+        }
+        let mut payload: JsonValue = serde_json::from_str(payload_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse payload: {}", e)).logged())?;
+        let specs: Vec<String> = serde_json::from_str(&result.obligations).unwrap_or_default();
+
+        let mut applied = 0;
+        for spec in &specs {
+            let call = ObligationCall::parse(spec);
+            let outcome = call
+                .args
+                .iter()
+                .map(|arg| expr::evaluate(arg, &Literals))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| e.to_string())
+                .and_then(|args| transform(&mut payload, &call, &args));
+            match outcome {
+                Ok(true) => applied += 1,
+                Ok(false) => {}
+                Err(reason) => {
+                    return Err(PolicyEngineError::validation(format!("Obligation '{}' cannot be applied: {}", spec, reason))
+                        .with_details(json!({ "obligation": spec, "reason": reason }))
+                        .logged()
+                        .into());
+                }
+            }
+        }
+
+        if self.debug_mode {
+            console_log!("Applied {} payload transformations", applied);
+        }
+        Ok(to_json(&payload)?)
+    }
+}
@@ -0,0 +1,111 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::error::{to_json, PolicyEngineError};
+use crate::{PolicyContext, PolicyEngine, PolicyResult};
+
+// Codes the engine itself sets on results it produces outside any rule
+pub const QUOTA_EXCEEDED: &str = "quota_exceeded";
+pub const DELEGATION_REJECTED: &str = "delegation_rejected";
+pub const DEFAULT_DENY: &str = "default_deny";
+pub const CAPSULE_REVOKED: &str = "capsule_revoked";
+pub const CAPSULE_EXPIRED: &str = "capsule_expired";
+pub const CAPSULE_LOCKED: &str = "capsule_locked";
+pub const CONSTRAINT_VIOLATED: &str = "constraint_violated";
+pub const WATERMARK_UNAVAILABLE: &str = "watermark_unavailable";
+
+const DEFAULT_LOCALE: &str = "en";
+
+// Localized messages for reason codes, one catalog per locale:
+//
+//   engine.load_message_catalog("de", '{ "mfa_required": "Bitte bestätigen Sie Ihre Anmeldung." }');
+//
+// A result's `message` comes from the context's `locale` ("de-AT" falls
+// back to "de"), then from the default locale. Codes without a message
+// leave it unset; `reason` stays the untranslated diagnostic.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MessageCatalogs {
+    catalogs: HashMap<String, HashMap<String, String>>,
+    default_locale: String,
+}
+
+impl Default for MessageCatalogs {
+    fn default() -> Self {
+        MessageCatalogs { catalogs: HashMap::new(), default_locale: DEFAULT_LOCALE.to_string() }
+    }
+}
+
+// Locale tags compare case-insensitively, with `_` read as `-`
+fn normalize(locale: &str) -> String {
+    locale.trim().replace('_', "-").to_lowercase()
+}
+
+impl MessageCatalogs {
+    pub fn message(&self, code: &str, locale: Option<&str>) -> Option<&str> {
+        let requested = locale.map(normalize).filter(|locale| !locale.is_empty());
+        let language = requested.as_deref().and_then(|locale| locale.split_once('-')).map(|(language, _)| language.to_string());
+        requested
+            .into_iter()
+            .chain(language)
+            .chain(std::iter::once(self.default_locale.clone()))
+            .find_map(|locale| self.catalogs.get(&locale)?.get(code))
+            .map(String::as_str)
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Accepts a JSON object of reason code to message, replacing any
+    // catalog loaded for the locale before
+    #[wasm_bindgen]
+    pub fn load_message_catalog(&mut self, locale: &str, catalog_json: &str) -> Result<(), JsValue> {
+        let locale = normalize(locale);
+        if locale.is_empty() {
+            return Err(PolicyEngineError::validation("Message catalog locale must not be empty").logged().into());
+        }
+        let catalog: HashMap<String, String> = serde_json::from_str(catalog_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse message catalog '{}': {}", locale, e)).logged())?;
+        if self.debug_mode {
+            console_log!("Loaded {} messages for locale {}", catalog.len(), locale);
+        }
+        self.messages.catalogs.insert(locale, catalog);
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn remove_message_catalog(&mut self, locale: &str) -> bool {
+        self.messages.catalogs.remove(&normalize(locale)).is_some()
+    }
+
+    // Locale used when the context has none or its catalog lacks a code
+    #[wasm_bindgen]
+    pub fn set_default_locale(&mut self, locale: &str) {
+        self.messages.default_locale = normalize(locale);
+    }
+
+    // {locale: number of messages}
+    #[wasm_bindgen]
+    pub fn get_message_locales(&self) -> Result<String, JsValue> {
+        let locales: BTreeMap<&str, usize> =
+            self.messages.catalogs.iter().map(|(locale, catalog)| (locale.as_str(), catalog.len())).collect();
+        Ok(to_json(&locales)?)
+    }
+
+    // The message for a code in a locale (with the same fallbacks as
+    // evaluation), for showing a stored decision in another language
+    #[wasm_bindgen]
+    pub fn localize_reason(&self, reason_code: &str, locale: Option<String>) -> Option<String> {
+        self.messages.message(reason_code, locale.as_deref()).map(str::to_string)
+    }
+}
+
+impl PolicyEngine {
+    pub(crate) fn localize_result(&self, result: &mut PolicyResult, context: &PolicyContext) {
+        result.message = result
+            .reason_code
+            .as_deref()
+            .and_then(|code| self.messages.message(code, context.locale.as_deref()))
+            .map(str::to_string);
+    }
+}
@@ -0,0 +1,215 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+
+use crate::error::{to_json, PolicyEngineError};
+use crate::{guard, CompiledPolicy, Policy, PolicyEngine, PolicyResult, PolicySelection};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SelectorOperator {
+    In,
+    NotIn,
+    Exists,
+    DoesNotExist,
+}
+
+// Kubernetes-style set-based requirement on one label
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LabelRequirement {
+    pub key: String,
+    pub operator: SelectorOperator,
+    #[serde(default)]
+    pub values: Vec<String>,
+}
+
+// Input of `find_policies`. Every given criterion must hold; an empty
+// selector matches all policies.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PolicySelector {
+    pub match_labels: HashMap<String, String>,
+    pub match_expressions: Vec<LabelRequirement>,
+    pub owner: Option<String>,
+    // Policies must carry all of these tags
+    pub tags: Vec<String>,
+}
+
+// Entry in the output of `find_policies` and `list_policies`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicySummary {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    pub enabled: bool,
+    // Own rules plus rules linked from rule_refs
+    pub rule_count: usize,
+    pub owner: Option<String>,
+    pub labels: HashMap<String, String>,
+    pub tags: Vec<String>,
+    pub source: Option<String>,
+    // digest::policy_hash; equal for identical policies
+    pub content_hash: String,
+}
+
+impl LabelRequirement {
+    fn matches(&self, labels: &HashMap<String, String>) -> bool {
+        let value = labels.get(&self.key);
+        match self.operator {
+            SelectorOperator::In => value.is_some_and(|v| self.values.contains(v)),
+            SelectorOperator::NotIn => value.is_none_or(|v| !self.values.contains(v)),
+            SelectorOperator::Exists => value.is_some(),
+            SelectorOperator::DoesNotExist => value.is_none(),
+        }
+    }
+}
+
+impl PolicySelector {
+    // Kubernetes label selector syntax, comma-separated requirements:
+    //
+    //   domain=payments          (or ==)
+    //   tier!=internal
+    //   env in (prod,staging)    env notin (dev)
+    //   legacy                   !legacy
+    pub fn parse_labels(text: &str) -> Result<PolicySelector, String> {
+        let mut selector = PolicySelector::default();
+        let mut depth = 0;
+        let mut start = 0;
+        let mut parts = Vec::new();
+        for (index, c) in text.char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                ',' if depth == 0 => {
+                    parts.push(&text[start..index]);
+                    start = index + 1;
+                }
+                _ => {}
+            }
+        }
+        parts.push(&text[start..]);
+
+        let set = |key: &str, list: &str, operator: SelectorOperator| -> Result<LabelRequirement, String> {
+            let values = list
+                .trim()
+                .strip_prefix('(')
+                .and_then(|list| list.strip_suffix(')'))
+                .ok_or_else(|| format!("expected a parenthesized value list after '{}'", key))?;
+            let values = values.split(',').map(str::trim).filter(|value| !value.is_empty()).map(String::from).collect();
+            Ok(LabelRequirement { key: key.to_string(), operator, values })
+        };
+        for part in parts.into_iter().map(str::trim).filter(|part| !part.is_empty()) {
+            let requirement = if let Some((key, value)) = part.split_once("!=") {
+                LabelRequirement { key: key.trim().to_string(), operator: SelectorOperator::NotIn, values: vec![value.trim().to_string()] }
+            } else if let Some((key, value)) = part.split_once("==").or_else(|| part.split_once('=')) {
+                selector.match_labels.insert(key.trim().to_string(), value.trim().to_string());
+                continue;
+            } else if let Some((key, list)) = part.split_once(" notin ") {
+                set(key.trim(), list, SelectorOperator::NotIn)?
+            } else if let Some((key, list)) = part.split_once(" in ") {
+                set(key.trim(), list, SelectorOperator::In)?
+            } else if let Some(key) = part.strip_prefix('!') {
+                LabelRequirement { key: key.trim().to_string(), operator: SelectorOperator::DoesNotExist, values: Vec::new() }
+            } else {
+                LabelRequirement { key: part.to_string(), operator: SelectorOperator::Exists, values: Vec::new() }
+            };
+            if requirement.key.is_empty() || requirement.key.contains(char::is_whitespace) {
+                return Err(format!("invalid requirement '{}'", part));
+            }
+            selector.match_expressions.push(requirement);
+        }
+        Ok(selector)
+    }
+
+    pub fn matches(&self, policy: &Policy) -> bool {
+        self.match_labels.iter().all(|(key, value)| policy.labels.get(key) == Some(value))
+            && self.match_expressions.iter().all(|requirement| requirement.matches(&policy.labels))
+            && self.owner.as_ref().is_none_or(|owner| policy.owner.as_ref() == Some(owner))
+            && self.tags.iter().all(|tag| policy.tags.contains(tag))
+    }
+}
+
+impl From<&CompiledPolicy> for PolicySummary {
+    fn from(compiled: &CompiledPolicy) -> Self {
+        let policy = &compiled.policy;
+        PolicySummary {
+            id: policy.id.clone(),
+            name: policy.name.clone(),
+            version: policy.version.clone(),
+            enabled: policy.enabled,
+            rule_count: compiled.rules().count(),
+            owner: policy.owner.clone(),
+            labels: policy.labels.clone(),
+            tags: policy.tags.clone(),
+            source: policy.source.clone(),
+            content_hash: compiled.content_hash().to_string(),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Loaded policies matching a JSON PolicySelector, as a JSON array of
+    // PolicySummary in load order
+    #[wasm_bindgen]
+    pub fn find_policies(&self, selector_json: &str) -> Result<String, JsValue> {
+        let selector: PolicySelector = serde_json::from_str(selector_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse policy selector: {}", e)).logged())?;
+
+        let matches: Vec<PolicySummary> = self
+            .policies
+            .iter()
+            .filter(|compiled| selector.matches(&compiled.policy))
+            .map(PolicySummary::from)
+            .collect();
+        Ok(to_json(&matches)?)
+    }
+
+    // Evaluates a request (statefully, like evaluate) against only the
+    // global policies the selector matches, so a gateway hosting several
+    // application domains skips the others' policies. `selector` is a
+    // label selector ("domain=payments,env in (prod)") or a JSON
+    // PolicySelector.
+    #[wasm_bindgen]
+    pub fn evaluate_scoped(&mut self, context_json: &str, selector: &str) -> Result<PolicyResult, JsValue> {
+        let parsed = if selector.trim_start().starts_with('{') {
+            serde_json::from_str(selector).map_err(|e| e.to_string())
+        } else {
+            PolicySelector::parse_labels(selector)
+        };
+        let selector = parsed.map_err(|e| {
+            PolicyEngineError::parse(format!("Failed to parse policy selector: {}", e))
+                .with_details(json!({ "selector": selector }))
+                .logged()
+        })?;
+        if self.debug_mode {
+            let matched = self.policies.iter().filter(|compiled| selector.matches(&compiled.policy)).count();
+            console_log!("Scoped evaluation over {} of {} policies", matched, self.policies.len());
+        }
+
+        let context = self.parse_context(context_json)?;
+        Ok(guard::guarded(|| self.evaluate_request(context, PolicySelection::Scoped(&selector))))
+    }
+
+    // JSON array of PolicySummary for every loaded policy, in load order
+    #[wasm_bindgen]
+    pub fn list_policies(&self) -> Result<String, JsValue> {
+        let summaries: Vec<PolicySummary> = self.policies.iter().map(PolicySummary::from).collect();
+        Ok(to_json(&summaries)?)
+    }
+
+    // Full JSON definition of a loaded policy
+    #[wasm_bindgen]
+    pub fn get_policy(&self, policy_id: &str) -> Result<String, JsValue> {
+        let compiled = self
+            .policies
+            .iter()
+            .find(|compiled| compiled.policy.id == policy_id)
+            .ok_or_else(|| {
+                PolicyEngineError::not_found(format!("Policy '{}' is not loaded", policy_id))
+                    .with_details(json!({ "policy_id": policy_id }))
+                    .logged()
+            })?;
+        Ok(to_json(&compiled.policy)?)
+    }
+}
@@ -0,0 +1,229 @@
+use wasm_bindgen::prelude::*;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::advice::{self, Advice};
+use crate::decision::Decision;
+use crate::error::PolicyEngineError;
+use crate::expr::{self, Expr, Value};
+use crate::functions::EvalScope;
+use crate::logging::LogLevel;
+use crate::vocabulary::Effect;
+use crate::{render_advice, render_list, PolicyContext, PolicyEngine, PolicyResult};
+
+// An obligation as written in a policy: either a bare identifier
+// (`log_access`) or a call with arguments evaluated against the request
+// context (`rate_limit(user_id, 100, "1h")`). Strings that are not valid
+// expressions are treated as an opaque identifier with no arguments.
+#[derive(Debug, Clone)]
+pub struct ObligationCall {
+    pub id: String,
+    pub args: Vec<Expr>,
+}
+
+impl ObligationCall {
+    pub fn parse(spec: &str) -> ObligationCall {
+        match expr::parse(spec) {
+            Ok(Expr::Attribute(path)) => ObligationCall { id: path.join("."), args: Vec::new() },
+            Ok(Expr::Call(name, args)) => ObligationCall { id: name, args },
+            _ => ObligationCall { id: spec.trim().to_string(), args: Vec::new() },
+        }
+    }
+
+    pub fn resolve_args(&self, scope: &EvalScope) -> Result<Vec<Value>, expr::ExprError> {
+        self.args.iter().map(|arg| expr::evaluate(arg, scope)).collect()
+    }
+}
+
+// An obligation of a whole policy (or of the root policy set), appended
+// to the policy's result when its decision matches `fulfill_on`, XACML
+// style. Written as a bare spec, fulfilled on PERMIT and DENY alike:
+//
+//   "log_access"
+//   { "obligation": "notify_owner(resource_owner)", "fulfill_on": "DENY" }
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "PolicyObligationSpec", into = "PolicyObligationSpec")]
+pub struct PolicyObligation {
+    pub obligation: String,
+    pub fulfill_on: Option<Effect>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum PolicyObligationSpec {
+    Spec(String),
+    Structured {
+        obligation: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        fulfill_on: Option<Effect>,
+    },
+}
+
+// Documents both spellings
+impl JsonSchema for PolicyObligation {
+    fn schema_name() -> String {
+        "PolicyObligation".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        PolicyObligationSpec::json_schema(gen)
+    }
+}
+
+impl From<PolicyObligationSpec> for PolicyObligation {
+    fn from(spec: PolicyObligationSpec) -> PolicyObligation {
+        match spec {
+            PolicyObligationSpec::Spec(obligation) => PolicyObligation { obligation, fulfill_on: None },
+            PolicyObligationSpec::Structured { obligation, fulfill_on } => PolicyObligation { obligation, fulfill_on },
+        }
+    }
+}
+
+// Bare specs are written back as strings
+impl From<PolicyObligation> for PolicyObligationSpec {
+    fn from(obligation: PolicyObligation) -> PolicyObligationSpec {
+        match obligation.fulfill_on {
+            None => PolicyObligationSpec::Spec(obligation.obligation),
+            fulfill_on => PolicyObligationSpec::Structured { obligation: obligation.obligation, fulfill_on },
+        }
+    }
+}
+
+impl From<String> for PolicyObligation {
+    fn from(obligation: String) -> PolicyObligation {
+        PolicyObligation { obligation, fulfill_on: None }
+    }
+}
+
+// Whether a policy-level obligation or advice applies to `decision`
+pub fn fulfills(fulfill_on: Option<&Effect>, decision: Decision) -> bool {
+    match fulfill_on {
+        Some(effect) => effect.decision() == decision,
+        None => matches!(decision, Decision::Permit | Decision::Deny),
+    }
+}
+
+// Payload passed (as JSON) to a registered obligation handler
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObligationInvocation {
+    pub obligation: String,
+    pub args: Vec<Value>,
+    pub request_id: String,
+    pub decision: Decision,
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Handlers are called as `handler(invocation_json)` after each
+    // evaluation whose result carries the obligation. Returning `false`
+    // or throwing leaves the obligation unacknowledged.
+    #[wasm_bindgen]
+    pub fn register_obligation_handler(&mut self, obligation_id: &str, handler: js_sys::Function) {
+        if self.debug_mode {
+            console_log!("Registered obligation handler: {}", obligation_id);
+        }
+        self.obligation_handlers.insert(obligation_id.to_string(), handler);
+    }
+
+    #[wasm_bindgen]
+    pub fn unregister_obligation_handler(&mut self, obligation_id: &str) -> bool {
+        self.obligation_handlers.remove(obligation_id).is_some()
+    }
+
+    // Obligations of the root policy set, in the same form as a policy's
+    // (`["log_access", {"obligation": "alert_soc", "fulfill_on": "DENY"}]`)
+    // and appended to final results the same way
+    #[wasm_bindgen]
+    pub fn set_root_obligations(&mut self, obligations_json: &str) -> Result<(), JsValue> {
+        let obligations: Vec<PolicyObligation> = serde_json::from_str(obligations_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse root obligations: {}", e)).logged())?;
+        if self.debug_mode {
+            console_log!("Root obligations: {}", obligations.len());
+        }
+        self.root_obligations = obligations;
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn set_root_advice(&mut self, advice_json: &str) -> Result<(), JsValue> {
+        let advice: Vec<Advice> = serde_json::from_str(advice_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse root advice: {}", e)).logged())?;
+        if self.debug_mode {
+            console_log!("Root advice: {}", advice.len());
+        }
+        self.root_advice = advice;
+        Ok(())
+    }
+}
+
+impl PolicyEngine {
+    // Appends the policy-level obligations and advice that apply to the
+    // result's decision after the rule's own, skipping obligations the
+    // result already carries
+    pub(crate) fn fulfill_policy_outputs(&self, result: &mut PolicyResult, obligations: &[PolicyObligation], advice: &[Advice]) {
+        let decision = result.decision;
+        let mut fulfilled = obligations.iter().filter(|item| fulfills(item.fulfill_on.as_ref(), decision)).peekable();
+        if fulfilled.peek().is_some() {
+            let mut specs: Vec<String> = serde_json::from_str(&result.obligations).unwrap_or_default();
+            for item in fulfilled {
+                if !specs.contains(&item.obligation) {
+                    specs.push(item.obligation.clone());
+                }
+            }
+            result.obligations = render_list(&specs);
+        }
+
+        let mut fulfilled = advice.iter().filter(|item| fulfills(item.fulfill_on.as_ref(), decision)).peekable();
+        if fulfilled.peek().is_some() {
+            let mut all = advice::parse_list(&result.advice);
+            all.extend(fulfilled.map(|item| Advice { fulfill_on: None, ..item.clone() }));
+            result.advice = render_advice(&all);
+        }
+    }
+
+    pub(crate) fn dispatch_obligations(&self, result: &mut PolicyResult, context: &PolicyContext, tenant: Option<&str>) {
+        if self.obligation_handlers.is_empty() {
+            return;
+        }
+
+        let specs: Vec<String> = serde_json::from_str(&result.obligations).unwrap_or_default();
+        let scope = EvalScope::new(self, context, tenant);
+        let mut acknowledged = Vec::new();
+
+        for spec in &specs {
+            let call = ObligationCall::parse(spec);
+            let handler = match self.obligation_handlers.get(&call.id) {
+                Some(handler) => handler,
+                None => continue,
+            };
+
+            let args = match call.resolve_args(&scope) {
+                Ok(args) => args,
+                Err(e) => {
+                    log_at!(LogLevel::Warn, "Obligation '{}' arguments failed to evaluate: {}", call.id, e);
+                    continue;
+                }
+            };
+
+            let invocation = ObligationInvocation {
+                obligation: call.id.clone(),
+                args,
+                request_id: context.request_id.clone(),
+                decision: result.decision,
+            };
+            let payload = serde_json::to_string(&invocation).unwrap_or_default();
+
+            match handler.call1(&JsValue::NULL, &JsValue::from_str(&payload)) {
+                Ok(ret) if ret.as_bool() == Some(false) => {}
+                Ok(_) => acknowledged.push(call.id),
+                Err(e) => {
+                    log_at!(LogLevel::Warn, "Obligation handler '{}' failed: {:?}", call.id, e);
+                }
+            }
+        }
+
+        result.acknowledged_obligations = serde_json::to_string(&acknowledged).unwrap_or_default();
+    }
+}
@@ -0,0 +1,367 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::attributes::context_field;
+use crate::decision::Decision;
+use crate::error::{to_json, PolicyEngineError};
+use crate::{guard, CompiledPolicy, PolicyEngine, PolicyResult, PolicySelection};
+
+// One resource a UI might show, and the operations it offers on it:
+//
+//   { "resource_type": "document", "resource_id": "d-42",
+//     "resource_classification": "internal", "operations": ["read", "write"] }
+//
+// Only resource fields may be set; everything else comes from the subject.
+#[derive(Debug, Deserialize)]
+struct Candidate {
+    operations: Vec<String>,
+    #[serde(flatten)]
+    resource: Map<String, Value>,
+}
+
+// The decision for every operation on one candidate, and the operations
+// the subject is permitted (PERMIT only: a CHALLENGE still needs a step-up)
+#[derive(Debug, Serialize)]
+struct ResourcePermissions {
+    index: usize,
+    resource_type: String,
+    resource_id: String,
+    permitted: Vec<String>,
+    decisions: BTreeMap<String, Decision>,
+}
+
+// Most actions evaluate_actions takes, so its mask fits JavaScript's
+// 32-bit bitwise operators
+const MAX_ACTIONS: usize = 32;
+
+// Output of evaluate_actions. Bit i of `permitted_mask` is set when the
+// i-th requested action is permitted (PERMIT only, as above).
+#[derive(Debug, Serialize)]
+struct ActionDecisions {
+    decisions: BTreeMap<String, Decision>,
+    permitted_mask: u32,
+}
+
+// Context fields enrich_context may set (GeoIP location, attested device
+// trust, declared purposes, the risk score, calendar business hours);
+// never fixed in an index
+const ENRICHED_FIELDS: &[&str] =
+    &["ip_country", "ip_city", "device_trust", "resource_attributes", "risk_score", "business_hours"];
+
+// One cell of an entitlement export
+#[derive(Debug, Serialize)]
+struct Entitlement {
+    subject: usize,
+    user_id: String,
+    resource: usize,
+    resource_type: String,
+    resource_id: String,
+    operation: String,
+    decision: Decision,
+    policy_id: Option<String>,
+    rule_id: Option<String>,
+}
+
+const CSV_HEADER: &str = "user_id,resource_type,resource_id,operation,decision,policy_id,rule_id";
+
+fn to_csv(entitlements: &[Entitlement]) -> String {
+    let mut csv = String::from(CSV_HEADER);
+    csv.push('\n');
+    for entitlement in entitlements {
+        let decision = entitlement.decision.to_string();
+        let fields = [
+            entitlement.user_id.as_str(),
+            &entitlement.resource_type,
+            &entitlement.resource_id,
+            &entitlement.operation,
+            &decision,
+            entitlement.policy_id.as_deref().unwrap_or_default(),
+            entitlement.rule_id.as_deref().unwrap_or_default(),
+        ];
+        let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+// RFC 4180 quoting for fields with separators, quotes or line breaks
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// A subject permitted by an access review, with the deciding rule
+#[derive(Debug, Serialize)]
+struct AccessHolder {
+    index: usize,
+    user_id: String,
+    policy_id: Option<String>,
+    rule_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct AccessReview {
+    resource_type: String,
+    resource_id: String,
+    operation: String,
+    evaluated: usize,
+    holders: Vec<AccessHolder>,
+}
+
+fn text(fields: &Map<String, Value>, field: &str) -> String {
+    fields.get(field).and_then(Value::as_str).unwrap_or_default().to_string()
+}
+
+fn check_resource(index: usize, resource: &Map<String, Value>) -> Result<(), PolicyEngineError> {
+    match resource.keys().find(|field| !field.starts_with("resource_")) {
+        Some(field) => Err(PolicyEngineError::validation(format!("Candidate {} sets '{}', which is not a resource field", index, field))
+            .with_details(json!({ "index": index, "field": field }))
+            .logged()),
+        None => Ok(()),
+    }
+}
+
+fn check_subject(index: usize, subject: &Map<String, Value>) -> Result<(), PolicyEngineError> {
+    match subject.keys().find(|field| field.starts_with("resource_") || *field == "operation") {
+        Some(field) => Err(PolicyEngineError::validation(format!("Subject {} sets '{}', which belongs to the resource context", index, field))
+            .with_details(json!({ "index": index, "field": field }))
+            .logged()),
+        None => Ok(()),
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Evaluates the subject against every candidate resource and operation
+    // and returns a JSON array of ResourcePermissions, one per candidate,
+    // so a UI can filter lists and menus in a single call. The queries are
+    // what-ifs: like evaluate_staged they leave quotas, sessions, travel
+    // history and baselines untouched.
+    #[wasm_bindgen]
+    pub fn query_permissions(&self, subject_context_json: &str, candidate_resources_json: &str) -> Result<String, JsValue> {
+        let subject: Map<String, Value> = serde_json::from_str(subject_context_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse subject context: {}", e)).logged())?;
+        let candidates: Vec<Candidate> = serde_json::from_str(candidate_resources_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse candidate resources: {}", e)).logged())?;
+
+        let mut permissions = Vec::with_capacity(candidates.len());
+        for (index, candidate) in candidates.iter().enumerate() {
+            check_resource(index, &candidate.resource)?;
+
+            let mut entry = ResourcePermissions {
+                index,
+                resource_type: text(&candidate.resource, "resource_type"),
+                resource_id: text(&candidate.resource, "resource_id"),
+                permitted: Vec::new(),
+                decisions: BTreeMap::new(),
+            };
+            for operation in &candidate.operations {
+                let mut raw = subject.clone();
+                raw.extend(candidate.resource.clone());
+                raw.insert("operation".to_string(), Value::String(operation.clone()));
+                let result = self.query(raw, &format!("Candidate {} operation '{}'", index, operation), PolicySelection::Explicit(&self.policies))?;
+                if result.decision == Decision::Permit {
+                    entry.permitted.push(operation.clone());
+                }
+                entry.decisions.insert(operation.clone(), result.decision);
+            }
+            permissions.push(entry);
+        }
+
+        if self.debug_mode {
+            let permitted = permissions.iter().filter(|entry| !entry.permitted.is_empty()).count();
+            console_log!("Permission query: {} of {} candidates permitted", permitted, permissions.len());
+        }
+        Ok(to_json(&permissions)?)
+    }
+
+    // Decisions for several operations on one request context (which must
+    // leave `operation` unset), for showing or hiding the buttons and menu
+    // items of a UI. Targets are matched once for the shared context, and
+    // each action only evaluates the policies still open. A what-if like
+    // query_permissions.
+    #[wasm_bindgen]
+    pub fn evaluate_actions(&self, context_json: &str, actions_json: &str) -> Result<String, JsValue> {
+        let context: Map<String, Value> = serde_json::from_str(context_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse context: {}", e)).logged())?;
+        let actions: Vec<String> = serde_json::from_str(actions_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse actions: {}", e)).logged())?;
+        if let Some(field) = ["operation", "action"].into_iter().find(|field| context.contains_key(*field)) {
+            return Err(PolicyEngineError::validation(format!("Context sets '{}'; actions are evaluated as the operation", field))
+                .with_details(json!({ "field": field }))
+                .logged()
+                .into());
+        }
+        if actions.is_empty() || actions.len() > MAX_ACTIONS || actions.iter().any(|action| action.trim().is_empty()) {
+            return Err(PolicyEngineError::validation(format!("Actions must be 1 to {} non-empty operations", MAX_ACTIONS))
+                .with_details(json!({ "count": actions.len(), "max": MAX_ACTIONS }))
+                .logged()
+                .into());
+        }
+
+        let open = self.open_policies(&context, "Context")?;
+        let selected: Vec<&CompiledPolicy> =
+            self.policies.iter().zip(&open).filter(|(_, open)| **open).map(|(policy, _)| policy).collect();
+        let mut output = ActionDecisions { decisions: BTreeMap::new(), permitted_mask: 0 };
+        for (index, action) in actions.iter().enumerate() {
+            let decision = match output.decisions.get(action) {
+                Some(decision) => *decision,
+                None => {
+                    let mut raw = context.clone();
+                    raw.insert("operation".to_string(), Value::String(action.clone()));
+                    let label = format!("Action '{}'", action);
+                    self.query(raw, &label, PolicySelection::Indexed(&selected))?.decision
+                }
+            };
+            if decision == Decision::Permit {
+                output.permitted_mask |= 1 << index;
+            }
+            output.decisions.insert(action.clone(), decision);
+        }
+
+        if self.debug_mode {
+            console_log!(
+                "Evaluated {} actions against {} of {} policies",
+                output.decisions.len(),
+                selected.len(),
+                self.policies.len()
+            );
+        }
+        Ok(to_json(&output)?)
+    }
+
+    // Access review: evaluates each candidate subject profile against one
+    // resource and operation and returns a JSON AccessReview naming the
+    // subjects permitted and the rule that permitted each. Like
+    // query_permissions this leaves engine state untouched.
+    #[wasm_bindgen]
+    pub fn who_can(&self, resource_context_json: &str, candidate_subjects_json: &str) -> Result<String, JsValue> {
+        let resource: Map<String, Value> = serde_json::from_str(resource_context_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse resource context: {}", e)).logged())?;
+        let subjects: Vec<Map<String, Value>> = serde_json::from_str(candidate_subjects_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse candidate subjects: {}", e)).logged())?;
+
+        let mut review = AccessReview {
+            resource_type: text(&resource, "resource_type"),
+            resource_id: text(&resource, "resource_id"),
+            operation: text(&resource, "operation"),
+            evaluated: subjects.len(),
+            holders: Vec::new(),
+        };
+        for (index, subject) in subjects.into_iter().enumerate() {
+            check_subject(index, &subject)?;
+            let user_id = text(&subject, "user_id");
+            let mut raw = resource.clone();
+            raw.extend(subject);
+            let result = self.query(raw, &format!("Subject {}", index), PolicySelection::Explicit(&self.policies))?;
+            if result.decision == Decision::Permit {
+                review.holders.push(AccessHolder { index, user_id, policy_id: result.policy_id, rule_id: result.rule_id });
+            }
+        }
+
+        if self.debug_mode {
+            console_log!("Access review: {} of {} subjects permitted", review.holders.len(), review.evaluated);
+        }
+        Ok(to_json(&review)?)
+    }
+
+    // Subject × resource × operation decisions for an access-certification
+    // campaign, as a JSON array of Entitlement or as CSV (`format` "json",
+    // the default, or "csv"). Subjects are shaped as in who_can, resources
+    // as in query_permissions. Targets are indexed first: each subject and
+    // each resource/operation is checked once against every target, and a
+    // cell only evaluates the policies neither side ruled out.
+    #[wasm_bindgen]
+    pub fn export_entitlements(&self, subjects_json: &str, resources_json: &str, format: Option<String>) -> Result<String, JsValue> {
+        let format = format.unwrap_or_else(|| "json".to_string());
+        if format != "json" && format != "csv" {
+            return Err(PolicyEngineError::unsupported_format(format!("Unknown entitlement export format '{}'", format))
+                .with_details(json!({ "format": format, "expected": ["json", "csv"] }))
+                .logged()
+                .into());
+        }
+        let subjects: Vec<Map<String, Value>> = serde_json::from_str(subjects_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse subjects: {}", e)).logged())?;
+        let resources: Vec<Candidate> = serde_json::from_str(resources_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse resources: {}", e)).logged())?;
+
+        // (resource index, operation, resource fields, policies still open)
+        let mut columns = Vec::new();
+        for (index, candidate) in resources.iter().enumerate() {
+            check_resource(index, &candidate.resource)?;
+            for operation in &candidate.operations {
+                let mut fields = candidate.resource.clone();
+                fields.insert("operation".to_string(), Value::String(operation.clone()));
+                let open = self.open_policies(&fields, &format!("Resource {} operation '{}'", index, operation))?;
+                columns.push((index, operation, fields, open));
+            }
+        }
+
+        let mut entitlements = Vec::with_capacity(subjects.len() * columns.len());
+        for (index, subject) in subjects.iter().enumerate() {
+            check_subject(index, subject)?;
+            let subject_open = self.open_policies(subject, &format!("Subject {}", index))?;
+            for (resource, operation, fields, open) in &columns {
+                let selected: Vec<&CompiledPolicy> = self
+                    .policies
+                    .iter()
+                    .zip(open.iter().zip(&subject_open))
+                    .filter(|(_, (resource_open, subject_open))| **resource_open && **subject_open)
+                    .map(|(policy, _)| policy)
+                    .collect();
+                let mut raw = subject.clone();
+                raw.extend(fields.clone());
+                let label = format!("Subject {} resource {} operation '{}'", index, resource, operation);
+                let result = self.query(raw, &label, PolicySelection::Indexed(&selected))?;
+                entitlements.push(Entitlement {
+                    subject: index,
+                    user_id: text(subject, "user_id"),
+                    resource: *resource,
+                    resource_type: text(fields, "resource_type"),
+                    resource_id: text(fields, "resource_id"),
+                    operation: (*operation).clone(),
+                    decision: result.decision,
+                    policy_id: result.policy_id,
+                    rule_id: result.rule_id,
+                });
+            }
+        }
+
+        if self.debug_mode {
+            console_log!("Exported {} entitlements for {} subjects", entitlements.len(), subjects.len());
+        }
+        match format.as_str() {
+            "csv" => Ok(to_csv(&entitlements)),
+            _ => Ok(to_json(&entitlements)?),
+        }
+    }
+}
+
+impl PolicyEngine {
+    // A what-if evaluation of a raw context, enriched as a request would
+    // be. Callers pass the active set explicitly (or indexed) so usage
+    // tracking does not count queries as requests.
+    pub(crate) fn query(&self, raw: Map<String, Value>, label: &str, selection: PolicySelection) -> Result<PolicyResult, JsValue> {
+        let mut context = self.check_context_value(Value::Object(raw)).map_err(|e| e.context(label).logged())?;
+        self.enrich_context(&mut context, selection.tenant());
+        Ok(guard::guarded(|| self.evaluate_context(&context, selection)))
+    }
+
+    // Per active policy, whether its target can still match once `fields`
+    // are fixed. Fields enrichment may overwrite are left unknown.
+    fn open_policies(&self, fields: &Map<String, Value>, label: &str) -> Result<Vec<bool>, JsValue> {
+        let fixed: BTreeSet<String> = fields
+            .keys()
+            .filter(|field| context_field(&[field.as_str()]).is_some() && !ENRICHED_FIELDS.contains(&field.as_str()))
+            .cloned()
+            .collect();
+        let context = self.partial_context(fields).map_err(|e| e.context(label).logged())?;
+        Ok(self.policies.iter().map(|policy| !self.ruled_out(policy, &context, &fixed)).collect())
+    }
+}
@@ -0,0 +1,329 @@
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use crate::baseline::BaselineTracker;
+use crate::bundle::{self, serde_bytes_vec};
+use crate::clock;
+use crate::digest::to_hex;
+use crate::error::{to_json, PolicyEngineError};
+use crate::logging::LogLevel;
+use crate::quota::QuotaTracker;
+use crate::replay::ENGINE_VERSION;
+use crate::validity::GrantedDecisions;
+use crate::PolicyEngine;
+
+const PERSISTED_MAGIC: &str = "uars-persisted-state";
+const PERSISTED_FORMAT_VERSION: u32 = 1;
+const OBJECT_STORE: &str = "state";
+const STATE_KEY: &str = "engine";
+
+const POLICIES_SECTION: &str = "policies";
+const DECISIONS_SECTION: &str = "decisions";
+const QUOTAS_SECTION: &str = "quotas";
+const BASELINES_SECTION: &str = "baselines";
+
+// One independently restorable part of the persisted state. The hash
+// catches truncated or corrupted storage, not tampering: anything that can
+// write the store can recompute it.
+#[derive(Serialize, Deserialize)]
+struct Section {
+    name: String,
+    sha256: String,
+    #[serde(with = "serde_bytes_vec")]
+    bytes: Vec<u8>,
+}
+
+impl Section {
+    fn new(name: &str, bytes: Vec<u8>) -> Section {
+        Section { name: name.to_string(), sha256: to_hex(&Sha256::digest(&bytes)), bytes }
+    }
+
+    fn verified(&self) -> bool {
+        to_hex(&Sha256::digest(&self.bytes)) == self.sha256
+    }
+}
+
+// CBOR record kept under a single IndexedDB (or localStorage) key: the
+// compiled policy bundle, granted decisions, quota counters and user
+// baselines
+#[derive(Serialize, Deserialize)]
+struct PersistedState {
+    magic: String,
+    format_version: u32,
+    engine_version: String,
+    saved_at: DateTime<Utc>,
+    sections: Vec<Section>,
+}
+
+// Output of `restore_persisted_state`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoreReport {
+    pub saved_at: DateTime<Utc>,
+    pub restored: Vec<String>,
+    pub skipped: Vec<SkippedSection>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedSection {
+    pub section: String,
+    pub reason: String,
+}
+
+fn to_cbor<T: Serialize>(value: &T) -> Result<Vec<u8>, PolicyEngineError> {
+    let mut bytes = Vec::new();
+    ciborium::into_writer(value, &mut bytes)
+        .map_err(|e| PolicyEngineError::internal(format!("Failed to encode persisted state: {}", e)))?;
+    Ok(bytes)
+}
+
+fn from_cbor<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> Result<T, PolicyEngineError> {
+    ciborium::from_reader(bytes).map_err(|e| PolicyEngineError::parse(format!("Failed to decode persisted state: {}", e)))
+}
+
+fn decode_state(bytes: &[u8]) -> Result<PersistedState, PolicyEngineError> {
+    let state: PersistedState = from_cbor(bytes)?;
+    if state.magic != PERSISTED_MAGIC {
+        return Err(PolicyEngineError::parse("not a persisted engine state"));
+    }
+    // Bundles hold compiled expressions, so only the writing engine reads them
+    if state.format_version != PERSISTED_FORMAT_VERSION || state.engine_version != ENGINE_VERSION {
+        return Err(PolicyEngineError::unsupported_format(format!(
+            "Persisted state from engine {} (format {}) cannot be loaded by engine {} (format {})",
+            state.engine_version, state.format_version, ENGINE_VERSION, PERSISTED_FORMAT_VERSION
+        ))
+        .with_details(json!({
+            "engine_version": state.engine_version,
+            "format_version": state.format_version,
+            "supported_engine_version": ENGINE_VERSION,
+            "supported_format_version": PERSISTED_FORMAT_VERSION,
+        })));
+    }
+    Ok(state)
+}
+
+// Resolves with the request's result once it succeeds
+async fn request_result(request: &web_sys::IdbRequest) -> Result<JsValue, JsValue> {
+    let promise = js_sys::Promise::new(&mut |resolve, reject| {
+        let on_success = Closure::once_into_js(move |_event: JsValue| {
+            let _ = resolve.call0(&JsValue::NULL);
+        });
+        let on_error = Closure::once_into_js(move |event: JsValue| {
+            let _ = reject.call1(&JsValue::NULL, &event);
+        });
+        request.set_onsuccess(Some(on_success.unchecked_ref()));
+        request.set_onerror(Some(on_error.unchecked_ref()));
+    });
+    JsFuture::from(promise).await?;
+    request.result()
+}
+
+// Resolves once the transaction has committed
+async fn committed(transaction: &web_sys::IdbTransaction) -> Result<(), JsValue> {
+    let promise = js_sys::Promise::new(&mut |resolve, reject| {
+        let on_complete = Closure::once_into_js(move |_event: JsValue| {
+            let _ = resolve.call0(&JsValue::NULL);
+        });
+        let on_error = Closure::once_into_js(move |event: JsValue| {
+            let _ = reject.call1(&JsValue::NULL, &event);
+        });
+        transaction.set_oncomplete(Some(on_complete.unchecked_ref()));
+        transaction.set_onerror(Some(on_error.unchecked_ref()));
+    });
+    JsFuture::from(promise).await.map(|_| ())
+}
+
+fn object_store(db: &web_sys::IdbDatabase, mode: web_sys::IdbTransactionMode) -> Result<(web_sys::IdbTransaction, web_sys::IdbObjectStore), JsValue> {
+    let transaction = db.transaction_with_str_and_mode(OBJECT_STORE, mode)?;
+    let store = transaction.object_store(OBJECT_STORE)?;
+    Ok((transaction, store))
+}
+
+// IndexedDB adapter for persisted engine state. It only moves bytes; the
+// engine produces and consumes them synchronously:
+//
+//   const store = await StateStore.open("uars-policy-engine");
+//   const saved = await store.load();
+//   if (saved) engine.restore_persisted_state(saved);
+//   ...
+//   await store.save(engine.export_persisted_state());
+#[wasm_bindgen]
+pub struct StateStore {
+    db: web_sys::IdbDatabase,
+}
+
+#[wasm_bindgen]
+impl StateStore {
+    // Opens (creating if needed) the named database. Works in windows and
+    // workers.
+    #[wasm_bindgen]
+    pub async fn open(name: String) -> Result<StateStore, JsValue> {
+        let factory: web_sys::IdbFactory = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("indexedDB"))?
+            .dyn_into()
+            .map_err(|_| PolicyEngineError::invalid_state("IndexedDB is not available"))?;
+        let request = factory.open_with_u32(&name, 1)?;
+
+        let upgrade = request.clone();
+        let on_upgrade = Closure::once_into_js(move |_event: JsValue| {
+            if let Ok(db) = upgrade.result().and_then(|db| db.dyn_into::<web_sys::IdbDatabase>()) {
+                if !db.object_store_names().contains(OBJECT_STORE) {
+                    let _ = db.create_object_store(OBJECT_STORE);
+                }
+            }
+        });
+        request.set_onupgradeneeded(Some(on_upgrade.unchecked_ref()));
+
+        let db = request_result(&request).await?.dyn_into::<web_sys::IdbDatabase>()?;
+        Ok(StateStore { db })
+    }
+
+    // Stores bytes from `export_persisted_state`, replacing earlier state.
+    // Returns a Promise.
+    #[wasm_bindgen]
+    pub fn save(&self, state: Vec<u8>) -> js_sys::Promise {
+        let db = self.db.clone();
+        future_to_promise(async move {
+            let (transaction, store) = object_store(&db, web_sys::IdbTransactionMode::Readwrite)?;
+            let value = js_sys::Uint8Array::from(state.as_slice());
+            store.put_with_key(&value, &JsValue::from_str(STATE_KEY))?;
+            committed(&transaction).await?;
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    // Promise of the stored bytes, or undefined when nothing is stored
+    #[wasm_bindgen]
+    pub fn load(&self) -> js_sys::Promise {
+        let db = self.db.clone();
+        future_to_promise(async move {
+            let (_transaction, store) = object_store(&db, web_sys::IdbTransactionMode::Readonly)?;
+            let request = store.get(&JsValue::from_str(STATE_KEY))?;
+            let value = request_result(&request).await?;
+            Ok(match value.dyn_into::<js_sys::Uint8Array>() {
+                Ok(bytes) => bytes.into(),
+                Err(_) => JsValue::UNDEFINED,
+            })
+        })
+    }
+
+    // Deletes the stored state. Returns a Promise.
+    #[wasm_bindgen]
+    pub fn clear(&self) -> js_sys::Promise {
+        let db = self.db.clone();
+        future_to_promise(async move {
+            let (transaction, store) = object_store(&db, web_sys::IdbTransactionMode::Readwrite)?;
+            store.delete(&JsValue::from_str(STATE_KEY))?;
+            committed(&transaction).await?;
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    #[wasm_bindgen]
+    pub fn close(&self) {
+        self.db.close();
+    }
+}
+
+fn local_storage() -> Result<web_sys::Storage, PolicyEngineError> {
+    js_sys::global()
+        .dyn_into::<web_sys::Window>()
+        .ok()
+        .and_then(|window| window.local_storage().ok().flatten())
+        .ok_or_else(|| PolicyEngineError::invalid_state("localStorage is not available"))
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Compiled policy bundle, granted decisions, quota counters and user
+    // baselines, each with a SHA-256 hash, for `StateStore.save` or
+    // localStorage
+    #[wasm_bindgen]
+    pub fn export_persisted_state(&self) -> Result<Vec<u8>, JsValue> {
+        let state = PersistedState {
+            magic: PERSISTED_MAGIC.to_string(),
+            format_version: PERSISTED_FORMAT_VERSION,
+            engine_version: ENGINE_VERSION.to_string(),
+            saved_at: clock::now(),
+            sections: vec![
+                Section::new(POLICIES_SECTION, bundle::encode(self.policies.clone())?),
+                Section::new(DECISIONS_SECTION, to_cbor(&self.granted)?),
+                Section::new(QUOTAS_SECTION, to_cbor(&self.quotas)?),
+                Section::new(BASELINES_SECTION, to_cbor(&self.baselines)?),
+            ],
+        };
+        Ok(to_cbor(&state).map_err(|e| e.logged())?)
+    }
+
+    // Restores persisted state, replacing the loaded policies, granted
+    // decisions, quota counters and baselines. A section whose hash does not match is
+    // skipped (and logged) and the rest is restored. Returns a JSON
+    // RestoreReport.
+    #[wasm_bindgen]
+    pub fn restore_persisted_state(&mut self, bytes: &[u8]) -> Result<String, JsValue> {
+        let state = decode_state(bytes).map_err(|e| e.logged())?;
+        let mut report = RestoreReport { saved_at: state.saved_at, restored: Vec::new(), skipped: Vec::new() };
+
+        for section in &state.sections {
+            let restored = if !section.verified() {
+                Err(PolicyEngineError::validation("integrity hash mismatch"))
+            } else {
+                self.restore_section(section)
+            };
+            match restored {
+                Ok(()) => report.restored.push(section.name.clone()),
+                Err(error) => {
+                    log_at!(LogLevel::Warn, "Skipped persisted section '{}': {}", section.name, error.message());
+                    report.skipped.push(SkippedSection { section: section.name.clone(), reason: error.message().to_string() });
+                }
+            }
+        }
+        Ok(to_json(&report)?)
+    }
+
+    // Synchronous fallback for pages without IndexedDB; stored as base64
+    // under `key`
+    #[wasm_bindgen]
+    pub fn persist_to_local_storage(&self, key: &str) -> Result<(), JsValue> {
+        let bytes = self.export_persisted_state()?;
+        local_storage()?.set_item(key, &STANDARD.encode(bytes))?;
+        Ok(())
+    }
+
+    // JSON RestoreReport, or null when nothing is stored under `key`
+    #[wasm_bindgen]
+    pub fn restore_from_local_storage(&mut self, key: &str) -> Result<String, JsValue> {
+        let encoded = match local_storage()?.get_item(key)? {
+            Some(encoded) => encoded,
+            None => return Ok("null".to_string()),
+        };
+        let bytes = STANDARD
+            .decode(encoded)
+            .map_err(|e| PolicyEngineError::parse(format!("Persisted state is not valid base64: {}", e)).logged())?;
+        self.restore_persisted_state(&bytes)
+    }
+}
+
+impl PolicyEngine {
+    fn restore_section(&mut self, section: &Section) -> Result<(), PolicyEngineError> {
+        match section.name.as_str() {
+            POLICIES_SECTION => {
+                let mut policies = bundle::decode(&section.bytes)?;
+                for compiled in &mut policies {
+                    self.link_rule_refs(compiled)?;
+                }
+                self.policies = policies;
+            }
+            DECISIONS_SECTION => self.granted = from_cbor::<GrantedDecisions>(&section.bytes)?,
+            QUOTAS_SECTION => self.quotas = from_cbor::<QuotaTracker>(&section.bytes)?,
+            BASELINES_SECTION => self.baselines = from_cbor::<BaselineTracker>(&section.bytes)?,
+            other => return Err(PolicyEngineError::unsupported_format(format!("unknown section '{}'", other))),
+        }
+        Ok(())
+    }
+}
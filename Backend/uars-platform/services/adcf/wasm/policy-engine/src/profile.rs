@@ -0,0 +1,250 @@
+#[cfg(feature = "telemetry")]
+use wasm_bindgen::prelude::*;
+#[cfg(feature = "telemetry")]
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+#[cfg(feature = "telemetry")]
+use crate::error::{to_json, PolicyEngineError};
+#[cfg(feature = "telemetry")]
+use crate::stats;
+use crate::{create_sample_context, create_sample_policy, PolicyContext, PolicyEngine};
+
+// Phase timings of one evaluation, in milliseconds. `total_ms` runs from
+// the start of context parsing to the end of post-processing (quotas,
+// obligation dispatch), so it is the engine's whole share of a request.
+#[cfg(feature = "telemetry")]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EvaluationProfile {
+    pub request_id: Option<String>,
+    pub parse_ms: f64,
+    pub enrich_ms: f64,
+    pub target_ms: f64,
+    pub rules_ms: f64,
+    pub combine_ms: f64,
+    pub post_ms: f64,
+    pub total_ms: f64,
+    pub policies_considered: usize,
+    pub policies_applicable: usize,
+    #[serde(skip)]
+    started_at: f64,
+}
+
+// Phases timed from the evaluation loop
+pub enum Phase {
+    Enrich,
+    Rules,
+    Combine,
+}
+
+// Output of `benchmark`
+#[cfg(feature = "telemetry")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkReport {
+    pub policy_count: usize,
+    pub context_count: usize,
+    pub compile_ms: f64,
+    pub total_ms: f64,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+    pub evaluations_per_second: f64,
+    // Per-phase means over all evaluations
+    pub phases: EvaluationProfile,
+}
+
+pub const MAX_BENCHMARK_POLICIES: usize = 10_000;
+pub const MAX_BENCHMARK_CONTEXTS: usize = 100_000;
+
+// `count` role-gated policies on top of the sample policy: many targets,
+// a few rules each, the shape of a typical deployment
+pub fn synthetic_policy_set(count: usize) -> String {
+    let mut policies: Vec<Value> = vec![serde_json::from_str(&create_sample_policy()).unwrap_or_default()];
+    for i in 0..count {
+        policies.push(json!({
+            "id": format!("bench-policy-{}", i),
+            "name": format!("Bench policy {}", i),
+            "version": "1.0.0",
+            "description": "",
+            "target": format!("resource.type == 'data_capsule' && 'team-{}' in user.groups", i % 8),
+            "combining_algorithm": "deny-overrides",
+            "rules": [
+                {
+                    "id": "permit-analysts",
+                    "name": "Analysts may read",
+                    "description": "",
+                    "priority": 100,
+                    "condition": "'analyst' in user.roles && operation == 'read' && risk_score < 5.0",
+                    "effect": "PERMIT",
+                    "obligations": [],
+                    "advice": []
+                },
+                {
+                    "id": "deny-untrusted",
+                    "name": "Untrusted devices",
+                    "description": "",
+                    "priority": 200,
+                    "condition": "device.trust != 'trusted' || vpn_detected == true",
+                    "effect": "DENY",
+                    "obligations": [],
+                    "advice": []
+                }
+            ],
+            "obligations": [],
+            "advice": []
+        }));
+    }
+    Value::Array(policies).to_string()
+}
+
+// Sample contexts varied across users, groups and risk scores
+pub fn synthetic_contexts(count: usize) -> Vec<String> {
+    let base: Value = serde_json::from_str(&create_sample_context()).unwrap_or_default();
+    (0..count)
+        .map(|i| {
+            let mut context = base.clone();
+            context["request_id"] = json!(format!("req-{}", i));
+            context["user_id"] = json!(format!("user-{}", i % 50));
+            context["user_groups"] = json!([format!("team-{}", i % 8), "research"]);
+            context["risk_score"] = json!((i % 10) as f64);
+            context.to_string()
+        })
+        .collect()
+}
+
+#[cfg(feature = "telemetry")]
+fn percentile(sorted: &[f64], fraction: f64) -> f64 {
+    match sorted.len() {
+        0 => 0.0,
+        len => sorted[((len - 1) as f64 * fraction).round() as usize],
+    }
+}
+
+// Evaluates `context_count` synthetic requests against `policy_count`
+// synthetic policies in a scratch engine and returns a JSON
+// BenchmarkReport. Meant for running on real end-user devices; the
+// caller's engines are not touched. Needs the telemetry feature.
+#[cfg(feature = "telemetry")]
+#[wasm_bindgen]
+pub fn benchmark(policy_count: usize, context_count: usize) -> Result<String, JsValue> {
+    if policy_count > MAX_BENCHMARK_POLICIES || context_count == 0 || context_count > MAX_BENCHMARK_CONTEXTS {
+        return Err(PolicyEngineError::validation(format!(
+            "benchmark takes up to {} policies and 1 to {} contexts",
+            MAX_BENCHMARK_POLICIES, MAX_BENCHMARK_CONTEXTS
+        ))
+        .with_details(json!({ "policy_count": policy_count, "context_count": context_count }))
+        .logged()
+        .into());
+    }
+
+    let mut engine = PolicyEngine::new();
+    let policies = synthetic_policy_set(policy_count);
+    let started = stats::now_ms();
+    engine.load_policies_atomic(&policies)?;
+    let compile_ms = stats::now_ms() - started;
+
+    let contexts = synthetic_contexts(context_count);
+    let mut totals = EvaluationProfile::default();
+    let mut latencies = Vec::with_capacity(context_count);
+    for context in &contexts {
+        engine.evaluate(context)?;
+        let profile = engine.last_profile.borrow();
+        totals.parse_ms += profile.parse_ms;
+        totals.enrich_ms += profile.enrich_ms;
+        totals.target_ms += profile.target_ms;
+        totals.rules_ms += profile.rules_ms;
+        totals.combine_ms += profile.combine_ms;
+        totals.post_ms += profile.post_ms;
+        totals.total_ms += profile.total_ms;
+        totals.policies_considered += profile.policies_considered;
+        totals.policies_applicable += profile.policies_applicable;
+        latencies.push(profile.total_ms);
+    }
+
+    let n = context_count as f64;
+    let phases = EvaluationProfile {
+        request_id: None,
+        parse_ms: totals.parse_ms / n,
+        enrich_ms: totals.enrich_ms / n,
+        target_ms: totals.target_ms / n,
+        rules_ms: totals.rules_ms / n,
+        combine_ms: totals.combine_ms / n,
+        post_ms: totals.post_ms / n,
+        total_ms: totals.total_ms / n,
+        policies_considered: totals.policies_considered / context_count,
+        policies_applicable: totals.policies_applicable / context_count,
+        started_at: 0.0,
+    };
+    latencies.sort_by(f64::total_cmp);
+    let report = BenchmarkReport {
+        policy_count: engine.get_policy_count(),
+        context_count,
+        compile_ms,
+        total_ms: totals.total_ms,
+        mean_ms: phases.total_ms,
+        p50_ms: percentile(&latencies, 0.5),
+        p95_ms: percentile(&latencies, 0.95),
+        max_ms: latencies.last().copied().unwrap_or(0.0),
+        evaluations_per_second: if totals.total_ms > 0.0 { n * 1000.0 / totals.total_ms } else { 0.0 },
+        phases,
+    };
+    Ok(to_json(&report)?)
+}
+
+#[cfg(feature = "telemetry")]
+#[wasm_bindgen]
+impl PolicyEngine {
+    // JSON EvaluationProfile of the most recent evaluation (all zero
+    // before the first)
+    #[wasm_bindgen]
+    pub fn profile_last_evaluation(&self) -> Result<String, JsValue> {
+        Ok(to_json(&*self.last_profile.borrow())?)
+    }
+}
+
+#[cfg(feature = "telemetry")]
+impl PolicyEngine {
+    // Starts a new profile once the context is parsed; `started` is when
+    // parsing began
+    pub(crate) fn begin_profile(&self, started: f64) {
+        let now = stats::now_ms();
+        *self.last_profile.borrow_mut() =
+            EvaluationProfile { parse_ms: now - started, started_at: started, ..EvaluationProfile::default() };
+    }
+
+    pub(crate) fn record_targets(&self, considered: usize, applicable: usize, elapsed_ms: f64) {
+        let mut profile = self.last_profile.borrow_mut();
+        profile.target_ms = elapsed_ms;
+        profile.policies_considered = considered;
+        profile.policies_applicable = applicable;
+    }
+
+    pub(crate) fn finish_profile(&self, context: &PolicyContext, post_ms: f64) {
+        let mut profile = self.last_profile.borrow_mut();
+        profile.request_id = Some(context.request_id.clone());
+        profile.post_ms = post_ms;
+        profile.total_ms = stats::now_ms() - profile.started_at;
+    }
+
+    pub(crate) fn record_phase(&self, phase: Phase, elapsed_ms: f64) {
+        let mut profile = self.last_profile.borrow_mut();
+        match phase {
+            Phase::Enrich => profile.enrich_ms = elapsed_ms,
+            Phase::Rules => profile.rules_ms = elapsed_ms,
+            Phase::Combine => profile.combine_ms = elapsed_ms,
+        }
+    }
+}
+
+// Without telemetry nothing is profiled
+#[cfg(not(feature = "telemetry"))]
+impl PolicyEngine {
+    pub(crate) fn begin_profile(&self, _started: f64) {}
+
+    pub(crate) fn record_targets(&self, _considered: usize, _applicable: usize, _elapsed_ms: f64) {}
+
+    pub(crate) fn finish_profile(&self, _context: &PolicyContext, _post_ms: f64) {}
+
+    pub(crate) fn record_phase(&self, _phase: Phase, _elapsed_ms: f64) {}
+}
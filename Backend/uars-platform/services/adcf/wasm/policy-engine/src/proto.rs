@@ -0,0 +1,571 @@
+use wasm_bindgen::prelude::*;
+use chrono::{DateTime, Duration, Utc};
+use prost::Message;
+use std::collections::{HashMap, HashSet};
+
+use crate::advice::{self, Advice, Severity};
+use crate::confidence::AttributeQuality;
+use crate::delegation::DelegationLink;
+use crate::error::PolicyEngineError;
+use crate::obligations::PolicyObligation;
+use crate::vocabulary::{CombiningAlgorithm, DeviceTrust, Effect, ThreatLevel};
+use crate::{format, guard, stats, DeviceAttestation, Policy, PolicyContext, PolicyEngine, PolicyResult, PolicyRule, PolicySelection};
+
+// Messages of proto/policy_engine.proto (package uars.policy.v1), written
+// as prost-build would generate them so the build needs no protoc. Keep
+// tags in step with the .proto file.
+pub mod v1 {
+    use std::collections::HashMap;
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct DelegationLink {
+        #[prost(string, tag = "1")]
+        pub actor: String,
+        #[prost(string, tag = "2")]
+        pub on_behalf_of: String,
+        #[prost(string, tag = "3")]
+        pub grant_id: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct DeviceAttestation {
+        #[prost(string, tag = "1")]
+        pub attestation_object: String,
+        #[prost(string, tag = "2")]
+        pub client_data_json: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct AttributeQuality {
+        #[prost(double, optional, tag = "1")]
+        pub confidence: Option<f64>,
+        #[prost(string, optional, tag = "2")]
+        pub observed_at: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct PolicyContext {
+        #[prost(string, optional, tag = "1")]
+        pub request_id: Option<String>,
+        #[prost(string, optional, tag = "2")]
+        pub timestamp: Option<String>,
+        #[prost(string, optional, tag = "3")]
+        pub operation: Option<String>,
+        #[prost(string, optional, tag = "4")]
+        pub user_id: Option<String>,
+        #[prost(string, repeated, tag = "5")]
+        pub user_roles: Vec<String>,
+        #[prost(string, repeated, tag = "6")]
+        pub user_groups: Vec<String>,
+        #[prost(map = "string, string", tag = "7")]
+        pub user_attributes: HashMap<String, String>,
+        #[prost(string, optional, tag = "8")]
+        pub device_id: Option<String>,
+        #[prost(string, optional, tag = "9")]
+        pub device_type: Option<String>,
+        #[prost(string, optional, tag = "10")]
+        pub device_trust: Option<String>,
+        #[prost(bool, optional, tag = "11")]
+        pub device_attested: Option<bool>,
+        #[prost(string, optional, tag = "12")]
+        pub device_posture_token: Option<String>,
+        #[prost(message, optional, tag = "13")]
+        pub device_attestation: Option<DeviceAttestation>,
+        #[prost(string, optional, tag = "14")]
+        pub ip_address: Option<String>,
+        #[prost(string, optional, tag = "15")]
+        pub ip_country: Option<String>,
+        #[prost(string, optional, tag = "16")]
+        pub ip_city: Option<String>,
+        #[prost(string, optional, tag = "17")]
+        pub network_zone: Option<String>,
+        #[prost(bool, optional, tag = "18")]
+        pub vpn_detected: Option<bool>,
+        #[prost(string, optional, tag = "19")]
+        pub session_id: Option<String>,
+        #[prost(int64, optional, tag = "20")]
+        pub session_age_seconds: Option<i64>,
+        #[prost(string, optional, tag = "21")]
+        pub auth_method: Option<String>,
+        #[prost(bool, optional, tag = "22")]
+        pub mfa_verified: Option<bool>,
+        #[prost(string, optional, tag = "23")]
+        pub time_of_day: Option<String>,
+        #[prost(string, optional, tag = "24")]
+        pub day_of_week: Option<String>,
+        #[prost(bool, optional, tag = "25")]
+        pub business_hours: Option<bool>,
+        #[prost(double, optional, tag = "26")]
+        pub risk_score: Option<f64>,
+        #[prost(string, optional, tag = "27")]
+        pub threat_level: Option<String>,
+        #[prost(string, optional, tag = "28")]
+        pub resource_type: Option<String>,
+        #[prost(string, optional, tag = "29")]
+        pub resource_id: Option<String>,
+        #[prost(string, optional, tag = "30")]
+        pub resource_classification: Option<String>,
+        #[prost(string, optional, tag = "31")]
+        pub resource_owner: Option<String>,
+        #[prost(map = "string, string", tag = "32")]
+        pub resource_attributes: HashMap<String, String>,
+        #[prost(string, optional, tag = "33")]
+        pub intent_purpose: Option<String>,
+        #[prost(string, optional, tag = "34")]
+        pub intent_justification: Option<String>,
+        #[prost(int64, optional, tag = "35")]
+        pub intent_duration_seconds: Option<i64>,
+        #[prost(message, repeated, tag = "36")]
+        pub delegation: Vec<DelegationLink>,
+        #[prost(map = "string, string", tag = "37")]
+        pub constraints: HashMap<String, String>,
+        #[prost(map = "string, string", tag = "38")]
+        pub metadata: HashMap<String, String>,
+        #[prost(map = "string, message", tag = "39")]
+        pub attribute_quality: HashMap<String, AttributeQuality>,
+        #[prost(string, optional, tag = "40")]
+        pub locale: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Advice {
+        #[prost(string, tag = "1")]
+        pub id: String,
+        #[prost(string, tag = "2")]
+        pub severity: String,
+        #[prost(string, tag = "3")]
+        pub message: String,
+        #[prost(map = "string, string", tag = "4")]
+        pub attributes: HashMap<String, String>,
+        #[prost(string, optional, tag = "5")]
+        pub fulfill_on: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct PolicyObligation {
+        #[prost(string, tag = "1")]
+        pub obligation: String,
+        #[prost(string, optional, tag = "2")]
+        pub fulfill_on: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct PolicyRule {
+        #[prost(string, tag = "1")]
+        pub id: String,
+        #[prost(string, tag = "2")]
+        pub name: String,
+        #[prost(string, tag = "3")]
+        pub description: String,
+        #[prost(int32, tag = "4")]
+        pub priority: i32,
+        #[prost(string, tag = "5")]
+        pub condition: String,
+        #[prost(string, tag = "6")]
+        pub effect: String,
+        #[prost(string, repeated, tag = "7")]
+        pub obligations: Vec<String>,
+        #[prost(message, repeated, tag = "8")]
+        pub advice: Vec<Advice>,
+        #[prost(string, optional, tag = "9")]
+        pub challenge_json: Option<String>,
+        #[prost(string, optional, tag = "10")]
+        pub approval_json: Option<String>,
+        #[prost(bool, tag = "11")]
+        pub break_glass: bool,
+        #[prost(string, optional, tag = "12")]
+        pub reason_code: Option<String>,
+        #[prost(string, optional, tag = "13")]
+        pub remediation: Option<String>,
+        #[prost(string, repeated, tag = "14")]
+        pub environments: Vec<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Policy {
+        #[prost(string, tag = "1")]
+        pub id: String,
+        #[prost(string, tag = "2")]
+        pub name: String,
+        #[prost(string, tag = "3")]
+        pub version: String,
+        #[prost(string, tag = "4")]
+        pub description: String,
+        #[prost(string, tag = "5")]
+        pub target: String,
+        #[prost(message, repeated, tag = "6")]
+        pub rules: Vec<PolicyRule>,
+        #[prost(map = "string, string", tag = "7")]
+        pub definitions: HashMap<String, String>,
+        #[prost(string, tag = "8")]
+        pub combining_algorithm: String,
+        #[prost(message, repeated, tag = "9")]
+        pub obligations: Vec<PolicyObligation>,
+        #[prost(message, repeated, tag = "10")]
+        pub advice: Vec<Advice>,
+        #[prost(string, optional, tag = "11")]
+        pub source: Option<String>,
+        #[prost(string, repeated, tag = "12")]
+        pub rule_refs: Vec<String>,
+        #[prost(map = "string, string", tag = "13")]
+        pub labels: HashMap<String, String>,
+        #[prost(string, optional, tag = "14")]
+        pub owner: Option<String>,
+        #[prost(string, repeated, tag = "15")]
+        pub tags: Vec<String>,
+        #[prost(bool, optional, tag = "16")]
+        pub enabled: Option<bool>,
+        #[prost(int32, tag = "17")]
+        pub priority: i32,
+        #[prost(string, repeated, tag = "18")]
+        pub environments: Vec<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct PolicySet {
+        #[prost(message, repeated, tag = "1")]
+        pub policies: Vec<Policy>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct PolicyResult {
+        #[prost(string, tag = "1")]
+        pub decision: String,
+        #[prost(string, tag = "2")]
+        pub reason: String,
+        #[prost(double, tag = "3")]
+        pub confidence: f64,
+        #[prost(string, repeated, tag = "4")]
+        pub obligations: Vec<String>,
+        #[prost(message, repeated, tag = "5")]
+        pub advice: Vec<Advice>,
+        #[prost(string, optional, tag = "6")]
+        pub challenge_json: Option<String>,
+        #[prost(string, optional, tag = "7")]
+        pub approval_json: Option<String>,
+        #[prost(string, repeated, tag = "8")]
+        pub acknowledged_obligations: Vec<String>,
+        #[prost(double, optional, tag = "9")]
+        pub retry_after: Option<f64>,
+        #[prost(string, optional, tag = "10")]
+        pub policy_id: Option<String>,
+        #[prost(string, optional, tag = "11")]
+        pub rule_id: Option<String>,
+        #[prost(string, optional, tag = "12")]
+        pub error_code: Option<String>,
+        #[prost(string, optional, tag = "13")]
+        pub reason_code: Option<String>,
+        #[prost(string, optional, tag = "14")]
+        pub message: Option<String>,
+        #[prost(double, optional, tag = "15")]
+        pub max_session_age: Option<f64>,
+        #[prost(bool, tag = "16")]
+        pub reauth_required: bool,
+        #[prost(string, optional, tag = "17")]
+        pub decisive_identity: Option<String>,
+        #[prost(string, optional, tag = "18")]
+        pub valid_until: Option<String>,
+        #[prost(string, optional, tag = "19")]
+        pub clipped_by: Option<String>,
+        #[prost(double, optional, tag = "20")]
+        pub ttl_seconds: Option<f64>,
+        #[prost(string, repeated, tag = "21")]
+        pub revalidate_on: Vec<String>,
+        #[prost(string, repeated, tag = "22")]
+        pub dependencies: Vec<String>,
+    }
+}
+
+fn datetime(field: &str, text: &str) -> Result<DateTime<Utc>, PolicyEngineError> {
+    DateTime::parse_from_rfc3339(text)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|e| PolicyEngineError::validation(format!("{} is not an RFC 3339 timestamp: {}", field, e)))
+}
+
+fn duration(field: &str, seconds: i64) -> Result<Duration, PolicyEngineError> {
+    Duration::try_seconds(seconds).ok_or_else(|| PolicyEngineError::validation(format!("{} is out of range: {}", field, seconds)))
+}
+
+// Free-form maps carry JSON-encoded values
+fn json_map(field: &str, map: HashMap<String, String>) -> Result<HashMap<String, serde_json::Value>, PolicyEngineError> {
+    map.into_iter()
+        .map(|(key, text)| {
+            let value = serde_json::from_str(&text)
+                .map_err(|e| PolicyEngineError::validation(format!("{}.{} is not valid JSON: {}", field, key, e)))?;
+            Ok((key, value))
+        })
+        .collect()
+}
+
+fn json_list(text: &str) -> Vec<String> {
+    serde_json::from_str(text).unwrap_or_default()
+}
+
+// Unset severity means info
+fn advice_list(owner: &str, advice: Vec<v1::Advice>) -> Result<Vec<Advice>, PolicyEngineError> {
+    advice
+        .into_iter()
+        .map(|proto| {
+            let severity = match proto.severity.as_str() {
+                "" => Severity::default(),
+                text => Severity::parse(text).ok_or_else(|| {
+                    PolicyEngineError::validation(format!("Advice '{}' of {} has unknown severity '{}'", proto.id, owner, text))
+                })?,
+            };
+            let attributes = json_map(&format!("advice '{}' attributes", proto.id), proto.attributes)?;
+            Ok(Advice {
+                id: proto.id,
+                severity,
+                message: proto.message,
+                attributes: attributes.into_iter().collect(),
+                fulfill_on: proto.fulfill_on.as_deref().map(Effect::parse),
+            })
+        })
+        .collect()
+}
+
+impl From<&Advice> for v1::Advice {
+    fn from(advice: &Advice) -> v1::Advice {
+        v1::Advice {
+            id: advice.id.clone(),
+            severity: advice.severity.as_str().to_string(),
+            message: advice.message.clone(),
+            attributes: advice.attributes.iter().map(|(key, value)| (key.clone(), value.to_string())).collect(),
+            fulfill_on: advice.fulfill_on.as_ref().map(ToString::to_string),
+        }
+    }
+}
+
+// "null" marks an absent spec in PolicyResult's JSON fields
+fn json_spec(text: &str) -> Option<String> {
+    (!text.is_empty() && text != "null").then(|| text.to_string())
+}
+
+fn spec<T: serde::de::DeserializeOwned>(field: &str, text: Option<String>) -> Result<Option<T>, PolicyEngineError> {
+    text.map(|text| {
+        serde_json::from_str(&text).map_err(|e| PolicyEngineError::validation(format!("Invalid {}: {}", field, e)))
+    })
+    .transpose()
+}
+
+impl TryFrom<v1::PolicyContext> for PolicyContext {
+    type Error = PolicyEngineError;
+
+    // Set fields become supplied fields under their JSON names
+    fn try_from(proto: v1::PolicyContext) -> Result<PolicyContext, PolicyEngineError> {
+        let mut context = PolicyContext::default();
+        let mut supplied = HashSet::new();
+        macro_rules! take {
+            ($($field:ident),*) => {$(
+                if let Some(value) = proto.$field {
+                    context.$field = value;
+                    supplied.insert(stringify!($field).to_string());
+                }
+            )*};
+        }
+        macro_rules! take_some {
+            ($($field:ident),*) => {$(
+                if proto.$field.is_some() {
+                    context.$field = proto.$field;
+                    supplied.insert(stringify!($field).to_string());
+                }
+            )*};
+        }
+        macro_rules! take_list {
+            ($($field:ident),*) => {$(
+                if !proto.$field.is_empty() {
+                    supplied.insert(stringify!($field).to_string());
+                }
+                context.$field = proto.$field.into_iter().map(Into::into).collect();
+            )*};
+        }
+        macro_rules! take_json {
+            ($($field:ident),*) => {$(
+                if !proto.$field.is_empty() {
+                    supplied.insert(stringify!($field).to_string());
+                }
+                context.$field = json_map(stringify!($field), proto.$field)?;
+            )*};
+        }
+
+        take!(request_id, operation, user_id, device_id, device_type, device_attested, ip_address, ip_country, ip_city);
+        take!(network_zone, vpn_detected, session_id, auth_method, mfa_verified, time_of_day, day_of_week);
+        take!(business_hours, risk_score, resource_type, resource_id, resource_classification, resource_owner);
+        take_some!(device_posture_token, intent_purpose, intent_justification, locale);
+        take_list!(user_roles, user_groups, delegation);
+        take_json!(user_attributes, resource_attributes, constraints, metadata);
+
+        if let Some(timestamp) = proto.timestamp {
+            context.timestamp = datetime("timestamp", &timestamp)?;
+            supplied.insert("timestamp".to_string());
+        }
+        if let Some(trust) = proto.device_trust {
+            context.device_trust = DeviceTrust::parse(&trust);
+            supplied.insert("device_trust".to_string());
+        }
+        if let Some(level) = proto.threat_level {
+            context.threat_level = ThreatLevel::parse(&level);
+            supplied.insert("threat_level".to_string());
+        }
+        if let Some(attestation) = proto.device_attestation {
+            context.device_attestation = Some(DeviceAttestation {
+                attestation_object: attestation.attestation_object,
+                client_data_json: attestation.client_data_json,
+            });
+            supplied.insert("device_attestation".to_string());
+        }
+        if let Some(seconds) = proto.session_age_seconds {
+            context.session_age = duration("session_age_seconds", seconds)?;
+            supplied.insert("session_age".to_string());
+        }
+        if let Some(seconds) = proto.intent_duration_seconds {
+            context.intent_duration = Some(duration("intent_duration_seconds", seconds)?);
+            supplied.insert("intent_duration".to_string());
+        }
+        for (path, quality) in proto.attribute_quality {
+            let observed_at = match quality.observed_at {
+                Some(text) => Some(datetime(&format!("attribute_quality.{}.observed_at", path), &text)?),
+                None => None,
+            };
+            context.attribute_quality.insert(path, AttributeQuality { confidence: quality.confidence, observed_at });
+            supplied.insert("attribute_quality".to_string());
+        }
+
+        context.supplied_fields = Some(supplied);
+        Ok(context)
+    }
+}
+
+impl From<v1::DelegationLink> for DelegationLink {
+    fn from(link: v1::DelegationLink) -> DelegationLink {
+        DelegationLink { actor: link.actor, on_behalf_of: link.on_behalf_of, grant_id: link.grant_id }
+    }
+}
+
+impl From<v1::PolicyObligation> for PolicyObligation {
+    fn from(proto: v1::PolicyObligation) -> PolicyObligation {
+        PolicyObligation { obligation: proto.obligation, fulfill_on: proto.fulfill_on.as_deref().map(Effect::parse) }
+    }
+}
+
+impl TryFrom<v1::PolicyRule> for PolicyRule {
+    type Error = PolicyEngineError;
+
+    fn try_from(proto: v1::PolicyRule) -> Result<PolicyRule, PolicyEngineError> {
+        let context = format!("rule '{}'", proto.id);
+        Ok(PolicyRule {
+            challenge: spec(&format!("challenge of {}", context), proto.challenge_json)?,
+            approval: spec(&format!("approval of {}", context), proto.approval_json)?,
+            id: proto.id,
+            name: proto.name,
+            description: proto.description,
+            priority: proto.priority,
+            condition: proto.condition,
+            effect: Effect::parse(&proto.effect),
+            obligations: proto.obligations,
+            advice: advice_list(&context, proto.advice)?,
+            break_glass: proto.break_glass,
+            reason_code: proto.reason_code,
+            remediation: proto.remediation,
+            environments: proto.environments,
+            rendered: Default::default(),
+        })
+    }
+}
+
+impl TryFrom<v1::Policy> for Policy {
+    type Error = PolicyEngineError;
+
+    fn try_from(proto: v1::Policy) -> Result<Policy, PolicyEngineError> {
+        let advice = advice_list(&format!("policy '{}'", proto.id), proto.advice)?;
+        Ok(Policy {
+            id: proto.id,
+            name: proto.name,
+            version: proto.version,
+            format_version: format::current_format_version(),
+            description: proto.description,
+            target: proto.target,
+            rules: proto.rules.into_iter().map(PolicyRule::try_from).collect::<Result<_, _>>()?,
+            definitions: proto.definitions.into_iter().collect(),
+            combining_algorithm: CombiningAlgorithm::parse(&proto.combining_algorithm),
+            obligations: proto.obligations.into_iter().map(PolicyObligation::from).collect(),
+            advice,
+            source: proto.source,
+            rule_refs: proto.rule_refs,
+            labels: proto.labels,
+            owner: proto.owner,
+            tags: proto.tags,
+            enabled: proto.enabled.unwrap_or(true),
+            priority: proto.priority,
+            environments: proto.environments,
+        })
+    }
+}
+
+impl From<&PolicyResult> for v1::PolicyResult {
+    fn from(result: &PolicyResult) -> v1::PolicyResult {
+        v1::PolicyResult {
+            decision: result.decision.as_str().to_string(),
+            reason: result.reason.to_string(),
+            confidence: result.confidence,
+            obligations: json_list(&result.obligations),
+            advice: advice::parse_list(&result.advice).iter().map(v1::Advice::from).collect(),
+            challenge_json: json_spec(&result.challenge),
+            approval_json: json_spec(&result.approval),
+            acknowledged_obligations: json_list(&result.acknowledged_obligations),
+            retry_after: result.retry_after,
+            policy_id: result.policy_id.clone(),
+            rule_id: result.rule_id.clone(),
+            error_code: result.error_code.clone(),
+            reason_code: result.reason_code.clone(),
+            message: result.message.clone(),
+            max_session_age: result.max_session_age,
+            reauth_required: result.reauth_required,
+            decisive_identity: result.decisive_identity.clone(),
+            valid_until: result.valid_until.clone(),
+            clipped_by: result.clipped_by.clone(),
+            ttl_seconds: result.ttl_seconds,
+            revalidate_on: result.revalidate_on.clone(),
+            dependencies: result.dependencies.clone(),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // evaluate() over Protobuf: takes an encoded uars.policy.v1.PolicyContext
+    // and returns an encoded PolicyResult, so native hosts (the Go services
+    // embedding the engine) skip JSON entirely. Malformed bytes or field
+    // values fail like malformed JSON does; evaluation errors come back as
+    // an INDETERMINATE result.
+    #[wasm_bindgen]
+    pub fn evaluate_proto(&mut self, context_bytes: &[u8]) -> Result<Vec<u8>, JsValue> {
+        let started = stats::now_ms();
+        let proto = v1::PolicyContext::decode(context_bytes)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to decode context: {}", e)).logged())?;
+        let context = PolicyContext::try_from(proto)
+            .and_then(|context| self.check_vocabulary(&context).map(|_| context))
+            .map_err(PolicyEngineError::logged)?;
+        self.begin_profile(started);
+        let result = guard::guarded(|| self.evaluate_request(context, PolicySelection::Global));
+        Ok(v1::PolicyResult::from(&result).encode_to_vec())
+    }
+
+    // load_policies() for an encoded uars.policy.v1.PolicySet
+    #[wasm_bindgen]
+    pub fn load_policies_proto(&mut self, policy_set_bytes: &[u8]) -> Result<(), JsValue> {
+        let set = v1::PolicySet::decode(policy_set_bytes)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to decode policy set: {}", e)).logged())?;
+        let policies = set
+            .policies
+            .into_iter()
+            .map(Policy::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.context("Failed to convert policy").logged())?;
+        for policy in policies {
+            self.add_policy(policy)?;
+        }
+        console_log!("Loaded {} policies", self.policies.len());
+        Ok(())
+    }
+}
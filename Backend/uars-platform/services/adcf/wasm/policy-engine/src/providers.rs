@@ -0,0 +1,218 @@
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::AbortSignal;
+
+use crate::attributes::{canonical_path, context_field};
+use crate::error::PolicyEngineError;
+use crate::logging::LogLevel;
+use crate::{guard, stats, PolicyEngine, PolicyResult, PolicySelection};
+
+// Context fields held as chrono durations, which providers cannot supply
+const UNSUPPORTED_FIELDS: &[&str] = &["session_age", "intent_duration"];
+
+// Host functions (policy information points) supplying context
+// attributes a request leaves out, keyed by canonical path
+#[derive(Default)]
+pub struct AttributeProviders {
+    providers: BTreeMap<String, js_sys::Function>,
+}
+
+// Where a canonical path lives in context JSON: a top-level field, then
+// keys inside it for the free-form maps
+fn json_path(canonical: &str) -> Vec<&str> {
+    canonical.split('.').collect()
+}
+
+fn is_present(context: &Map<String, Value>, path: &[&str]) -> bool {
+    let Some((field, rest)) = path.split_first() else {
+        return false;
+    };
+    let mut value = context.get(*field);
+    for key in rest {
+        value = value.and_then(|value| value.get(key));
+    }
+    value.is_some_and(|value| !value.is_null())
+}
+
+fn insert(context: &mut Map<String, Value>, path: &[&str], provided: Value) {
+    let Some((last, parents)) = path.split_last() else {
+        return;
+    };
+    let mut map = context;
+    for key in parents {
+        let entry = map.entry(key.to_string()).or_insert_with(|| json!({}));
+        if !entry.is_object() {
+            *entry = json!({});
+        }
+        let Value::Object(child) = entry else {
+            return;
+        };
+        map = child;
+    }
+    map.insert(last.to_string(), provided);
+}
+
+// A Promise that rejects when the signal aborts, for racing provider
+// Promises against. The listener is removed on drop, so an evaluation
+// that finished (or was cancelled) holds nothing on the signal.
+struct AbortWatch {
+    signal: AbortSignal,
+    listener: Closure<dyn FnMut()>,
+    aborted: js_sys::Promise,
+}
+
+impl AbortWatch {
+    fn new(signal: AbortSignal) -> AbortWatch {
+        let mut reject = None;
+        // The executor runs synchronously, so `reject` is set on return
+        let aborted = js_sys::Promise::new(&mut |_, rejecter| reject = Some(rejecter));
+        let reject = reject.unwrap_or_else(|| js_sys::Function::new_no_args(""));
+        let reason = signal.clone();
+        let listener = Closure::<dyn FnMut()>::new(move || {
+            let _ = reject.call1(&JsValue::NULL, &reason.reason());
+        });
+        let _ = signal.add_event_listener_with_callback("abort", listener.as_ref().unchecked_ref());
+        AbortWatch { signal, listener, aborted }
+    }
+
+    // Whichever settles first: `promise` or the abort
+    fn race(&self, promise: js_sys::Promise) -> js_sys::Promise {
+        js_sys::Promise::race(&js_sys::Array::of2(&promise, &self.aborted))
+    }
+}
+
+impl Drop for AbortWatch {
+    fn drop(&mut self) {
+        let _ = self.signal.remove_event_listener_with_callback("abort", self.listener.as_ref().unchecked_ref());
+    }
+}
+
+fn cancelled(provided: usize, abandoned: usize) -> JsValue {
+    PolicyEngineError::cancelled("Evaluation cancelled")
+        .with_details(json!({ "attributes_provided": provided, "providers_abandoned": abandoned }))
+        .logged()
+        .into()
+}
+
+// Awaits what a provider returned (a value or a Promise of one) as JSON;
+// None for null or undefined
+async fn settle(returned: JsValue, watch: Option<&AbortWatch>) -> Result<Option<Value>, String> {
+    let promise = js_sys::Promise::resolve(&returned);
+    let promise = match watch {
+        Some(watch) => watch.race(promise),
+        None => promise,
+    };
+    let value = JsFuture::from(promise)
+        .await
+        .map_err(|e| format!("rejected: {:?}", e))?;
+    if value.is_null() || value.is_undefined() {
+        return Ok(None);
+    }
+    let text: String = js_sys::JSON::stringify(&value)
+        .map_err(|e| format!("returned an unserializable value: {:?}", e))?
+        .into();
+    serde_json::from_str(&text).map(Some).map_err(|e| e.to_string())
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Registers `provider(context_json, attribute, signal)` to supply
+    // `attribute` (any context path, e.g. "user.attributes.clearance" or
+    // "risk_score") to evaluate_async when a request leaves it out. It
+    // may return the value or a Promise of it, so providers can be async
+    // functions backed by the network; `signal` is the AbortSignal given
+    // to evaluate_async (or undefined), to pass on to fetch(). Replaces
+    // an earlier provider of the same attribute.
+    #[wasm_bindgen]
+    pub fn register_attribute_provider(&mut self, attribute: &str, provider: js_sys::Function) -> Result<(), JsValue> {
+        let segments: Vec<&str> = attribute.split('.').map(str::trim).collect();
+        let supported = context_field(&segments).filter(|field| !UNSUPPORTED_FIELDS.contains(field));
+        if supported.is_none() {
+            return Err(PolicyEngineError::validation(format!("'{}' is not a context attribute a provider can supply", attribute))
+                .with_details(json!({ "attribute": attribute }))
+                .logged()
+                .into());
+        }
+        let canonical = canonical_path(&segments);
+        if self.debug_mode {
+            console_log!("Registered attribute provider: {}", canonical);
+        }
+        self.attribute_providers.providers.insert(canonical, provider);
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn unregister_attribute_provider(&mut self, attribute: &str) -> bool {
+        let segments: Vec<&str> = attribute.split('.').map(str::trim).collect();
+        self.attribute_providers.providers.remove(&canonical_path(&segments)).is_some()
+    }
+
+    // evaluate() as a Promise of the PolicyResult, with registered
+    // attribute providers filling in what the request leaves out. All
+    // providers needed are called up front and awaited together, so slow
+    // ones overlap and the JS thread is free meanwhile. A provider that
+    // throws, rejects or returns null leaves its attribute out (policies
+    // see it as not supplied, see has()), with a warning.
+    //
+    // The engine is borrowed until the Promise settles: other calls on it
+    // before then throw, so hosts await each evaluation or use one engine
+    // per concurrent caller.
+    //
+    // With `signal` (an AbortSignal, e.g. from an AbortController aborted
+    // on navigation, or AbortSignal.timeout(ms)), aborting rejects the
+    // Promise straight away with a CANCELLED error: providers still
+    // pending are abandoned (they get the same signal to stop their own
+    // work), nothing is evaluated and the engine is free again. An
+    // already aborted signal cancels before any provider is called.
+    #[wasm_bindgen]
+    pub async fn evaluate_async(&mut self, context_json: String, signal: Option<AbortSignal>) -> Result<PolicyResult, JsValue> {
+        let started = stats::now_ms();
+        if signal.as_ref().is_some_and(AbortSignal::aborted) {
+            return Err(cancelled(0, 0));
+        }
+        let mut context: Map<String, Value> = serde_json::from_str(&context_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse context: {}", e)).logged())?;
+
+        let signal_arg = signal.as_ref().map_or(JsValue::UNDEFINED, |signal| signal.clone().into());
+        let mut pending = Vec::new();
+        for (attribute, provider) in &self.attribute_providers.providers {
+            if is_present(&context, &json_path(attribute)) {
+                continue;
+            }
+            match provider.call3(&JsValue::NULL, &JsValue::from_str(&context_json), &JsValue::from_str(attribute), &signal_arg) {
+                Ok(returned) => pending.push((attribute.clone(), returned)),
+                Err(e) => log_at!(LogLevel::Warn, "Attribute provider '{}' threw: {:?}", attribute, e),
+            }
+        }
+
+        let watch = signal.map(AbortWatch::new);
+        let total = pending.len();
+        let mut provided = 0;
+        for (settled, (attribute, returned)) in pending.into_iter().enumerate() {
+            let outcome = settle(returned, watch.as_ref()).await;
+            if watch.as_ref().is_some_and(|watch| watch.signal.aborted()) {
+                return Err(cancelled(provided, total - settled));
+            }
+            match outcome {
+                Ok(Some(value)) => {
+                    insert(&mut context, &json_path(&attribute), value);
+                    provided += 1;
+                }
+                Ok(None) => {}
+                Err(e) => log_at!(LogLevel::Warn, "Attribute provider '{}' {}", attribute, e),
+            }
+        }
+        drop(watch);
+        if self.debug_mode {
+            console_log!("Attribute providers supplied {} attributes", provided);
+        }
+
+        let parsed = self.check_context_value(Value::Object(context)).map_err(PolicyEngineError::logged);
+        self.begin_profile(started);
+        let context = parsed?;
+        Ok(guard::guarded(|| self.evaluate_request(context, PolicySelection::Global)))
+    }
+}
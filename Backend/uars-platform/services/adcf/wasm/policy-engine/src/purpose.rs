@@ -0,0 +1,219 @@
+use wasm_bindgen::prelude::*;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use crate::clock;
+use crate::decision::Decision;
+use crate::error::{to_json, PolicyEngineError};
+use crate::expr::matches_path;
+use crate::logging::LogLevel;
+use crate::templates::literal;
+use crate::{PolicyContext, PolicyEngine, PolicyResult};
+
+// Attached to every PERMIT issued for a stated purpose:
+// `purpose_limit("research analysis")`
+pub const PURPOSE_LIMIT_OBLIGATION: &str = "purpose_limit";
+
+// resource_attributes key policies test the stated purpose against, e.g.
+// `intent.purpose in resource.attributes.allowed_purposes`
+pub const ALLOWED_PURPOSES_ATTRIBUTE: &str = "allowed_purposes";
+
+// Purpose-bound grants kept for report_violation; the oldest are dropped
+// beyond this
+pub const MAX_PURPOSE_GRANTS: usize = 10_000;
+
+// A PERMIT bound to the purpose it was requested for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PurposeGrant {
+    pub request_id: String,
+    pub user_id: String,
+    pub operation: String,
+    pub resource_id: String,
+    pub purpose: String,
+    pub granted_at: DateTime<Utc>,
+}
+
+// Use of a grant outside its purpose, as reported by the host
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PurposeViolation {
+    pub timestamp: DateTime<Utc>,
+    pub grant: PurposeGrant,
+    pub description: String,
+}
+
+// Declared purposes per resource pattern (matches_path) and the grants
+// issued under them
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PurposeRegistry {
+    declarations: HashMap<String, Vec<String>>,
+    grants: HashMap<String, PurposeGrant>,
+    order: VecDeque<String>,
+    violations: Vec<PurposeViolation>,
+}
+
+impl PurposeRegistry {
+    // The most specific (longest) matching pattern declares the purposes
+    fn allowed_purposes(&self, resource_id: &str) -> Option<&Vec<String>> {
+        self.declarations
+            .iter()
+            .filter(|(pattern, _)| matches_path(resource_id, pattern))
+            .max_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then_with(|| b.cmp(a)))
+            .map(|(_, purposes)| purposes)
+    }
+
+    fn record_grant(&mut self, grant: PurposeGrant) {
+        if self.grants.insert(grant.request_id.clone(), grant.clone()).is_none() {
+            self.order.push_back(grant.request_id);
+        }
+        while self.order.len() > MAX_PURPOSE_GRANTS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.grants.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Declares the purposes a resource (or every resource matching the
+    // pattern, e.g. "patients/**") may be accessed for. `purposes_json`
+    // is a JSON array of purpose names.
+    #[wasm_bindgen]
+    pub fn set_resource_purposes(&mut self, resource_pattern: &str, purposes_json: &str) -> Result<(), JsValue> {
+        let purposes: Vec<String> = serde_json::from_str(purposes_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse purposes: {}", e)).logged())?;
+        if resource_pattern.trim().is_empty() {
+            return Err(PolicyEngineError::validation("Resource pattern must not be empty").logged().into());
+        }
+        self.purposes.declarations.insert(resource_pattern.to_string(), purposes);
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn remove_resource_purposes(&mut self, resource_pattern: &str) -> bool {
+        self.purposes.declarations.remove(resource_pattern).is_some()
+    }
+
+    // JSON object of resource pattern -> allowed purposes
+    #[wasm_bindgen]
+    pub fn get_resource_purposes(&self) -> String {
+        let sorted: BTreeMap<&String, &Vec<String>> = self.purposes.declarations.iter().collect();
+        serde_json::to_string(&sorted).unwrap_or_default()
+    }
+
+    // Reports that the access granted for `request_id` was used outside
+    // its stated purpose. Returns the recorded PurposeViolation JSON.
+    #[wasm_bindgen]
+    pub fn report_violation(&mut self, request_id: &str, description: &str) -> Result<String, JsValue> {
+        let grant = self.purposes.grants.get(request_id).cloned().ok_or_else(|| {
+            PolicyEngineError::not_found(format!("No purpose-bound grant for request '{}'", request_id))
+                .with_details(json!({ "request_id": request_id }))
+                .logged()
+        })?;
+        log_at!(
+            LogLevel::Warn,
+            "Purpose violation by '{}' on '{}' (granted for '{}'): {}",
+            grant.user_id,
+            grant.resource_id,
+            grant.purpose,
+            description
+        );
+
+        let violation = PurposeViolation { timestamp: clock::now(), grant, description: description.to_string() };
+        let json = to_json(&violation)?;
+        self.purposes.violations.push(violation);
+        Ok(json)
+    }
+
+    // JSON array of PurposeViolation, oldest first
+    #[wasm_bindgen]
+    pub fn get_purpose_violations(&self) -> Result<String, JsValue> {
+        Ok(to_json(&self.purposes.violations)?)
+    }
+
+    // Returns the violations like `get_purpose_violations` and empties the
+    // list
+    #[wasm_bindgen]
+    pub fn drain_purpose_violations(&mut self) -> Result<String, JsValue> {
+        let json = to_json(&self.purposes.violations)?;
+        self.purposes.violations.clear();
+        Ok(json)
+    }
+}
+
+impl PolicyEngine {
+    // Resources without their own allowed_purposes attribute take the
+    // declared purposes for their ID
+    pub(crate) fn inject_allowed_purposes(&self, context: &mut PolicyContext) {
+        if context.resource_attributes.contains_key(ALLOWED_PURPOSES_ATTRIBUTE) {
+            return;
+        }
+        if let Some(purposes) = self.purposes.allowed_purposes(&context.resource_id) {
+            context.resource_attributes.insert(ALLOWED_PURPOSES_ATTRIBUTE.to_string(), json!(purposes));
+        }
+    }
+
+    // Binds a PERMIT to the stated purpose: the result carries a
+    // purpose_limit obligation and the grant is kept for report_violation
+    pub(crate) fn bind_purpose(&mut self, result: &mut PolicyResult, context: &PolicyContext) {
+        let purpose = match &context.intent_purpose {
+            Some(purpose) if result.decision == Decision::Permit && !purpose.trim().is_empty() => purpose,
+            _ => return,
+        };
+
+        let mut obligations: Vec<String> = serde_json::from_str(&result.obligations).unwrap_or_default();
+        obligations.push(format!("{}({})", PURPOSE_LIMIT_OBLIGATION, literal(&json!(purpose))));
+        result.obligations = serde_json::to_string(&obligations).unwrap_or_else(|_| "[]".to_string()).into();
+
+        self.purposes.record_grant(PurposeGrant {
+            request_id: context.request_id.clone(),
+            user_id: context.user_id.clone(),
+            operation: context.operation.clone(),
+            resource_id: context.resource_id.clone(),
+            purpose: purpose.clone(),
+            granted_at: context.timestamp,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(purpose: Option<&str>) -> PolicyContext {
+        PolicyContext {
+            request_id: "r1".to_string(),
+            resource_id: "projects/p1/capsules/c1".to_string(),
+            intent_purpose: purpose.map(str::to_string),
+            ..PolicyContext::default()
+        }
+    }
+
+    #[test]
+    fn the_most_specific_declaration_sets_the_allowed_purposes() {
+        let mut engine = PolicyEngine::new();
+        engine.purposes.declarations.insert("projects/**".to_string(), vec!["support".to_string()]);
+        engine.purposes.declarations.insert("projects/*/capsules/**".to_string(), vec!["research".to_string()]);
+
+        let mut context = request(None);
+        engine.inject_allowed_purposes(&mut context);
+        assert_eq!(context.resource_attributes[ALLOWED_PURPOSES_ATTRIBUTE], json!(["research"]));
+    }
+
+    #[test]
+    fn permits_are_bound_to_their_stated_purpose() {
+        let mut engine = PolicyEngine::new();
+        let mut result = PolicyResult::new(Decision::Permit, "permitted", 1.0);
+        engine.bind_purpose(&mut result, &request(Some("research")));
+        let obligations: Vec<String> = serde_json::from_str(&result.obligations).unwrap();
+        assert_eq!(obligations, [r#"purpose_limit("research")"#]);
+        assert_eq!(engine.purposes.grants["r1"].purpose, "research");
+
+        let mut engine = PolicyEngine::new();
+        let mut result = PolicyResult::new(Decision::Deny, "denied", 1.0);
+        engine.bind_purpose(&mut result, &request(Some("research")));
+        assert!(engine.purposes.grants.is_empty());
+    }
+}
@@ -0,0 +1,264 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use chrono::{DateTime, Duration, Utc};
+
+use crate::clock;
+use crate::decision::Decision;
+use crate::expr::Value;
+use crate::functions::EvalScope;
+use crate::logging::LogLevel;
+use crate::messages;
+use crate::obligations::ObligationCall;
+use crate::{state_key, PolicyContext, PolicyEngine, PolicyResult};
+
+pub const RATE_LIMIT_OBLIGATION: &str = "rate_limit";
+
+// Longest accepted window; keeps timestamp arithmetic far from overflow
+const MAX_WINDOW_DAYS: i64 = 3650;
+
+// Parses window strings such as "30s", "15m", "1h", "7d"
+pub fn parse_window(window: &str) -> Option<Duration> {
+    let window = window.trim();
+    let unit = window.chars().last()?;
+    let amount: i64 = window[..window.len() - unit.len_utf8()].trim().parse().ok()?;
+    if amount <= 0 {
+        return None;
+    }
+    let duration = match unit {
+        's' => Duration::try_seconds(amount),
+        'm' => Duration::try_minutes(amount),
+        'h' => Duration::try_hours(amount),
+        'd' => Duration::try_days(amount),
+        _ => None,
+    }?;
+    (duration <= Duration::days(MAX_WINDOW_DAYS)).then_some(duration)
+}
+
+// Arguments of `rate_limit(key, limit, window)` after evaluation
+struct RateLimit {
+    key: String,
+    limit: usize,
+    window: Duration,
+}
+
+impl RateLimit {
+    fn from_args(args: &[Value]) -> Result<RateLimit, String> {
+        let key = match args.first() {
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Number(n)) => n.to_string(),
+            Some(other) => return Err(format!("key must be a string, found {}", other.type_name())),
+            None => return Err("missing key".to_string()),
+        };
+        let limit = match args.get(1) {
+            Some(Value::Number(n)) if *n >= 0.0 => *n as usize,
+            _ => return Err("limit must be a non-negative number".to_string()),
+        };
+        let window = match args.get(2) {
+            Some(Value::String(s)) => {
+                parse_window(s).ok_or_else(|| format!("invalid window '{}'", s))?
+            }
+            _ => return Err("window must be a duration string".to_string()),
+        };
+        Ok(RateLimit { key, limit, window })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuotaUsage {
+    pub quota: String,
+    pub key: String,
+    pub used: usize,
+}
+
+// Hits inside one sliding window, oldest first
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct Window {
+    seconds: i64,
+    hits: VecDeque<DateTime<Utc>>,
+}
+
+impl Window {
+    fn expire(&mut self, now: DateTime<Utc>) {
+        let Some(window) = Duration::try_seconds(self.seconds) else {
+            return;
+        };
+        while let Some(oldest) = self.hits.front() {
+            if oldest.checked_add_signed(window).is_some_and(|end| end <= now) {
+                self.hits.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+// Sliding-window counters. Each bucket is identified by the obligation
+// text plus the evaluated key, so different rules with different limits
+// keep separate counts for the same user. Hits are at the engine clock;
+// the request timestamp is the caller's to choose.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct QuotaTracker {
+    windows: HashMap<(String, String), Window>,
+}
+
+impl QuotaTracker {
+    // Drops expired hits and returns Err(retry_after_seconds) when the
+    // quota is exhausted
+    fn check(&mut self, quota: &str, limit: &RateLimit, now: DateTime<Utc>) -> Result<(), f64> {
+        let hits = match self.windows.get_mut(&(quota.to_string(), limit.key.clone())) {
+            Some(window) => {
+                window.seconds = limit.window.num_seconds();
+                window.expire(now);
+                &window.hits
+            }
+            None if limit.limit > 0 => return Ok(()),
+            None => return Err(limit.window.num_seconds() as f64),
+        };
+
+        if hits.len() >= limit.limit {
+            let retry_after = match hits.front() {
+                Some(oldest) => (*oldest + limit.window - now).num_milliseconds() as f64 / 1000.0,
+                None => limit.window.num_seconds() as f64,
+            };
+            return Err(retry_after.max(0.0));
+        }
+        Ok(())
+    }
+
+    fn record(&mut self, quota: &str, limit: &RateLimit, now: DateTime<Utc>) {
+        let window = self.windows.entry((quota.to_string(), limit.key.clone())).or_default();
+        window.seconds = limit.window.num_seconds();
+        window.hits.push_back(now);
+    }
+
+    // Forgets buckets with no hits left in their window, so keys that
+    // went idle do not accumulate
+    fn prune(&mut self, now: DateTime<Utc>) {
+        self.windows.retain(|_, window| {
+            window.expire(now);
+            !window.hits.is_empty()
+        });
+    }
+
+    pub fn usage(&self) -> Vec<QuotaUsage> {
+        self.windows
+            .iter()
+            .map(|((quota, key), window)| QuotaUsage {
+                quota: quota.clone(),
+                key: key.clone(),
+                used: window.hits.len(),
+            })
+            .collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.windows.clear();
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    #[wasm_bindgen]
+    pub fn get_quota_usage(&self) -> String {
+        serde_json::to_string(&self.quotas.usage()).unwrap_or_default()
+    }
+
+    #[wasm_bindgen]
+    pub fn reset_quotas(&mut self) {
+        self.quotas.clear();
+    }
+}
+
+impl PolicyEngine {
+    // Applies rate_limit obligations carried by a PERMIT. Exhausting any
+    // quota converts the result into a DENY with a retry_after hint.
+    pub(crate) fn enforce_quotas(&mut self, result: &mut PolicyResult, context: &PolicyContext, tenant: Option<&str>) {
+        let now = clock::now();
+        self.quotas.prune(now);
+        if result.decision != Decision::Permit {
+            return;
+        }
+
+        let specs: Vec<String> = serde_json::from_str(&result.obligations).unwrap_or_default();
+        let mut limits = Vec::new();
+        {
+            let scope = EvalScope::new(self, context, tenant);
+            for spec in &specs {
+                let call = ObligationCall::parse(spec);
+                if call.id != RATE_LIMIT_OBLIGATION {
+                    continue;
+                }
+                let parsed = call
+                    .resolve_args(&scope)
+                    .map_err(|e| e.to_string())
+                    .and_then(|args| RateLimit::from_args(&args))
+                    .map(|mut limit| {
+                        limit.key = state_key(tenant, &limit.key);
+                        limit
+                    });
+                match parsed {
+                    Ok(limit) => limits.push((spec.clone(), limit)),
+                    Err(e) => log_at!(LogLevel::Warn, "Ignoring invalid obligation '{}': {}", spec, e),
+                }
+            }
+        }
+
+        // Check every quota before recording so a denied request does not
+        // consume from the quotas that still had room
+        for (spec, limit) in &limits {
+            if let Err(retry_after) = self.quotas.check(spec, limit, now) {
+                if self.debug_mode {
+                    console_log!("Quota exceeded: {} for key {}", spec, limit.key);
+                }
+                *result = PolicyResult::new(
+                    Decision::Deny,
+                    format!("Quota exceeded: {}", spec),
+                    1.0
+                );
+                result.reason_code = Some(messages::QUOTA_EXCEEDED.to_string());
+                result.retry_after = Some(retry_after);
+                return;
+            }
+        }
+
+        for (spec, limit) in &limits {
+            self.quotas.record(spec, limit, now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+    use serde_json::json;
+    use std::rc::Rc;
+
+    fn enforce(engine: &mut PolicyEngine, key: &str, timestamp: &str) -> Decision {
+        let context = PolicyContext {
+            timestamp: DateTime::parse_from_rfc3339(timestamp).unwrap().with_timezone(&Utc),
+            ..PolicyContext::default()
+        };
+        let mut result = PolicyResult::new(Decision::Permit, "permitted", 1.0);
+        result.obligations = json!([format!("rate_limit('{}', 1, '1h')", key)]).to_string().into();
+        engine.enforce_quotas(&mut result, &context, None);
+        result.decision
+    }
+
+    #[test]
+    fn windows_follow_the_engine_clock_not_the_request() {
+        let start = DateTime::parse_from_rfc3339("2026-01-01T00:00:00Z").unwrap().with_timezone(&Utc);
+        clock::set_clock(Rc::new(FixedClock(start)));
+        let mut engine = PolicyEngine::new();
+        assert_eq!(enforce(&mut engine, "k", "2000-01-01T00:00:00Z"), Decision::Permit);
+        assert_eq!(enforce(&mut engine, "k", "2010-01-01T00:00:00Z"), Decision::Deny);
+
+        // An hour on, the idle key is forgotten
+        clock::set_clock(Rc::new(FixedClock(start + Duration::hours(1))));
+        assert_eq!(enforce(&mut engine, "other", "2000-01-01T00:00:00Z"), Decision::Permit);
+        assert_eq!(engine.quotas.usage().len(), 1);
+        assert_eq!(enforce(&mut engine, "k", "2000-01-01T00:00:00Z"), Decision::Permit);
+        clock::use_system_clock();
+    }
+}
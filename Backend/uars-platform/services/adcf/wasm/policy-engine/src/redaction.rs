@@ -0,0 +1,339 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+
+use crate::digest::to_hex;
+use crate::error::{to_json, PolicyEngineError};
+use crate::validation::builtin_context_schema;
+use crate::{PolicyContext, PolicyEngine};
+
+pub(crate) const MASK: &str = "[REDACTED]";
+
+// Context values shorter than this are not scrubbed from free text, where
+// they would mangle unrelated words
+const MIN_SCRUBBED_LEN: usize = 3;
+
+// Field classes a policy can name instead of listing fields
+const CLASSIFICATIONS: &[(&str, &[&str])] = &[
+    ("pii", &["user_attributes", "intent_justification"]),
+    ("network", &["ip_address", "ip_city"]),
+    ("identifiers", &["user_id", "device_id", "session_id", "resource_owner"]),
+    ("credentials", &["device_posture_token", "device_attestation"]),
+];
+
+// Which context fields never leave the engine unmasked: log lines,
+// break-glass audit entries and evaluation records (evaluate_recorded).
+// Results go back to the caller that supplied the context and are not
+// redacted.
+//
+//   { "fields": ["user_attributes.email"], "classifications": ["pii", "network"],
+//     "strict_privacy": true, "salt": "per-deployment secret" }
+//
+// Masked values read "[REDACTED]". Under `strict_privacy` identifiers
+// (the "identifiers" class, whether listed or not) are replaced by a
+// salted hash instead, so entries about one user can still be correlated.
+// A record evaluated under a policy carries the masked context, so it
+// only replays identically if the masked fields did not decide the result.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactionPolicy {
+    // Context paths; a map field covers everything under it
+    pub fields: Vec<String>,
+    pub classifications: Vec<String>,
+    pub strict_privacy: bool,
+    pub salt: String,
+    // Regular expressions masked in every log line (regex feature), for
+    // values that reach logs without being context fields
+    pub patterns: Vec<String>,
+}
+
+pub struct Redactor {
+    policy: RedactionPolicy,
+    paths: Vec<Vec<String>>,
+    #[cfg(feature = "regex")]
+    patterns: Vec<regex::Regex>,
+}
+
+fn identifier(path: &[String]) -> bool {
+    path.len() == 1 && CLASSIFICATIONS.iter().any(|(class, fields)| *class == "identifiers" && fields.contains(&path[0].as_str()))
+}
+
+impl Redactor {
+    pub fn new(policy: RedactionPolicy) -> Result<Redactor, PolicyEngineError> {
+        let mut paths: Vec<Vec<String>> = Vec::new();
+        for class in &policy.classifications {
+            let (_, fields) = CLASSIFICATIONS.iter().find(|(name, _)| name == class).ok_or_else(|| {
+                let known: Vec<&str> = CLASSIFICATIONS.iter().map(|(name, _)| *name).collect();
+                PolicyEngineError::validation(format!("Unknown redaction classification '{}'", class))
+                    .with_details(json!({ "classification": class, "expected": known }))
+            })?;
+            paths.extend(fields.iter().map(|field| vec![field.to_string()]));
+        }
+        let known = builtin_context_schema()["properties"].as_object().cloned().unwrap_or_default();
+        for field in &policy.fields {
+            let path: Vec<String> = field.split('.').map(str::to_string).collect();
+            if !known.contains_key(&path[0]) {
+                return Err(PolicyEngineError::validation(format!("Redaction field '{}' is not a context field", field))
+                    .with_details(json!({ "field": field })));
+            }
+            paths.push(path);
+        }
+        if policy.strict_privacy {
+            paths.extend(CLASSIFICATIONS.iter().filter(|(class, _)| *class == "identifiers").flat_map(|(_, fields)| {
+                fields.iter().map(|field| vec![field.to_string()])
+            }));
+        }
+        paths.sort();
+        paths.dedup();
+
+        #[cfg(feature = "regex")]
+        let patterns = policy
+            .patterns
+            .iter()
+            .map(|pattern| {
+                regex::Regex::new(pattern).map_err(|e| {
+                    PolicyEngineError::validation(format!("Invalid redaction pattern '{}': {}", pattern, e))
+                        .with_details(json!({ "pattern": pattern }))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        #[cfg(not(feature = "regex"))]
+        if !policy.patterns.is_empty() {
+            return Err(PolicyEngineError::validation("Redaction patterns require the regex feature"));
+        }
+
+        Ok(Redactor {
+            policy,
+            paths,
+            #[cfg(feature = "regex")]
+            patterns,
+        })
+    }
+
+    fn replacement(&self, path: &[String], value: &str) -> String {
+        if self.policy.strict_privacy && identifier(path) {
+            let digest = Sha256::digest(format!("{}{}", self.policy.salt, value).as_bytes());
+            format!("anon:{}", &to_hex(&digest)[..16])
+        } else {
+            MASK.to_string()
+        }
+    }
+
+    // Masks the policy's fields in a serialized context
+    pub fn redact_json(&self, context: &mut Value) {
+        for path in &self.paths {
+            let Some(target) = path.iter().try_fold(&mut *context, |value, key| value.get_mut(key)) else { continue };
+            mask(target, &|value| self.replacement(path, value));
+        }
+    }
+
+    // (value, replacement) for every string the policy masks in a
+    // context, longest first so a value is replaced before its prefixes
+    fn replacements(&self, context: &PolicyContext) -> Vec<(String, String)> {
+        let Ok(context) = serde_json::to_value(context) else { return Vec::new() };
+        let mut replacements = Vec::new();
+        for path in &self.paths {
+            let Some(target) = path.iter().try_fold(&context, |value, key| value.get(key)) else { continue };
+            strings(target, &mut |value| {
+                if value.chars().count() >= MIN_SCRUBBED_LEN {
+                    replacements.push((value.to_string(), self.replacement(path, value)));
+                }
+            });
+        }
+        replacements.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        replacements.dedup_by(|(a, _), (b, _)| a == b);
+        replacements
+    }
+
+    // A top-level field's value as audit entries may record it
+    pub fn field(&self, name: &str, value: &str) -> String {
+        match self.paths.iter().find(|path| path.len() == 1 && path[0] == name) {
+            Some(path) if !value.is_empty() => self.replacement(path, value),
+            _ => value.to_string(),
+        }
+    }
+
+    pub fn policy(&self) -> &RedactionPolicy {
+        &self.policy
+    }
+
+    // Free text (justifications, reasons) with the context's masked values
+    // and the policy's patterns replaced
+    pub fn scrub(&self, text: &str, context: &PolicyContext) -> String {
+        let scrubbed = scrub_values(text, &self.replacements(context));
+        #[cfg(feature = "regex")]
+        let scrubbed = scrub_patterns(scrubbed, &self.patterns);
+        scrubbed
+    }
+}
+
+// Replaces every scalar under `value`, keeping maps and lists so a masked
+// context still parses
+pub(crate) fn mask(value: &mut Value, replacement: &dyn Fn(&str) -> String) {
+    match value {
+        Value::Object(map) => map.values_mut().for_each(|value| mask(value, replacement)),
+        Value::Array(items) => items.iter_mut().for_each(|value| mask(value, replacement)),
+        Value::Null => {}
+        Value::String(text) => *value = Value::String(replacement(text)),
+        other => *other = Value::String(replacement(&other.to_string())),
+    }
+}
+
+fn strings(value: &Value, found: &mut dyn FnMut(&str)) {
+    match value {
+        Value::Object(map) => map.values().for_each(|value| strings(value, found)),
+        Value::Array(items) => items.iter().for_each(|value| strings(value, found)),
+        Value::String(text) => found(text),
+        _ => {}
+    }
+}
+
+fn scrub_values(text: &str, replacements: &[(String, String)]) -> String {
+    replacements.iter().fold(text.to_string(), |text, (value, replacement)| {
+        if text.contains(value.as_str()) {
+            text.replace(value.as_str(), replacement)
+        } else {
+            text
+        }
+    })
+}
+
+#[cfg(feature = "regex")]
+fn scrub_patterns(text: String, patterns: &[regex::Regex]) -> String {
+    patterns.iter().fold(text, |text, pattern| pattern.replace_all(&text, MASK).into_owned())
+}
+
+// What the logger masks. Module-wide like the logger itself: the active
+// policy's patterns, plus the sensitive values of the context being
+// evaluated while a `LogRedaction` guard is alive.
+#[derive(Default)]
+struct LogScrubber {
+    values: Vec<(String, String)>,
+    #[cfg(feature = "regex")]
+    patterns: Vec<regex::Regex>,
+}
+
+thread_local! {
+    static LOG_SCRUBBER: RefCell<LogScrubber> = RefCell::new(LogScrubber::default());
+}
+
+pub fn scrub_log(message: &str) -> String {
+    LOG_SCRUBBER.with(|scrubber| {
+        let scrubber = scrubber.borrow();
+        let scrubbed = scrub_values(message, &scrubber.values);
+        #[cfg(feature = "regex")]
+        let scrubbed = scrub_patterns(scrubbed, &scrubber.patterns);
+        scrubbed
+    })
+}
+
+// Restores the previously scrubbed values when an evaluation ends, so
+// nested evaluations (break-glass, diffs) unwind correctly
+pub struct LogRedaction {
+    previous: Option<Vec<(String, String)>>,
+}
+
+impl Drop for LogRedaction {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            LOG_SCRUBBER.with(|scrubber| scrubber.borrow_mut().values = previous);
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Replaces the redaction policy (see RedactionPolicy)
+    #[wasm_bindgen]
+    pub fn set_redaction_policy(&mut self, policy_json: &str) -> Result<(), JsValue> {
+        let policy: RedactionPolicy = serde_json::from_str(policy_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse redaction policy: {}", e)).logged())?;
+        let redactor = Redactor::new(policy).map_err(PolicyEngineError::logged)?;
+        self.install_redactor(Some(redactor));
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_redaction_policy(&mut self) {
+        self.install_redactor(None);
+    }
+
+    // The active policy as JSON, or "null"
+    #[wasm_bindgen]
+    pub fn get_redaction_policy(&self) -> Result<String, JsValue> {
+        Ok(to_json(&self.redactor.as_ref().map(|redactor| &redactor.policy))?)
+    }
+
+    // A context JSON with the policy's fields masked, for hosts shipping
+    // contexts to their own logs
+    #[wasm_bindgen]
+    pub fn redact_context(&self, context_json: &str) -> Result<String, JsValue> {
+        let mut context: Value = serde_json::from_str(context_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse context: {}", e)).logged())?;
+        if let Some(redactor) = &self.redactor {
+            redactor.redact_json(&mut context);
+        }
+        Ok(to_json(&context)?)
+    }
+}
+
+impl PolicyEngine {
+    pub(crate) fn install_redactor(&mut self, redactor: Option<Redactor>) {
+        #[cfg(feature = "regex")]
+        LOG_SCRUBBER.with(|scrubber| {
+            scrubber.borrow_mut().patterns = redactor.as_ref().map(|redactor| redactor.patterns.clone()).unwrap_or_default();
+        });
+        self.redactor = redactor;
+    }
+
+    // Masks the context's sensitive values in log lines until the guard
+    // drops. Does nothing without a policy or a log sink.
+    pub(crate) fn redact_logs(&self, context: &PolicyContext) -> LogRedaction {
+        let Some(redactor) = &self.redactor else { return LogRedaction { previous: None } };
+        if !crate::logging::enabled(crate::logging::LogLevel::Error) {
+            return LogRedaction { previous: None };
+        }
+        let values = redactor.replacements(context);
+        let previous = LOG_SCRUBBER.with(|scrubber| std::mem::replace(&mut scrubber.borrow_mut().values, values));
+        LogRedaction { previous: Some(previous) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redactor(policy: Value) -> Redactor {
+        Redactor::new(serde_json::from_value(policy).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn masked_fields_leave_no_trace_in_records_or_text() {
+        let redactor = redactor(json!({ "fields": ["user_attributes.email"], "classifications": ["network"] }));
+        let mut context = PolicyContext { ip_address: "203.0.113.9".to_string(), ..PolicyContext::default() };
+        context.user_attributes.insert("email".to_string(), json!("alice@example.com"));
+        context.user_attributes.insert("team".to_string(), json!("ops"));
+
+        let mut record = serde_json::to_value(&context).unwrap();
+        redactor.redact_json(&mut record);
+        assert_eq!(record["ip_address"], json!(MASK));
+        assert_eq!(record["user_attributes"]["email"], json!(MASK));
+        assert_eq!(record["user_attributes"]["team"], json!("ops"));
+
+        let text = redactor.scrub("alice@example.com asked from 203.0.113.9", &context);
+        assert_eq!(text, format!("{} asked from {}", MASK, MASK));
+    }
+
+    #[test]
+    fn strict_privacy_hashes_identifiers_with_the_salt() {
+        let salted = |salt: &str| redactor(json!({ "strict_privacy": true, "salt": salt })).field("user_id", "alice");
+        assert!(salted("s1").starts_with("anon:"));
+        assert!(!salted("s1").contains("alice"));
+        assert_eq!(salted("s1"), salted("s1"));
+        assert_ne!(salted("s1"), salted("s2"));
+
+        assert!(Redactor::new(serde_json::from_value(json!({ "fields": ["nonexistent"] })).unwrap()).is_err());
+    }
+}
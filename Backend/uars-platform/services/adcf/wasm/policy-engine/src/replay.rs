@@ -0,0 +1,125 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+
+use crate::digest::policy_set_hash;
+use crate::error::{to_json, PolicyEngineError};
+use crate::{guard, PolicyEngine, PolicyResult, PolicySelection};
+
+pub const ENGINE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// Everything needed to reproduce a decision: the context exactly as
+// submitted, the timestamp it was evaluated at, and the policy set and
+// engine version that produced `result`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvaluationRecord {
+    pub engine_version: String,
+    pub policy_set_hash: String,
+    pub timestamp: DateTime<Utc>,
+    pub context: serde_json::Value,
+    pub result: PolicyResult,
+}
+
+// Output of `replay_evaluation`. `identical` compares the serialized
+// results byte for byte.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayReport {
+    pub identical: bool,
+    pub recorded: PolicyResult,
+    pub replayed: PolicyResult,
+}
+
+// Deterministic evaluations depend only on the context, the timestamp
+// and the active policy set. The context timestamp is pinned and now()
+// returns it, the wall-clock limit is ignored, history-dependent and
+// random functions (impossible_travel(), unusual_*(), random(), uuid())
+// fail the rule instead of consulting engine state, and nothing is
+// recorded (travel history, baselines, quotas, obligations).
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Evaluates deterministically and returns a JSON EvaluationRecord
+    #[wasm_bindgen]
+    pub fn evaluate_recorded(&mut self, context_json: &str) -> Result<String, JsValue> {
+        let mut context: serde_json::Value = serde_json::from_str(context_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse context: {}", e)).logged())?;
+        let (timestamp, result) = self.evaluate_deterministic(context_json, None)?;
+        if let Some(redactor) = &self.redactor {
+            redactor.redact_json(&mut context);
+        }
+
+        let record = EvaluationRecord {
+            engine_version: ENGINE_VERSION.to_string(),
+            policy_set_hash: policy_set_hash(&self.policies),
+            timestamp,
+            context,
+            result,
+        };
+        Ok(to_json(&record)?)
+    }
+
+    // Re-executes a record against the active policies and returns a JSON
+    // ReplayReport. Fails with CONFLICT if the engine version or policy
+    // set differs from the one that produced the record.
+    #[wasm_bindgen]
+    pub fn replay_evaluation(&mut self, record_json: &str) -> Result<String, JsValue> {
+        let record: EvaluationRecord = serde_json::from_str(record_json).map_err(|e| {
+            PolicyEngineError::parse(format!("Failed to parse evaluation record: {}", e)).logged()
+        })?;
+
+        if record.engine_version != ENGINE_VERSION {
+            return Err(PolicyEngineError::conflict(format!(
+                "Record was produced by engine {}, this is {}",
+                record.engine_version, ENGINE_VERSION
+            ))
+            .with_details(serde_json::json!({
+                "recorded_version": record.engine_version,
+                "engine_version": ENGINE_VERSION,
+            }))
+            .logged()
+            .into());
+        }
+
+        let current = policy_set_hash(&self.policies);
+        if record.policy_set_hash != current {
+            return Err(PolicyEngineError::conflict(format!(
+                "Record was produced by policy set {}, engine has {}",
+                record.policy_set_hash, current
+            ))
+            .with_details(serde_json::json!({
+                "recorded_hash": record.policy_set_hash,
+                "current_hash": current,
+            }))
+            .logged()
+            .into());
+        }
+
+        let (_, replayed) = self.evaluate_deterministic(&record.context.to_string(), Some(record.timestamp))?;
+        let identical = to_json(&record.result)? == to_json(&replayed)?;
+        if self.debug_mode && !identical {
+            console_log!("Replay of {} differs from the record", record.result.decision);
+        }
+
+        Ok(to_json(&ReplayReport { identical, recorded: record.result, replayed })?)
+    }
+}
+
+impl PolicyEngine {
+    fn evaluate_deterministic(
+        &mut self,
+        context_json: &str,
+        timestamp: Option<DateTime<Utc>>,
+    ) -> Result<(DateTime<Utc>, PolicyResult), JsValue> {
+        let mut context = self.parse_context(context_json)?;
+        if let Some(timestamp) = timestamp {
+            context.timestamp = timestamp;
+        }
+        let max_duration_ms = self.limits.max_duration_ms.take();
+        self.deterministic.set(true);
+        self.enrich_context(&mut context, None);
+        let result = guard::guarded(|| self.evaluate_context(&context, PolicySelection::Global));
+        self.deterministic.set(false);
+        self.limits.max_duration_ms = max_duration_ms;
+
+        Ok((context.timestamp, result))
+    }
+}
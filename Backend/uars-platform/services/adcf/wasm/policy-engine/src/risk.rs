@@ -0,0 +1,446 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+
+use crate::error::{to_json, PolicyEngineError};
+use crate::logging::LogLevel;
+use crate::{PolicyContext, PolicyEngine};
+
+// Names of the signals every scorer has; host signals take other names
+const BUILTIN_SIGNALS: &[&str] = &["vpn", "device_trust", "geo", "session_age", "threat_level"];
+
+// How a computed score is merged into the context before evaluation
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RiskInjection {
+    Disabled,
+    Replace,
+    Max,
+}
+
+// Scoring profile loaded from JSON. Each signal produces a raw value in
+// [0, 1] which is multiplied by its weight; the weighted sum plus
+// `base_score` is clamped to [0, max_score]. Signals registered with
+// register_risk_signal are weighted by name like the built-in ones.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RiskProfile {
+    pub weights: HashMap<String, f64>,
+    pub base_score: f64,
+    pub max_score: f64,
+    pub injection: RiskInjection,
+
+    // device_trust value -> raw signal (unlisted values score as 1.0)
+    pub device_trust_scores: HashMap<String, f64>,
+
+    // threat_level value -> raw signal (unlisted values score as 0.5)
+    pub threat_level_scores: HashMap<String, f64>,
+
+    // Countries considered normal; empty means no allow-list
+    pub trusted_countries: Vec<String>,
+    pub high_risk_countries: Vec<String>,
+
+    // Travel from the user's previous location faster than this (km/h)
+    // scores the geo signal 1.0
+    pub impossible_travel_kmh: f64,
+
+    // Sessions older than this score 1.0; younger ones scale linearly
+    pub max_session_age_hours: f64,
+}
+
+impl Default for RiskProfile {
+    fn default() -> Self {
+        let weights = [
+            ("vpn", 1.5),
+            ("device_trust", 2.5),
+            ("geo", 2.0),
+            ("session_age", 1.5),
+            ("threat_level", 2.5),
+        ];
+        let device_trust = [
+            ("trusted", 0.0),
+            ("managed", 0.2),
+            ("known", 0.5),
+            ("unknown", 0.8),
+            ("untrusted", 1.0),
+        ];
+        let threat_level = [
+            ("none", 0.0),
+            ("low", 0.1),
+            ("medium", 0.5),
+            ("high", 0.8),
+            ("critical", 1.0),
+        ];
+
+        RiskProfile {
+            weights: to_map(&weights),
+            base_score: 0.0,
+            max_score: 10.0,
+            injection: RiskInjection::Replace,
+            device_trust_scores: to_map(&device_trust),
+            threat_level_scores: to_map(&threat_level),
+            trusted_countries: Vec::new(),
+            high_risk_countries: Vec::new(),
+            impossible_travel_kmh: 1000.0,
+            max_session_age_hours: 12.0,
+        }
+    }
+}
+
+fn to_map(entries: &[(&str, f64)]) -> HashMap<String, f64> {
+    entries.iter().map(|(k, v)| (k.to_string(), *v)).collect()
+}
+
+// What the engine has seen of the requesting user beyond the context
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Observations {
+    // Implied speed since the user's previous location, when known
+    pub travel_speed_kmh: Option<f64>,
+}
+
+// A single input to the risk score. Implementations return a raw value
+// in [0, 1]; weighting is applied by the scorer.
+pub trait RiskSignal {
+    fn name(&self) -> &str;
+    fn evaluate(&self, context: &PolicyContext, profile: &RiskProfile, observed: &Observations) -> f64;
+}
+
+struct VpnSignal;
+
+impl RiskSignal for VpnSignal {
+    fn name(&self) -> &str {
+        "vpn"
+    }
+
+    fn evaluate(&self, context: &PolicyContext, _profile: &RiskProfile, _observed: &Observations) -> f64 {
+        if context.vpn_detected { 1.0 } else { 0.0 }
+    }
+}
+
+struct DeviceTrustSignal;
+
+impl RiskSignal for DeviceTrustSignal {
+    fn name(&self) -> &str {
+        "device_trust"
+    }
+
+    fn evaluate(&self, context: &PolicyContext, profile: &RiskProfile, _observed: &Observations) -> f64 {
+        let trust = context.device_trust.as_str().to_lowercase();
+        profile.device_trust_scores.get(&trust).copied().unwrap_or(1.0)
+    }
+}
+
+struct GeoSignal;
+
+impl RiskSignal for GeoSignal {
+    fn name(&self) -> &str {
+        "geo"
+    }
+
+    fn evaluate(&self, context: &PolicyContext, profile: &RiskProfile, observed: &Observations) -> f64 {
+        if observed.travel_speed_kmh.is_some_and(|speed| speed > profile.impossible_travel_kmh) {
+            return 1.0;
+        }
+        let country = context.ip_country.to_uppercase();
+        if country.is_empty() {
+            return 0.5;
+        }
+        if profile.high_risk_countries.iter().any(|c| c.eq_ignore_ascii_case(&country)) {
+            return 1.0;
+        }
+        if !profile.trusted_countries.is_empty()
+            && !profile.trusted_countries.iter().any(|c| c.eq_ignore_ascii_case(&country))
+        {
+            return 0.5;
+        }
+        0.0
+    }
+}
+
+struct SessionAgeSignal;
+
+impl RiskSignal for SessionAgeSignal {
+    fn name(&self) -> &str {
+        "session_age"
+    }
+
+    fn evaluate(&self, context: &PolicyContext, profile: &RiskProfile, _observed: &Observations) -> f64 {
+        if profile.max_session_age_hours <= 0.0 {
+            return 0.0;
+        }
+        let hours = context.session_age.num_seconds() as f64 / 3600.0;
+        (hours / profile.max_session_age_hours).clamp(0.0, 1.0)
+    }
+}
+
+struct ThreatLevelSignal;
+
+impl RiskSignal for ThreatLevelSignal {
+    fn name(&self) -> &str {
+        "threat_level"
+    }
+
+    fn evaluate(&self, context: &PolicyContext, profile: &RiskProfile, _observed: &Observations) -> f64 {
+        let level = context.threat_level.as_str().to_lowercase();
+        profile.threat_level_scores.get(&level).copied().unwrap_or(0.5)
+    }
+}
+
+// A host function called as `signal(context_json)`. It should return a
+// number in [0, 1]; one that throws or returns anything else scores 1.0,
+// so a broken signal raises risk rather than hiding it.
+struct CallbackSignal {
+    name: String,
+    callback: js_sys::Function,
+}
+
+impl RiskSignal for CallbackSignal {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn evaluate(&self, context: &PolicyContext, _profile: &RiskProfile, _observed: &Observations) -> f64 {
+        let payload = serde_json::to_string(context).unwrap_or_default();
+        match self.callback.call1(&JsValue::NULL, &JsValue::from_str(&payload)).map(|returned| returned.as_f64()) {
+            Ok(Some(raw)) if raw.is_finite() => raw,
+            Ok(_) => {
+                log_at!(LogLevel::Warn, "Risk signal '{}' did not return a number", self.name);
+                1.0
+            }
+            Err(e) => {
+                log_at!(LogLevel::Warn, "Risk signal '{}' threw {:?}", self.name, e);
+                1.0
+            }
+        }
+    }
+}
+
+// Per-signal breakdown returned by `compute_risk`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignalContribution {
+    pub signal: String,
+    pub raw: f64,
+    pub weight: f64,
+    pub contribution: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RiskAssessment {
+    pub score: f64,
+    pub signals: Vec<SignalContribution>,
+}
+
+pub struct RiskScorer {
+    profile: RiskProfile,
+    signals: Vec<Box<dyn RiskSignal>>,
+}
+
+impl RiskScorer {
+    pub fn new(profile: RiskProfile) -> RiskScorer {
+        RiskScorer {
+            profile,
+            signals: vec![
+                Box::new(VpnSignal),
+                Box::new(DeviceTrustSignal),
+                Box::new(GeoSignal),
+                Box::new(SessionAgeSignal),
+                Box::new(ThreatLevelSignal),
+            ],
+        }
+    }
+
+    pub fn profile(&self) -> &RiskProfile {
+        &self.profile
+    }
+
+    pub fn add_signal(&mut self, signal: Box<dyn RiskSignal>) {
+        self.signals.push(signal);
+    }
+
+    pub fn assess(&self, context: &PolicyContext, observed: &Observations) -> RiskAssessment {
+        let mut signals = Vec::with_capacity(self.signals.len());
+        let mut total = self.profile.base_score;
+
+        for signal in &self.signals {
+            // Signals without a configured weight are disabled
+            let weight = match self.profile.weights.get(signal.name()) {
+                Some(w) => *w,
+                None => continue,
+            };
+            let raw = signal.evaluate(context, &self.profile, observed).clamp(0.0, 1.0);
+            let contribution = raw * weight;
+            total += contribution;
+            signals.push(SignalContribution {
+                signal: signal.name().to_string(),
+                raw,
+                weight,
+                contribution,
+            });
+        }
+
+        RiskAssessment {
+            score: total.clamp(0.0, self.profile.max_score.max(0.0)),
+            signals,
+        }
+    }
+
+    // Merge the computed score into the context according to the profile
+    pub fn inject(&self, context: &mut PolicyContext, observed: &Observations) {
+        if self.profile.injection == RiskInjection::Disabled {
+            return;
+        }
+        let score = self.assess(context, observed).score;
+        context.risk_score = match self.profile.injection {
+            RiskInjection::Max => context.risk_score.max(score),
+            _ => score,
+        };
+        if let Some(supplied) = &mut context.supplied_fields {
+            supplied.insert("risk_score".to_string());
+        }
+    }
+}
+
+impl Default for RiskScorer {
+    fn default() -> Self {
+        RiskScorer::new(RiskProfile::default())
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    #[wasm_bindgen]
+    pub fn set_risk_profile(&mut self, profile_json: &str) -> Result<(), JsValue> {
+        match serde_json::from_str::<RiskProfile>(profile_json) {
+            Ok(profile) => {
+                if self.debug_mode {
+                    console_log!("Loaded risk profile ({} signals weighted)", profile.weights.len());
+                }
+                self.risk = Some(self.risk_scorer(profile));
+                Ok(())
+            }
+            Err(e) => Err(PolicyEngineError::parse(format!("Failed to parse risk profile: {}", e)).logged().into()),
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_risk_profile(&mut self) {
+        self.risk = None;
+    }
+
+    // Adds (or replaces) a host signal (see CallbackSignal) to the
+    // current and any later risk profile. It only counts once the
+    // profile gives `name` a weight.
+    #[wasm_bindgen]
+    pub fn register_risk_signal(&mut self, name: &str, signal: js_sys::Function) -> Result<(), JsValue> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(PolicyEngineError::validation("Risk signal name must not be empty").logged().into());
+        }
+        if BUILTIN_SIGNALS.contains(&name) {
+            return Err(PolicyEngineError::conflict(format!("Risk signal '{}' is built in", name))
+                .with_details(json!({ "signal": name }))
+                .logged()
+                .into());
+        }
+        if self.debug_mode {
+            console_log!("Registered risk signal: {}", name);
+        }
+        self.risk_signals.insert(name.to_string(), signal);
+        self.rebuild_risk_scorer();
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn unregister_risk_signal(&mut self, name: &str) -> bool {
+        let removed = self.risk_signals.remove(name.trim()).is_some();
+        if removed {
+            self.rebuild_risk_scorer();
+        }
+        removed
+    }
+
+    // Returns the risk assessment as JSON. Uses the configured profile,
+    // or the default profile if none has been set. Travel is judged
+    // against the user's history in `tenant_id`.
+    #[wasm_bindgen]
+    pub fn compute_risk(&self, context_json: &str, tenant_id: Option<String>) -> Result<String, JsValue> {
+        if let Some(tenant) = tenant_id.as_deref().filter(|tenant| !self.tenants.contains_key(*tenant)) {
+            return Err(PolicyEngineError::not_found(format!("Tenant '{}' does not exist", tenant))
+                .with_details(json!({ "tenant_id": tenant }))
+                .logged()
+                .into());
+        }
+        let context = self.parse_context(context_json)?;
+        let observed = self.observations(&context, tenant_id.as_deref());
+
+        let assessment = match &self.risk {
+            Some(scorer) => scorer.assess(&context, &observed),
+            None => self.risk_scorer(RiskProfile::default()).assess(&context, &observed),
+        };
+
+        Ok(to_json(&assessment)?)
+    }
+}
+
+impl PolicyEngine {
+    // A scorer for `profile` with the built-in and registered host signals
+    fn risk_scorer(&self, profile: RiskProfile) -> RiskScorer {
+        let mut scorer = RiskScorer::new(profile);
+        for (name, callback) in &self.risk_signals {
+            scorer.add_signal(Box::new(CallbackSignal { name: name.clone(), callback: callback.clone() }));
+        }
+        scorer
+    }
+
+    // Travel history is engine state a replayed record cannot carry
+    pub(crate) fn observations(&self, context: &PolicyContext, tenant: Option<&str>) -> Observations {
+        if self.deterministic.get() {
+            return Observations::default();
+        }
+        #[cfg(feature = "geo")]
+        let travel_speed_kmh = self.geo.travel_speed(&crate::state_key(tenant, &context.user_id), context);
+        #[cfg(not(feature = "geo"))]
+        let travel_speed_kmh = {
+            let _ = (context, tenant);
+            None
+        };
+        Observations { travel_speed_kmh }
+    }
+
+    fn rebuild_risk_scorer(&mut self) {
+        if let Some(scorer) = self.risk.take() {
+            self.risk = Some(self.risk_scorer(scorer.profile));
+        }
+    }
+}
+
+#[cfg(all(test, feature = "geo"))]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+
+    fn geo_raw(assessment: &RiskAssessment) -> f64 {
+        assessment.signals.iter().find(|signal| signal.signal == "geo").unwrap().raw
+    }
+
+    #[test]
+    fn impossible_travel_scores_the_geo_signal() {
+        let mut engine = PolicyEngine::new();
+        let mut context = PolicyContext { user_id: "alice".to_string(), ip_country: "US".to_string(), ..Default::default() };
+        context.timestamp = Utc::now() - Duration::hours(1);
+        engine.geo.record("alice", &context);
+
+        context.ip_country = "JP".to_string();
+        context.timestamp = Utc::now();
+        let scorer = RiskScorer::default();
+        let observed = engine.observations(&context, None);
+        assert!(observed.travel_speed_kmh.is_some_and(|speed| speed > 1000.0));
+        assert_eq!(geo_raw(&scorer.assess(&context, &observed)), 1.0);
+
+        // Another tenant's user of the same name has no history
+        let observed = engine.observations(&context, Some("acme"));
+        assert_eq!(observed.travel_speed_kmh, None);
+        assert_eq!(geo_raw(&scorer.assess(&context, &observed)), 0.0);
+    }
+}
@@ -0,0 +1,168 @@
+use wasm_bindgen::prelude::*;
+use serde_json::json;
+use std::collections::HashMap;
+
+use crate::approval::ApprovalSpec;
+use crate::error::PolicyEngineError;
+use crate::expr::{self, Expr};
+use crate::vocabulary;
+use crate::{CompiledPolicy, Policy, PolicyEngine, PolicyRule};
+
+// Shared rules keyed by rule ID, referenced from policies as
+// "library:rule-id". Linked copies take the reference as their rule ID so
+// results and coverage show where a rule came from.
+pub type RuleLibrary = HashMap<String, (PolicyRule, Expr)>;
+
+fn compile_library(name: &str, rules: Vec<PolicyRule>) -> Result<RuleLibrary, PolicyEngineError> {
+    let mut library = RuleLibrary::new();
+    for rule in rules {
+        let mut condition = expr::parse(&rule.condition).map_err(|e| {
+            PolicyEngineError::compile(format!("Rule library '{}' rule '{}': {}", name, rule.id, e))
+                .with_details(json!({ "library": name, "rule_id": rule.id, "offset": e.offset }))
+        })?;
+        vocabulary::canonicalize(&mut condition);
+        if let Some(Err(e)) = rule.approval.as_ref().map(ApprovalSpec::check) {
+            return Err(PolicyEngineError::validation(format!("Rule library '{}' rule '{}': {}", name, rule.id, e))
+                .with_details(json!({ "library": name, "rule_id": rule.id })));
+        }
+        if library.contains_key(&rule.id) {
+            return Err(PolicyEngineError::conflict(format!("Rule library '{}' defines '{}' twice", name, rule.id))
+                .with_details(json!({ "library": name, "rule_id": rule.id })));
+        }
+        library.insert(rule.id.clone(), (rule, condition));
+    }
+    Ok(library)
+}
+
+// Resolves a policy's rule references against `libraries`
+pub(crate) fn link(libraries: &HashMap<String, RuleLibrary>, compiled: &mut CompiledPolicy) -> Result<(), PolicyEngineError> {
+    compiled.linked = resolve(libraries, &compiled.policy)?;
+    Ok(())
+}
+
+fn resolve(libraries: &HashMap<String, RuleLibrary>, policy: &Policy) -> Result<Vec<(PolicyRule, Expr)>, PolicyEngineError> {
+    policy
+        .rule_refs
+        .iter()
+        .map(|reference| {
+            let found = reference
+                .split_once(':')
+                .and_then(|(library, rule_id)| libraries.get(library)?.get(rule_id));
+            match found {
+                Some((rule, condition)) => {
+                    let mut rule = rule.clone();
+                    rule.id = reference.clone();
+                    Ok((rule, condition.clone()))
+                }
+                None => Err(PolicyEngineError::not_found(format!(
+                    "Policy '{}' references unknown rule '{}'",
+                    policy.id, reference
+                ))
+                .with_details(json!({ "policy_id": policy.id, "rule_ref": reference }))),
+            }
+        })
+        .collect()
+}
+
+fn references(policy: &Policy, library: &str) -> bool {
+    policy
+        .rule_refs
+        .iter()
+        .any(|reference| reference.split_once(':').is_some_and(|(name, _)| name == library))
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Loads (or replaces) a named library from a JSON array of rules.
+    // Loaded policies that reference the library are relinked, so a fixed
+    // library rule takes effect everywhere at once; if any reference would
+    // no longer resolve the library is rejected and nothing changes.
+    #[wasm_bindgen]
+    pub fn load_rule_library(&mut self, name: &str, rules_json: &str) -> Result<(), JsValue> {
+        let mut rules: Vec<PolicyRule> = serde_json::from_str(rules_json).map_err(|e| {
+            PolicyEngineError::parse(format!("Failed to parse rule library '{}': {}", name, e)).logged()
+        })?;
+        self.substitute_library_constants(name, &mut rules).map_err(PolicyEngineError::logged)?;
+        let library = compile_library(name, rules).map_err(PolicyEngineError::logged)?;
+        self.check_library_vocabulary(name, &library).map_err(PolicyEngineError::logged)?;
+
+        let mut libraries = self.rule_libraries.clone();
+        libraries.insert(name.to_string(), library);
+        for compiled in self.all_policies() {
+            resolve(&libraries, &compiled.policy)
+                .map_err(|e| e.context(&format!("Rule library '{}' breaks a loaded policy", name)).logged())?;
+        }
+
+        self.rule_libraries = libraries;
+        self.relink(name);
+        if self.debug_mode {
+            console_log!("Loaded rule library: {}", name);
+        }
+        Ok(())
+    }
+
+    // Fails with CONFLICT while any loaded policy still references it
+    #[wasm_bindgen]
+    pub fn remove_rule_library(&mut self, name: &str) -> Result<bool, JsValue> {
+        if let Some(user) = self.all_policies().into_iter().find(|c| references(&c.policy, name)) {
+            return Err(PolicyEngineError::conflict(format!(
+                "Rule library '{}' is referenced by policy '{}'",
+                name, user.policy.id
+            ))
+            .with_details(json!({ "library": name, "policy_id": user.policy.id }))
+            .logged()
+            .into());
+        }
+        Ok(self.rule_libraries.remove(name).is_some())
+    }
+
+    #[wasm_bindgen]
+    pub fn list_rule_libraries(&self) -> String {
+        let mut names: Vec<&String> = self.rule_libraries.keys().collect();
+        names.sort();
+        serde_json::to_string(&names).unwrap_or_default()
+    }
+}
+
+impl PolicyEngine {
+    // Compiles a policy and links its rule references
+    pub(crate) fn compile_policy(&self, mut policy: Policy) -> Result<CompiledPolicy, PolicyEngineError> {
+        self.substitute_policy_constants(&mut policy)?;
+        let mut compiled = CompiledPolicy::compile(policy)?;
+        self.link_rule_refs(&mut compiled)?;
+        self.check_policy_vocabulary(&compiled)?;
+        Ok(compiled)
+    }
+
+    pub(crate) fn compile_policies(&self, policies: Vec<Policy>) -> Result<Vec<CompiledPolicy>, PolicyEngineError> {
+        policies.into_iter().map(|policy| self.compile_policy(policy)).collect()
+    }
+
+    pub(crate) fn link_rule_refs(&self, compiled: &mut CompiledPolicy) -> Result<(), PolicyEngineError> {
+        link(&self.rule_libraries, compiled)
+    }
+
+    // Global, tenant and staged policies
+    fn all_policies(&self) -> Vec<&CompiledPolicy> {
+        let mut all: Vec<&CompiledPolicy> = self.policies.iter().collect();
+        for tenant in self.tenants.values() {
+            all.extend(tenant.own_policies());
+        }
+        all.extend(self.staged_policies());
+        all.extend(self.experiments.policies());
+        all
+    }
+
+    // Callers have already checked every reference resolves
+    fn relink(&mut self, library: &str) {
+        let libraries = &self.rule_libraries;
+        let tenant_policies = self.tenants.values_mut().flat_map(|tenant| tenant.own_policies_mut().iter_mut());
+        let staged = self.staged.iter_mut().flatten();
+        let profiles = self.experiments.policies_mut();
+        for compiled in self.policies.iter_mut().chain(tenant_policies).chain(staged).chain(profiles) {
+            if references(&compiled.policy, library) {
+                compiled.linked = resolve(libraries, &compiled.policy).unwrap_or_default();
+            }
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use wasm_bindgen::prelude::*;
+use schemars::schema::{RootSchema, Schema};
+use schemars::schema_for;
+use serde_json::Value;
+use std::sync::OnceLock;
+
+use crate::replay::ENGINE_VERSION;
+use crate::{Policy, PolicyContext};
+
+// JSON Schemas generated from the Rust data model, so tooling built on
+// them tracks the engine it talks to. Each carries the engine version as
+// "x-engine-version".
+fn with_version(mut schema: RootSchema) -> String {
+    schema.schema.extensions.insert("x-engine-version".to_string(), ENGINE_VERSION.into());
+    serde_json::to_string(&schema).unwrap_or_default()
+}
+
+// Schema of a Policy document (one element of a `load_policies` array)
+#[wasm_bindgen]
+pub fn policy_schema() -> String {
+    with_version(schema_for!(Policy))
+}
+
+// Schema of an evaluation context. Every field is optional; durations
+// (session_age, intent_duration) are [seconds, nanoseconds] pairs.
+#[wasm_bindgen]
+pub fn context_schema() -> String {
+    let mut schema = schema_for!(PolicyContext);
+    // The timestamp defaults to the time of parsing, not a fixed value
+    let timestamp = schema.schema.object.as_mut().and_then(|object| object.properties.get_mut("timestamp"));
+    if let Some(Schema::Object(timestamp)) = timestamp {
+        timestamp.metadata().default = None;
+    }
+    with_version(schema)
+}
+
+fn policy_schema_value() -> &'static Value {
+    static SCHEMA: OnceLock<Value> = OnceLock::new();
+    SCHEMA.get_or_init(|| serde_json::to_value(schema_for!(Policy)).unwrap_or_default())
+}
+
+// Fields of a policy document (a Policy or an array of them) the data
+// model does not have, as JSON pointers. Deserialization ignores them, so
+// a misspelt optional field silently takes its default.
+pub fn unknown_policy_fields(document: &Value) -> Vec<String> {
+    let schema = policy_schema_value();
+    let mut unknown = Vec::new();
+    match document {
+        Value::Array(policies) => {
+            for (index, policy) in policies.iter().enumerate() {
+                unknown_fields(schema, schema, policy, &format!("/{}", index), &mut unknown);
+            }
+        }
+        policy => unknown_fields(schema, schema, policy, "", &mut unknown),
+    }
+    unknown
+}
+
+// `schema` and the subschemas it combines (anyOf, allOf, oneOf), with
+// references into the root's definitions followed
+fn alternatives<'a>(root: &'a Value, schema: &'a Value, found: &mut Vec<&'a Value>) {
+    let schema = match schema.get("$ref").and_then(Value::as_str).and_then(|r| r.strip_prefix("#/definitions/")) {
+        Some(name) => match root.get("definitions").and_then(|definitions| definitions.get(name)) {
+            Some(definition) => definition,
+            None => return,
+        },
+        None => schema,
+    };
+    found.push(schema);
+    for combinator in ["anyOf", "allOf", "oneOf"] {
+        for subschema in schema.get(combinator).and_then(Value::as_array).into_iter().flatten() {
+            alternatives(root, subschema, found);
+        }
+    }
+}
+
+fn unknown_fields(root: &Value, schema: &Value, value: &Value, pointer: &str, unknown: &mut Vec<String>) {
+    let mut schemas = Vec::new();
+    alternatives(root, schema, &mut schemas);
+    match value {
+        Value::Object(fields) => {
+            let properties: Vec<&serde_json::Map<String, Value>> =
+                schemas.iter().filter_map(|schema| schema.get("properties")?.as_object()).collect();
+            let additional = schemas.iter().find_map(|schema| schema.get("additionalProperties"));
+            for (name, field) in fields {
+                let path = format!("{}/{}", pointer, name.replace('~', "~0").replace('/', "~1"));
+                match properties.iter().find_map(|properties| properties.get(name)).or(additional) {
+                    // `true`: free-form values
+                    Some(Value::Bool(true)) => {}
+                    Some(Value::Bool(false)) => unknown.push(path),
+                    Some(field_schema) => unknown_fields(root, field_schema, field, &path, unknown),
+                    // Only objects the model describes field by field are checked
+                    None if properties.is_empty() => {}
+                    None => unknown.push(path),
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(item_schema) = schemas.iter().find_map(|schema| schema.get("items")) {
+                for (index, item) in items.iter().enumerate() {
+                    unknown_fields(root, item_schema, item, &format!("{}/{}", pointer, index), unknown);
+                }
+            }
+        }
+        _ => {}
+    }
+}
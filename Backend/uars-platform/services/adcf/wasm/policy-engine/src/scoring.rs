@@ -0,0 +1,220 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::error::PolicyEngineError;
+use crate::expr::Value;
+use crate::logging::LogLevel;
+use crate::{PolicyContext, PolicyEngine};
+
+// Advisory model scoring. A registered scorer receives selected context
+// features and its output is exposed to expressions under `model`, so
+// policies can blend rules with a model:
+//
+//   risk_score < 7 && model.score < 0.8
+//
+// The scorer runs at most once per request, the first time a condition
+// reads `model.*`. When it fails (or in deterministic replay, which
+// cannot re-run it) `model.score` is `fallback`, or missing if none is
+// configured, so comparisons against it are false.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScoringConfig {
+    // Attribute paths passed to the scorer, e.g. "risk_score", "device.trust"
+    pub features: Vec<String>,
+    // Numeric codes for string features (ONNX models take numbers only),
+    // e.g. { "device.trust": { "trusted": 0, "unknown": 1 } }
+    pub encodings: HashMap<String, HashMap<String, f64>>,
+    pub fallback: Option<f64>,
+}
+
+enum Backend {
+    Callback(js_sys::Function),
+    #[cfg(feature = "onnx")]
+    Onnx(Box<onnx::OnnxModel>),
+}
+
+pub struct Scorer {
+    config: ScoringConfig,
+    backend: Backend,
+}
+
+impl Scorer {
+    fn features(&self, context: &PolicyContext) -> BTreeMap<String, Value> {
+        self.config
+            .features
+            .iter()
+            .map(|feature| {
+                let path: Vec<String> = feature.split('.').map(str::to_string).collect();
+                (feature.clone(), context.attribute(&path).unwrap_or(Value::Null))
+            })
+            .collect()
+    }
+
+    // `model` as expressions see it: a map with at least `score`
+    pub fn score(&self, context: &PolicyContext) -> Option<Value> {
+        let features = self.features(context);
+        let output = match &self.backend {
+            Backend::Callback(callback) => call_scorer(callback, &features),
+            #[cfg(feature = "onnx")]
+            Backend::Onnx(model) => self.numeric(&features).and_then(|inputs| model.run(&inputs)).map(score_map),
+        };
+        match output {
+            Ok(output) => Some(output),
+            Err(e) => {
+                log_at!(LogLevel::Warn, "Model scoring failed: {}", e);
+                self.config.fallback.map(score_map)
+            }
+        }
+    }
+
+    // Features as model inputs: numbers as-is, booleans as 0/1, strings
+    // through `encodings`; missing values are 0
+    #[cfg(feature = "onnx")]
+    fn numeric(&self, features: &BTreeMap<String, Value>) -> Result<Vec<f32>, String> {
+        self.config
+            .features
+            .iter()
+            .map(|feature| match &features[feature] {
+                Value::Number(n) => Ok(*n as f32),
+                Value::Bool(b) => Ok(if *b { 1.0 } else { 0.0 }),
+                Value::Null => Ok(0.0),
+                Value::String(s) => self
+                    .config
+                    .encodings
+                    .get(feature)
+                    .and_then(|codes| codes.get(s))
+                    .map(|code| *code as f32)
+                    .ok_or_else(|| format!("feature '{}' has no encoding for '{}'", feature, s)),
+                other => Err(format!("feature '{}' is a {}, not a number", feature, other.type_name())),
+            })
+            .collect()
+    }
+}
+
+fn score_map(score: f64) -> Value {
+    Value::Map(BTreeMap::from([("score".to_string(), Value::Number(score))]))
+}
+
+// The callback gets `{"features": {...}}` as JSON and returns a number
+// (the score) or an object with a numeric `score` and any other outputs
+fn call_scorer(callback: &js_sys::Function, features: &BTreeMap<String, Value>) -> Result<Value, String> {
+    let payload = serde_json::json!({ "features": features }).to_string();
+    let returned = callback
+        .call1(&JsValue::NULL, &JsValue::from_str(&payload))
+        .map_err(|e| format!("scorer threw {:?}", e))?;
+    if let Some(score) = returned.as_f64() {
+        return Ok(score_map(score));
+    }
+    let json: String = js_sys::JSON::stringify(&returned)
+        .map_err(|e| format!("scorer returned an unserializable value: {:?}", e))?
+        .into();
+    let output: serde_json::Value = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+    match output.get("score") {
+        Some(score) if score.is_number() => Ok(Value::from(&output)),
+        _ => Err("scorer must return a number or an object with a numeric score".to_string()),
+    }
+}
+
+#[cfg(feature = "onnx")]
+mod onnx {
+    use tract_onnx::prelude::*;
+
+    pub struct OnnxModel {
+        plan: TypedRunnableModel<TypedModel>,
+        inputs: usize,
+    }
+
+    impl OnnxModel {
+        // A model taking one f32 tensor of shape [1, inputs]; the first
+        // value of its first output is the score
+        pub fn load(bytes: &[u8], inputs: usize) -> TractResult<OnnxModel> {
+            let plan = tract_onnx::onnx()
+                .model_for_read(&mut std::io::Cursor::new(bytes))?
+                .with_input_fact(0, f32::fact([1, inputs]).into())?
+                .into_optimized()?
+                .into_runnable()?;
+            Ok(OnnxModel { plan, inputs })
+        }
+
+        pub fn run(&self, values: &[f32]) -> Result<f64, String> {
+            let input = Tensor::from_shape(&[1, self.inputs], values).map_err(|e| e.to_string())?;
+            let outputs = self.plan.run(tvec!(input.into())).map_err(|e| e.to_string())?;
+            let output = outputs.first().ok_or("model produced no output")?;
+            let view = output.to_array_view::<f32>().map_err(|e| e.to_string())?;
+            view.iter().next().map(|score| *score as f64).ok_or_else(|| "model output is empty".to_string())
+        }
+    }
+}
+
+fn parse_config(config_json: &str) -> Result<ScoringConfig, PolicyEngineError> {
+    let config: ScoringConfig = serde_json::from_str(config_json)
+        .map_err(|e| PolicyEngineError::parse(format!("Failed to parse scoring config: {}", e)))?;
+    if config.features.is_empty() {
+        return Err(PolicyEngineError::validation("Scoring config must list at least one feature"));
+    }
+    Ok(config)
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Registers a JS scoring function (see ScoringConfig), replacing any
+    // scorer or model set before
+    #[wasm_bindgen]
+    pub fn register_scoring_function(&mut self, config_json: &str, scorer: js_sys::Function) -> Result<(), JsValue> {
+        let config = parse_config(config_json).map_err(PolicyEngineError::logged)?;
+        if self.debug_mode {
+            console_log!("Registered scoring function ({} features)", config.features.len());
+        }
+        self.scorer = Some(Scorer { config, backend: Backend::Callback(scorer) });
+        Ok(())
+    }
+
+    // Loads an ONNX model evaluated in-module; its input is the features
+    // in config order as one [1, n] f32 tensor
+    #[cfg(feature = "onnx")]
+    #[wasm_bindgen]
+    pub fn load_scoring_model(&mut self, config_json: &str, model_bytes: &[u8]) -> Result<(), JsValue> {
+        let config = parse_config(config_json).map_err(PolicyEngineError::logged)?;
+        let model = onnx::OnnxModel::load(model_bytes, config.features.len())
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to load ONNX model: {}", e)).logged())?;
+        self.scorer = Some(Scorer { config, backend: Backend::Onnx(Box::new(model)) });
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_scoring(&mut self) {
+        self.scorer = None;
+    }
+
+    #[wasm_bindgen]
+    pub fn has_scoring(&self) -> bool {
+        self.scorer.is_some()
+    }
+}
+
+impl PolicyEngine {
+    // Runs the scorer for a request; deterministic replay cannot, so it
+    // gets the fallback
+    pub(crate) fn model_output(&self, context: &PolicyContext) -> Option<Value> {
+        let scorer = self.scorer.as_ref()?;
+        if self.deterministic.get() {
+            return scorer.config.fallback.map(score_map);
+        }
+        scorer.score(context)
+    }
+
+    // Attribute paths the scorer reads, empty without one
+    pub(crate) fn model_features(&self) -> &[String] {
+        self.scorer.as_ref().map_or(&[], |scorer| &scorer.config.features)
+    }
+}
+
+// `model.<path>` within the scorer's output
+pub fn lookup(output: &Value, path: &[String]) -> Option<Value> {
+    path.iter().try_fold(output, |value, key| match value {
+        Value::Map(map) => map.get(key),
+        _ => None,
+    })
+    .cloned()
+}
@@ -0,0 +1,91 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::decision::Decision;
+use crate::error::to_json;
+use crate::expr::Value;
+use crate::functions::EvalScope;
+use crate::logging::LogLevel;
+use crate::obligations::ObligationCall;
+use crate::quota::parse_window;
+use crate::{guard, PolicyContext, PolicyEngine, PolicyResult, PolicySelection};
+
+// `max_session_age(8h)` or `max_session_age("8h")` caps the session
+// lifetime; the tightest cap across matched rules wins
+pub const MAX_SESSION_AGE_OBLIGATION: &str = "max_session_age";
+// `reauth_required` asks the caller to re-authenticate now
+pub const REAUTH_OBLIGATION: &str = "reauth_required";
+
+// Output of `check_session`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionCheck {
+    pub decision: Decision,
+    pub session_age: f64,
+    pub max_session_age: Option<f64>,
+    pub reauth_required: bool,
+}
+
+fn max_age_seconds(args: &[Value]) -> Result<f64, String> {
+    match args.first() {
+        Some(Value::Number(seconds)) if *seconds > 0.0 => Ok(*seconds),
+        Some(Value::String(window)) => parse_window(window)
+            .map(|duration| duration.num_seconds() as f64)
+            .ok_or_else(|| format!("invalid duration '{}'", window)),
+        Some(other) => Err(format!("expected a positive duration, found {}", other)),
+        None => Err("missing duration".to_string()),
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Evaluates the global policies for their session-lifetime outcome
+    // without recording travel history, consuming quotas or dispatching
+    // obligations. Returns a JSON SessionCheck.
+    #[wasm_bindgen]
+    pub fn check_session(&self, context_json: &str) -> Result<String, JsValue> {
+        let mut context = self.parse_context(context_json)?;
+        self.enrich_context(&mut context, None);
+
+        let mut result = guard::guarded(|| self.evaluate_context(&context, PolicySelection::Global));
+        self.apply_session_obligations(&mut result, &context, None);
+
+        let check = SessionCheck {
+            decision: result.decision,
+            session_age: context.session_age.num_seconds() as f64,
+            max_session_age: result.max_session_age,
+            reauth_required: result.reauth_required,
+        };
+        Ok(to_json(&check)?)
+    }
+}
+
+impl PolicyEngine {
+    // Sets max_session_age and reauth_required from the result's
+    // obligations. A session already older than the cap needs re-auth.
+    pub(crate) fn apply_session_obligations(&self, result: &mut PolicyResult, context: &PolicyContext, tenant: Option<&str>) {
+        let specs: Vec<String> = serde_json::from_str(&result.obligations).unwrap_or_default();
+        let scope = EvalScope::new(self, context, tenant);
+
+        for spec in &specs {
+            let call = ObligationCall::parse(spec);
+            if call.id == REAUTH_OBLIGATION {
+                result.reauth_required = true;
+            } else if call.id == MAX_SESSION_AGE_OBLIGATION {
+                let parsed = call.resolve_args(&scope).map_err(|e| e.to_string()).and_then(|args| max_age_seconds(&args));
+                match parsed {
+                    Ok(seconds) => {
+                        let tightest = result.max_session_age.map_or(seconds, |current| current.min(seconds));
+                        result.max_session_age = Some(tightest);
+                    }
+                    Err(e) => log_at!(LogLevel::Warn, "Ignoring invalid obligation '{}': {}", spec, e),
+                }
+            }
+        }
+
+        if let Some(max_age) = result.max_session_age {
+            if context.session_age.num_seconds() as f64 > max_age {
+                result.reauth_required = true;
+            }
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+use crate::advice::Advice;
+use crate::approval::ApprovalRequest;
+use crate::baseline::BaselineTracker;
+use crate::calendar::Calendar;
+use crate::confidence::ConfidenceModel;
+use crate::consent::ConsentRegistry;
+use crate::dedupe::DedupeTracker;
+use crate::deployment::DeploymentConfig;
+use crate::derived::DerivedAttribute;
+use crate::break_glass::BreakGlassEntry;
+use crate::delegation::DelegationGrant;
+use crate::envoy::EnvoyConfig;
+use crate::error::PolicyEngineError;
+use crate::freshness::FreshnessConfig;
+use crate::experiments::Experiments;
+#[cfg(feature = "geo")]
+use crate::geo::GeoTracker;
+use crate::history::AttributeHistory;
+use crate::lattice::Lattices;
+use crate::limits::EvaluationLimits;
+use crate::messages::MessageCatalogs;
+use crate::obligations::PolicyObligation;
+use crate::purpose::PurposeRegistry;
+use crate::quota::QuotaTracker;
+use crate::redaction::{RedactionPolicy, Redactor};
+use crate::replay::ENGINE_VERSION;
+use crate::risk::{RiskProfile, RiskScorer};
+use crate::rule_library::{self, RuleLibrary};
+#[cfg(feature = "telemetry")]
+use crate::stats::StatsTracker;
+use crate::templates::PolicyTemplate;
+use crate::tenants::Tenant;
+use crate::validity::GrantedDecisions;
+use crate::velocity::OperationCounters;
+use crate::vocabulary::CombiningAlgorithm;
+use crate::{CompiledPolicy, PolicyEngine};
+
+const SNAPSHOT_MAGIC: &str = "uars-engine-snapshot";
+const SNAPSHOT_FORMAT_VERSION: u32 = 1;
+
+// CBOR engine snapshot: compiled policies (global, tenant, staged and
+// experiment profiles), rule libraries, templates and message catalogs,
+// configuration, per-user caches and counters.
+// Compiled expressions are stored as-is, so a snapshot only loads into
+// the engine version that wrote it.
+//
+// Host bindings are not part of it: obligation handlers, sync, signing,
+// watermark and attestation keys, the bundle key, key-encryption keys and
+// environment overrides stay with each instance and must be set up again
+// after import, as do the scoring function or model, the threat feed and
+// the GeoIP database.
+#[derive(Serialize, Deserialize)]
+struct EngineSnapshot {
+    magic: String,
+    format_version: u32,
+    engine_version: String,
+    policies: Vec<CompiledPolicy>,
+    tenants: HashMap<String, Tenant>,
+    staged: Option<Vec<CompiledPolicy>>,
+    experiments: Experiments,
+    rule_libraries: HashMap<String, RuleLibrary>,
+    templates: HashMap<String, PolicyTemplate>,
+    messages: MessageCatalogs,
+    debug_mode: bool,
+    strict_mode: bool,
+    root_algorithm: CombiningAlgorithm,
+    root_obligations: Vec<PolicyObligation>,
+    declared_obligations: BTreeSet<String>,
+    root_advice: Vec<Advice>,
+    risk_profile: Option<RiskProfile>,
+    confidence_model: Option<ConfidenceModel>,
+    redaction_policy: Option<RedactionPolicy>,
+    context_schema: Option<serde_json::Value>,
+    limits: EvaluationLimits,
+    freshness: FreshnessConfig,
+    calendar: Option<Calendar>,
+    deployment: DeploymentConfig,
+    derived: BTreeMap<String, DerivedAttribute>,
+    lattices: Lattices,
+    break_glass_seconds: f64,
+    #[cfg(feature = "geo")]
+    geo: GeoTracker,
+    quotas: QuotaTracker,
+    dedupe: DedupeTracker,
+    baselines: BaselineTracker,
+    history: AttributeHistory,
+    operation_counters: OperationCounters,
+    #[cfg(feature = "telemetry")]
+    stats: StatsTracker,
+    delegation_grants: HashMap<String, DelegationGrant>,
+    break_glass_log: VecDeque<BreakGlassEntry>,
+    approvals: HashMap<String, ApprovalRequest>,
+    purposes: PurposeRegistry,
+    consents: ConsentRegistry,
+    granted: GrantedDecisions,
+    envoy: EnvoyConfig,
+}
+
+fn decode(bytes: &[u8]) -> Result<EngineSnapshot, PolicyEngineError> {
+    let snapshot: EngineSnapshot = ciborium::from_reader(bytes)
+        .map_err(|e| PolicyEngineError::parse(format!("Failed to decode engine snapshot: {}", e)))?;
+    if snapshot.magic != SNAPSHOT_MAGIC {
+        return Err(PolicyEngineError::parse("not an engine snapshot"));
+    }
+    if snapshot.format_version != SNAPSHOT_FORMAT_VERSION || snapshot.engine_version != ENGINE_VERSION {
+        return Err(PolicyEngineError::unsupported_format(format!(
+            "Snapshot from engine {} (format {}) cannot be loaded by engine {} (format {})",
+            snapshot.engine_version, snapshot.format_version, ENGINE_VERSION, SNAPSHOT_FORMAT_VERSION
+        ))
+        .with_details(json!({
+            "engine_version": snapshot.engine_version,
+            "format_version": snapshot.format_version,
+            "supported_engine_version": ENGINE_VERSION,
+            "supported_format_version": SNAPSHOT_FORMAT_VERSION,
+        })));
+    }
+    Ok(snapshot)
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Serializes the engine so another instance (a Web Worker, or this
+    // page after a reload) can start from it with `import_state` instead
+    // of downloading and compiling the policy set again
+    #[wasm_bindgen]
+    pub fn export_state(&self) -> Result<Vec<u8>, JsValue> {
+        let snapshot = EngineSnapshot {
+            magic: SNAPSHOT_MAGIC.to_string(),
+            format_version: SNAPSHOT_FORMAT_VERSION,
+            engine_version: ENGINE_VERSION.to_string(),
+            policies: self.policies.clone(),
+            tenants: self.tenants.clone(),
+            staged: self.staged.clone(),
+            experiments: self.experiments.clone(),
+            rule_libraries: self.rule_libraries.clone(),
+            templates: self.templates.clone(),
+            messages: self.messages.clone(),
+            debug_mode: self.debug_mode,
+            strict_mode: self.strict_mode,
+            root_algorithm: self.root_algorithm.clone(),
+            root_obligations: self.root_obligations.clone(),
+            declared_obligations: self.declared_obligations.clone(),
+            root_advice: self.root_advice.clone(),
+            risk_profile: self.risk.as_ref().map(|scorer| scorer.profile().clone()),
+            confidence_model: self.confidence.clone(),
+            redaction_policy: self.redactor.as_ref().map(|redactor| redactor.policy().clone()),
+            context_schema: self.context_schema.clone(),
+            limits: self.limits.clone(),
+            freshness: self.freshness.clone(),
+            calendar: self.calendar.clone(),
+            deployment: self.deployment.clone(),
+            derived: self.derived.clone(),
+            lattices: self.lattices.clone(),
+            break_glass_seconds: self.break_glass_seconds,
+            #[cfg(feature = "geo")]
+            geo: self.geo.clone(),
+            quotas: self.quotas.clone(),
+            dedupe: self.dedupe.clone(),
+            baselines: self.baselines.clone(),
+            history: self.history.clone(),
+            operation_counters: self.operation_counters.clone(),
+            #[cfg(feature = "telemetry")]
+            stats: self.stats.borrow().clone(),
+            delegation_grants: self.delegation_grants.clone(),
+            break_glass_log: self.break_glass_log.clone(),
+            approvals: self.approvals.clone(),
+            purposes: self.purposes.clone(),
+            consents: self.consents.clone(),
+            granted: self.granted.clone(),
+            envoy: self.envoy.clone(),
+        };
+
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&snapshot, &mut bytes)
+            .map_err(|e| PolicyEngineError::internal(format!("Failed to encode engine snapshot: {}", e)).logged())?;
+        Ok(bytes)
+    }
+
+    // Replaces the engine's state with a snapshot from `export_state`.
+    // Host bindings (handlers, keys, sync) are kept. On any error the
+    // engine is left unchanged.
+    #[wasm_bindgen]
+    pub fn import_state(&mut self, bytes: &[u8]) -> Result<(), JsValue> {
+        let mut snapshot = decode(bytes).map_err(|e| e.logged())?;
+
+        // Rule references are stored unresolved, as in bundles
+        let libraries = &snapshot.rule_libraries;
+        snapshot
+            .policies
+            .iter_mut()
+            .chain(snapshot.tenants.values_mut().flat_map(|tenant| tenant.own_policies_mut().iter_mut()))
+            .chain(snapshot.staged.iter_mut().flatten())
+            .chain(snapshot.experiments.policies_mut())
+            .try_for_each(|compiled| rule_library::link(libraries, compiled))
+            .map_err(|e| e.context("Failed to link snapshot policies").logged())?;
+        let redactor = snapshot
+            .redaction_policy
+            .map(Redactor::new)
+            .transpose()
+            .map_err(|e| e.context("Failed to restore the redaction policy").logged())?;
+
+        self.policies = snapshot.policies;
+        self.tenants = snapshot.tenants;
+        self.staged = snapshot.staged;
+        self.experiments = snapshot.experiments;
+        self.rule_libraries = snapshot.rule_libraries;
+        self.templates = snapshot.templates;
+        self.messages = snapshot.messages;
+        self.debug_mode = snapshot.debug_mode;
+        self.strict_mode = snapshot.strict_mode;
+        self.root_algorithm = snapshot.root_algorithm;
+        self.root_obligations = snapshot.root_obligations;
+        self.declared_obligations = snapshot.declared_obligations;
+        self.root_advice = snapshot.root_advice;
+        self.risk = snapshot.risk_profile.map(RiskScorer::new);
+        self.confidence = snapshot.confidence_model;
+        self.install_redactor(redactor);
+        self.context_schema = snapshot.context_schema;
+        self.limits = snapshot.limits;
+        self.freshness = snapshot.freshness;
+        self.calendar = snapshot.calendar;
+        self.deployment = snapshot.deployment;
+        self.derived = snapshot.derived;
+        self.lattices = snapshot.lattices;
+        self.break_glass_seconds = snapshot.break_glass_seconds;
+        #[cfg(feature = "geo")]
+        {
+            self.geo = snapshot.geo;
+        }
+        self.quotas = snapshot.quotas;
+        self.dedupe = snapshot.dedupe;
+        self.baselines = snapshot.baselines;
+        self.history = snapshot.history;
+        self.operation_counters = snapshot.operation_counters;
+        #[cfg(feature = "telemetry")]
+        {
+            *self.stats.borrow_mut() = snapshot.stats;
+        }
+        self.delegation_grants = snapshot.delegation_grants;
+        self.break_glass_log = snapshot.break_glass_log;
+        self.approvals = snapshot.approvals;
+        self.purposes = snapshot.purposes;
+        self.consents = snapshot.consents;
+        self.granted = snapshot.granted;
+        self.envoy = snapshot.envoy;
+
+        console_log!("Imported engine state with {} policies", self.policies.len());
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+use wasm_bindgen::prelude::*;
+use serde::Serialize;
+use serde_json::{json, Map, Value as JsonValue};
+use std::collections::BTreeSet;
+
+use crate::attributes::context_field;
+use crate::error::{to_json, PolicyEngineError};
+use crate::expr::{self, Environment, Expr, ExprError, Value};
+use crate::{CompiledPolicy, Policy, PolicyContext, PolicyEngine};
+
+// Attributes fixed by the partial context; everything else is unknown
+struct KnownAttributes<'a> {
+    engine: &'a PolicyEngine,
+    context: &'a PolicyContext,
+    fixed: &'a BTreeSet<String>,
+}
+
+impl Environment for KnownAttributes<'_> {
+    fn resolve(&self, path: &[String]) -> Option<Value> {
+        let segments: Vec<&str> = path.iter().map(String::as_str).collect();
+        let field = context_field(&segments)?;
+        if !self.fixed.contains(field) {
+            return None;
+        }
+        self.context.attribute(path)
+    }
+
+    fn call(&self, name: &str, _args: &[Value]) -> Result<Value, ExprError> {
+        Err(ExprError::new(format!("{}() is not evaluated during specialization", name)))
+    }
+
+    fn lattice_rank(&self, path: &[String], value: &str) -> Option<Result<usize, ExprError>> {
+        self.engine.lattices.rank(path, value)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct RemovedRule {
+    policy_id: String,
+    rule_id: String,
+}
+
+// What specialize returns. `policies` loads like any policy set; the
+// rest says what the fixed attributes decided away.
+#[derive(Debug, Serialize)]
+struct Specialization {
+    fixed: BTreeSet<String>,
+    policies: Vec<Policy>,
+    removed_policies: Vec<String>,
+    removed_rules: Vec<RemovedRule>,
+}
+
+fn is_false(expr: &Expr) -> bool {
+    matches!(expr, Expr::Literal(Value::Bool(false) | Value::Null))
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Partially evaluates the global policies against the top-level
+    // context fields in `partial_context_json` (e.g. one user's roles and
+    // device), like OPA's partial evaluation:
+    //
+    //   engine.specialize('{ "user_id": "u1", "user_roles": ["analyst"], "device_trust": "MANAGED" }')
+    //
+    // Those fields are folded into the targets and conditions, policies and
+    // rules that can no longer match are dropped, and the residual set is
+    // returned with library rules inlined and definitions expanded. Loaded
+    // into another engine it decides any request with the same fixed
+    // fields as this engine would, so it can be shipped to the edge for
+    // that subject. Function calls stay in the residual, since they read
+    // engine state (travel history, baselines, threat feeds) or the clock.
+    #[wasm_bindgen]
+    pub fn specialize(&self, partial_context_json: &str) -> Result<String, JsValue> {
+        let (fixed, context) = self.fixed_fields(partial_context_json)?;
+        let env = KnownAttributes { engine: self, context: &context, fixed: &fixed };
+        let mut specialization =
+            Specialization { fixed: fixed.clone(), policies: Vec::new(), removed_policies: Vec::new(), removed_rules: Vec::new() };
+        for compiled in &self.policies {
+            match residual_policy(compiled, &env, &mut specialization.removed_rules) {
+                Some(policy) => specialization.policies.push(policy),
+                None => specialization.removed_policies.push(compiled.policy.id.clone()),
+            }
+        }
+        if self.debug_mode {
+            console_log!(
+                "Specialized {} policies to {} for fields {:?}",
+                self.policies.len(),
+                specialization.policies.len(),
+                fixed
+            );
+        }
+        Ok(to_json(&specialization)?)
+    }
+}
+
+// The policy with its target and rule conditions partially evaluated, or
+// None when it can never apply
+fn residual_policy(compiled: &CompiledPolicy, env: &KnownAttributes, removed: &mut Vec<RemovedRule>) -> Option<Policy> {
+    if !env.engine.policy_in_effect(compiled) {
+        return None;
+    }
+    let target = expr::partial(&compiled.target, env);
+    if is_false(&target) {
+        return None;
+    }
+
+    let mut rules = Vec::new();
+    for (rule, condition) in compiled.rules().filter(|(rule, _)| env.engine.in_deployment(&rule.environments)) {
+        let condition = expr::partial(condition, env);
+        if is_false(&condition) {
+            removed.push(RemovedRule { policy_id: compiled.policy.id.clone(), rule_id: rule.id.clone() });
+            continue;
+        }
+        let mut rule = rule.clone();
+        rule.condition = condition.to_string();
+        rules.push(rule);
+    }
+
+    Some(Policy {
+        target: target.to_string(),
+        rules,
+        definitions: Default::default(),
+        rule_refs: Vec::new(),
+        ..compiled.policy.clone()
+    })
+}
+
+impl PolicyEngine {
+    // The top-level fields a partial context JSON fixes, and a validated
+    // context holding them
+    pub(crate) fn fixed_fields(&self, partial_context_json: &str) -> Result<(BTreeSet<String>, PolicyContext), JsValue> {
+        let partial: JsonValue = serde_json::from_str(partial_context_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse partial context: {}", e)).logged())?;
+        let Some(fields) = partial.as_object() else {
+            return Err(PolicyEngineError::validation("Partial context must be a JSON object").logged().into());
+        };
+        let fixed: BTreeSet<String> = fields.keys().cloned().collect();
+        if let Some(field) = fixed.iter().find(|field| context_field(&[field.as_str()]).is_none()) {
+            return Err(PolicyEngineError::validation(format!("'{}' is not a context attribute policies can read", field))
+                .with_details(json!({ "field": field }))
+                .logged()
+                .into());
+        }
+        let context = self.partial_context(fields).map_err(|e| e.context("Invalid partial context").logged())?;
+        Ok((fixed, context))
+    }
+
+    // The expression with the fixed fields folded in
+    pub(crate) fn residual(&self, expr: &Expr, context: &PolicyContext, fixed: &BTreeSet<String>) -> Expr {
+        expr::partial(expr, &KnownAttributes { engine: self, context, fixed })
+    }
+
+    // A validated context holding `fields`; the fields left out take
+    // defaults that KnownAttributes never reads
+    pub(crate) fn partial_context(&self, fields: &Map<String, JsonValue>) -> Result<PolicyContext, PolicyEngineError> {
+        let mut full = serde_json::to_value(PolicyContext::default())
+            .map_err(|e| PolicyEngineError::internal(format!("Failed to serialize default context: {}", e)))?;
+        for (field, value) in fields {
+            full[field] = value.clone();
+        }
+        self.check_context_value(full)
+    }
+
+    // Whether the policy can never apply to a context with these fixed
+    // fields: it is disabled, for another environment, or its target folds
+    // to false
+    pub(crate) fn ruled_out(&self, compiled: &CompiledPolicy, context: &PolicyContext, fixed: &BTreeSet<String>) -> bool {
+        let env = KnownAttributes { engine: self, context, fixed };
+        !self.policy_in_effect(compiled) || is_false(&expr::partial(&compiled.target, &env))
+    }
+}
@@ -0,0 +1,137 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{to_json, PolicyEngineError};
+use crate::{guard, CompiledPolicy, Policy, PolicyEngine, PolicyResult, PolicySelection};
+
+// One field that differs between the active and staged results
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResultDifference {
+    pub field: String,
+    pub active: String,
+    pub staged: String,
+}
+
+// Output of `compare_staged`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StagedComparison {
+    pub active: PolicyResult,
+    pub staged: PolicyResult,
+    pub changed: bool,
+    pub differences: Vec<ResultDifference>,
+}
+
+impl StagedComparison {
+    fn new(active: PolicyResult, staged: PolicyResult) -> StagedComparison {
+        let fields = [
+            ("decision", active.decision.as_str(), staged.decision.as_str()),
+            ("obligations", &*active.obligations, &*staged.obligations),
+            ("advice", &*active.advice, &*staged.advice),
+            ("challenge", active.challenge.as_str(), staged.challenge.as_str()),
+            ("approval", active.approval.as_str(), staged.approval.as_str()),
+        ];
+        let differences: Vec<ResultDifference> = fields
+            .iter()
+            .filter(|(_, a, s)| a != s)
+            .map(|(field, a, s)| ResultDifference {
+                field: field.to_string(),
+                active: a.to_string(),
+                staged: s.to_string(),
+            })
+            .collect();
+
+        StagedComparison {
+            changed: !differences.is_empty(),
+            active,
+            staged,
+            differences,
+        }
+    }
+}
+
+// Candidate policies can be staged next to the active set and shadow-tested
+// against real contexts. Staged evaluations are side-effect free: they do
+// not record travel history, consume quotas or dispatch obligations.
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Replaces the staged set. It passes the same gate as
+    // `load_policies_atomic` (so promotion cannot fail) or nothing is staged.
+    #[wasm_bindgen]
+    pub fn stage_policies(&mut self, policies_json: &str) -> Result<(), JsValue> {
+        let policies: Vec<Policy> = self.parse_policies(policies_json, "staged policies").map_err(|e| e.logged())?;
+
+        let compiled = self.prepare_policy_set(policies)
+            .map_err(|e| e.context("Rejected staged policy set").logged())?;
+
+        if self.debug_mode {
+            console_log!("Staged {} policies", compiled.len());
+        }
+        self.staged = Some(compiled);
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn evaluate_staged(&self, context_json: &str) -> Result<PolicyResult, JsValue> {
+        self.require_staged()?;
+        let mut context = self.parse_context(context_json)?;
+        self.enrich_context(&mut context, None);
+        Ok(guard::guarded(|| self.evaluate_context(&context, PolicySelection::Staged)))
+    }
+
+    // Evaluates the context against both sets and returns a JSON
+    // StagedComparison with both results and the fields that differ
+    #[wasm_bindgen]
+    pub fn compare_staged(&self, context_json: &str) -> Result<String, JsValue> {
+        self.require_staged()?;
+        let mut context = self.parse_context(context_json)?;
+        self.enrich_context(&mut context, None);
+
+        // Both sides untracked: a comparison is not a request against the
+        // active set
+        let active = self.evaluate_context(&context, PolicySelection::Explicit(&self.policies))?;
+        let staged = self.evaluate_context(&context, PolicySelection::Staged)?;
+        let comparison = StagedComparison::new(active, staged);
+
+        if self.debug_mode && comparison.changed {
+            console_log!(
+                "Staged decision differs: {} -> {}",
+                comparison.active.decision,
+                comparison.staged.decision
+            );
+        }
+        Ok(to_json(&comparison)?)
+    }
+
+    // Makes the staged set active, returning the number of policies promoted
+    #[wasm_bindgen]
+    pub fn promote_staged(&mut self) -> Result<usize, JsValue> {
+        self.require_staged()?;
+        let staged = self.staged.take().unwrap_or_default();
+        console_log!("Promoted {} staged policies", staged.len());
+        self.policies = staged;
+        Ok(self.policies.len())
+    }
+
+    #[wasm_bindgen]
+    pub fn discard_staged(&mut self) -> bool {
+        self.staged.take().is_some()
+    }
+
+    #[wasm_bindgen]
+    pub fn get_staged_policy_count(&self) -> usize {
+        self.staged.as_ref().map(|s| s.len()).unwrap_or(0)
+    }
+}
+
+impl PolicyEngine {
+    pub(crate) fn staged_policies(&self) -> &[CompiledPolicy] {
+        self.staged.as_deref().unwrap_or_default()
+    }
+
+    fn require_staged(&self) -> Result<(), PolicyEngineError> {
+        if self.staged.is_none() {
+            return Err(PolicyEngineError::invalid_state("No policies are staged"));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+#[cfg(feature = "telemetry")]
+use wasm_bindgen::prelude::*;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "telemetry")]
+use std::collections::HashMap;
+
+#[cfg(feature = "telemetry")]
+use crate::clock;
+#[cfg(feature = "telemetry")]
+use crate::CompiledPolicy;
+use crate::{PolicyEngine, PolicyResult};
+
+// High-resolution milliseconds for timing; only differences are used,
+// so this reads the real clock rather than clock::now()
+pub fn now_ms() -> f64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        match web_sys::window().and_then(|window| window.performance()) {
+            Some(performance) => performance.now(),
+            None => js_sys::Date::now(),
+        }
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Utc::now().timestamp_micros() as f64 / 1000.0
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RuleStats {
+    pub rule_id: String,
+    pub evaluations: u64,
+    // Times the condition held and the rule produced its effect
+    pub hits: u64,
+    pub last_matched: Option<DateTime<Utc>>,
+    pub total_time_ms: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PolicyStats {
+    pub policy_id: String,
+    // Target checks, and how many of them applied
+    pub evaluations: u64,
+    pub applicable: u64,
+    // Times a rule of this policy decided the policy's result
+    pub hits: u64,
+    pub last_matched: Option<DateTime<Utc>>,
+    // Target and rule evaluation time
+    pub total_time_ms: f64,
+    pub rules: Vec<RuleStats>,
+}
+
+#[cfg(feature = "telemetry")]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct PolicyCounters {
+    stats: PolicyStats,
+    rules: HashMap<String, RuleStats>,
+}
+
+// Always-on counters keyed by policy and rule ID; they survive policy
+// reloads so a replaced policy keeps its history. Compiled out without
+// the telemetry feature.
+#[cfg(feature = "telemetry")]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatsTracker {
+    policies: HashMap<String, PolicyCounters>,
+}
+
+#[cfg(feature = "telemetry")]
+impl StatsTracker {
+    fn policy(&mut self, policy_id: &str) -> &mut PolicyCounters {
+        self.policies.entry(policy_id.to_string()).or_default()
+    }
+
+    pub fn record_target(&mut self, policy_id: &str, applicable: bool, elapsed_ms: f64) {
+        let stats = &mut self.policy(policy_id).stats;
+        stats.evaluations += 1;
+        if applicable {
+            stats.applicable += 1;
+        }
+        stats.total_time_ms += elapsed_ms;
+    }
+
+    pub fn record_rule(&mut self, policy_id: &str, rule_id: &str, result: &PolicyResult, elapsed_ms: f64) {
+        let policy = self.policy(policy_id);
+        policy.stats.total_time_ms += elapsed_ms;
+        let rule = policy.rules.entry(rule_id.to_string()).or_default();
+        rule.evaluations += 1;
+        rule.total_time_ms += elapsed_ms;
+        if result.decision.is_decisive() {
+            rule.hits += 1;
+            rule.last_matched = Some(clock::now());
+        }
+    }
+
+    pub fn record_policy_result(&mut self, policy_id: &str, result: &PolicyResult) {
+        if result.rule_id.is_some() {
+            let stats = &mut self.policy(policy_id).stats;
+            stats.hits += 1;
+            stats.last_matched = Some(clock::now());
+        }
+    }
+}
+
+#[cfg(feature = "telemetry")]
+#[wasm_bindgen]
+impl PolicyEngine {
+    // JSON array of PolicyStats for every loaded policy (global, then
+    // tenants by ID), rules in evaluation order. Policies and rules that
+    // never ran report zeros, which is what identifies stale rules.
+    #[wasm_bindgen]
+    pub fn get_policy_stats(&self) -> String {
+        let tracker = self.stats.borrow();
+        let mut policies: Vec<&CompiledPolicy> = self.policies.iter().collect();
+        let mut tenant_ids: Vec<&String> = self.tenants.keys().collect();
+        tenant_ids.sort();
+        for id in tenant_ids {
+            if let Some(tenant) = self.tenants.get(id) {
+                policies.extend(tenant.own_policies());
+            }
+        }
+
+        let report: Vec<PolicyStats> = policies
+            .into_iter()
+            .map(|compiled| {
+                let counters = tracker.policies.get(&compiled.policy.id);
+                let mut stats = counters.map(|c| c.stats.clone()).unwrap_or_default();
+                stats.policy_id = compiled.policy.id.clone();
+                stats.rules = compiled
+                    .rules()
+                    .map(|(rule, _)| {
+                        let mut rule_stats = counters.and_then(|c| c.rules.get(&rule.id)).cloned().unwrap_or_default();
+                        rule_stats.rule_id = rule.id.clone();
+                        rule_stats
+                    })
+                    .collect();
+                stats
+            })
+            .collect();
+        serde_json::to_string(&report).unwrap_or_default()
+    }
+
+    #[wasm_bindgen]
+    pub fn reset_policy_stats(&mut self) {
+        self.stats.get_mut().policies.clear();
+    }
+}
+
+// Counter hooks for the evaluation loop, no-ops without telemetry
+#[cfg(feature = "telemetry")]
+impl PolicyEngine {
+    pub(crate) fn record_target_stats(&self, policy_id: &str, applicable: bool, elapsed_ms: f64) {
+        self.stats.borrow_mut().record_target(policy_id, applicable, elapsed_ms);
+    }
+
+    pub(crate) fn record_rule_stats(&self, policy_id: &str, rule_id: &str, result: &PolicyResult, elapsed_ms: f64) {
+        self.stats.borrow_mut().record_rule(policy_id, rule_id, result, elapsed_ms);
+    }
+
+    pub(crate) fn record_policy_stats(&self, policy_id: &str, result: &PolicyResult) {
+        self.stats.borrow_mut().record_policy_result(policy_id, result);
+    }
+}
+
+#[cfg(not(feature = "telemetry"))]
+impl PolicyEngine {
+    pub(crate) fn record_target_stats(&self, _policy_id: &str, _applicable: bool, _elapsed_ms: f64) {}
+
+    pub(crate) fn record_rule_stats(&self, _policy_id: &str, _rule_id: &str, _result: &PolicyResult, _elapsed_ms: f64) {}
+
+    pub(crate) fn record_policy_stats(&self, _policy_id: &str, _result: &PolicyResult) {}
+}
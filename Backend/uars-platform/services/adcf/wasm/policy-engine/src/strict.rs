@@ -0,0 +1,159 @@
+use wasm_bindgen::prelude::*;
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use std::collections::BTreeSet;
+
+use crate::attributes::context_field;
+use crate::bag::AttributeBag;
+use crate::error::{to_json, PolicyEngineError};
+use crate::expr::{Expr, Value};
+use crate::format;
+use crate::obligations::ObligationCall;
+use crate::{CompiledPolicy, PolicyEngine, PolicyRule};
+
+// Obligations the engine itself acts on or emits
+const BUILTIN_OBLIGATIONS: &[&str] = &[
+    crate::quota::RATE_LIMIT_OBLIGATION,
+    crate::capsule::TTL_OBLIGATION,
+    crate::capsule::SELF_DESTRUCT_OBLIGATION,
+    crate::purpose::PURPOSE_LIMIT_OBLIGATION,
+    crate::masking::MASK_FIELDS_OBLIGATION,
+    crate::masking::REDACT_PATTERN_OBLIGATION,
+    crate::masking::DOWNSAMPLE_OBLIGATION,
+    crate::break_glass::BREAK_GLASS_ALERT_OBLIGATION,
+    crate::break_glass::RECORD_JUSTIFICATION_OBLIGATION,
+    crate::break_glass::BREAK_GLASS_UNTIL_OBLIGATION,
+    crate::session::MAX_SESSION_AGE_OBLIGATION,
+    crate::session::REAUTH_OBLIGATION,
+    crate::admission::PATCH_OBLIGATION,
+    #[cfg(feature = "crypto")]
+    crate::key_release::RELEASE_KEY_OBLIGATION,
+    #[cfg(feature = "crypto")]
+    crate::watermark::WATERMARK_OBLIGATION,
+];
+
+// Functions whose first argument is an attribute path: has("mfa.verified")
+const PATH_FUNCTIONS: &[&str] = &["has", "avg_over", "min_over", "max_over", "sum_over", "count_over"];
+
+// Whether a path names something an evaluation can resolve: a context
+// field (or a key of one of its free-form maps), a category of an
+// attribute bag, the scorer's output or a derived attribute
+fn is_known_attribute(segments: &[&str]) -> bool {
+    context_field(segments).is_some()
+        || AttributeBag::default().is_category_path(segments)
+        || segments.first() == Some(&"model")
+        || segments.first() == Some(&crate::derived::DERIVED_ROOT)
+}
+
+fn attribute_paths(expression: &Expr, found: &mut Vec<String>) {
+    match expression {
+        Expr::Literal(_) => {}
+        Expr::Attribute(path) => found.push(path.join(".")),
+        Expr::Call(name, args) => {
+            if let (true, Some(Expr::Literal(Value::String(path)))) = (PATH_FUNCTIONS.contains(&name.as_str()), args.first()) {
+                found.push(path.clone());
+            }
+            args.iter().for_each(|arg| attribute_paths(arg, found));
+        }
+        Expr::List(items) => items.iter().for_each(|item| attribute_paths(item, found)),
+        Expr::Unary(_, operand) => attribute_paths(operand, found),
+        Expr::Binary(_, left, right) => {
+            attribute_paths(left, found);
+            attribute_paths(right, found);
+        }
+    }
+}
+
+// Attribute paths a (compiled, definitions inlined) expression reads that
+// no request can supply, such as `mfa.verifed`
+pub fn unknown_attributes(expression: &Expr, rule_id: Option<&str>, unknown: &mut Vec<serde_json::Value>) {
+    let mut paths = Vec::new();
+    attribute_paths(expression, &mut paths);
+    paths.sort();
+    paths.dedup();
+    for path in paths {
+        let segments: Vec<&str> = path.split('.').map(str::trim).collect();
+        if !is_known_attribute(&segments) {
+            unknown.push(json!({ "field": "attribute", "value": path, "rule_id": rule_id }));
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Obligation ids (a JSON array) that PEPs fulfil without a handler
+    // registered here. In strict mode a policy may only carry these, the
+    // engine's own obligations and those with a registered handler.
+    // Replaces the earlier declaration.
+    #[wasm_bindgen]
+    pub fn declare_obligations(&mut self, obligation_ids_json: &str) -> Result<(), JsValue> {
+        let ids: BTreeSet<String> = serde_json::from_str(obligation_ids_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse obligation ids: {}", e)).logged())?;
+        if ids.iter().any(|id| id.trim().is_empty()) {
+            return Err(PolicyEngineError::validation("Obligation ids must not be empty").logged().into());
+        }
+        self.declared_obligations = ids;
+        Ok(())
+    }
+
+    // Every obligation id strict mode accepts, sorted
+    #[wasm_bindgen]
+    pub fn get_known_obligations(&self) -> Result<String, JsValue> {
+        let known: BTreeSet<&str> = BUILTIN_OBLIGATIONS
+            .iter()
+            .copied()
+            .chain(self.obligation_handlers.keys().map(String::as_str))
+            .chain(self.declared_obligations.iter().map(String::as_str))
+            .collect();
+        Ok(to_json(&known)?)
+    }
+}
+
+impl PolicyEngine {
+    // format::parse_policy_json, plus strict mode's check that the JSON
+    // has no fields the policy format lacks (which would otherwise be
+    // dropped without a word)
+    pub(crate) fn parse_policies<T: DeserializeOwned>(&self, json: &str, what: &str) -> Result<T, PolicyEngineError> {
+        let parsed = format::parse_policy_json(json, what)?;
+        if self.strict_mode {
+            let unknown = format::unknown_policy_fields(json);
+            if !unknown.is_empty() {
+                return Err(PolicyEngineError::validation(format!("Failed to parse {}: {} unknown field(s)", what, unknown.len()))
+                    .with_details(json!({ "unknown_fields": unknown })));
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn is_known_obligation(&self, id: &str) -> bool {
+        BUILTIN_OBLIGATIONS.contains(&id) || self.obligation_handlers.contains_key(id) || self.declared_obligations.contains(id)
+    }
+
+    // Obligations of a policy (its own and its rules') that strict mode
+    // does not know. Lenient engines accept any obligation, so this only
+    // reports under strict mode.
+    pub(crate) fn unknown_obligations<'a>(
+        &self,
+        policy_obligations: impl Iterator<Item = &'a str>,
+        rules: impl Iterator<Item = &'a PolicyRule>,
+        unknown: &mut Vec<serde_json::Value>,
+    ) {
+        if !self.strict_mode {
+            return;
+        }
+        let specs = policy_obligations
+            .map(|spec| (spec, None))
+            .chain(rules.flat_map(|rule| rule.obligations.iter().map(move |spec| (spec.as_str(), Some(rule.id.as_str())))));
+        for (spec, rule_id) in specs {
+            let id = ObligationCall::parse(spec).id;
+            if !self.is_known_obligation(&id) {
+                unknown.push(json!({ "field": "obligation", "value": id, "rule_id": rule_id }));
+            }
+        }
+    }
+
+    pub(crate) fn unknown_policy_obligations(&self, compiled: &CompiledPolicy, unknown: &mut Vec<serde_json::Value>) {
+        let policy_obligations = compiled.policy.obligations.iter().map(|item| item.obligation.as_str());
+        self.unknown_obligations(policy_obligations, compiled.rules().map(|(rule, _)| rule), unknown);
+    }
+}
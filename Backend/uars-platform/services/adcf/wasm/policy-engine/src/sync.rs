@@ -0,0 +1,338 @@
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::clock;
+use crate::digest::policy_set_hash;
+use crate::error::{to_json, PolicyEngineError};
+use crate::format;
+use crate::logging::LogLevel;
+use crate::{Policy, PolicyEngine};
+
+// Global `fetch` and timers, so sync works in windows and workers alike
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_name = fetch)]
+    fn fetch_request(request: &web_sys::Request) -> js_sys::Promise;
+
+    #[wasm_bindgen(js_name = setInterval)]
+    fn set_interval(handler: &js_sys::Function, timeout: u32) -> JsValue;
+
+    #[wasm_bindgen(js_name = clearInterval)]
+    fn clear_interval(handle: &JsValue);
+}
+
+// Passed (as JSON) to the success callback once a new policy set is live
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncApplied {
+    pub source: String,
+    pub policy_count: usize,
+    pub etag: Option<String>,
+    pub policy_set_hash: String,
+}
+
+// Output of `get_sync_status`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncStatus {
+    pub url: Option<String>,
+    pub interval_ms: u32,
+    pub etag_support: bool,
+    pub etag: Option<String>,
+    pub update_pending: bool,
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_failure: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+// A downloaded set waiting for the engine to swap it in
+struct PendingUpdate {
+    policies: Vec<Policy>,
+    etag: Option<String>,
+    source: &'static str,
+}
+
+// State shared between the engine and in-flight fetches
+#[derive(Default)]
+struct SyncShared {
+    url: Option<String>,
+    etag_support: bool,
+    etag: Option<String>,
+    in_flight: bool,
+    pending: Option<PendingUpdate>,
+    on_success: Option<js_sys::Function>,
+    on_failure: Option<js_sys::Function>,
+    last_success: Option<DateTime<Utc>>,
+    last_failure: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+}
+
+// Records a failure and notifies the host. The callback runs after the
+// state is released so it may query the sync status.
+fn report_failure(shared: &RefCell<SyncShared>, error: &PolicyEngineError) {
+    log_at!(LogLevel::Warn, "Policy sync failed: {}", error.message());
+    let callback = {
+        let mut state = shared.borrow_mut();
+        state.last_failure = Some(clock::now());
+        state.last_error = Some(error.message().to_string());
+        state.on_failure.clone()
+    };
+    if let Some(callback) = callback {
+        if let Err(e) = callback.call1(&JsValue::NULL, &JsValue::from_str(&error.to_json())) {
+            log_at!(LogLevel::Warn, "Policy sync failure callback threw: {:?}", e);
+        }
+    }
+}
+
+// Policy distribution endpoint polling. Fetches run in the background;
+// a downloaded set is swapped in atomically before the next evaluation
+// (or at once by `apply_pending_sync`), so an evaluation always sees one
+// complete policy set.
+#[derive(Default)]
+pub struct PolicySync {
+    shared: Rc<RefCell<SyncShared>>,
+    interval_ms: u32,
+    timer: Option<(JsValue, Closure<dyn FnMut()>)>,
+}
+
+impl PolicySync {
+    fn stop(&mut self) {
+        if let Some((handle, _closure)) = self.timer.take() {
+            clear_interval(&handle);
+        }
+    }
+}
+
+impl Drop for PolicySync {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn poll(shared: Rc<RefCell<SyncShared>>) {
+    let (url, etag) = {
+        let mut state = shared.borrow_mut();
+        let url = match (&state.url, state.in_flight) {
+            (Some(url), false) => url.clone(),
+            _ => return,
+        };
+        state.in_flight = true;
+        (url, state.etag.clone().filter(|_| state.etag_support))
+    };
+
+    spawn_local(async move {
+        let outcome = fetch_policies(&url, etag.as_deref()).await;
+        shared.borrow_mut().in_flight = false;
+        match outcome {
+            Ok(Some(pending)) => shared.borrow_mut().pending = Some(pending),
+            // 304: the endpoint still serves the set we have
+            Ok(None) => shared.borrow_mut().last_success = Some(clock::now()),
+            Err(error) => report_failure(&shared, &error),
+        }
+    });
+}
+
+async fn fetch_policies(url: &str, etag: Option<&str>) -> Result<Option<PendingUpdate>, PolicyEngineError> {
+    let network = |e: JsValue| {
+        PolicyEngineError::evaluation(format!("Policy sync request failed: {:?}", e)).with_details(json!({ "url": url }))
+    };
+
+    let init = web_sys::RequestInit::new();
+    init.set_method("GET");
+    let request = web_sys::Request::new_with_str_and_init(url, &init).map_err(network)?;
+    request.headers().set("Accept", "application/json").map_err(network)?;
+    if let Some(etag) = etag {
+        request.headers().set("If-None-Match", etag).map_err(network)?;
+    }
+
+    let response: web_sys::Response = JsFuture::from(fetch_request(&request))
+        .await
+        .and_then(|response| response.dyn_into())
+        .map_err(network)?;
+    if response.status() == 304 {
+        return Ok(None);
+    }
+    if !response.ok() {
+        return Err(PolicyEngineError::evaluation(format!("Policy sync got HTTP {}", response.status()))
+            .with_details(json!({ "url": url, "status": response.status() })));
+    }
+
+    let etag = response.headers().get("ETag").ok().flatten();
+    let body = JsFuture::from(response.text().map_err(network)?).await.map_err(network)?;
+    let body = body.as_string().unwrap_or_default();
+    let policies = format::parse_policy_json::<Vec<Policy>>(&body, "synced policies")?;
+    Ok(Some(PendingUpdate { policies, etag, source: "poll" }))
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Polls `url` for a JSON policy array every `interval_ms` (0 disables
+    // polling; updates then arrive via `sync_now` or
+    // `push_policy_update`). With `etag_support` requests carry
+    // If-None-Match and a 304 keeps the current set. Replaces any earlier
+    // sync configuration and fetches once right away.
+    #[wasm_bindgen]
+    pub fn configure_sync(&mut self, url: &str, interval_ms: u32, etag_support: bool) -> Result<(), JsValue> {
+        if url.trim().is_empty() {
+            return Err(PolicyEngineError::validation("Sync URL must not be empty").logged().into());
+        }
+        let sync = self.sync.get_or_insert_with(PolicySync::default);
+        sync.stop();
+        {
+            let mut state = sync.shared.borrow_mut();
+            state.url = Some(url.to_string());
+            state.etag_support = etag_support;
+            state.etag = None;
+        }
+        sync.interval_ms = interval_ms;
+
+        if interval_ms > 0 {
+            let shared = sync.shared.clone();
+            let closure = Closure::<dyn FnMut()>::new(move || poll(shared.clone()));
+            let handle = set_interval(closure.as_ref().unchecked_ref(), interval_ms);
+            sync.timer = Some((handle, closure));
+        }
+        poll(sync.shared.clone());
+        Ok(())
+    }
+
+    // Callbacks receive JSON: SyncApplied on success, an error object
+    // `{ code, message, details }` on failure. Either may be omitted.
+    #[wasm_bindgen]
+    pub fn set_sync_callbacks(&mut self, on_success: Option<js_sys::Function>, on_failure: Option<js_sys::Function>) {
+        let sync = self.sync.get_or_insert_with(PolicySync::default);
+        let mut state = sync.shared.borrow_mut();
+        state.on_success = on_success;
+        state.on_failure = on_failure;
+    }
+
+    // Fetches now instead of waiting for the next interval
+    #[wasm_bindgen]
+    pub fn sync_now(&self) -> Result<(), JsValue> {
+        match &self.sync {
+            Some(sync) if sync.shared.borrow().url.is_some() => {
+                poll(sync.shared.clone());
+                Ok(())
+            }
+            _ => Err(PolicyEngineError::invalid_state("Policy sync is not configured").logged().into()),
+        }
+    }
+
+    // Swaps in a policy set pushed by the host (e.g. from a WebSocket or
+    // server-sent event) through the same path as a polled update.
+    // Returns the number of policies now loaded.
+    #[wasm_bindgen]
+    pub fn push_policy_update(&mut self, policies_json: &str, etag: Option<String>) -> Result<usize, JsValue> {
+        let parsed = self.parse_policies::<Vec<Policy>>(policies_json, "pushed policies");
+        let sync = self.sync.get_or_insert_with(PolicySync::default);
+        let policies = match parsed {
+            Ok(policies) => policies,
+            Err(error) => {
+                report_failure(&sync.shared, &error);
+                return Err(error.into());
+            }
+        };
+        sync.shared.borrow_mut().pending = Some(PendingUpdate { policies, etag, source: "push" });
+        self.swap_pending_sync()?;
+        Ok(self.policies.len())
+    }
+
+    // Swaps in a downloaded set now, if one is waiting. Returns whether
+    // the policy set changed.
+    #[wasm_bindgen]
+    pub fn apply_pending_sync(&mut self) -> Result<bool, JsValue> {
+        Ok(self.swap_pending_sync()?)
+    }
+
+    // Stops polling; the current policy set stays loaded
+    #[wasm_bindgen]
+    pub fn stop_sync(&mut self) {
+        self.sync = None;
+    }
+
+    // JSON SyncStatus
+    #[wasm_bindgen]
+    pub fn get_sync_status(&self) -> Result<String, JsValue> {
+        let status = match &self.sync {
+            Some(sync) => {
+                let state = sync.shared.borrow();
+                SyncStatus {
+                    url: state.url.clone(),
+                    interval_ms: sync.interval_ms,
+                    etag_support: state.etag_support,
+                    etag: state.etag.clone(),
+                    update_pending: state.pending.is_some(),
+                    last_success: state.last_success,
+                    last_failure: state.last_failure,
+                    last_error: state.last_error.clone(),
+                }
+            }
+            None => SyncStatus {
+                url: None,
+                interval_ms: 0,
+                etag_support: false,
+                etag: None,
+                update_pending: false,
+                last_success: None,
+                last_failure: None,
+                last_error: None,
+            },
+        };
+        Ok(to_json(&status)?)
+    }
+}
+
+impl PolicyEngine {
+    // Also run before every evaluation; failures reach the failure
+    // callback and leave the current set in place
+    pub(crate) fn swap_pending_sync(&mut self) -> Result<bool, PolicyEngineError> {
+        let shared = match &self.sync {
+            Some(sync) => sync.shared.clone(),
+            None => return Ok(false),
+        };
+        let pending = match shared.borrow_mut().pending.take() {
+            Some(pending) => pending,
+            None => return Ok(false),
+        };
+
+        // Nothing changes unless the whole set validates
+        let compiled = match self.prepare_policy_set(pending.policies) {
+            Ok(compiled) => compiled,
+            Err(error) => {
+                let error = error.context("Failed to compile synced policy");
+                report_failure(&shared, &error);
+                return Err(error);
+            }
+        };
+        self.policies = compiled;
+
+        let applied = SyncApplied {
+            source: pending.source.to_string(),
+            policy_count: self.policies.len(),
+            etag: pending.etag.clone(),
+            policy_set_hash: policy_set_hash(&self.policies),
+        };
+        console_log!("Synced {} policies ({})", applied.policy_count, applied.source);
+
+        let callback = {
+            let mut state = shared.borrow_mut();
+            if pending.etag.is_some() {
+                state.etag = pending.etag;
+            }
+            state.last_success = Some(clock::now());
+            state.last_error = None;
+            state.on_success.clone()
+        };
+        if let Some(callback) = callback {
+            let payload = to_json(&applied)?;
+            if let Err(e) = callback.call1(&JsValue::NULL, &JsValue::from_str(&payload)) {
+                log_at!(LogLevel::Warn, "Policy sync success callback threw: {:?}", e);
+            }
+        }
+        Ok(true)
+    }
+}
@@ -0,0 +1,290 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeSet, HashMap};
+
+use crate::error::{to_json, PolicyEngineError};
+use crate::expr;
+use crate::{CompiledPolicy, Policy, PolicyEngine};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateParameter {
+    pub name: String,
+    // "string", "number", "bool" or "list"; unchecked when absent
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<Value>,
+    #[serde(default)]
+    pub description: String,
+}
+
+// A policy with `{{name}}` placeholders. In `target` and rule conditions
+// a placeholder becomes a literal of the parameter's value (strings are
+// quoted and escaped, so values cannot inject expression syntax). In
+// other fields a string that is exactly one placeholder takes the raw
+// JSON value, and embedded placeholders are replaced by its text.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyTemplate {
+    pub id: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub parameters: Vec<TemplateParameter>,
+    pub policy: Value,
+}
+
+impl PolicyTemplate {
+    fn check(&self) -> Result<(), PolicyEngineError> {
+        let mut used = BTreeSet::new();
+        collect_placeholders(&self.policy, &mut used)?;
+        for name in used {
+            if !self.parameters.iter().any(|p| p.name == name) {
+                return Err(self.error(&name, format!("Template '{}' uses undeclared parameter '{}'", self.id, name)));
+            }
+        }
+        Ok(())
+    }
+
+    fn error(&self, parameter: &str, message: String) -> PolicyEngineError {
+        PolicyEngineError::validation(message).with_details(json!({ "template_id": self.id, "parameter": parameter }))
+    }
+
+    // Declared parameters resolved from `params` or their defaults
+    fn bind(&self, params: &Map<String, Value>) -> Result<HashMap<String, Value>, PolicyEngineError> {
+        if let Some(unknown) = params.keys().find(|key| !self.parameters.iter().any(|p| &p.name == *key)) {
+            return Err(self.error(unknown, format!("Template '{}' has no parameter '{}'", self.id, unknown)));
+        }
+
+        let mut bound = HashMap::new();
+        for parameter in &self.parameters {
+            let value = match params.get(&parameter.name).or(parameter.default.as_ref()) {
+                Some(value) => value.clone(),
+                None => {
+                    return Err(self.error(
+                        &parameter.name,
+                        format!("Template '{}' requires parameter '{}'", self.id, parameter.name),
+                    ));
+                }
+            };
+            if let Some(kind) = &parameter.kind {
+                if !kind_matches(kind, &value) {
+                    return Err(self.error(
+                        &parameter.name,
+                        format!("Parameter '{}' must be a {}", parameter.name, kind),
+                    ));
+                }
+            }
+            bound.insert(parameter.name.clone(), value);
+        }
+        Ok(bound)
+    }
+
+    pub fn instantiate(&self, params: &Map<String, Value>) -> Result<Policy, PolicyEngineError> {
+        let bound = self.bind(params)?;
+        let mut policy = self.policy.clone();
+        substitute_value(&mut policy, &bound, false)?;
+        serde_json::from_value(policy).map_err(|e| {
+            PolicyEngineError::validation(format!("Template '{}' produced an invalid policy: {}", self.id, e))
+                .with_details(json!({ "template_id": self.id }))
+        })
+    }
+}
+
+fn kind_matches(kind: &str, value: &Value) -> bool {
+    match kind {
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "bool" => value.is_boolean(),
+        "list" => value.is_array(),
+        _ => true,
+    }
+}
+
+// Calls `on_placeholder` with each `{{name}}` in `text` and splices in
+// the returned replacement
+fn replace_placeholders<F>(text: &str, mut on_placeholder: F) -> Result<String, PolicyEngineError>
+where
+    F: FnMut(&str) -> Result<String, PolicyEngineError>,
+{
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let end = rest[start..].find("}}").ok_or_else(|| {
+            PolicyEngineError::validation(format!("Unterminated placeholder in '{}'", text))
+        })?;
+        output.push_str(&rest[..start]);
+        output.push_str(&on_placeholder(rest[start + 2..start + end].trim())?);
+        rest = &rest[start + end + 2..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+fn collect_placeholders(value: &Value, names: &mut BTreeSet<String>) -> Result<(), PolicyEngineError> {
+    match value {
+        Value::String(text) => {
+            replace_placeholders(text, |name| {
+                names.insert(name.to_string());
+                Ok(String::new())
+            })?;
+        }
+        Value::Array(items) => {
+            for item in items {
+                collect_placeholders(item, names)?;
+            }
+        }
+        Value::Object(map) => {
+            for child in map.values() {
+                collect_placeholders(child, names)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn whole_placeholder(text: &str) -> Option<&str> {
+    let inner = text.strip_prefix("{{")?.strip_suffix("}}")?;
+    (!inner.contains("{{")).then(|| inner.trim())
+}
+
+fn bound<'a>(params: &'a HashMap<String, Value>, name: &str) -> Result<&'a Value, PolicyEngineError> {
+    params
+        .get(name)
+        .ok_or_else(|| PolicyEngineError::validation(format!("Unbound template parameter '{}'", name)))
+}
+
+fn substitute_value(value: &mut Value, params: &HashMap<String, Value>, expression: bool) -> Result<(), PolicyEngineError> {
+    match value {
+        Value::String(text) if expression => {
+            *text = replace_placeholders(text, |name| Ok(literal(bound(params, name)?)))?;
+        }
+        Value::String(text) => {
+            if let Some(name) = whole_placeholder(text) {
+                *value = bound(params, name)?.clone();
+            } else {
+                *text = replace_placeholders(text, |name| {
+                    Ok(match bound(params, name)? {
+                        Value::String(s) => s.clone(),
+                        other => other.to_string(),
+                    })
+                })?;
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                substitute_value(item, params, expression)?;
+            }
+        }
+        Value::Object(map) => {
+            for (key, child) in map.iter_mut() {
+                let is_expression = matches!(key.as_str(), "target" | "condition");
+                substitute_value(child, params, expression || is_expression)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+// Renders a parameter value as expression source
+pub(crate) fn literal(value: &Value) -> String {
+    match value {
+        Value::String(s) => expr::quote(s),
+        Value::Array(items) => {
+            let parts: Vec<String> = items.iter().map(literal).collect();
+            format!("[{}]", parts.join(", "))
+        }
+        Value::Object(_) => "null".to_string(),
+        other => other.to_string(),
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Adds or replaces a template; every placeholder must be declared
+    #[wasm_bindgen]
+    pub fn load_template(&mut self, template_json: &str) -> Result<(), JsValue> {
+        let template: PolicyTemplate = serde_json::from_str(template_json)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse template: {}", e)).logged())?;
+        template.check().map_err(PolicyEngineError::logged)?;
+
+        if self.debug_mode {
+            console_log!("Loaded template: {}", template.id);
+        }
+        self.templates.insert(template.id.clone(), template);
+        Ok(())
+    }
+
+    // Returns the concrete policy JSON without loading it
+    #[wasm_bindgen]
+    pub fn instantiate_template(&self, template_id: &str, params_json: &str) -> Result<String, JsValue> {
+        let compiled = self.instantiate(template_id, params_json)?;
+        Ok(to_json(&compiled.policy)?)
+    }
+
+    // Instantiates and loads one policy from a template
+    #[wasm_bindgen]
+    pub fn load_template_instance(&mut self, template_id: &str, params_json: &str) -> Result<(), JsValue> {
+        let compiled = self.instantiate(template_id, params_json)?;
+        self.insert_unique(compiled, None);
+        Ok(())
+    }
+
+    // Instantiates a JSON array of parameter objects; every instance must
+    // compile or none are loaded. Returns the number loaded, not counting
+    // instances identical to a loaded policy.
+    #[wasm_bindgen]
+    pub fn load_template_instances(&mut self, template_id: &str, params_array_json: &str) -> Result<usize, JsValue> {
+        let all: Vec<Value> = serde_json::from_str(params_array_json).map_err(|e| {
+            PolicyEngineError::parse(format!("Failed to parse template parameters: {}", e)).logged()
+        })?;
+
+        let mut compiled = Vec::with_capacity(all.len());
+        for params in &all {
+            compiled.push(self.instantiate(template_id, &params.to_string())?);
+        }
+
+        let mut count = 0;
+        for compiled in compiled {
+            if self.insert_unique(compiled, None) {
+                count += 1;
+            }
+        }
+        if self.debug_mode {
+            console_log!("Loaded {} policies from template {}", count, template_id);
+        }
+        Ok(count)
+    }
+
+    #[wasm_bindgen]
+    pub fn list_templates(&self) -> String {
+        let mut ids: Vec<&String> = self.templates.keys().collect();
+        ids.sort();
+        serde_json::to_string(&ids).unwrap_or_default()
+    }
+
+    #[wasm_bindgen]
+    pub fn remove_template(&mut self, template_id: &str) -> bool {
+        self.templates.remove(template_id).is_some()
+    }
+}
+
+impl PolicyEngine {
+    fn instantiate(&self, template_id: &str, params_json: &str) -> Result<CompiledPolicy, PolicyEngineError> {
+        let template = self.templates.get(template_id).ok_or_else(|| {
+            PolicyEngineError::not_found(format!("Template '{}' is not loaded", template_id))
+                .with_details(json!({ "template_id": template_id }))
+                .logged()
+        })?;
+        let params: Map<String, Value> = serde_json::from_str(params_json).map_err(|e| {
+            PolicyEngineError::parse(format!("Failed to parse template parameters: {}", e)).logged()
+        })?;
+
+        let policy = template.instantiate(&params).map_err(PolicyEngineError::logged)?;
+        self.compile_policy(policy).map_err(|e| {
+            e.context(&format!("Failed to compile instance of template '{}'", template_id)).logged()
+        })
+    }
+}
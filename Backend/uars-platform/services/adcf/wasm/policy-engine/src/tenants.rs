@@ -0,0 +1,157 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{guard, CompiledPolicy, Policy, PolicyEngine, PolicyResult, PolicySelection};
+
+// Tenant-scoped policy set. Tenants never see each other's policies or
+// per-user state. When `inherit_global` is set, the engine's global
+// policies are evaluated alongside the tenant's own (tenant policies
+// first, among equal priorities) and combined under the root algorithm,
+// so global guardrails still apply.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Tenant {
+    policies: Vec<CompiledPolicy>,
+    inherit_global: bool,
+}
+
+impl Tenant {
+    pub(crate) fn own_policies(&self) -> &[CompiledPolicy] {
+        &self.policies
+    }
+
+    pub(crate) fn own_policies_mut(&mut self) -> &mut [CompiledPolicy] {
+        &mut self.policies
+    }
+
+    pub(crate) fn inherits_global(&self) -> bool {
+        self.inherit_global
+    }
+
+    pub(crate) fn push_policy(&mut self, compiled: CompiledPolicy) {
+        self.policies.push(compiled);
+    }
+
+    pub(crate) fn layered_policies<'a>(&'a self, global: &'a [CompiledPolicy]) -> Vec<&'a CompiledPolicy> {
+        let mut layered: Vec<&CompiledPolicy> = self.policies.iter().collect();
+        if self.inherit_global {
+            layered.extend(global.iter());
+        }
+        layered
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    #[wasm_bindgen]
+    pub fn load_policy_for_tenant(&mut self, tenant_id: &str, policy_json: &str) -> Result<(), JsValue> {
+        let policy: Policy = self.parse_policies(policy_json, &format!("policy for tenant '{}'", tenant_id))
+            .map_err(|e| e.logged())?;
+        self.add_tenant_policies(tenant_id, vec![policy])
+    }
+
+    #[wasm_bindgen]
+    pub fn load_policies_for_tenant(&mut self, tenant_id: &str, policies_json: &str) -> Result<(), JsValue> {
+        let policies: Vec<Policy> = self.parse_policies(policies_json, &format!("policies for tenant '{}'", tenant_id))
+            .map_err(|e| e.logged())?;
+        self.add_tenant_policies(tenant_id, policies)
+    }
+
+    #[wasm_bindgen]
+    pub fn evaluate_for_tenant(&mut self, tenant_id: &str, context_json: &str) -> Result<PolicyResult, JsValue> {
+        if self.debug_mode {
+            console_log!("Starting policy evaluation for tenant {}", tenant_id);
+        }
+
+        let context = self.parse_context(context_json)?;
+        Ok(guard::guarded(|| self.evaluate_request(context, PolicySelection::Tenant(tenant_id))))
+    }
+
+    // Opt a tenant in or out of layering the global policies beneath its own
+    #[wasm_bindgen]
+    pub fn set_tenant_inherits_global(&mut self, tenant_id: &str, inherit: bool) {
+        self.tenants.entry(tenant_id.to_string()).or_default().inherit_global = inherit;
+    }
+
+    #[wasm_bindgen]
+    pub fn get_tenant_policy_count(&self, tenant_id: &str) -> usize {
+        self.tenants.get(tenant_id).map(|t| t.policies.len()).unwrap_or(0)
+    }
+
+    #[wasm_bindgen]
+    pub fn list_tenants(&self) -> String {
+        let mut ids: Vec<&String> = self.tenants.keys().collect();
+        ids.sort();
+        serde_json::to_string(&ids).unwrap_or_default()
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_tenant(&mut self, tenant_id: &str) -> bool {
+        let removed = self.tenants.remove(tenant_id).is_some();
+        if removed {
+            console_log!("Cleared policies for tenant {}", tenant_id);
+        }
+        removed
+    }
+}
+
+impl PolicyEngine {
+    // Compiles every policy before adding any, so a bad policy leaves the
+    // tenant unchanged; policies it already has are skipped
+    fn add_tenant_policies(&mut self, tenant_id: &str, policies: Vec<Policy>) -> Result<(), JsValue> {
+        let mut compiled = Vec::with_capacity(policies.len());
+        for policy in policies {
+            if self.debug_mode {
+                console_log!("Loading policy for tenant {}: {} ({})", tenant_id, policy.name, policy.id);
+            }
+            compiled.push(self.compile_policy(policy).map_err(|e| e.context("Failed to compile policy").logged())?);
+        }
+
+        self.tenants.entry(tenant_id.to_string()).or_default();
+        for compiled in compiled {
+            self.insert_unique(compiled, Some(tenant_id));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn policy(id: &str) -> Policy {
+        serde_json::from_value(json!({
+            "id": id,
+            "name": id,
+            "version": "1.0.0",
+            "description": "",
+            "target": "true",
+            "combining_algorithm": "deny-overrides",
+            "rules": [],
+            "obligations": [],
+            "advice": []
+        }))
+        .unwrap()
+    }
+
+    fn selected(engine: &PolicyEngine, tenant: &str) -> Vec<String> {
+        engine.selected_policies(PolicySelection::Tenant(tenant)).iter().map(|compiled| compiled.policy.id.clone()).collect()
+    }
+
+    #[test]
+    fn tenants_only_see_their_own_and_inherited_policies() {
+        let mut engine = PolicyEngine::new();
+        let global = engine.compile_policy(policy("global")).unwrap();
+        engine.policies.push(global);
+        engine.add_tenant_policies("acme", vec![policy("acme-only")]).unwrap();
+        engine.add_tenant_policies("globex", vec![policy("globex-only")]).unwrap();
+
+        assert_eq!(selected(&engine, "acme"), ["acme-only"]);
+        assert_eq!(selected(&engine, "globex"), ["globex-only"]);
+        assert!(selected(&engine, "initech").is_empty());
+
+        engine.set_tenant_inherits_global("acme", true);
+        assert_eq!(selected(&engine, "acme"), ["acme-only", "global"]);
+        assert_eq!(selected(&engine, "globex"), ["globex-only"]);
+    }
+}
@@ -0,0 +1,139 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::decision::Decision;
+use crate::error::{to_json, PolicyEngineError};
+use crate::{PolicyEngine, PolicyResult, PolicySelection};
+
+// A policy unit test. `expected_obligations`, when given, must match the
+// result's obligations exactly (order-insensitive).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyTestCase {
+    pub name: String,
+    pub context: serde_json::Value,
+    pub expected_decision: String,
+    #[serde(default)]
+    pub expected_obligations: Option<Vec<String>>,
+    #[serde(default)]
+    pub tenant: Option<String>,
+}
+
+// An assertion that did not hold
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestMismatch {
+    pub field: String,
+    pub expected: serde_json::Value,
+    pub actual: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestOutcome {
+    pub name: String,
+    pub passed: bool,
+    pub decision: Option<Decision>,
+    pub reason: Option<String>,
+    pub mismatches: Vec<TestMismatch>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestReport {
+    pub passed: usize,
+    pub failed: usize,
+    pub results: Vec<TestOutcome>,
+}
+
+impl PolicyTestCase {
+    fn check(&self, result: &PolicyResult) -> Vec<TestMismatch> {
+        let mut mismatches = Vec::new();
+
+        if result.decision.as_str() != self.expected_decision {
+            mismatches.push(TestMismatch {
+                field: "decision".to_string(),
+                expected: self.expected_decision.clone().into(),
+                actual: result.decision.as_str().into(),
+            });
+        }
+
+        if let Some(expected) = &self.expected_obligations {
+            let mut actual: Vec<String> = serde_json::from_str(&result.obligations).unwrap_or_default();
+            let mut wanted = expected.clone();
+            actual.sort();
+            wanted.sort();
+            if actual != wanted {
+                mismatches.push(TestMismatch {
+                    field: "obligations".to_string(),
+                    expected: wanted.into(),
+                    actual: actual.into(),
+                });
+            }
+        }
+
+        mismatches
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Runs a JSON array of PolicyTestCase against the loaded policies and
+    // returns a JSON TestReport. Test runs are side-effect free: no travel
+    // history, quota usage or obligation dispatch.
+    #[wasm_bindgen]
+    pub fn run_tests(&self, tests_json: &str) -> Result<String, JsValue> {
+        let cases: Vec<PolicyTestCase> = serde_json::from_str(tests_json).map_err(|e| {
+            PolicyEngineError::parse(format!("Failed to parse test cases: {}", e)).logged()
+        })?;
+
+        let results: Vec<TestOutcome> = cases.iter().map(|case| self.run_test(case)).collect();
+        let passed = results.iter().filter(|r| r.passed).count();
+        let report = TestReport {
+            passed,
+            failed: results.len() - passed,
+            results,
+        };
+
+        if self.debug_mode {
+            console_log!("Policy tests: {} passed, {} failed", report.passed, report.failed);
+        }
+        Ok(to_json(&report)?)
+    }
+}
+
+impl PolicyEngine {
+    fn run_test(&self, case: &PolicyTestCase) -> TestOutcome {
+        let failed = |error: String| TestOutcome {
+            name: case.name.clone(),
+            passed: false,
+            decision: None,
+            reason: None,
+            mismatches: Vec::new(),
+            error: Some(error),
+        };
+
+        let mut context = match self.check_context(&case.context.to_string()) {
+            Ok(context) => context,
+            Err(e) => return failed(e.to_json()),
+        };
+        self.enrich_context(&mut context, case.tenant.as_deref());
+
+        let selection = match &case.tenant {
+            Some(tenant) => PolicySelection::Tenant(tenant),
+            None => PolicySelection::Global,
+        };
+        let result = match self.evaluate_context(&context, selection) {
+            Ok(result) => result,
+            Err(e) => return failed(e.as_string().unwrap_or_else(|| "evaluation failed".to_string())),
+        };
+
+        let mismatches = case.check(&result);
+        TestOutcome {
+            name: case.name.clone(),
+            passed: mismatches.is_empty(),
+            decision: Some(result.decision),
+            reason: Some(result.reason.into_owned()),
+            mismatches,
+            error: None,
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::net::IpAddr;
+
+use crate::error::{to_json, PolicyEngineError};
+use crate::expr::{ExprError, Value};
+use crate::PolicyEngine;
+
+// IP reputation feed behind ip_reputation(), ip_is_tor() and
+// ip_threat_categories(). Reputation runs from 0 (clean or unlisted) to
+// 100 (known bad).
+//
+// JSON feeds list networks (CIDR or single addresses) and autonomous
+// systems; a network naming its `asn` also gets that AS's reputation and
+// categories:
+//
+//   { "name": "abuse-feed",
+//     "networks": { "203.0.113.0/24": { "reputation": 90, "categories": ["botnet"], "asn": 64500 },
+//                   "198.51.100.7": { "categories": ["tor"] } },
+//     "asns": { "64500": { "reputation": 60, "categories": ["bulletproof_hosting"] } } }
+//
+// MMDB feeds (mmdb feature) are MaxMind-format databases whose records
+// carry `reputation` (or `score`), `categories` and/or
+// `is_tor_exit_node`, such as GeoIP2 Anonymous IP.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThreatRecord {
+    pub reputation: Option<f64>,
+    pub categories: Vec<String>,
+    pub asn: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct JsonFeed {
+    name: String,
+    networks: BTreeMap<String, ThreatRecord>,
+    asns: HashMap<u32, ThreatRecord>,
+}
+
+// What a feed says about one address
+#[derive(Debug, Default)]
+pub struct ThreatMatch {
+    pub reputation: f64,
+    pub categories: BTreeSet<String>,
+}
+
+impl ThreatMatch {
+    fn add(&mut self, reputation: Option<f64>, categories: &[String]) {
+        if let Some(reputation) = reputation {
+            self.reputation = self.reputation.max(reputation.clamp(0.0, 100.0));
+        }
+        self.categories.extend(categories.iter().map(|category| category.to_lowercase()));
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Network {
+    bits: u128,
+    prefix: u32,
+    v6: bool,
+}
+
+impl Network {
+    fn parse(text: &str) -> Result<Network, String> {
+        let (address, prefix) = match text.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (text, None),
+        };
+        let ip: IpAddr = address.trim().parse().map_err(|_| format!("'{}' is not an IP network", text))?;
+        let (bits, v6) = address_bits(ip);
+        let width = if v6 { 128 } else { 32 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.trim().parse::<u32>().ok().filter(|p| *p <= width),
+            None => Some(width),
+        }
+        .ok_or_else(|| format!("'{}' has an invalid prefix length", text))?;
+        Ok(Network { bits: bits & mask(prefix, width), prefix, v6 })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        let (bits, v6) = address_bits(ip);
+        let width = if v6 { 128 } else { 32 };
+        v6 == self.v6 && bits & mask(self.prefix, width) == self.bits
+    }
+}
+
+// IPv4 (and IPv4-mapped IPv6) addresses as their 32 bits
+fn address_bits(ip: IpAddr) -> (u128, bool) {
+    match ip.to_canonical() {
+        IpAddr::V4(v4) => (u32::from(v4) as u128, false),
+        IpAddr::V6(v6) => (u128::from(v6), true),
+    }
+}
+
+fn mask(prefix: u32, width: u32) -> u128 {
+    if prefix == 0 {
+        0
+    } else {
+        (u128::MAX << (128 - prefix)) >> (128 - width)
+    }
+}
+
+enum Source {
+    // Longest prefix first, so the first containing network wins
+    Table { networks: Vec<(Network, ThreatRecord)>, asns: HashMap<u32, ThreatRecord> },
+    #[cfg(feature = "mmdb")]
+    Mmdb(maxminddb::Reader<Vec<u8>>),
+}
+
+pub struct ThreatFeed {
+    name: String,
+    source: Source,
+}
+
+#[cfg(feature = "mmdb")]
+#[derive(Deserialize)]
+struct MmdbRecord {
+    #[serde(default, alias = "score")]
+    reputation: Option<f64>,
+    #[serde(default)]
+    categories: Vec<String>,
+    #[serde(default)]
+    is_tor_exit_node: bool,
+}
+
+impl ThreatFeed {
+    fn from_json(bytes: &[u8]) -> Result<ThreatFeed, PolicyEngineError> {
+        let feed: JsonFeed = serde_json::from_slice(bytes)
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to parse threat feed: {}", e)))?;
+        let mut networks = feed
+            .networks
+            .into_iter()
+            .map(|(network, record)| Ok((Network::parse(&network)?, record)))
+            .collect::<Result<Vec<_>, String>>()
+            .map_err(|e| PolicyEngineError::validation(format!("Invalid threat feed: {}", e)))?;
+        networks.sort_by_key(|(network, _)| std::cmp::Reverse(network.prefix));
+        Ok(ThreatFeed { name: feed.name, source: Source::Table { networks, asns: feed.asns } })
+    }
+
+    #[cfg(feature = "mmdb")]
+    fn from_mmdb(bytes: &[u8]) -> Result<ThreatFeed, PolicyEngineError> {
+        let reader = maxminddb::Reader::from_source(bytes.to_vec())
+            .map_err(|e| PolicyEngineError::parse(format!("Failed to open MMDB threat feed: {}", e)))?;
+        let name = reader.metadata.database_type.clone();
+        Ok(ThreatFeed { name, source: Source::Mmdb(reader) })
+    }
+
+    fn format(&self) -> &'static str {
+        match &self.source {
+            Source::Table { .. } => "json",
+            #[cfg(feature = "mmdb")]
+            Source::Mmdb(_) => "mmdb",
+        }
+    }
+
+    pub fn lookup(&self, ip: IpAddr) -> ThreatMatch {
+        let mut found = ThreatMatch::default();
+        match &self.source {
+            Source::Table { networks, asns } => {
+                if let Some((_, record)) = networks.iter().find(|(network, _)| network.contains(ip)) {
+                    found.add(record.reputation, &record.categories);
+                    if let Some(asn) = record.asn.and_then(|asn| asns.get(&asn)) {
+                        found.add(asn.reputation, &asn.categories);
+                    }
+                }
+            }
+            #[cfg(feature = "mmdb")]
+            Source::Mmdb(reader) => {
+                if let Ok(record) = reader.lookup::<MmdbRecord>(ip) {
+                    found.add(record.reputation, &record.categories);
+                    if record.is_tor_exit_node {
+                        found.categories.insert("tor".to_string());
+                    }
+                }
+            }
+        }
+        found
+    }
+}
+
+// The address argument of an ip_* function; None for a missing address
+fn ip_arg(function: &str, args: &[Value]) -> Result<Option<IpAddr>, ExprError> {
+    match args {
+        [Value::Null] => Ok(None),
+        [Value::String(text)] if text.is_empty() => Ok(None),
+        [Value::String(text)] => text
+            .parse()
+            .map(Some)
+            .map_err(|_| ExprError::new(format!("{}() argument '{}' is not an IP address", function, text))),
+        _ => Err(ExprError::new(format!("{}() takes one IP address", function))),
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Loads a JSON or (mmdb feature) MaxMind-format feed, replacing the
+    // current one
+    #[wasm_bindgen]
+    pub fn load_threat_feed(&mut self, feed: &[u8]) -> Result<(), JsValue> {
+        let is_json = feed.iter().find(|byte| !byte.is_ascii_whitespace()) == Some(&b'{');
+        let loaded = if is_json {
+            ThreatFeed::from_json(feed)
+        } else {
+            #[cfg(feature = "mmdb")]
+            {
+                ThreatFeed::from_mmdb(feed)
+            }
+            #[cfg(not(feature = "mmdb"))]
+            {
+                Err(PolicyEngineError::validation("MMDB threat feeds require the mmdb feature"))
+            }
+        };
+        let loaded = loaded.map_err(PolicyEngineError::logged)?;
+        if self.debug_mode {
+            console_log!("Loaded {} threat feed '{}'", loaded.format(), loaded.name);
+        }
+        self.threat_feed = Some(loaded);
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_threat_feed(&mut self) {
+        self.threat_feed = None;
+    }
+
+    // {"name", "format", "networks", "asns"} for the loaded feed (counts
+    // for JSON feeds only), or "null"
+    #[wasm_bindgen]
+    pub fn get_threat_feed_info(&self) -> Result<String, JsValue> {
+        let info = self.threat_feed.as_ref().map(|feed| {
+            let (networks, asns) = match &feed.source {
+                Source::Table { networks, asns } => (Some(networks.len()), Some(asns.len())),
+                #[cfg(feature = "mmdb")]
+                Source::Mmdb(_) => (None, None),
+            };
+            serde_json::json!({ "name": feed.name, "format": feed.format(), "networks": networks, "asns": asns })
+        });
+        Ok(to_json(&info)?)
+    }
+}
+
+impl PolicyEngine {
+    // Evaluates ip_reputation(), ip_is_tor() and ip_threat_categories();
+    // None for other functions
+    pub(crate) fn call_threat_function(&self, name: &str, args: &[Value]) -> Option<Result<Value, ExprError>> {
+        if !matches!(name, "ip_reputation" | "ip_is_tor" | "ip_threat_categories") {
+            return None;
+        }
+        let Some(feed) = &self.threat_feed else {
+            return Some(Err(ExprError::new(format!("{}() needs a threat feed (load_threat_feed)", name))));
+        };
+        Some(ip_arg(name, args).map(|ip| {
+            let found = ip.map(|ip| feed.lookup(ip));
+            match (name, found) {
+                ("ip_reputation", found) => found.map_or(Value::Null, |found| Value::Number(found.reputation)),
+                ("ip_is_tor", found) => Value::Bool(found.is_some_and(|found| found.categories.contains("tor"))),
+                (_, found) => Value::List(
+                    found.map(|found| found.categories.into_iter().map(Value::String).collect()).unwrap_or_default(),
+                ),
+            }
+        }))
+    }
+}
@@ -0,0 +1,220 @@
+use wasm_bindgen::prelude::*;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use crate::attributes::canonical_path;
+use crate::decision::Decision;
+use crate::error::to_json;
+use crate::functions::EvalScope;
+use crate::{PolicyContext, PolicyEngine, PolicyResult};
+
+const DEFAULT_CAPACITY: usize = 1000;
+
+// Context attributes built-in functions read on the policy's behalf
+const FUNCTION_READS: &[(&str, &[&str])] = &[
+    ("impossible_travel", &["user_id", "ip_country", "ip_city", "timestamp"]),
+    ("unusual_location", &["user_id", "ip_country"]),
+    ("unusual_time", &["user_id", "timestamp"]),
+    ("unusual_resource", &["user_id", "resource_type", "resource_id"]),
+    ("delegation_valid", &["user_id", "delegation"]),
+    ("acting_identity", &["user_id", "delegation"]),
+    ("delegation_depth", &["delegation"]),
+    ("avg_over", &["user_id", "timestamp"]),
+    ("min_over", &["user_id", "timestamp"]),
+    ("max_over", &["user_id", "timestamp"]),
+    ("sum_over", &["user_id", "timestamp"]),
+    ("count_over", &["user_id", "timestamp"]),
+    ("count_operations", &["timestamp"]),
+    ("is_business_hours", &["timestamp"]),
+    ("is_holiday", &["timestamp"]),
+    ("in_window", &["timestamp"]),
+    ("has_consent", &["user_id", "timestamp", "intent_purpose"]),
+];
+
+pub fn function_reads(name: &str) -> &'static [&'static str] {
+    FUNCTION_READS.iter().find(|(function, _)| *function == name).map_or(&[], |(_, reads)| reads)
+}
+
+// Attributes one policy read: its target, and its rule conditions when
+// the target matched
+#[derive(Debug, Clone, Serialize)]
+pub struct PolicyUsage {
+    pub policy_id: String,
+    pub applicable: bool,
+    pub attributes: BTreeSet<String>,
+}
+
+// Reads of one evaluation, collected by its EvalScope. Only what
+// expressions actually evaluated counts: a rule skipped by a limit or an
+// operand short-circuited away read nothing.
+#[derive(Debug, Default)]
+pub struct UsageRecorder {
+    policies: Vec<PolicyUsage>,
+    current: Option<usize>,
+    paused: bool,
+}
+
+impl UsageRecorder {
+    pub fn enter_policy(&mut self, policy_id: &str) {
+        let index = match self.policies.iter().position(|usage| usage.policy_id == policy_id) {
+            Some(index) => index,
+            None => {
+                self.policies.push(PolicyUsage {
+                    policy_id: policy_id.to_string(),
+                    applicable: false,
+                    attributes: BTreeSet::new(),
+                });
+                self.policies.len() - 1
+            }
+        };
+        self.current = Some(index);
+    }
+
+    pub fn set_applicable(&mut self, applicable: bool) {
+        if let Some(index) = self.current {
+            self.policies[index].applicable = applicable;
+        }
+    }
+
+    pub fn read(&mut self, segments: &[&str]) {
+        if self.paused {
+            return;
+        }
+        if let Some(index) = self.current {
+            self.policies[index].attributes.insert(canonical_path(segments));
+        }
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+}
+
+// What get_attribute_usage reports for one evaluation. `unread` lists
+// top-level context fields the caller supplied that no policy read, the
+// candidates for a PEP to stop collecting (request_id, which keys the
+// report, is never among them).
+#[derive(Debug, Clone, Serialize)]
+pub struct AttributeUsage {
+    pub request_id: String,
+    pub timestamp: DateTime<Utc>,
+    pub decision: Decision,
+    pub policy_id: Option<String>,
+    pub attributes: BTreeSet<String>,
+    pub policies: Vec<PolicyUsage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unread: Option<BTreeSet<String>>,
+}
+
+impl AttributeUsage {
+    fn new(recorder: UsageRecorder, context: &PolicyContext, result: &PolicyResult) -> AttributeUsage {
+        let attributes: BTreeSet<String> =
+            recorder.policies.iter().flat_map(|usage| usage.attributes.iter().cloned()).collect();
+        let unread = context.supplied_fields.as_ref().map(|supplied| {
+            let read: BTreeSet<&str> =
+                attributes.iter().map(|name| name.split('.').next().unwrap_or(name)).collect();
+            supplied
+                .iter()
+                .filter(|field| field.as_str() != "request_id" && !read.contains(field.as_str()))
+                .cloned()
+                .collect()
+        });
+        AttributeUsage {
+            request_id: context.request_id.clone(),
+            timestamp: context.timestamp,
+            decision: result.decision,
+            policy_id: result.policy_id.clone(),
+            attributes,
+            policies: recorder.policies,
+            unread,
+        }
+    }
+}
+
+// Recent evaluations' attribute usage while tracking is enabled, oldest
+// dropped first
+#[derive(Debug)]
+pub struct UsageTracker {
+    enabled: bool,
+    capacity: usize,
+    reports: VecDeque<AttributeUsage>,
+}
+
+impl Default for UsageTracker {
+    fn default() -> Self {
+        UsageTracker { enabled: false, capacity: DEFAULT_CAPACITY, reports: VecDeque::new() }
+    }
+}
+
+impl UsageTracker {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+// Totals over the retained reports
+#[derive(Debug, Serialize)]
+struct UsageSummary {
+    evaluations: usize,
+    read: BTreeMap<String, usize>,
+    unread: BTreeMap<String, usize>,
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Keeps attribute usage for the last `capacity` (default 1000)
+    // evaluations; disabling drops what was kept
+    #[wasm_bindgen]
+    pub fn set_attribute_usage_tracking(&mut self, enabled: bool, capacity: Option<usize>) {
+        let mut tracker = self.usage.borrow_mut();
+        tracker.enabled = enabled;
+        tracker.capacity = capacity.unwrap_or(DEFAULT_CAPACITY).max(1);
+        if !enabled {
+            tracker.reports.clear();
+        }
+        while tracker.reports.len() > tracker.capacity {
+            tracker.reports.pop_front();
+        }
+    }
+
+    // The most recent AttributeUsage recorded for a request id, or "null"
+    #[wasm_bindgen]
+    pub fn get_attribute_usage(&self, request_id: &str) -> Result<String, JsValue> {
+        let tracker = self.usage.borrow();
+        Ok(to_json(&tracker.reports.iter().rev().find(|report| report.request_id == request_id))?)
+    }
+
+    // How many retained evaluations read each attribute, and how often
+    // each supplied field went unread
+    #[wasm_bindgen]
+    pub fn get_attribute_usage_summary(&self) -> Result<String, JsValue> {
+        let tracker = self.usage.borrow();
+        let mut summary = UsageSummary { evaluations: tracker.reports.len(), read: BTreeMap::new(), unread: BTreeMap::new() };
+        for report in &tracker.reports {
+            for attribute in &report.attributes {
+                *summary.read.entry(attribute.clone()).or_default() += 1;
+            }
+            for field in report.unread.iter().flatten() {
+                *summary.unread.entry(field.clone()).or_default() += 1;
+            }
+        }
+        Ok(to_json(&summary)?)
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_attribute_usage(&mut self) {
+        self.usage.borrow_mut().reports.clear();
+    }
+}
+
+impl PolicyEngine {
+    pub(crate) fn record_attribute_usage(&self, scope: &EvalScope, result: &Result<PolicyResult, JsValue>) {
+        let (Some(recorder), Ok(result)) = (scope.take_usage(), result) else { return };
+        let mut tracker = self.usage.borrow_mut();
+        if tracker.reports.len() >= tracker.capacity {
+            tracker.reports.pop_front();
+        }
+        tracker.reports.push_back(AttributeUsage::new(recorder, scope.context, result));
+    }
+}
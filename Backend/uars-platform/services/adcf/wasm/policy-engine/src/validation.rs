@@ -0,0 +1,340 @@
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+use crate::error::PolicyEngineError;
+use crate::vocabulary::{DeviceTrust, ThreatLevel};
+use crate::{stats, PolicyContext, PolicyEngine};
+
+// Field-level problem found while validating an incoming context
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldError {
+    pub path: String,
+    pub problem: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub found: Option<String>,
+}
+
+impl FieldError {
+    fn new(path: &str, problem: &str) -> FieldError {
+        FieldError {
+            path: if path.is_empty() { "/".to_string() } else { path.to_string() },
+            problem: problem.to_string(),
+            expected: None,
+            found: None,
+        }
+    }
+
+    fn expected(mut self, expected: impl Into<String>, found: impl Into<String>) -> FieldError {
+        self.expected = Some(expected.into());
+        self.found = Some(found.into());
+        self
+    }
+}
+
+// Schema describing the shape PolicyContext deserialization requires
+pub fn builtin_context_schema() -> &'static Value {
+    static SCHEMA: OnceLock<Value> = OnceLock::new();
+    SCHEMA.get_or_init(build_context_schema)
+}
+
+fn build_context_schema() -> Value {
+    let string = json!({ "type": "string" });
+    let boolean = json!({ "type": "boolean" });
+    let strings = json!({ "type": "array", "items": { "type": "string" } });
+    let object = json!({ "type": "object" });
+    // chrono durations serialize as [seconds, nanoseconds]
+    let duration = json!({ "type": "array", "items": { "type": "integer" }, "minItems": 2, "maxItems": 2 });
+    let delegation_link = json!({
+        "type": "object",
+        "required": ["actor", "on_behalf_of", "grant_id"],
+        "properties": { "actor": string, "on_behalf_of": string, "grant_id": string },
+    });
+
+    let properties = json!({
+        "request_id": string,
+        "timestamp": { "type": "string", "format": "date-time" },
+        "operation": string,
+        "user_id": string,
+        "user_roles": strings,
+        "user_groups": strings,
+        "user_attributes": object,
+        "device_id": string,
+        "device_type": string,
+        "device_trust": string,
+        "device_attested": boolean,
+        "device_posture_token": { "type": ["string", "null"] },
+        "device_attestation": {
+            "type": ["object", "null"],
+            "required": ["attestation_object", "client_data_json"],
+            "properties": { "attestation_object": string, "client_data_json": string },
+        },
+        "ip_address": string,
+        "ip_country": string,
+        "ip_city": string,
+        "network_zone": string,
+        "vpn_detected": boolean,
+        "session_id": string,
+        "session_age": duration,
+        "auth_method": string,
+        "mfa_verified": boolean,
+        "time_of_day": string,
+        "day_of_week": string,
+        "business_hours": boolean,
+        "risk_score": { "type": "number" },
+        "threat_level": string,
+        "resource_type": string,
+        "resource_id": string,
+        "resource_classification": string,
+        "resource_owner": string,
+        "resource_attributes": object,
+        "intent_purpose": { "type": ["string", "null"] },
+        "intent_justification": { "type": ["string", "null"] },
+        "intent_duration": { "type": ["array", "null"], "items": { "type": "integer" } },
+        "delegation": { "type": "array", "items": delegation_link },
+        "constraints": object,
+        "metadata": object,
+        "attribute_quality": object,
+        "locale": { "type": ["string", "null"] },
+    });
+
+    // All fields are optional (see PolicyContext defaults); only types are checked
+    json!({ "type": "object", "properties": properties })
+}
+
+fn type_of(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn type_matches(expected: &str, value: &Value) -> bool {
+    let actual = type_of(value);
+    expected == actual || (expected == "number" && actual == "integer")
+}
+
+// Validates `value` against a JSON Schema subset: type, required,
+// properties, additionalProperties (boolean), items, enum, minimum,
+// maximum, minLength, maxLength, minItems, maxItems and
+// format "date-time".
+pub fn validate(schema: &Value, value: &Value) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    validate_at(schema, value, "", &mut errors);
+    errors
+}
+
+fn validate_at(schema: &Value, value: &Value, path: &str, errors: &mut Vec<FieldError>) {
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(types) => types.iter().filter_map(|t| t.as_str()).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|t| type_matches(t, value)) {
+            errors.push(FieldError::new(path, "wrong_type").expected(allowed.join("|"), type_of(value)));
+            return;
+        }
+    }
+
+    if let Some(Value::Array(options)) = schema.get("enum") {
+        if !options.contains(value) {
+            let expected: Vec<String> = options.iter().map(|o| o.to_string()).collect();
+            errors.push(FieldError::new(path, "not_allowed").expected(expected.join("|"), value.to_string()));
+        }
+    }
+
+    match value {
+        Value::Object(map) => {
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for field in required.iter().filter_map(|f| f.as_str()) {
+                    if !map.contains_key(field) {
+                        errors.push(FieldError::new(&format!("{}/{}", path, field), "missing"));
+                    }
+                }
+            }
+
+            let properties = schema.get("properties").and_then(|p| p.as_object());
+            let closed = schema.get("additionalProperties") == Some(&Value::Bool(false));
+            for (key, child) in map {
+                let child_path = format!("{}/{}", path, key);
+                match properties.and_then(|p| p.get(key)) {
+                    Some(child_schema) => validate_at(child_schema, child, &child_path, errors),
+                    None if closed => errors.push(FieldError::new(&child_path, "unexpected")),
+                    None => {}
+                }
+            }
+        }
+        Value::Array(items) => {
+            check_bound(schema, "minItems", items.len() as f64, path, "too_few_items", |n, b| n < b, errors);
+            check_bound(schema, "maxItems", items.len() as f64, path, "too_many_items", |n, b| n > b, errors);
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    validate_at(item_schema, item, &format!("{}/{}", path, index), errors);
+                }
+            }
+        }
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or(0.0);
+            check_bound(schema, "minimum", n, path, "below_minimum", |n, b| n < b, errors);
+            check_bound(schema, "maximum", n, path, "above_maximum", |n, b| n > b, errors);
+        }
+        Value::String(s) => {
+            let length = s.chars().count() as f64;
+            check_bound(schema, "minLength", length, path, "too_short", |n, b| n < b, errors);
+            check_bound(schema, "maxLength", length, path, "too_long", |n, b| n > b, errors);
+            if schema.get("format").and_then(|f| f.as_str()) == Some("date-time")
+                && chrono::DateTime::parse_from_rfc3339(s).is_err()
+            {
+                errors.push(FieldError::new(path, "invalid_format").expected("date-time", s.clone()));
+            }
+        }
+        _ => {}
+    }
+}
+
+fn check_bound(
+    schema: &Value,
+    keyword: &str,
+    actual: f64,
+    path: &str,
+    problem: &str,
+    violates: fn(f64, f64) -> bool,
+    errors: &mut Vec<FieldError>,
+) {
+    if let Some(bound) = schema.get(keyword).and_then(|b| b.as_f64()) {
+        if violates(actual, bound) {
+            errors.push(FieldError::new(path, problem).expected(format!("{} {}", keyword, bound), actual.to_string()));
+        }
+    }
+}
+
+// Rejected contexts carry their field errors as `details.fields`
+// Strict mode only: enumerated context fields holding unrecognised values
+fn unknown_vocabulary(context: &PolicyContext) -> Vec<FieldError> {
+    let mut fields = Vec::new();
+    if !context.device_trust.is_known() {
+        fields.push(
+            FieldError::new("/device_trust", "unknown_value")
+                .expected(spelled(DeviceTrust::KNOWN), context.device_trust.as_str()),
+        );
+    }
+    if !context.threat_level.is_known() {
+        fields.push(
+            FieldError::new("/threat_level", "unknown_value")
+                .expected(spelled(ThreatLevel::KNOWN), context.threat_level.as_str()),
+        );
+    }
+    fields
+}
+
+// Known values for an error message; the empty "not supplied" value is implied
+fn spelled(known: &[&str]) -> String {
+    known.iter().filter(|value| !value.is_empty()).copied().collect::<Vec<_>>().join("|")
+}
+
+fn with_fields(error: PolicyEngineError, fields: &[FieldError]) -> PolicyEngineError {
+    error.with_details(json!({ "fields": fields }))
+}
+
+fn fields_of(error: &PolicyEngineError) -> Vec<FieldError> {
+    error
+        .details()
+        .get("fields")
+        .and_then(|fields| serde_json::from_value(fields.clone()).ok())
+        .unwrap_or_default()
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Additional constraints checked after the built-in context shape,
+    // e.g. enums for device_trust or required user_attributes keys
+    #[wasm_bindgen]
+    pub fn set_context_schema(&mut self, schema_json: &str) -> Result<(), JsValue> {
+        match serde_json::from_str::<Value>(schema_json) {
+            Ok(schema) if schema.is_object() => {
+                self.context_schema = Some(schema);
+                Ok(())
+            }
+            Ok(_) => Err(PolicyEngineError::validation("Context schema must be a JSON object").into()),
+            Err(e) => Err(PolicyEngineError::parse(format!("Failed to parse context schema: {}", e)).logged().into()),
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_context_schema(&mut self) {
+        self.context_schema = None;
+    }
+
+    // Returns the field errors (JSON array) for a context without evaluating it
+    #[wasm_bindgen]
+    pub fn validate_context(&self, context_json: &str) -> String {
+        let errors = match self.check_context(context_json) {
+            Ok(_) => Vec::new(),
+            Err(error) => fields_of(&error),
+        };
+        serde_json::to_string(&errors).unwrap_or_default()
+    }
+}
+
+impl PolicyEngine {
+    pub(crate) fn check_context(&self, context_json: &str) -> Result<PolicyContext, PolicyEngineError> {
+        let raw: Value = serde_json::from_str(context_json).map_err(|e| {
+            let error = PolicyEngineError::parse(format!("Failed to parse context: {}", e));
+            with_fields(error, &[FieldError::new("", "invalid_json")])
+        })?;
+        self.check_context_value(raw)
+    }
+
+    // Same checks for a context that is already parsed JSON
+    pub(crate) fn check_context_value(&self, raw: Value) -> Result<PolicyContext, PolicyEngineError> {
+        let mut fields = validate(builtin_context_schema(), &raw);
+        if let Some(schema) = &self.context_schema {
+            fields.extend(validate(schema, &raw));
+        }
+        if !fields.is_empty() {
+            let error = PolicyEngineError::validation(format!("Context failed validation ({} field errors)", fields.len()));
+            return Err(with_fields(error, &fields));
+        }
+
+        let supplied: HashSet<String> = raw
+            .as_object()
+            .map(|map| map.keys().cloned().collect())
+            .unwrap_or_default();
+
+        let mut context: PolicyContext = serde_json::from_value(raw)
+            .map_err(|e| PolicyEngineError::validation(format!("Failed to parse context: {}", e)))?;
+        self.check_vocabulary(&context)?;
+        context.supplied_fields = Some(supplied);
+        Ok(context)
+    }
+
+    // Strict mode's vocabulary check, for contexts that did not arrive as
+    // JSON (evaluate_proto)
+    pub(crate) fn check_vocabulary(&self, context: &PolicyContext) -> Result<(), PolicyEngineError> {
+        if self.strict_mode {
+            let fields = unknown_vocabulary(context);
+            if !fields.is_empty() {
+                let error = PolicyEngineError::validation("Context uses values outside the known vocabulary");
+                return Err(with_fields(error, &fields));
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn parse_context(&self, context_json: &str) -> Result<PolicyContext, JsValue> {
+        let started = stats::now_ms();
+        let context = self.check_context(context_json).map_err(PolicyEngineError::logged);
+        self.begin_profile(started);
+        Ok(context?)
+    }
+}
@@ -0,0 +1,164 @@
+use wasm_bindgen::prelude::*;
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::clock;
+use crate::decision::Decision;
+#[cfg(feature = "crypto")]
+use crate::decision_token::DecisionClaims;
+use crate::digest::policy_set_hash;
+use crate::environment::format_time;
+#[cfg(feature = "crypto")]
+use crate::jose;
+use crate::{PolicyContext, PolicyEngine, PolicyResult, PolicySelection};
+
+// Longest window a PERMIT is granted for, however long the intent or
+// session cap asks for
+pub const MAX_VALIDITY_DAYS: i64 = 3650;
+
+// A PERMIT with a validity window, kept by request_id so the PEP can
+// re-check it without evaluating again
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GrantedDecision {
+    pub valid_until: DateTime<Utc>,
+    pub tenant: Option<String>,
+    pub policy_set_hash: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GrantedDecisions {
+    by_request: HashMap<String, GrantedDecision>,
+}
+
+// `now` plus `duration` (the longest window when unrepresentable),
+// clamped to [0, MAX_VALIDITY_DAYS]
+fn window_end(now: DateTime<Utc>, duration: Option<TimeDelta>) -> DateTime<Utc> {
+    let cap = TimeDelta::days(MAX_VALIDITY_DAYS);
+    let duration = duration.unwrap_or(cap).clamp(TimeDelta::zero(), cap);
+    now.checked_add_signed(duration).unwrap_or(now)
+}
+
+pub fn parse_valid_until(result: &PolicyResult) -> Option<DateTime<Utc>> {
+    let text = result.valid_until.as_deref()?;
+    DateTime::parse_from_rfc3339(text).ok().map(|time| time.with_timezone(&Utc))
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Whether a previously granted access still holds: the decision was a
+    // PERMIT, its valid_until has not passed and the policy set that
+    // produced it is unchanged. Accepts a decision token from
+    // `evaluate_signed` (verified against the engine's signing key) or the
+    // request_id of an earlier evaluation. Anything else is not valid;
+    // tokens need the crypto feature.
+    #[wasm_bindgen]
+    pub fn is_decision_still_valid(&self, decision_token_or_id: &str) -> bool {
+        let now = clock::now();
+        #[cfg(feature = "crypto")]
+        if decision_token_or_id.matches('.').count() == 2 {
+            return self.decision_token_valid(decision_token_or_id, now).unwrap_or(false);
+        }
+
+        match self.granted.by_request.get(decision_token_or_id) {
+            Some(granted) if now < granted.valid_until => {
+                let selection = match &granted.tenant {
+                    Some(tenant) => PolicySelection::Tenant(tenant),
+                    None => PolicySelection::Global,
+                };
+                policy_set_hash(self.selected_policies(selection)) == granted.policy_set_hash
+            }
+            _ => false,
+        }
+    }
+}
+
+impl PolicyEngine {
+    #[cfg(feature = "crypto")]
+    fn decision_token_valid(&self, token: &str, now: DateTime<Utc>) -> Result<bool, String> {
+        let (_, payload) = jose::verify(token, &self.decision_verification_keys())?;
+        jose::check_times(&payload, now)?;
+        let claims: DecisionClaims = serde_json::from_value(payload).map_err(|e| e.to_string())?;
+
+        Ok(claims.decision == Decision::Permit
+            && claims.valid_until.is_some_and(|until| now.timestamp() < until)
+            && claims.policy_set_hash == policy_set_hash(&self.policies))
+    }
+
+    // A PERMIT is valid for the requested intent_duration, cut short by a
+    // session lifetime cap or a window set earlier (break-glass expiry).
+    // Windows start at the engine clock, not the caller's timestamp.
+    // Windowed PERMITs are kept for is_decision_still_valid.
+    pub(crate) fn apply_validity(&mut self, result: &mut PolicyResult, context: &PolicyContext, tenant: Option<&str>) {
+        let now = clock::now();
+        self.granted.by_request.retain(|_, granted| granted.valid_until > now);
+        if result.decision != Decision::Permit {
+            result.valid_until = None;
+            self.granted.by_request.remove(&context.request_id);
+            return;
+        }
+
+        let session_cap = result.max_session_age.map(|max_age| {
+            let remaining = TimeDelta::try_milliseconds((max_age * 1000.0) as i64)
+                .and_then(|max_age| max_age.checked_sub(&context.session_age));
+            window_end(now, remaining)
+        });
+        let valid_until = [
+            parse_valid_until(result),
+            context.intent_duration.map(|duration| window_end(now, Some(duration))),
+            session_cap,
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+
+        result.valid_until = valid_until.map(format_time);
+        match valid_until {
+            Some(valid_until) => {
+                let selection = match tenant {
+                    Some(tenant) => PolicySelection::Tenant(tenant),
+                    None => PolicySelection::Global,
+                };
+                let granted = GrantedDecision {
+                    valid_until,
+                    tenant: tenant.map(String::from),
+                    policy_set_hash: policy_set_hash(self.selected_policies(selection)),
+                };
+                self.granted.by_request.insert(context.request_id.clone(), granted);
+            }
+            None => {
+                self.granted.by_request.remove(&context.request_id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn valid_until(context: serde_json::Value, max_session_age: Option<f64>) -> DateTime<Utc> {
+        let context: PolicyContext = serde_json::from_value(context).unwrap();
+        let mut result = PolicyResult::new(Decision::Permit, "permitted", 1.0);
+        result.max_session_age = max_session_age;
+        PolicyEngine::new().apply_validity(&mut result, &context, None);
+        parse_valid_until(&result).unwrap()
+    }
+
+    #[test]
+    fn windows_start_at_the_engine_clock_and_are_capped() {
+        let cap = TimeDelta::days(MAX_VALIDITY_DAYS);
+        // valid_until is rendered to the millisecond
+        let before = clock::now() - TimeDelta::milliseconds(1);
+        let until = valid_until(json!({ "timestamp": "2000-01-01T00:00:00Z", "intent_duration": [600, 0] }), None);
+        assert!(until >= before + TimeDelta::seconds(600) && until <= clock::now() + TimeDelta::seconds(600));
+
+        let until = valid_until(json!({ "intent_duration": [9_000_000_000_000_000i64, 0] }), None);
+        assert!(until <= clock::now() + cap);
+        let until = valid_until(json!({ "session_age": [-9_000_000_000_000_000i64, 0] }), Some(f64::MAX));
+        assert!(until <= clock::now() + cap);
+        let until = valid_until(json!({ "session_age": [9_000_000_000_000_000i64, 0] }), Some(3600.0));
+        assert!(until <= clock::now());
+    }
+}
@@ -0,0 +1,119 @@
+use wasm_bindgen::prelude::*;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use crate::clock;
+use crate::error::to_json;
+use crate::expr::{ExprError, Value};
+use crate::quota;
+use crate::{PolicyContext, PolicyEngine};
+
+// Longest window count_operations() accepts; older permits are dropped
+const MAX_WINDOW_DAYS: i64 = 30;
+// Most recent permits kept per user and operation
+const MAX_HITS: usize = 10_000;
+
+// Matches every operation in count_operations()
+pub const ANY_OPERATION: &str = "*";
+
+// Sliding-window counters of permitted operations per user, behind
+// count_operations(). Only PERMITs count, so denied attempts cannot lock
+// a user out of their own quota. Permits are recorded and counted at the
+// engine clock; the request timestamp is the caller's to choose.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct OperationCounters {
+    users: HashMap<String, HashMap<String, VecDeque<DateTime<Utc>>>>,
+}
+
+impl OperationCounters {
+    pub fn record(&mut self, user_key: &str, context: &PolicyContext) {
+        if context.user_id.is_empty() || context.operation.is_empty() {
+            return;
+        }
+        let hits = self
+            .users
+            .entry(user_key.to_string())
+            .or_default()
+            .entry(context.operation.clone())
+            .or_default();
+        let at = clock::now();
+        let position = hits.iter().rposition(|seen| *seen <= at).map_or(0, |index| index + 1);
+        hits.insert(position, at);
+        let oldest = hits.back().and_then(|latest| latest.checked_sub_signed(Duration::days(MAX_WINDOW_DAYS)));
+        while hits.len() > MAX_HITS || hits.front().zip(oldest).is_some_and(|(first, oldest)| *first < oldest) {
+            hits.pop_front();
+        }
+    }
+
+    // Permits of `operation` (or any, for "*") in the `window` up to `until`
+    pub fn count(&self, user_key: &str, operation: &str, window: Duration, until: DateTime<Utc>) -> usize {
+        let Some(operations) = self.users.get(user_key) else {
+            return 0;
+        };
+        let since = until.checked_sub_signed(window).unwrap_or(DateTime::<Utc>::MIN_UTC);
+        let in_window = |hits: &VecDeque<DateTime<Utc>>| hits.iter().filter(|at| **at > since && **at <= until).count();
+        match operation {
+            ANY_OPERATION => operations.values().map(in_window).sum(),
+            operation => operations.get(operation).map_or(0, in_window),
+        }
+    }
+}
+
+// The window argument: a duration string up to 30 days
+pub fn window_arg(function: &str, args: &[Value], index: usize) -> Result<Duration, ExprError> {
+    let window = match args.get(index) {
+        Some(Value::String(text)) => quota::parse_window(text),
+        Some(_) => None,
+        None => return Err(ExprError::new(format!("{}() missing argument {}", function, index + 1))),
+    };
+    window.filter(|window| *window <= Duration::days(MAX_WINDOW_DAYS)).ok_or_else(|| {
+        ExprError::new(format!(
+            "{}() argument {} must be a window such as \"1h\", at most {}d",
+            function,
+            index + 1,
+            MAX_WINDOW_DAYS
+        ))
+    })
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // Permits per operation a user has in the counters (up to the last
+    // 30 days)
+    #[wasm_bindgen]
+    pub fn get_operation_counts(&self, user_id: &str) -> Result<String, JsValue> {
+        let counts: BTreeMap<&String, usize> = self
+            .operation_counters
+            .users
+            .get(user_id)
+            .map(|operations| operations.iter().map(|(operation, hits)| (operation, hits.len())).collect())
+            .unwrap_or_default();
+        Ok(to_json(&counts)?)
+    }
+
+    #[wasm_bindgen]
+    pub fn reset_operation_counters(&mut self) {
+        self.operation_counters.users.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn permits_count_at_the_engine_clock() {
+        let mut counters = OperationCounters::default();
+        let backdated = PolicyContext {
+            user_id: "alice".to_string(),
+            operation: "read".to_string(),
+            timestamp: clock::now() - Duration::days(365),
+            ..PolicyContext::default()
+        };
+        counters.record("alice", &backdated);
+        counters.record("alice", &backdated);
+        assert_eq!(counters.count("alice", "read", Duration::hours(1), clock::now()), 2);
+        assert_eq!(counters.count("alice", ANY_OPERATION, Duration::hours(1), clock::now()), 2);
+    }
+}
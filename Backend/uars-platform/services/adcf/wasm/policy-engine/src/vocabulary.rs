@@ -0,0 +1,422 @@
+use wasm_bindgen::prelude::*;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Schema, SchemaObject};
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::json;
+use std::fmt;
+
+use crate::attributes::canonical_path;
+use crate::decision::Decision;
+use crate::error::PolicyEngineError;
+use crate::expr::{BinaryOp, Expr, Value};
+use crate::logging::LogLevel;
+use crate::rule_library::RuleLibrary;
+use crate::strict;
+use crate::{CompiledPolicy, PolicyEngine};
+
+// Spellings are matched ignoring case, and `-`, `_` and spaces are
+// interchangeable, so "Permit", "pending-approval" and "Deny_Overrides"
+// all resolve
+fn normalize(text: &str) -> String {
+    text.trim()
+        .chars()
+        .map(|c| if c == '-' || c == ' ' { '_' } else { c.to_ascii_lowercase() })
+        .collect()
+}
+
+// Closed vocabularies for policy and context fields that used to be free
+// strings. Each value has a canonical spelling (what the engine writes)
+// plus aliases; anything else is kept as `Other` so lenient engines load
+// it as before, and strict engines reject it.
+macro_rules! vocabulary {
+    (
+        $(#[$meta:meta])*
+        $name:ident {
+            $($(#[$variant_meta:meta])* $variant:ident = $canonical:literal $(| $alias:literal)*,)*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($(#[$variant_meta])* $variant,)*
+            // Outside the vocabulary, as written
+            Other(String),
+        }
+
+        impl $name {
+            pub const KNOWN: &'static [&'static str] = &[$($canonical),*];
+
+            pub fn parse(text: &str) -> $name {
+                let key = normalize(text);
+                $(
+                    if key == normalize($canonical) $(|| key == normalize($alias))* {
+                        return $name::$variant;
+                    }
+                )*
+                $name::Other(text.to_string())
+            }
+
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $($name::$variant => $canonical,)*
+                    $name::Other(text) => text,
+                }
+            }
+
+            pub fn is_known(&self) -> bool {
+                !matches!(self, $name::Other(_))
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.as_str())
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<$name, D::Error> {
+                Ok($name::parse(&String::deserialize(deserializer)?))
+            }
+        }
+
+        impl JsonSchema for $name {
+            fn schema_name() -> String {
+                stringify!($name).to_string()
+            }
+
+            fn json_schema(_: &mut SchemaGenerator) -> Schema {
+                SchemaObject {
+                    instance_type: Some(InstanceType::String.into()),
+                    enum_values: Some($name::KNOWN.iter().map(|value| json!(value)).collect()),
+                    ..SchemaObject::default()
+                }
+                .into()
+            }
+        }
+    };
+}
+
+vocabulary! {
+    // What a rule produces when its condition holds
+    Effect {
+        Permit = "PERMIT" | "allow",
+        Deny = "DENY",
+        Challenge = "CHALLENGE",
+        PendingApproval = "PENDING_APPROVAL",
+        Indeterminate = "INDETERMINATE",
+    }
+}
+
+impl Effect {
+    // Unknown effects never win a combining algorithm
+    pub fn decision(&self) -> Decision {
+        match self {
+            Effect::Permit => Decision::Permit,
+            Effect::Deny => Decision::Deny,
+            Effect::Challenge => Decision::Challenge,
+            Effect::PendingApproval => Decision::PendingApproval,
+            Effect::Indeterminate => Decision::Indeterminate,
+            Effect::Other(_) => Decision::NotApplicable,
+        }
+    }
+}
+
+vocabulary! {
+    // XACML 3.0 identifiers are accepted as aliases
+    CombiningAlgorithm {
+        PermitOverrides = "permit-overrides"
+            | "urn:oasis:names:tc:xacml:3.0:rule-combining-algorithm:permit-overrides"
+            | "urn:oasis:names:tc:xacml:3.0:policy-combining-algorithm:permit-overrides",
+        DenyOverrides = "deny-overrides"
+            | "urn:oasis:names:tc:xacml:3.0:rule-combining-algorithm:deny-overrides"
+            | "urn:oasis:names:tc:xacml:3.0:policy-combining-algorithm:deny-overrides",
+        FirstApplicable = "first-applicable"
+            | "urn:oasis:names:tc:xacml:1.0:rule-combining-algorithm:first-applicable"
+            | "urn:oasis:names:tc:xacml:1.0:policy-combining-algorithm:first-applicable",
+        PermitUnlessDeny = "permit-unless-deny"
+            | "urn:oasis:names:tc:xacml:3.0:rule-combining-algorithm:permit-unless-deny"
+            | "urn:oasis:names:tc:xacml:3.0:policy-combining-algorithm:permit-unless-deny",
+        DenyUnlessPermit = "deny-unless-permit"
+            | "urn:oasis:names:tc:xacml:3.0:rule-combining-algorithm:deny-unless-permit"
+            | "urn:oasis:names:tc:xacml:3.0:policy-combining-algorithm:deny-unless-permit",
+        // As the unordered ones, but the first winning result in
+        // evaluation order decides rather than the most confident
+        OrderedPermitOverrides = "ordered-permit-overrides"
+            | "urn:oasis:names:tc:xacml:3.0:rule-combining-algorithm:ordered-permit-overrides"
+            | "urn:oasis:names:tc:xacml:3.0:policy-combining-algorithm:ordered-permit-overrides",
+        OrderedDenyOverrides = "ordered-deny-overrides"
+            | "urn:oasis:names:tc:xacml:3.0:rule-combining-algorithm:ordered-deny-overrides"
+            | "urn:oasis:names:tc:xacml:3.0:policy-combining-algorithm:ordered-deny-overrides",
+    }
+}
+
+vocabulary! {
+    // The risk profile scores these (see risk.rs)
+    #[derive(Default)]
+    DeviceTrust {
+        // Not supplied
+        #[default]
+        Unspecified = "",
+        Trusted = "trusted",
+        Managed = "managed",
+        Known = "known",
+        Unknown = "unknown",
+        Untrusted = "untrusted",
+    }
+}
+
+vocabulary! {
+    #[derive(Default)]
+    ThreatLevel {
+        // Not supplied
+        #[default]
+        Unspecified = "",
+        None = "none",
+        Low = "low",
+        Medium = "medium" | "moderate",
+        High = "high",
+        Critical = "critical" | "severe",
+    }
+}
+
+// Context attributes whose values are a vocabulary, so policy literals
+// compared against them can be checked and canonicalized
+#[derive(Debug, Clone, Copy)]
+enum Field {
+    DeviceTrust,
+    ThreatLevel,
+}
+
+impl Field {
+    fn of(expression: &Expr) -> Option<Field> {
+        let Expr::Attribute(path) = expression else { return None };
+        let segments: Vec<&str> = path.iter().map(String::as_str).collect();
+        match canonical_path(&segments).as_str() {
+            "device_trust" => Some(Field::DeviceTrust),
+            "threat_level" => Some(Field::ThreatLevel),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Field::DeviceTrust => "device_trust",
+            Field::ThreatLevel => "threat_level",
+        }
+    }
+
+    fn known(self) -> &'static [&'static str] {
+        match self {
+            Field::DeviceTrust => DeviceTrust::KNOWN,
+            Field::ThreatLevel => ThreatLevel::KNOWN,
+        }
+    }
+
+    // Canonical spelling, if the value is in the vocabulary
+    fn canonical(self, text: &str) -> Option<String> {
+        match self {
+            Field::DeviceTrust => Some(DeviceTrust::parse(text)).filter(DeviceTrust::is_known).map(|v| v.to_string()),
+            Field::ThreatLevel => Some(ThreatLevel::parse(text)).filter(ThreatLevel::is_known).map(|v| v.to_string()),
+        }
+    }
+}
+
+// For an `==`, `!=` or `in` comparison against a vocabulary attribute,
+// the field and the other operand
+fn comparison(expression: &Expr) -> Option<(Field, &Expr)> {
+    let Expr::Binary(BinaryOp::Eq | BinaryOp::Ne | BinaryOp::In, left, right) = expression else { return None };
+    match (Field::of(left), Field::of(right)) {
+        (Some(field), _) => Some((field, right)),
+        (_, Some(field)) => Some((field, left)),
+        _ => None,
+    }
+}
+
+// String literals of an operand: a single literal or a literal list
+fn literals(operand: &Expr) -> Vec<&str> {
+    let items = match operand {
+        Expr::List(items) => items.as_slice(),
+        single => std::slice::from_ref(single),
+    };
+    items
+        .iter()
+        .filter_map(|item| match item {
+            Expr::Literal(Value::String(text)) => Some(text.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn canonicalize_literals(field: Field, operand: &mut Expr) {
+    let items = match operand {
+        Expr::List(items) => items.as_mut_slice(),
+        single => std::slice::from_mut(single),
+    };
+    for item in items {
+        if let Expr::Literal(Value::String(text)) = item {
+            if let Some(canonical) = field.canonical(text) {
+                *text = canonical;
+            }
+        }
+    }
+}
+
+// Rewrites literals compared against vocabulary attributes to their
+// canonical spelling, matching how contexts are read
+// (`device.trust == 'Trusted'` still matches)
+pub fn canonicalize(expression: &mut Expr) {
+    match expression {
+        Expr::Literal(_) | Expr::Attribute(_) => {}
+        Expr::List(items) | Expr::Call(_, items) => items.iter_mut().for_each(canonicalize),
+        Expr::Unary(_, operand) => canonicalize(operand),
+        Expr::Binary(op, left, right) => {
+            if matches!(op, BinaryOp::Eq | BinaryOp::Ne | BinaryOp::In) {
+                match (Field::of(left), Field::of(right)) {
+                    (Some(field), _) => canonicalize_literals(field, right),
+                    (_, Some(field)) => canonicalize_literals(field, left),
+                    _ => {}
+                }
+            }
+            canonicalize(left);
+            canonicalize(right);
+        }
+    }
+}
+
+fn unknown_literals(expression: &Expr, rule_id: Option<&str>, unknown: &mut Vec<serde_json::Value>) {
+    if let Some((field, operand)) = comparison(expression) {
+        for text in literals(operand).into_iter().filter(|text| field.canonical(text).is_none()) {
+            unknown.push(json!({
+                "field": field.name(),
+                "value": text,
+                "rule_id": rule_id,
+                "expected": field.known(),
+            }));
+        }
+    }
+    match expression {
+        Expr::Literal(_) | Expr::Attribute(_) => {}
+        Expr::List(items) | Expr::Call(_, items) => {
+            items.iter().for_each(|item| unknown_literals(item, rule_id, unknown));
+        }
+        Expr::Unary(_, operand) => unknown_literals(operand, rule_id, unknown),
+        Expr::Binary(_, left, right) => {
+            unknown_literals(left, rule_id, unknown);
+            unknown_literals(right, rule_id, unknown);
+        }
+    }
+}
+
+fn unknown_effect(effect: &Effect, rule_id: &str, unknown: &mut Vec<serde_json::Value>) {
+    if let Effect::Other(text) = effect {
+        unknown.push(json!({
+            "field": "effect",
+            "value": text,
+            "rule_id": rule_id,
+            "expected": Effect::KNOWN,
+        }));
+    }
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // In strict mode policies (and rule libraries) with an unknown effect,
+    // comparing device_trust/threat_level against an unknown value, reading
+    // an attribute no context has, carrying an obligation that is neither
+    // built in, handled nor declared (see declare_obligations), or with
+    // JSON fields the policy format lacks are rejected at load time, and
+    // so are contexts carrying unknown values. Off by default: unknown
+    // values and attributes load with a warning, unknown effects never
+    // decide, unknown attributes read null, unknown fields and obligations
+    // are passed over and unknown context values compare as written.
+    #[wasm_bindgen]
+    pub fn set_strict_mode(&mut self, enabled: bool) {
+        self.strict_mode = enabled;
+    }
+
+    #[wasm_bindgen]
+    pub fn is_strict_mode(&self) -> bool {
+        self.strict_mode
+    }
+
+    // Algorithm combining the results of the applicable policies, which
+    // are evaluated by descending priority (load order among equals).
+    // deny-overrides by default; first-applicable and the ordered
+    // variants let higher-priority layers (e.g. org before team before
+    // resource policies) decide.
+    #[wasm_bindgen]
+    pub fn set_root_algorithm(&mut self, name: &str) -> Result<(), JsValue> {
+        let algorithm = CombiningAlgorithm::parse(name);
+        if !algorithm.is_known() {
+            return Err(PolicyEngineError::unknown_algorithm(format!("Unknown root combining algorithm '{}'", name))
+                .with_details(json!({ "algorithm": name, "expected": CombiningAlgorithm::KNOWN }))
+                .logged()
+                .into());
+        }
+        if self.debug_mode {
+            console_log!("Root combining algorithm: {}", algorithm);
+        }
+        self.root_algorithm = algorithm;
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn get_root_algorithm(&self) -> String {
+        self.root_algorithm.to_string()
+    }
+}
+
+impl PolicyEngine {
+    fn check_unknown_values(&self, what: String, mut details: serde_json::Value, unknown: Vec<serde_json::Value>) -> Result<(), PolicyEngineError> {
+        if unknown.is_empty() {
+            return Ok(());
+        }
+        if !self.strict_mode {
+            log_at!(LogLevel::Warn, "{} uses unknown values: {}", what, serde_json::Value::from(unknown));
+            return Ok(());
+        }
+        let message = format!("{} uses {} unknown value(s)", what, unknown.len());
+        details["unknown_values"] = unknown.into();
+        Err(PolicyEngineError::validation(message).with_details(details))
+    }
+
+    // Load-time check of a compiled policy, linked rules included
+    pub(crate) fn check_policy_vocabulary(&self, compiled: &CompiledPolicy) -> Result<(), PolicyEngineError> {
+        let mut unknown = Vec::new();
+        unknown_literals(&compiled.target, None, &mut unknown);
+        strict::unknown_attributes(&compiled.target, None, &mut unknown);
+        for (rule, condition) in compiled.rules() {
+            unknown_effect(&rule.effect, &rule.id, &mut unknown);
+            unknown_literals(condition, Some(&rule.id), &mut unknown);
+            strict::unknown_attributes(condition, Some(&rule.id), &mut unknown);
+        }
+        self.unknown_policy_obligations(compiled, &mut unknown);
+        let policy = &compiled.policy;
+        let fulfill_on = policy.obligations.iter().filter_map(|item| item.fulfill_on.as_ref())
+            .chain(policy.advice.iter().filter_map(|item| item.fulfill_on.as_ref()));
+        for effect in fulfill_on.filter(|effect| !effect.is_known()) {
+            unknown.push(json!({ "field": "fulfill_on", "value": effect.as_str(), "expected": Effect::KNOWN }));
+        }
+        let policy_id = &compiled.policy.id;
+        self.check_unknown_values(format!("Policy '{}'", policy_id), json!({ "policy_id": policy_id }), unknown)
+    }
+
+    pub(crate) fn check_library_vocabulary(&self, name: &str, library: &RuleLibrary) -> Result<(), PolicyEngineError> {
+        let mut unknown = Vec::new();
+        for (rule, condition) in library.values() {
+            unknown_effect(&rule.effect, &rule.id, &mut unknown);
+            unknown_literals(condition, Some(&rule.id), &mut unknown);
+            strict::unknown_attributes(condition, Some(&rule.id), &mut unknown);
+        }
+        self.unknown_obligations(std::iter::empty(), library.values().map(|(rule, _)| rule), &mut unknown);
+        self.check_unknown_values(format!("Rule library '{}'", name), json!({ "library": name }), unknown)
+    }
+}
@@ -0,0 +1,164 @@
+use wasm_bindgen::prelude::*;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+use crate::decision::Decision;
+use crate::error::{to_json, PolicyEngineError};
+use crate::expr;
+use crate::jose;
+use crate::messages;
+use crate::obligations::ObligationCall;
+use crate::{PolicyContext, PolicyEngine, PolicyResult};
+
+// Obligation a PERMIT rule carries to have downloaded content watermarked.
+// Rules write `watermark()`; the engine fills in the payload, so PEPs
+// receive `watermark("<payload>")` to embed.
+pub const WATERMARK_OBLIGATION: &str = "watermark";
+
+const WATERMARK_VERSION: u8 = 1;
+const MIN_KEY_LEN: usize = 32;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub struct WatermarkKey {
+    id: Option<String>,
+    key: Zeroizing<Vec<u8>>,
+}
+
+// What a watermark attests: who was granted what, when. Fields are
+// serialized in this order, so the same request always yields the same
+// payload.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatermarkClaims {
+    pub v: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kid: Option<String>,
+    pub user_id: String,
+    pub request_id: String,
+    pub resource_id: String,
+    pub timestamp: String,
+}
+
+fn mac(key: &[u8]) -> Result<HmacSha256, PolicyEngineError> {
+    if key.len() < MIN_KEY_LEN {
+        return Err(PolicyEngineError::validation(format!(
+            "Watermark key must be at least {} bytes, got {}",
+            MIN_KEY_LEN,
+            key.len()
+        )));
+    }
+    HmacSha256::new_from_slice(key).map_err(|_| PolicyEngineError::internal("Failed to initialize watermark HMAC"))
+}
+
+// Payload layout: base64url(claims JSON) "." base64url(HMAC-SHA256 of the
+// first part)
+fn sign(key: &WatermarkKey, claims: &WatermarkClaims) -> Result<String, PolicyEngineError> {
+    let body = serde_json::to_vec(claims)
+        .map_err(|e| PolicyEngineError::internal(format!("Failed to serialize watermark: {}", e)))?;
+    let body = jose::encode_segment(&body);
+    let mut mac = mac(&key.key)?;
+    mac.update(body.as_bytes());
+    Ok(format!("{}.{}", body, jose::encode_segment(&mac.finalize().into_bytes())))
+}
+
+// Checks a watermark payload against the key that made it and returns
+// its WatermarkClaims JSON, for tracing a leaked document back to the
+// request that released it
+#[wasm_bindgen]
+pub fn verify_watermark(payload: &str, key: &[u8]) -> Result<String, JsValue> {
+    let mut mac = mac(key).map_err(|e| e.logged())?;
+    let claims = payload
+        .trim()
+        .split_once('.')
+        .ok_or_else(|| "not a watermark payload".to_string())
+        .and_then(|(body, tag)| {
+            mac.update(body.as_bytes());
+            mac.verify_slice(&jose::decode_segment(tag)?)
+                .map_err(|_| "signature mismatch (wrong key or altered payload)".to_string())?;
+            serde_json::from_slice::<WatermarkClaims>(&jose::decode_segment(body)?).map_err(|e| format!("malformed claims: {}", e))
+        })
+        .and_then(|claims| match claims.v {
+            WATERMARK_VERSION => Ok(claims),
+            version => Err(format!("unsupported version {}", version)),
+        })
+        .map_err(|reason| {
+            PolicyEngineError::validation(format!("Invalid watermark: {}", reason))
+                .with_details(json!({ "reason": reason }))
+                .logged()
+        })?;
+    Ok(to_json(&claims)?)
+}
+
+#[wasm_bindgen]
+impl PolicyEngine {
+    // HMAC key (at least 32 bytes) for watermark() obligations. `key_id`
+    // is carried in payloads, so a rotated key can still be told apart.
+    #[wasm_bindgen]
+    pub fn set_watermark_key(&mut self, key: &[u8], key_id: Option<String>) -> Result<(), JsValue> {
+        mac(key).map_err(|e| e.logged())?;
+        self.watermark_key = Some(WatermarkKey { id: key_id, key: Zeroizing::new(key.to_vec()) });
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_watermark_key(&mut self) {
+        self.watermark_key = None;
+    }
+}
+
+impl PolicyEngine {
+    // Fills in the payload of a PERMIT's watermark() obligations. Without
+    // a watermark key the content cannot be traced, so the PERMIT becomes
+    // a DENY rather than releasing it unmarked.
+    pub(crate) fn apply_watermark(&self, result: &mut PolicyResult, context: &PolicyContext) {
+        if result.decision != Decision::Permit {
+            return;
+        }
+        let mut obligations: Vec<String> = serde_json::from_str(&result.obligations).unwrap_or_default();
+        let pending: Vec<usize> = obligations
+            .iter()
+            .enumerate()
+            .filter(|(_, spec)| {
+                let call = ObligationCall::parse(spec);
+                call.id == WATERMARK_OBLIGATION && call.args.is_empty()
+            })
+            .map(|(index, _)| index)
+            .collect();
+        if pending.is_empty() {
+            return;
+        }
+
+        let claims = WatermarkClaims {
+            v: WATERMARK_VERSION,
+            kid: self.watermark_key.as_ref().and_then(|key| key.id.clone()),
+            user_id: context.user_id.clone(),
+            request_id: context.request_id.clone(),
+            resource_id: context.resource_id.clone(),
+            timestamp: context.timestamp.to_rfc3339(),
+        };
+        let signed = match &self.watermark_key {
+            Some(key) => sign(key, &claims),
+            None => Err(PolicyEngineError::invalid_state("No watermark key is configured")),
+        };
+        let payload = match signed {
+            Ok(payload) => payload,
+            Err(e) => {
+                if self.debug_mode {
+                    console_log!("Watermark withheld: {}", e.message());
+                }
+                *result = PolicyResult::new(Decision::Deny, format!("Watermark unavailable: {}", e.message()), 1.0);
+                result.reason_code = Some(messages::WATERMARK_UNAVAILABLE.to_string());
+                return;
+            }
+        };
+
+        let obligation = format!("{}({})", WATERMARK_OBLIGATION, expr::quote(&payload));
+        for index in pending {
+            obligations[index] = obligation.clone();
+        }
+        result.obligations = serde_json::to_string(&obligations).unwrap_or_else(|_| "[]".to_string()).into();
+    }
+}
@@ -0,0 +1,61 @@
+use wasm_bindgen::prelude::*;
+use serde::Deserialize;
+
+use crate::error::PolicyEngineError;
+use crate::{Policy, PolicyEngine};
+
+// YAML policy loading for policy-as-code repositories. A document may hold
+// a single policy, a list of policies, or several `---` separated
+// documents. `source` is recorded on each policy that does not already
+// declare one.
+#[wasm_bindgen]
+impl PolicyEngine {
+    #[wasm_bindgen]
+    pub fn load_policy_yaml(&mut self, policy_yaml: &str, source: Option<String>) -> Result<(), JsValue> {
+        match serde_yaml::from_str::<Policy>(policy_yaml) {
+            Ok(policy) => self.add_policy(with_source(policy, &source)),
+            Err(e) => Err(PolicyEngineError::parse(format!("Failed to parse YAML policy: {}", e)).logged().into()),
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn load_policies_yaml(&mut self, policies_yaml: &str, source: Option<String>) -> Result<(), JsValue> {
+        let policies = match parse_documents(policies_yaml) {
+            Ok(policies) => policies,
+            Err(e) => {
+                return Err(PolicyEngineError::parse(format!("Failed to parse YAML policies: {}", e)).logged().into());
+            }
+        };
+
+        for policy in policies {
+            self.add_policy(with_source(policy, &source))?;
+        }
+        console_log!("Loaded {} policies", self.policies.len());
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PolicyDocument {
+    Many(Vec<Policy>),
+    One(Box<Policy>),
+}
+
+fn parse_documents(yaml: &str) -> Result<Vec<Policy>, serde_yaml::Error> {
+    let mut policies = Vec::new();
+    for document in serde_yaml::Deserializer::from_str(yaml) {
+        match PolicyDocument::deserialize(document)? {
+            PolicyDocument::Many(many) => policies.extend(many),
+            PolicyDocument::One(one) => policies.push(*one),
+        }
+    }
+    Ok(policies)
+}
+
+fn with_source(mut policy: Policy, source: &Option<String>) -> Policy {
+    if policy.source.is_none() {
+        policy.source = source.clone();
+    }
+    policy
+}